@@ -36,7 +36,10 @@ describe("listGoals", () => {
     expect(mockFetch).toHaveBeenCalledWith(
       "/api/goals",
       expect.objectContaining({
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          "X-Conductor-Actor": "ui",
+        },
       }),
     );
     expect(result).toEqual(goals);
@@ -56,7 +59,10 @@ describe("createGoal", () => {
       expect.objectContaining({
         method: "POST",
         body: JSON.stringify(newGoal),
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          "X-Conductor-Actor": "ui",
+        },
       }),
     );
     expect(result).toEqual(created);
@@ -73,7 +79,10 @@ describe("listAgents", () => {
     expect(mockFetch).toHaveBeenCalledWith(
       "/api/agents",
       expect.objectContaining({
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          "X-Conductor-Actor": "ui",
+        },
       }),
     );
     expect(result).toEqual(agents);
@@ -91,7 +100,10 @@ describe("nudgeAgent", () => {
       expect.objectContaining({
         method: "POST",
         body: JSON.stringify({ message: "wake up" }),
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          "X-Conductor-Actor": "ui",
+        },
       }),
     );
   });
@@ -107,7 +119,10 @@ describe("killAgent", () => {
       "/api/agents/agent-456/kill",
       expect.objectContaining({
         method: "POST",
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          "X-Conductor-Actor": "ui",
+        },
       }),
     );
   });
@@ -129,7 +144,10 @@ describe("getStats", () => {
     expect(mockFetch).toHaveBeenCalledWith(
       "/api/stats",
       expect.objectContaining({
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          "X-Conductor-Actor": "ui",
+        },
       }),
     );
     expect(result).toEqual(stats);
@@ -161,7 +179,10 @@ describe("decomposeGoal", () => {
       "/api/goals/goal-1/decompose",
       expect.objectContaining({
         method: "POST",
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          "X-Conductor-Actor": "ui",
+        },
       }),
     );
     expect(result).toEqual(tasks);
@@ -179,7 +200,10 @@ describe("dispatchGoal", () => {
       "/api/goals/goal-2/dispatch",
       expect.objectContaining({
         method: "POST",
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          "X-Conductor-Actor": "ui",
+        },
       }),
     );
     expect(result).toEqual(agents);
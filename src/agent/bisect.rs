@@ -0,0 +1,479 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::agent::scripts::{self, ScriptOutcome};
+use crate::agent::{repo_lock, worktree};
+use crate::db::queries::{AgentRun, ListFilter};
+use crate::db::Database;
+
+/// One check run per repository at a time; each uses its own scratch worktree
+static CHECKS: LazyLock<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A commit the check was run against
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CheckedCommit {
+    pub commit: String,
+    pub passed: bool,
+    /// Task whose merge commit this is, if any
+    pub task_id: Option<String>,
+}
+
+/// Outcome of a post-merge check, bisected over the goal's merges when it failed
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CheckReport {
+    pub head: String,
+    pub passed: bool,
+    /// The check as run against `head`
+    pub check: ScriptOutcome,
+    /// Merged run whose merge commit first fails the check
+    pub culprit: Option<AgentRun>,
+    /// Why no culprit was found for a failing check
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Every commit tested during bisection, in test order
+    pub tested: Vec<CheckedCommit>,
+    /// What was done about the culprit (`mark`, `revert`, `fix_up`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    /// Fix-up or conflicting-revert task created for the culprit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_up_task_id: Option<String>,
+}
+
+/// Run `command` against the repo's HEAD in a scratch worktree. When it fails, bisect
+/// over the merge commits recorded for the goal's tasks, oldest first, to find the
+/// first merge after which the check fails. Reverted merges are skipped, and only
+/// merges after the last passing check or revert are considered.
+pub async fn check_and_bisect(
+    db: &Database,
+    goal_space_id: &str,
+    repo_path: &Path,
+    command: &str,
+    timeout: Duration,
+) -> Result<CheckReport> {
+    let check_lock = {
+        let key = std::fs::canonicalize(repo_path).unwrap_or_else(|_| repo_path.to_path_buf());
+        CHECKS.lock().unwrap().entry(key).or_default().clone()
+    };
+    let _guard = check_lock.lock().await;
+
+    let head = worktree::rev_parse(repo_path, "HEAD").await?;
+    let scratch = PathBuf::from(worktree::WORKTREE_BASE)
+        .join(format!("check-{}", &uuid::Uuid::new_v4().to_string()[..8]));
+    tokio::fs::create_dir_all(worktree::WORKTREE_BASE)
+        .await
+        .context("Failed to create worktree base directory")?;
+    {
+        let _lock = repo_lock::lock(repo_path, "check_worktree").await;
+        let scratch_str = scratch.to_string_lossy();
+        let output = repo_lock::git(
+            repo_path,
+            repo_path,
+            &["worktree", "add", "--detach", &scratch_str, &head],
+        )
+        .await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("Failed to create check worktree: {}", stderr.trim());
+        }
+    }
+
+    let result = bisect_in(
+        db,
+        goal_space_id,
+        repo_path,
+        &scratch,
+        &head,
+        command,
+        timeout,
+    )
+    .await;
+
+    if let Err(e) = worktree::remove_worktree(repo_path, &scratch).await {
+        tracing::warn!(
+            "Failed to remove check worktree {}: {}",
+            scratch.display(),
+            e
+        );
+    }
+    result
+}
+
+async fn bisect_in(
+    db: &Database,
+    goal_space_id: &str,
+    repo_path: &Path,
+    scratch: &Path,
+    head: &str,
+    command: &str,
+    timeout: Duration,
+) -> Result<CheckReport> {
+    let check = run_check(scratch, head, command, timeout).await?;
+    let mut report = CheckReport {
+        head: head.to_string(),
+        passed: check.success,
+        check,
+        culprit: None,
+        note: None,
+        tested: Vec::new(),
+        action: None,
+        follow_up_task_id: None,
+    };
+    if report.passed {
+        return Ok(report);
+    }
+
+    // Task merges on main's first-parent history, oldest first
+    let output = Command::new("git")
+        .args(["rev-list", "--first-parent", head])
+        .current_dir(repo_path)
+        .output()
+        .await
+        .context("Failed to run git rev-list")?;
+    let position: HashMap<String, usize> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .enumerate()
+        .map(|(i, sha)| (sha.to_string(), i))
+        .collect();
+
+    // Main is known good at the last passing check, and a revert removes an earlier
+    // culprit; only merges after the latest of those can have broken the check
+    let since = db
+        .list_goal_history_filtered(
+            goal_space_id,
+            &ListFilter {
+                event_type: Some("post_merge_check_passed,task_reverted".into()),
+                ..Default::default()
+            },
+        )?
+        .into_iter()
+        .filter_map(|entry| {
+            let metadata = entry.metadata?;
+            let commit = match entry.event_type.as_str() {
+                "task_reverted" => metadata.get("revert_commit")?,
+                _ => metadata.get("head")?,
+            };
+            position.get(commit.as_str()?).copied()
+        })
+        .min();
+
+    let mut merges: Vec<(usize, AgentRun)> = db
+        .list_merged_agent_runs(goal_space_id)?
+        .into_iter()
+        .filter_map(|run| {
+            let pos = *position.get(run.merge_commit.as_deref()?)?;
+            Some((pos, run))
+        })
+        .filter(|(pos, _)| since.is_none_or(|since| *pos < since))
+        .collect();
+    merges.sort_by_key(|(pos, _)| std::cmp::Reverse(*pos));
+    let merges: Vec<AgentRun> = merges.into_iter().map(|(_, run)| run).collect();
+
+    if merges.is_empty() {
+        report.note = Some(match since {
+            Some(_) => "No task merges since the last passing check or revert".to_string(),
+            None => "No merged tasks on main to bisect".to_string(),
+        });
+        return Ok(report);
+    }
+
+    // Find the first merge that fails; HEAD is known to fail
+    let (mut lo, mut hi) = (0, merges.len());
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        let run = &merges[mid];
+        let commit = run.merge_commit.clone().unwrap_or_default();
+        let passed = run_check(scratch, &commit, command, timeout).await?.success;
+        report.tested.push(CheckedCommit {
+            commit,
+            passed,
+            task_id: Some(run.task_id.clone()),
+        });
+        if passed {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+
+    if lo == merges.len() {
+        report.note =
+            Some("The check passes after the last task merge; the regression came later".into());
+        return Ok(report);
+    }
+    if lo == 0 {
+        // Make sure main was healthy before the first task landed
+        let first = merges[0].merge_commit.clone().unwrap_or_default();
+        let base = worktree::rev_parse(repo_path, &format!("{}^1", first)).await?;
+        let passed = run_check(scratch, &base, command, timeout).await?.success;
+        report.tested.push(CheckedCommit {
+            commit: base,
+            passed,
+            task_id: None,
+        });
+        if !passed {
+            report.note = Some("The check already failed before the first task merge".into());
+            return Ok(report);
+        }
+    }
+
+    report.culprit = Some(merges[lo].clone());
+    Ok(report)
+}
+
+/// Check out `commit` in the scratch worktree and run the check there
+async fn run_check(
+    scratch: &Path,
+    commit: &str,
+    command: &str,
+    timeout: Duration,
+) -> Result<ScriptOutcome> {
+    let output = Command::new("git")
+        .args(["checkout", "-q", "--detach", commit])
+        .current_dir(scratch)
+        .output()
+        .await
+        .context("Failed to run git checkout")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("Failed to check out {}: {}", commit, stderr.trim());
+    }
+    scripts::run_script(
+        command,
+        scratch,
+        timeout,
+        &[("CONDUCTOR_COMMIT", commit.to_string())],
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{Actor, CreateGoalSpace, CreateTask};
+
+    fn run_git(dir: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_bisect_finds_first_failing_merge() {
+        let repo = std::env::temp_dir().join(format!("conductor-bisect-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+        run_git(&repo, &["config", "user.email", "test@example.com"]);
+        run_git(&repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("README.md"), "hello\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "init"]);
+
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: repo.to_string_lossy().into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+
+        // Four tasks land in order; the third one breaks the check
+        let mut tasks = Vec::new();
+        for (i, file) in ["a.txt", "b.txt", "broken.txt", "d.txt"].iter().enumerate() {
+            let task = db
+                .create_task(
+                    &goal.id,
+                    &CreateTask {
+                        title: format!("Task {}", i),
+                        description: "D".into(),
+                        priority: 0,
+                        depends_on: vec![],
+                        settings: Default::default(),
+                    },
+                )
+                .unwrap();
+            let branch = format!("task-{}", i);
+            run_git(&repo, &["checkout", "-q", "-b", &branch]);
+            std::fs::write(repo.join(file), "x\n").unwrap();
+            run_git(&repo, &["add", "."]);
+            run_git(&repo, &["commit", "-q", "-m", file]);
+            run_git(&repo, &["checkout", "-q", "main"]);
+            run_git(&repo, &["merge", "-q", "--no-ff", &branch, "-m", "merge"]);
+            let merge = run_git(&repo, &["rev-parse", "HEAD"]);
+            let run = db
+                .create_agent_run(&task.id, &goal.id, None, Some(&branch), "sonnet", None)
+                .unwrap();
+            db.set_agent_run_merge_commit(&run.id, &merge).unwrap();
+            tasks.push(task);
+        }
+
+        let report = check_and_bisect(
+            &db,
+            &goal.id,
+            &repo,
+            "test ! -f broken.txt",
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+        assert!(!report.passed);
+        assert_eq!(report.culprit.unwrap().task_id, tasks[2].id);
+        assert!(report.tested.len() <= 3);
+
+        let report = check_and_bisect(&db, &goal.id, &repo, "true", Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert!(report.passed);
+        assert!(report.tested.is_empty());
+
+        // The scratch worktrees were cleaned up
+        let worktrees = run_git(&repo, &["worktree", "list"]);
+        assert_eq!(worktrees.lines().count(), 1);
+
+        std::fs::remove_dir_all(&repo).ok();
+    }
+
+    /// Merge a branch adding `file` to main and record it as a task's merged run
+    fn land(db: &Database, repo: &Path, goal_id: &str, file: &str) -> AgentRun {
+        let task = db
+            .create_task(
+                goal_id,
+                &CreateTask {
+                    title: format!("Add {}", file),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let branch = format!("add-{}", file.replace('.', "-"));
+        run_git(repo, &["checkout", "-q", "-b", &branch]);
+        std::fs::write(repo.join(file), "x\n").unwrap();
+        run_git(repo, &["add", "."]);
+        run_git(repo, &["commit", "-q", "-m", file]);
+        run_git(repo, &["checkout", "-q", "main"]);
+        run_git(repo, &["merge", "-q", "--no-ff", &branch, "-m", "merge"]);
+        let merge = run_git(repo, &["rev-parse", "HEAD"]);
+        let run = db
+            .create_agent_run(&task.id, goal_id, None, Some(&branch), "sonnet", None)
+            .unwrap();
+        db.set_agent_run_merge_commit(&run.id, &merge).unwrap();
+        db.get_agent_run(&run.id).unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_bisect_skips_reverted_regression() {
+        let repo = std::env::temp_dir().join(format!("conductor-bisect-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+        run_git(&repo, &["config", "user.email", "test@example.com"]);
+        run_git(&repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("README.md"), "hello\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "init"]);
+
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: repo.to_string_lossy().into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let command = "test ! -f first-bug.txt && test ! -f second-bug.txt";
+
+        // The first regression is found and reverted; work merged after it still
+        // carries the bug until the revert lands
+        land(&db, &repo, &goal.id, "a.txt");
+        let first = land(&db, &repo, &goal.id, "first-bug.txt");
+        land(&db, &repo, &goal.id, "b.txt");
+        let report = check_and_bisect(&db, &goal.id, &repo, command, Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert_eq!(report.culprit.unwrap().id, first.id);
+
+        let merge = first.merge_commit.clone().unwrap();
+        run_git(&repo, &["revert", "--no-edit", "-m", "1", &merge]);
+        let revert = run_git(&repo, &["rev-parse", "HEAD"]);
+        db.insert_agent_event(&first.id, "reverted", None, "Reverted", None, None)
+            .unwrap();
+        db.insert_goal_history(
+            &goal.id,
+            Actor::System,
+            "task_reverted",
+            "Reverted",
+            Some(serde_json::json!({"task_id": first.task_id, "revert_commit": revert})),
+        )
+        .unwrap();
+
+        // The second regression is blamed on its own merge, not on the reverted one
+        // or on the merges that landed while the first was still on main
+        land(&db, &repo, &goal.id, "c.txt");
+        let second = land(&db, &repo, &goal.id, "second-bug.txt");
+        let report = check_and_bisect(&db, &goal.id, &repo, command, Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert!(!report.passed);
+        assert_eq!(report.culprit.unwrap().id, second.id);
+        assert!(report
+            .tested
+            .iter()
+            .all(|checked| checked.task_id.as_deref() != Some(first.task_id.as_str())));
+
+        // After a passing check, only later merges are bisected
+        db.insert_agent_event(&second.id, "reverted", None, "Reverted", None, None)
+            .unwrap();
+        run_git(
+            &repo,
+            &[
+                "revert",
+                "--no-edit",
+                "-m",
+                "1",
+                second.merge_commit.as_deref().unwrap(),
+            ],
+        );
+        let report = check_and_bisect(&db, &goal.id, &repo, command, Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert!(report.passed);
+        let head = run_git(&repo, &["rev-parse", "HEAD"]);
+        db.insert_goal_history(
+            &goal.id,
+            Actor::System,
+            "post_merge_check_passed",
+            "Passed",
+            Some(serde_json::json!({"head": head})),
+        )
+        .unwrap();
+        std::fs::write(repo.join("first-bug.txt"), "x\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "direct commit"]);
+        let report = check_and_bisect(&db, &goal.id, &repo, command, Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert!(report.culprit.is_none());
+        assert!(report.tested.is_empty());
+        assert_eq!(
+            report.note.as_deref(),
+            Some("No task merges since the last passing check or revert")
+        );
+
+        std::fs::remove_dir_all(&repo).ok();
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tokio::process::Command;
+
+use crate::agent::{repo_lock, worktree};
+
+/// Why the user's checkout isn't safe to merge a branch into
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckoutProblem {
+    /// Tracked files have uncommitted changes (`git status --porcelain` lines)
+    Dirty(Vec<String>),
+    DetachedHead,
+    /// Checked out on a branch other than the configured `target_branch`
+    UnexpectedBranch {
+        expected: String,
+        actual: String,
+    },
+}
+
+impl std::fmt::Display for CheckoutProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckoutProblem::Dirty(files) => write!(
+                f,
+                "checkout has uncommitted changes to {} file(s)",
+                files.len()
+            ),
+            CheckoutProblem::DetachedHead => write!(f, "checkout has a detached HEAD"),
+            CheckoutProblem::UnexpectedBranch { expected, actual } => {
+                write!(f, "checkout is on {} instead of {}", actual, expected)
+            }
+        }
+    }
+}
+
+/// Check whether a merge can safely run in the repo's main checkout.
+/// Untracked files are ignored; git refuses a merge that would overwrite one.
+pub async fn inspect(
+    repo_path: &Path,
+    expected_branch: Option<&str>,
+) -> Result<Option<CheckoutProblem>> {
+    let Ok(branch) = worktree::current_branch(repo_path).await else {
+        return Ok(Some(CheckoutProblem::DetachedHead));
+    };
+    if let Some(expected) = expected_branch {
+        if branch != expected {
+            return Ok(Some(CheckoutProblem::UnexpectedBranch {
+                expected: expected.to_string(),
+                actual: branch,
+            }));
+        }
+    }
+
+    let output = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .current_dir(repo_path)
+        .output()
+        .await
+        .context("Failed to run git status")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git status failed: {}", stderr.trim());
+    }
+    let dirty: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.to_string())
+        .collect();
+    if !dirty.is_empty() {
+        return Ok(Some(CheckoutProblem::Dirty(dirty)));
+    }
+
+    Ok(None)
+}
+
+/// Stash uncommitted changes to tracked files in the main checkout
+pub async fn stash(repo_path: &Path, message: &str) -> Result<()> {
+    let _lock = repo_lock::lock(repo_path, "stash").await;
+    let output = repo_lock::git(
+        repo_path,
+        repo_path,
+        &["stash", "push", "--quiet", "-m", message],
+    )
+    .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git stash failed: {}", stderr.trim());
+    }
+    Ok(())
+}
+
+/// Restore the most recent stash. On conflict the stash is kept so nothing is lost.
+pub async fn unstash(repo_path: &Path) -> Result<()> {
+    let _lock = repo_lock::lock(repo_path, "unstash").await;
+    let output = repo_lock::git(repo_path, repo_path, &["stash", "pop", "--quiet"]).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "Could not restore stashed changes (they are kept in `git stash list`): {}",
+            stderr.trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+    }
+
+    #[tokio::test]
+    async fn test_inspect_and_stash() {
+        let repo =
+            std::env::temp_dir().join(format!("conductor-checkout-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+        run_git(&repo, &["config", "user.email", "test@example.com"]);
+        run_git(&repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("README.md"), "hello\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "init"]);
+
+        // Untracked files don't count
+        std::fs::write(repo.join("notes.txt"), "scratch\n").unwrap();
+        assert_eq!(inspect(&repo, Some("main")).await.unwrap(), None);
+
+        assert_eq!(
+            inspect(&repo, Some("develop")).await.unwrap(),
+            Some(CheckoutProblem::UnexpectedBranch {
+                expected: "develop".into(),
+                actual: "main".into()
+            })
+        );
+
+        std::fs::write(repo.join("README.md"), "editing\n").unwrap();
+        let problem = inspect(&repo, None).await.unwrap().unwrap();
+        assert_eq!(problem, CheckoutProblem::Dirty(vec![" M README.md".into()]));
+        assert_eq!(
+            problem.to_string(),
+            "checkout has uncommitted changes to 1 file(s)"
+        );
+
+        stash(&repo, "test").await.unwrap();
+        assert_eq!(inspect(&repo, None).await.unwrap(), None);
+        unstash(&repo).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(repo.join("README.md")).unwrap(),
+            "editing\n"
+        );
+
+        run_git(&repo, &["checkout", "-q", "--detach"]);
+        assert_eq!(
+            inspect(&repo, None).await.unwrap(),
+            Some(CheckoutProblem::DetachedHead)
+        );
+
+        std::fs::remove_dir_all(&repo).ok();
+    }
+}
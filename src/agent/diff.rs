@@ -0,0 +1,406 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::agent::worktree;
+use crate::db::queries::AgentRun;
+
+/// Where the changes for an agent run were read from
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffSource {
+    /// The run's live worktree, including uncommitted edits and new files
+    Worktree,
+    /// The merge commit recorded when the run's branch landed
+    MergeCommit,
+    /// The run's branch, still present in the repository
+    Branch,
+}
+
+/// A resolved diff range for an agent run
+#[derive(Debug, Clone)]
+pub struct DiffTarget {
+    pub source: DiffSource,
+    /// Directory the git commands run in (worktree or repository)
+    pub dir: PathBuf,
+    /// Merge base the changes are compared against
+    pub base: String,
+    /// Tip of the changes, or `None` to compare against the working tree
+    pub head: Option<String>,
+}
+
+/// Per-file line counts; `None` for binary files
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FileStat {
+    pub path: String,
+    pub additions: Option<u64>,
+    pub deletions: Option<u64>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AgentDiff {
+    pub source: DiffSource,
+    pub base: String,
+    pub head: Option<String>,
+    pub files: Vec<FileStat>,
+    pub additions: u64,
+    pub deletions: u64,
+    pub diff: String,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CommitInfo {
+    pub sha: String,
+    pub author: String,
+    pub date: String,
+    pub subject: String,
+}
+
+/// Work out where to read an agent run's changes from.
+///
+/// A live worktree wins, then the recorded merge commit, then the branch.
+/// Returns `None` if none of them is available any more.
+pub async fn resolve_target(repo_path: &Path, run: &AgentRun) -> Result<Option<DiffTarget>> {
+    if let Some(ref wt) = run.worktree_path {
+        let wt = PathBuf::from(wt);
+        if wt.join(".git").exists() {
+            let main = worktree::current_branch(repo_path).await?;
+            let base = git(&wt, &["merge-base", "HEAD", &main]).await?;
+            return Ok(Some(DiffTarget {
+                source: DiffSource::Worktree,
+                dir: wt,
+                base,
+                head: None,
+            }));
+        }
+    }
+
+    if let Some(ref merge_commit) = run.merge_commit {
+        let tip = worktree::rev_parse(repo_path, &format!("{}^2", merge_commit)).await?;
+        let base = git(
+            repo_path,
+            &["merge-base", &format!("{}^1", merge_commit), &tip],
+        )
+        .await?;
+        return Ok(Some(DiffTarget {
+            source: DiffSource::MergeCommit,
+            dir: repo_path.to_path_buf(),
+            base,
+            head: Some(tip),
+        }));
+    }
+
+    if let Some(ref branch) = run.branch {
+        if let Ok(tip) = worktree::rev_parse(repo_path, &format!("refs/heads/{}", branch)).await {
+            let main = worktree::current_branch(repo_path).await?;
+            let base = git(repo_path, &["merge-base", &main, &tip]).await?;
+            return Ok(Some(DiffTarget {
+                source: DiffSource::Branch,
+                dir: repo_path.to_path_buf(),
+                base,
+                head: Some(tip),
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Compute the unified diff and per-file stats for a target
+pub async fn diff(target: &DiffTarget) -> Result<AgentDiff> {
+    let mut range = vec![target.base.as_str()];
+    if let Some(ref head) = target.head {
+        range.push(head.as_str());
+    }
+
+    let mut numstat_args = vec!["diff", "--numstat"];
+    numstat_args.extend(&range);
+    let mut files = parse_numstat(&git_raw(&target.dir, &numstat_args).await?);
+
+    let mut diff_args = vec!["diff"];
+    diff_args.extend(&range);
+    let mut diff = git_raw(&target.dir, &diff_args).await?;
+
+    // `git diff` against the working tree leaves out files the agent created
+    // but hasn't added yet, so diff each of them against /dev/null
+    if target.head.is_none() {
+        let untracked = git_raw(
+            &target.dir,
+            &["ls-files", "--others", "--exclude-standard", "-z"],
+        )
+        .await?;
+        for path in untracked.split('\0').filter(|p| !p.is_empty()) {
+            let numstat =
+                git_no_index(&target.dir, &["--numstat", "--", "/dev/null", path]).await?;
+            files.extend(parse_numstat(&numstat).into_iter().map(|f| FileStat {
+                path: path.to_string(),
+                ..f
+            }));
+            diff.push_str(&git_no_index(&target.dir, &["--", "/dev/null", path]).await?);
+        }
+    }
+
+    Ok(AgentDiff {
+        source: target.source,
+        base: target.base.clone(),
+        head: target.head.clone(),
+        additions: files.iter().filter_map(|f| f.additions).sum(),
+        deletions: files.iter().filter_map(|f| f.deletions).sum(),
+        files,
+        diff,
+    })
+}
+
+/// List the commits between the merge base and the tip of a target, oldest first
+pub async fn commits(target: &DiffTarget) -> Result<Vec<CommitInfo>> {
+    let range = format!(
+        "{}..{}",
+        target.base,
+        target.head.as_deref().unwrap_or("HEAD")
+    );
+    let output = git_raw(
+        &target.dir,
+        &[
+            "log",
+            "--reverse",
+            "--format=%H%x1f%an%x1f%aI%x1f%s",
+            &range,
+        ],
+    )
+    .await?;
+    Ok(parse_log(&output))
+}
+
+/// Parse `git diff --numstat` output
+pub fn parse_numstat(output: &str) -> Vec<FileStat> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let additions = parts.next()?;
+            let deletions = parts.next()?;
+            let path = parts.next()?;
+            Some(FileStat {
+                path: path.to_string(),
+                additions: additions.parse().ok(),
+                deletions: deletions.parse().ok(),
+            })
+        })
+        .collect()
+}
+
+/// Parse `git log` output produced with a `%x1f`-separated format
+pub fn parse_log(output: &str) -> Vec<CommitInfo> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(4, '\x1f');
+            Some(CommitInfo {
+                sha: parts.next()?.to_string(),
+                author: parts.next()?.to_string(),
+                date: parts.next()?.to_string(),
+                subject: parts.next().unwrap_or("").to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Run a git command and return its trimmed stdout
+async fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    Ok(git_raw(dir, args).await?.trim().to_string())
+}
+
+/// Run a git command and return its stdout unmodified
+async fn git_raw(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run git {}", args.join(" ")))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git {} failed: {}", args.join(" "), stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Run `git diff --no-index`, which exits 1 when the inputs differ
+async fn git_no_index(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(["diff", "--no-index"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .await
+        .context("Failed to run git diff --no-index")?;
+
+    if !matches!(output.status.code(), Some(0) | Some(1)) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git diff --no-index failed: {}", stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::goal::task::RunStatus;
+
+    #[test]
+    fn test_parse_numstat() {
+        let output = "3\t1\tsrc/main.rs\n-\t-\tlogo.png\n10\t0\tdocs/new file.md\n";
+        let files = parse_numstat(output);
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].path, "src/main.rs");
+        assert_eq!(files[0].additions, Some(3));
+        assert_eq!(files[0].deletions, Some(1));
+        assert_eq!(files[1].additions, None);
+        assert_eq!(files[2].path, "docs/new file.md");
+    }
+
+    #[test]
+    fn test_parse_numstat_empty() {
+        assert!(parse_numstat("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let output = "abc123\x1fAda\x1f2024-01-01T00:00:00Z\x1fAdd login\n\
+                      def456\x1fAda\x1f2024-01-02T00:00:00Z\x1fFix: a | b\n";
+        let commits = parse_log(output);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].sha, "abc123");
+        assert_eq!(commits[0].subject, "Add login");
+        assert_eq!(commits[1].subject, "Fix: a | b");
+    }
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(status.status.success(), "git {:?} failed", args);
+    }
+
+    fn agent_run(branch: &str) -> AgentRun {
+        AgentRun {
+            id: "run-1".into(),
+            task_id: "task-1".into(),
+            goal_space_id: "goal-1".into(),
+            claude_session_id: None,
+            worktree_path: Some("/nonexistent/worktree".into()),
+            branch: Some(branch.into()),
+            status: RunStatus::Done,
+            model: "sonnet".into(),
+            cost_usd: 0.0,
+            input_tokens: 0,
+            output_tokens: 0,
+            max_budget_usd: None,
+            started_at: "2024-01-01T00:00:00Z".into(),
+            last_activity_at: None,
+            finished_at: None,
+            merge_commit: None,
+            base_commit: None,
+            worktree_ready_ms: None,
+            failure_reason: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_branch_then_merge_commit_diff() {
+        let repo = std::env::temp_dir().join(format!("conductor-diff-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+        run_git(&repo, &["config", "user.email", "test@example.com"]);
+        run_git(&repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "init"]);
+
+        run_git(&repo, &["checkout", "-q", "-b", "conductor/test"]);
+        std::fs::write(repo.join("a.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(repo.join("b.txt"), "new\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "Agent work"]);
+        run_git(&repo, &["checkout", "-q", "main"]);
+
+        let mut run = agent_run("conductor/test");
+        let target = resolve_target(&repo, &run).await.unwrap().unwrap();
+        assert_eq!(target.source, DiffSource::Branch);
+        let d = diff(&target).await.unwrap();
+        assert_eq!(d.files.len(), 2);
+        assert_eq!(d.additions, 2);
+        assert!(d.diff.contains("+two"));
+        let c = commits(&target).await.unwrap();
+        assert_eq!(c.len(), 1);
+        assert_eq!(c[0].subject, "Agent work");
+
+        let merge_commit = worktree::merge_branch_to_main(&repo, "conductor/test")
+            .await
+            .unwrap();
+        run_git(&repo, &["branch", "-D", "conductor/test"]);
+        run.merge_commit = Some(merge_commit);
+
+        let target = resolve_target(&repo, &run).await.unwrap().unwrap();
+        assert_eq!(target.source, DiffSource::MergeCommit);
+        let d = diff(&target).await.unwrap();
+        assert_eq!(d.files.len(), 2);
+        assert_eq!(commits(&target).await.unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&repo).ok();
+    }
+
+    #[tokio::test]
+    async fn test_worktree_diff_includes_untracked_files() {
+        let repo = std::env::temp_dir().join(format!("conductor-diff-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+        run_git(&repo, &["config", "user.email", "test@example.com"]);
+        run_git(&repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        std::fs::write(repo.join(".gitignore"), "target/\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "init"]);
+
+        let wt = repo.join("wt");
+        run_git(
+            &repo,
+            &["worktree", "add", "-q", "-b", "conductor/live", "wt"],
+        );
+        std::fs::write(wt.join("a.txt"), "one\ntwo\n").unwrap();
+        std::fs::create_dir_all(wt.join("src")).unwrap();
+        std::fs::write(wt.join("src/new.rs"), "fn main() {}\n").unwrap();
+        std::fs::create_dir_all(wt.join("target")).unwrap();
+        std::fs::write(wt.join("target/out"), "ignored\n").unwrap();
+
+        let mut run = agent_run("conductor/live");
+        run.worktree_path = Some(wt.to_string_lossy().into());
+        let target = resolve_target(&repo, &run).await.unwrap().unwrap();
+        assert_eq!(target.source, DiffSource::Worktree);
+        let d = diff(&target).await.unwrap();
+        let paths: Vec<&str> = d.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "src/new.rs"]);
+        assert_eq!(d.additions, 2);
+        assert!(d.diff.contains("+++ b/src/new.rs"));
+        assert!(d.diff.contains("+fn main() {}"));
+
+        std::fs::remove_dir_all(&repo).ok();
+    }
+
+    #[tokio::test]
+    async fn test_no_source_available() {
+        let repo = std::env::temp_dir().join(format!("conductor-diff-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+
+        let run = agent_run("conductor/gone");
+        assert!(resolve_target(&repo, &run).await.unwrap().is_none());
+
+        std::fs::remove_dir_all(&repo).ok();
+    }
+}
@@ -197,8 +197,8 @@ pub fn parse_stream_json_line(line: &str) -> Option<ParsedEvent> {
     }
 }
 
-/// Store a parsed event in the database and return it
-pub fn store_event(
+/// Store a parsed event through the batched event writer and return it
+pub async fn store_event(
     db: &Database,
     agent_run_id: &str,
     event: &ParsedEvent,
@@ -284,7 +284,7 @@ pub fn store_event(
         ParsedEvent::System { message } => ("system", None, message.clone(), None),
     };
 
-    db.insert_agent_event(
+    db.record_agent_event(
         agent_run_id,
         event_type,
         tool_name,
@@ -292,6 +292,52 @@ pub fn store_event(
         Some(raw_line),
         cost_delta,
     )
+    .await
+}
+
+/// Generate a human-readable summary of a tool's input
+fn summarize_tool_input(tool_name: &str, input: &Value) -> String {
+    match tool_name {
+        "Read" => {
+            let path = input
+                .get("file_path")
+                .and_then(|p| p.as_str())
+                .unwrap_or("?");
+            format!("Reading {}", path)
+        }
+        "Edit" => {
+            let path = input
+                .get("file_path")
+                .and_then(|p| p.as_str())
+                .unwrap_or("?");
+            format!("Editing {}", path)
+        }
+        "Write" => {
+            let path = input
+                .get("file_path")
+                .and_then(|p| p.as_str())
+                .unwrap_or("?");
+            format!("Writing {}", path)
+        }
+        "Bash" => {
+            let cmd = input.get("command").and_then(|c| c.as_str()).unwrap_or("?");
+            let truncated = if cmd.len() > 80 {
+                format!("{}...", &cmd[..80])
+            } else {
+                cmd.to_string()
+            };
+            format!("Running: {}", truncated)
+        }
+        "Grep" => {
+            let pattern = input.get("pattern").and_then(|p| p.as_str()).unwrap_or("?");
+            format!("Searching for '{}'", pattern)
+        }
+        "Glob" => {
+            let pattern = input.get("pattern").and_then(|p| p.as_str()).unwrap_or("?");
+            format!("Finding files matching '{}'", pattern)
+        }
+        _ => format!("Using {}", tool_name),
+    }
 }
 
 #[cfg(test)]
@@ -686,48 +732,3 @@ mod tests {
         }
     }
 }
-
-/// Generate a human-readable summary of a tool's input
-fn summarize_tool_input(tool_name: &str, input: &Value) -> String {
-    match tool_name {
-        "Read" => {
-            let path = input
-                .get("file_path")
-                .and_then(|p| p.as_str())
-                .unwrap_or("?");
-            format!("Reading {}", path)
-        }
-        "Edit" => {
-            let path = input
-                .get("file_path")
-                .and_then(|p| p.as_str())
-                .unwrap_or("?");
-            format!("Editing {}", path)
-        }
-        "Write" => {
-            let path = input
-                .get("file_path")
-                .and_then(|p| p.as_str())
-                .unwrap_or("?");
-            format!("Writing {}", path)
-        }
-        "Bash" => {
-            let cmd = input.get("command").and_then(|c| c.as_str()).unwrap_or("?");
-            let truncated = if cmd.len() > 80 {
-                format!("{}...", &cmd[..80])
-            } else {
-                cmd.to_string()
-            };
-            format!("Running: {}", truncated)
-        }
-        "Grep" => {
-            let pattern = input.get("pattern").and_then(|p| p.as_str()).unwrap_or("?");
-            format!("Searching for '{}'", pattern)
-        }
-        "Glob" => {
-            let pattern = input.get("pattern").and_then(|p| p.as_str()).unwrap_or("?");
-            format!("Finding files matching '{}'", pattern)
-        }
-        _ => format!("Using {}", tool_name),
-    }
-}
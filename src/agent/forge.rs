@@ -0,0 +1,505 @@
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use std::path::{Path, PathBuf};
+
+use crate::agent::diff::{self, CommitInfo, FileStat};
+use crate::agent::worktree;
+use crate::db::queries::{AgentEvent, AgentRun};
+use crate::db::Database;
+
+/// Outcome of a verification command (tests, build, lint) the agent ran
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VerificationResult {
+    pub command: String,
+    pub passed: bool,
+    pub output: String,
+}
+
+/// Everything needed to open a pull request for an agent branch
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PrBundle {
+    pub agent_run_id: String,
+    pub task_id: String,
+    pub goal_space_id: String,
+    /// Branch the changes live on
+    pub head: String,
+    /// Branch the changes should land on
+    pub base: String,
+    pub title: String,
+    /// Rendered markdown description
+    pub body: String,
+    pub task_description: String,
+    pub agent_summary: Option<String>,
+    pub model: String,
+    pub cost_usd: f64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub verification: Vec<VerificationResult>,
+    pub commits: Vec<CommitInfo>,
+    pub files: Vec<FileStat>,
+    pub additions: u64,
+    pub deletions: u64,
+}
+
+/// Where a branch was published and where its bundle was written
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Published {
+    pub forge: String,
+    pub remote: String,
+    pub branch: String,
+    /// Link to the opened pull request, for forges that create one
+    pub url: Option<String>,
+    pub markdown_path: PathBuf,
+    pub json_path: PathBuf,
+}
+
+/// A place finished agent branches can be landed through.
+///
+/// Implementations push the branch and, where the forge supports it,
+/// open a pull request from the bundle.
+pub trait Forge: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Publish the bundle's branch; returns the pull request URL if one was opened
+    fn publish<'a>(
+        &'a self,
+        repo_path: &'a Path,
+        bundle: &'a PrBundle,
+    ) -> BoxFuture<'a, Result<Option<String>>>;
+}
+
+/// Plain git remote: pushes the branch and leaves opening the PR to the team
+pub struct GitRemoteForge {
+    pub remote: String,
+}
+
+impl Forge for GitRemoteForge {
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn publish<'a>(
+        &'a self,
+        repo_path: &'a Path,
+        bundle: &'a PrBundle,
+    ) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move {
+            worktree::push_branch(repo_path, &self.remote, &bundle.head).await?;
+            Ok(None)
+        })
+    }
+}
+
+/// Pick the forge for a configured remote
+pub fn forge_for_remote(remote: &str) -> Box<dyn Forge> {
+    Box::new(GitRemoteForge {
+        remote: remote.to_string(),
+    })
+}
+
+/// Build the PR bundle for an agent run's branch
+pub async fn build_bundle(db: &Database, repo_path: &Path, run: &AgentRun) -> Result<PrBundle> {
+    let head = run.branch.clone().context("Agent run has no branch")?;
+    let task = db.get_task(&run.task_id)?.context("Task not found")?;
+    let events = db.agent_events_with_raw(&run.id)?;
+    let base = worktree::current_branch(repo_path).await?;
+
+    let (commits, files, additions, deletions) = match diff::resolve_target(repo_path, run).await? {
+        Some(target) => {
+            let d = diff::diff(&target).await?;
+            (
+                diff::commits(&target).await?,
+                d.files,
+                d.additions,
+                d.deletions,
+            )
+        }
+        None => (Vec::new(), Vec::new(), 0, 0),
+    };
+
+    let mut bundle = PrBundle {
+        agent_run_id: run.id.clone(),
+        task_id: task.id,
+        goal_space_id: run.goal_space_id.clone(),
+        head,
+        base,
+        title: task.title,
+        body: String::new(),
+        task_description: task.description,
+        agent_summary: agent_summary(&events),
+        model: run.model.clone(),
+        cost_usd: run.cost_usd,
+        input_tokens: run.input_tokens,
+        output_tokens: run.output_tokens,
+        verification: verification_results(&events),
+        commits,
+        files,
+        additions,
+        deletions,
+    };
+    bundle.body = render_markdown(&bundle);
+    Ok(bundle)
+}
+
+/// Push an agent run's branch to its configured remote and write the PR bundle.
+///
+/// `remote` overrides the `push_remote` setting. Returns `None` when no
+/// remote is configured.
+pub async fn publish_run(
+    db: &Database,
+    agent_run_id: &str,
+    remote: Option<&str>,
+) -> Result<Option<Published>> {
+    let run = db
+        .get_agent_run(agent_run_id)?
+        .context("Agent run not found")?;
+    let task = db.get_task(&run.task_id)?.context("Task not found")?;
+    let goal = db
+        .get_goal_space(&run.goal_space_id)?
+        .context("Goal space not found")?;
+
+    let remote = match remote {
+        Some(r) => r.to_string(),
+        None => match crate::goal::space::effective_settings(db, &goal, &task)?.push_remote() {
+            Some(r) => r,
+            None => return Ok(None),
+        },
+    };
+
+    let repo_path = Path::new(&goal.repo_path);
+    let bundle = build_bundle(db, repo_path, &run).await?;
+    let (markdown_path, json_path) = write_bundle(repo_path, &bundle).await?;
+
+    let forge = forge_for_remote(&remote);
+    let url = forge.publish(repo_path, &bundle).await?;
+
+    let published = Published {
+        forge: forge.name().to_string(),
+        remote,
+        branch: bundle.head.clone(),
+        url,
+        markdown_path,
+        json_path,
+    };
+    db.insert_agent_event(
+        agent_run_id,
+        "branch_pushed",
+        None,
+        &format!("Pushed branch {} to {}", published.branch, published.remote),
+        Some(&serde_json::to_string(&published)?),
+        None,
+    )?;
+
+    Ok(Some(published))
+}
+
+/// Write the bundle as markdown and JSON under `.git/conductor/prs/`
+pub async fn write_bundle(repo_path: &Path, bundle: &PrBundle) -> Result<(PathBuf, PathBuf)> {
+    let output = tokio::process::Command::new("git")
+        .args(["rev-parse", "--git-common-dir"])
+        .current_dir(repo_path)
+        .output()
+        .await
+        .context("Failed to run git rev-parse")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("Failed to find git directory: {}", stderr.trim());
+    }
+    let git_dir = repo_path.join(String::from_utf8_lossy(&output.stdout).trim());
+
+    let dir = git_dir.join("conductor").join("prs");
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let markdown_path = dir.join(format!("{}.md", bundle.agent_run_id));
+    let json_path = dir.join(format!("{}.json", bundle.agent_run_id));
+    tokio::fs::write(&markdown_path, &bundle.body).await?;
+    tokio::fs::write(&json_path, serde_json::to_string_pretty(bundle)?).await?;
+
+    Ok((markdown_path, json_path))
+}
+
+/// Render the pull request description
+pub fn render_markdown(bundle: &PrBundle) -> String {
+    let mut md = format!("# {}\n\n", bundle.title);
+
+    md.push_str("## Task\n\n");
+    if bundle.task_description.trim().is_empty() {
+        md.push_str("_No description._\n\n");
+    } else {
+        md.push_str(&format!("{}\n\n", bundle.task_description.trim()));
+    }
+
+    md.push_str("## Agent summary\n\n");
+    match bundle.agent_summary {
+        Some(ref summary) => md.push_str(&format!("{}\n\n", summary.trim())),
+        None => md.push_str("_The agent did not report a summary._\n\n"),
+    }
+
+    md.push_str("## Changes\n\n");
+    md.push_str(&format!(
+        "{} files changed, +{} -{}\n\n",
+        bundle.files.len(),
+        bundle.additions,
+        bundle.deletions
+    ));
+    for file in &bundle.files {
+        let count = |n: Option<u64>| n.map(|n| n.to_string()).unwrap_or_else(|| "-".into());
+        md.push_str(&format!(
+            "- `{}` (+{} -{})\n",
+            file.path,
+            count(file.additions),
+            count(file.deletions)
+        ));
+    }
+    if !bundle.commits.is_empty() {
+        md.push_str("\n### Commits\n\n");
+        for commit in &bundle.commits {
+            let short: String = commit.sha.chars().take(8).collect();
+            md.push_str(&format!("- `{}` {}\n", short, commit.subject));
+        }
+    }
+    md.push('\n');
+
+    md.push_str("## Verification\n\n");
+    if bundle.verification.is_empty() {
+        md.push_str("_No test, build or lint commands were run._\n\n");
+    } else {
+        for check in &bundle.verification {
+            let outcome = if check.passed { "passed" } else { "failed" };
+            md.push_str(&format!("- `{}`: {}\n", check.command, outcome));
+        }
+        md.push('\n');
+    }
+
+    md.push_str("## Cost\n\n");
+    md.push_str(&format!(
+        "{} · ${:.4} · {} input / {} output tokens\n\n",
+        bundle.model, bundle.cost_usd, bundle.input_tokens, bundle.output_tokens
+    ));
+
+    md.push_str(&format!(
+        "---\n`{}` → `{}` · conductor agent `{}`\n",
+        bundle.head, bundle.base, bundle.agent_run_id
+    ));
+    md
+}
+
+/// The agent's final result text, if it reported one
+pub fn agent_summary(events: &[AgentEvent]) -> Option<String> {
+    let event = events.iter().rev().find(|e| e.event_type == "result")?;
+    let from_raw = event
+        .raw_json
+        .as_deref()
+        .and_then(|raw| serde_json::from_str::<serde_json::Value>(raw).ok())
+        .and_then(|v| v.get("result").and_then(|r| r.as_str()).map(String::from))
+        .filter(|s| !s.trim().is_empty());
+    from_raw.or_else(|| Some(event.summary.clone()))
+}
+
+/// Pair each verification command the agent ran with the result that followed it
+pub fn verification_results(events: &[AgentEvent]) -> Vec<VerificationResult> {
+    let mut results = Vec::new();
+    let mut pending: Option<String> = None;
+
+    for event in events {
+        match event.event_type.as_str() {
+            "tool_call" => {
+                pending = None;
+                if event.tool_name.as_deref() == Some("Bash") {
+                    let command = event
+                        .summary
+                        .strip_prefix("Running: ")
+                        .unwrap_or(&event.summary);
+                    if is_verification_command(command) {
+                        pending = Some(command.to_string());
+                    }
+                }
+            }
+            "tool_result" => {
+                if let Some(command) = pending.take() {
+                    let (passed, output) = match event.summary.strip_prefix("[ERROR] ") {
+                        Some(rest) => (false, rest),
+                        None => (
+                            true,
+                            event
+                                .summary
+                                .strip_prefix("[OK] ")
+                                .unwrap_or(&event.summary),
+                        ),
+                    };
+                    results.push(VerificationResult {
+                        command,
+                        passed,
+                        output: output.to_string(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    results
+}
+
+/// Whether a shell command looks like a test, build or lint run
+fn is_verification_command(command: &str) -> bool {
+    const MARKERS: &[&str] = &[
+        "test", "clippy", "lint", "check", "build", "pytest", "tsc", "vet",
+    ];
+    command
+        .split(|c: char| c.is_whitespace() || c == ':' || c == '&' || c == ';')
+        .any(|word| MARKERS.contains(&word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: &str, tool_name: Option<&str>, summary: &str) -> AgentEvent {
+        AgentEvent {
+            id: 0,
+            agent_run_id: "run-1".into(),
+            event_type: event_type.into(),
+            tool_name: tool_name.map(String::from),
+            summary: summary.into(),
+            raw_json: None,
+            cost_delta_usd: None,
+            created_at: "2024-01-01T00:00:00Z".into(),
+        }
+    }
+
+    fn bundle() -> PrBundle {
+        PrBundle {
+            agent_run_id: "run-1".into(),
+            task_id: "task-1".into(),
+            goal_space_id: "goal-1".into(),
+            head: "conductor/run-1-add-login".into(),
+            base: "main".into(),
+            title: "Add login".into(),
+            body: String::new(),
+            task_description: "Add a login form".into(),
+            agent_summary: Some("Added the form and tests".into()),
+            model: "sonnet".into(),
+            cost_usd: 0.5,
+            input_tokens: 100,
+            output_tokens: 50,
+            verification: vec![VerificationResult {
+                command: "cargo test".into(),
+                passed: true,
+                output: String::new(),
+            }],
+            commits: vec![],
+            files: vec![FileStat {
+                path: "src/login.rs".into(),
+                additions: Some(10),
+                deletions: Some(2),
+            }],
+            additions: 10,
+            deletions: 2,
+        }
+    }
+
+    #[test]
+    fn test_verification_results_pairs_calls_and_results() {
+        let events = vec![
+            event("tool_call", Some("Bash"), "Running: cargo test"),
+            event("tool_result", Some("unknown"), "[ERROR] 1 test failed"),
+            event("tool_call", Some("Bash"), "Running: ls -la"),
+            event("tool_result", Some("unknown"), "[OK] files"),
+            event("tool_call", Some("Edit"), "Editing src/lib.rs"),
+            event("tool_result", Some("unknown"), "[OK] "),
+            event("tool_call", Some("Bash"), "Running: cd app && npm test"),
+            event("tool_result", Some("unknown"), "[OK] all passed"),
+        ];
+        let results = verification_results(&events);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].command, "cargo test");
+        assert!(!results[0].passed);
+        assert_eq!(results[0].output, "1 test failed");
+        assert_eq!(results[1].command, "cd app && npm test");
+        assert!(results[1].passed);
+    }
+
+    #[test]
+    fn test_is_verification_command() {
+        assert!(is_verification_command("cargo clippy -- -D warnings"));
+        assert!(is_verification_command("npm run build"));
+        assert!(is_verification_command("go vet ./..."));
+        assert!(!is_verification_command("cat tests.txt"));
+        assert!(!is_verification_command("git status"));
+    }
+
+    #[test]
+    fn test_agent_summary_prefers_full_result_text() {
+        let mut result = event("result", None, "Completed: Done (in=1, out=1)");
+        result.raw_json = Some(r#"{"type":"result","result":"Implemented login"}"#.into());
+        let events = vec![event("text_output", None, "hi"), result];
+        assert_eq!(agent_summary(&events).as_deref(), Some("Implemented login"));
+
+        let events = vec![event("result", None, "Completed: ok (in=1, out=1)")];
+        assert_eq!(
+            agent_summary(&events).as_deref(),
+            Some("Completed: ok (in=1, out=1)")
+        );
+        assert!(agent_summary(&[]).is_none());
+    }
+
+    #[test]
+    fn test_render_markdown_sections() {
+        let md = render_markdown(&bundle());
+        assert!(md.starts_with("# Add login\n"));
+        assert!(md.contains("Add a login form"));
+        assert!(md.contains("Added the form and tests"));
+        assert!(md.contains("1 files changed, +10 -2"));
+        assert!(md.contains("- `cargo test`: passed"));
+        assert!(md.contains("$0.5000"));
+        assert!(md.contains("`conductor/run-1-add-login` → `main`"));
+    }
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+    }
+
+    #[tokio::test]
+    async fn test_git_remote_forge_pushes_to_bare_repo() {
+        let root = std::env::temp_dir().join(format!("conductor-forge-{}", uuid::Uuid::new_v4()));
+        let repo = root.join("repo");
+        let remote = root.join("remote.git");
+        std::fs::create_dir_all(&repo).unwrap();
+        std::fs::create_dir_all(&remote).unwrap();
+        run_git(&remote, &["init", "-q", "--bare"]);
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+        run_git(&repo, &["config", "user.email", "test@example.com"]);
+        run_git(&repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "init"]);
+        run_git(&repo, &["branch", "conductor/run-1-add-login"]);
+
+        let forge = forge_for_remote(&remote.to_string_lossy());
+        assert_eq!(forge.name(), "git");
+        let url = forge.publish(&repo, &bundle()).await.unwrap();
+        assert!(url.is_none());
+
+        let pushed = worktree::rev_parse(&remote, "refs/heads/conductor/run-1-add-login")
+            .await
+            .unwrap();
+        let local = worktree::rev_parse(&repo, "HEAD").await.unwrap();
+        assert_eq!(pushed, local);
+
+        let (md, json) = write_bundle(&repo, &bundle()).await.unwrap();
+        assert!(md.starts_with(repo.join(".git")));
+        let parsed: PrBundle =
+            serde_json::from_str(&std::fs::read_to_string(json).unwrap()).unwrap();
+        assert_eq!(parsed.head, "conductor/run-1-add-login");
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
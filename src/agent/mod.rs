@@ -1,3 +1,11 @@
+pub mod bisect;
+pub mod checkout;
+pub mod diff;
 pub mod event_parser;
+pub mod forge;
+pub mod pool;
+pub mod repo_lock;
+pub mod scripts;
 pub mod session;
+pub mod stack;
 pub mod worktree;
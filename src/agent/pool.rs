@@ -0,0 +1,477 @@
+use anyhow::{Context, Result};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+use crate::agent::{repo_lock, worktree};
+use crate::db::queries::{CacheStrategy, GoalSettings};
+
+/// Shared build-directory caches, one subdirectory per repository
+pub const CACHE_BASE: &str = "/tmp/conductor/cache";
+/// Directory under `WORKTREE_BASE` holding idle pooled worktrees
+pub const POOL_DIR: &str = "pool";
+/// Suffix of pooled worktrees still being created, which can't be checked out yet
+const WARMING_SUFFIX: &str = ".warming";
+
+/// How an agent's worktree is provisioned
+#[derive(Debug, Clone, Default)]
+pub struct WorktreeSetup {
+    /// Idle worktrees to keep ready per repository; 0 disables the pool
+    pub pool_size: u32,
+    /// Build directories (relative to the repo root) to share or clone
+    pub cache_dirs: Vec<String>,
+    pub cache_strategy: CacheStrategy,
+    /// Run in the worktree before the agent starts
+    pub setup_command: Option<String>,
+    /// Run in the worktree after the agent finishes, before it is released
+    pub teardown_command: Option<String>,
+    pub script_timeout: Duration,
+}
+
+impl WorktreeSetup {
+    pub fn from_settings(settings: &GoalSettings) -> Self {
+        Self {
+            pool_size: settings.warm_pool_size(),
+            cache_dirs: settings.cache_dirs(),
+            cache_strategy: settings.cache_strategy(),
+            setup_command: settings.setup_command(),
+            teardown_command: settings.teardown_command(),
+            script_timeout: Duration::from_secs(settings.script_timeout_secs()),
+        }
+    }
+}
+
+/// A worktree checked out for an agent
+#[derive(Debug)]
+pub struct ReadyWorktree {
+    pub path: PathBuf,
+    /// Whether it came from the warm pool rather than a fresh `git worktree add`
+    pub from_pool: bool,
+    pub ready_ms: u64,
+}
+
+/// Get a worktree for an agent on a new branch, from the warm pool when one is idle.
+/// A pooled worktree is reset to `start_point` (or the repo's HEAD) and keeps its
+/// ignored build directories.
+pub async fn acquire(
+    repo_path: &Path,
+    agent_id: &str,
+    branch: &str,
+    start_point: Option<&str>,
+    setup: &WorktreeSetup,
+) -> Result<ReadyWorktree> {
+    let started = Instant::now();
+    let dest = PathBuf::from(worktree::WORKTREE_BASE).join(agent_id);
+
+    let pooled = if setup.pool_size > 0 {
+        match checkout_pooled(repo_path, &dest, branch, start_point).await {
+            Ok(pooled) => pooled,
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to check out pooled worktree for {}: {}",
+                    repo_path.display(),
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let from_pool = pooled.is_some();
+    let path = match pooled {
+        Some(path) => path,
+        None => worktree::create_worktree(repo_path, agent_id, branch, start_point).await?,
+    };
+
+    // Caches only save time, so a failure here doesn't stop the agent
+    if let Err(e) = prepare_caches(repo_path, &path, setup).await {
+        tracing::warn!(
+            "Failed to prepare build caches in {}: {}",
+            path.display(),
+            e
+        );
+    }
+
+    Ok(ReadyWorktree {
+        path,
+        from_pool,
+        ready_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Return a finished agent's worktree to the pool, or remove it if the pool is full
+pub async fn release(repo_path: &Path, worktree_path: &Path, pool_size: u32) -> Result<()> {
+    if pool_size > 0 {
+        match return_to_pool(repo_path, worktree_path, pool_size).await {
+            Ok(true) => return Ok(()),
+            Ok(false) => {}
+            Err(e) => tracing::warn!(
+                "Failed to return worktree {} to the pool: {}",
+                worktree_path.display(),
+                e
+            ),
+        }
+    }
+    worktree::remove_worktree(repo_path, worktree_path).await
+}
+
+/// Create idle worktrees until the pool for a repository holds `pool_size`.
+/// Returns how many were added.
+pub async fn replenish(repo_path: &Path, setup: &WorktreeSetup) -> Result<usize> {
+    let mut added = 0;
+    loop {
+        let warming = {
+            let _lock = repo_lock::lock(repo_path, "replenish_pool").await;
+            if idle_worktrees(repo_path).await?.len() >= setup.pool_size as usize {
+                break;
+            }
+            let dir = pool_dir(repo_path);
+            tokio::fs::create_dir_all(&dir)
+                .await
+                .context("Failed to create pool directory")?;
+            let warming = dir.join(format!("{}{}", uuid::Uuid::new_v4(), WARMING_SUFFIX));
+            let warming_str = warming.to_str().context("Pool path is not UTF-8")?;
+            git_ok(
+                repo_path,
+                repo_path,
+                &["worktree", "add", "--detach", warming_str, "HEAD"],
+            )
+            .await?;
+            warming
+        };
+
+        // Warm the caches outside the lock; the worktree isn't visible to checkouts yet
+        if let Err(e) = prepare_caches(repo_path, &warming, setup).await {
+            tracing::warn!(
+                "Failed to prepare build caches in {}: {}",
+                warming.display(),
+                e
+            );
+        }
+
+        let _lock = repo_lock::lock(repo_path, "replenish_pool").await;
+        let ready = PathBuf::from(
+            warming
+                .to_str()
+                .context("Pool path is not UTF-8")?
+                .trim_end_matches(WARMING_SUFFIX),
+        );
+        move_worktree(repo_path, &warming, &ready).await?;
+        added += 1;
+    }
+
+    if added > 0 {
+        tracing::info!(
+            "Added {} worktrees to the pool for {}",
+            added,
+            repo_path.display()
+        );
+    }
+    Ok(added)
+}
+
+/// Idle pooled worktrees for a repository that are ready to check out
+pub async fn idle_worktrees(repo_path: &Path) -> Result<Vec<PathBuf>> {
+    let dir = pool_dir(repo_path);
+    let mut idle = Vec::new();
+    let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+        return Ok(idle);
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let name = entry.file_name();
+        if path.is_dir() && !name.to_string_lossy().ends_with(WARMING_SUFFIX) {
+            idle.push(path);
+        }
+    }
+    idle.sort();
+    Ok(idle)
+}
+
+/// Move an idle pooled worktree to `dest` and reset it onto a new branch
+async fn checkout_pooled(
+    repo_path: &Path,
+    dest: &Path,
+    branch: &str,
+    start_point: Option<&str>,
+) -> Result<Option<PathBuf>> {
+    let _lock = repo_lock::lock(repo_path, "checkout_pooled").await;
+    let Some(pooled) = idle_worktrees(repo_path).await?.into_iter().next() else {
+        return Ok(None);
+    };
+
+    if let Err(e) = move_worktree(repo_path, &pooled, dest).await {
+        // Drop the broken entry so the next checkout doesn't hit it again
+        let pooled_str = pooled.to_string_lossy();
+        let _ = repo_lock::git(
+            repo_path,
+            repo_path,
+            &["worktree", "remove", "--force", &pooled_str],
+        )
+        .await;
+        let _ = tokio::fs::remove_dir_all(&pooled).await;
+        return Err(e);
+    }
+
+    let start = match start_point {
+        Some(start) => start.to_string(),
+        None => worktree::rev_parse(repo_path, "HEAD").await?,
+    };
+    git_ok(repo_path, dest, &["checkout", "-f", "-B", branch, &start]).await?;
+    git_ok(repo_path, dest, &["clean", "-fd"]).await?;
+
+    tracing::info!(
+        "Checked out pooled worktree at {} on branch {}",
+        dest.display(),
+        branch
+    );
+    Ok(Some(dest.to_path_buf()))
+}
+
+/// Detach a worktree from its branch, clean it and park it in the pool.
+/// Returns false if the pool is already full.
+async fn return_to_pool(repo_path: &Path, worktree_path: &Path, pool_size: u32) -> Result<bool> {
+    let _lock = repo_lock::lock(repo_path, "release_worktree").await;
+    if idle_worktrees(repo_path).await?.len() >= pool_size as usize {
+        return Ok(false);
+    }
+
+    // Detach so the branch can be merged, rebased or deleted
+    git_ok(repo_path, worktree_path, &["checkout", "-f", "--detach"]).await?;
+    git_ok(repo_path, worktree_path, &["clean", "-fd"]).await?;
+
+    let dir = pool_dir(repo_path);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .context("Failed to create pool directory")?;
+    move_worktree(
+        repo_path,
+        worktree_path,
+        &dir.join(uuid::Uuid::new_v4().to_string()),
+    )
+    .await?;
+
+    tracing::info!("Returned worktree {} to the pool", worktree_path.display());
+    Ok(true)
+}
+
+/// Link or clone the configured build directories into a worktree.
+/// Directories that already exist (a warm pooled worktree) are left alone.
+pub async fn prepare_caches(
+    repo_path: &Path,
+    worktree_path: &Path,
+    setup: &WorktreeSetup,
+) -> Result<()> {
+    for dir in &setup.cache_dirs {
+        let rel = Path::new(dir);
+        if !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+            tracing::warn!("Ignoring cache dir {} (must be relative to the repo)", dir);
+            continue;
+        }
+
+        let target = worktree_path.join(rel);
+        if target.exists() || target.is_symlink() {
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        match setup.cache_strategy {
+            CacheStrategy::Share => {
+                let shared = PathBuf::from(CACHE_BASE)
+                    .join(repo_key(repo_path))
+                    .join(rel);
+                tokio::fs::create_dir_all(&shared)
+                    .await
+                    .with_context(|| format!("Failed to create {}", shared.display()))?;
+                tokio::fs::symlink(&shared, &target)
+                    .await
+                    .with_context(|| format!("Failed to link {}", target.display()))?;
+            }
+            CacheStrategy::Clone => {
+                let source = repo_path.join(rel);
+                if source.is_dir() {
+                    clone_dir(&source, &target).await?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Copy a directory using copy-on-write clones where the filesystem supports them
+async fn clone_dir(source: &Path, target: &Path) -> Result<()> {
+    let mut cmd = Command::new("cp");
+    if cfg!(target_os = "macos") {
+        cmd.arg("-cR");
+    } else {
+        cmd.args(["-R", "--reflink=auto"]);
+    }
+    let output = cmd
+        .arg(source)
+        .arg(target)
+        .output()
+        .await
+        .context("Failed to run cp")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "Failed to clone {} to {}: {}",
+            source.display(),
+            target.display(),
+            stderr.trim()
+        );
+    }
+    Ok(())
+}
+
+async fn move_worktree(repo_path: &Path, from: &Path, to: &Path) -> Result<()> {
+    let from_str = from.to_str().context("Worktree path is not UTF-8")?;
+    let to_str = to.to_str().context("Worktree path is not UTF-8")?;
+    git_ok(
+        repo_path,
+        repo_path,
+        &["worktree", "move", from_str, to_str],
+    )
+    .await
+}
+
+/// Run git (with contention retries) and fail on a non-zero exit
+async fn git_ok(repo_path: &Path, dir: &Path, args: &[&str]) -> Result<()> {
+    let output = repo_lock::git(repo_path, dir, args).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git {} failed: {}", args.join(" "), stderr.trim());
+    }
+    Ok(())
+}
+
+fn pool_dir(repo_path: &Path) -> PathBuf {
+    PathBuf::from(worktree::WORKTREE_BASE)
+        .join(POOL_DIR)
+        .join(repo_key(repo_path))
+}
+
+/// Stable directory name for a repository, derived from its canonical path
+fn repo_key(repo_path: &Path) -> String {
+    let canonical = std::fs::canonicalize(repo_path).unwrap_or_else(|_| repo_path.to_path_buf());
+    canonical
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+    }
+
+    fn init_repo() -> PathBuf {
+        let repo = std::env::temp_dir().join(format!("conductor-pool-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+        run_git(&repo, &["config", "user.email", "test@example.com"]);
+        run_git(&repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join(".gitignore"), "node_modules\n").unwrap();
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "init"]);
+        repo
+    }
+
+    #[test]
+    fn test_repo_key_is_path_safe() {
+        let key = repo_key(Path::new("/no/such/My Repo"));
+        assert_eq!(key, "no-such-My-Repo");
+    }
+
+    #[tokio::test]
+    async fn test_pool_round_trip_keeps_ignored_dirs() {
+        let repo = init_repo();
+        let setup = WorktreeSetup {
+            pool_size: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(replenish(&repo, &setup).await.unwrap(), 1);
+        assert_eq!(replenish(&repo, &setup).await.unwrap(), 0);
+
+        let agent_id = uuid::Uuid::new_v4().to_string();
+        let ready = acquire(&repo, &agent_id, "conductor/pool-test", None, &setup)
+            .await
+            .unwrap();
+        assert!(ready.from_pool);
+        assert!(idle_worktrees(&repo).await.unwrap().is_empty());
+        assert_eq!(
+            worktree::current_branch(&ready.path).await.unwrap(),
+            "conductor/pool-test"
+        );
+
+        // Installed dependencies survive the return to the pool; tracked edits don't
+        std::fs::create_dir_all(ready.path.join("node_modules/dep")).unwrap();
+        std::fs::write(ready.path.join("a.txt"), "changed\n").unwrap();
+        release(&repo, &ready.path, 1).await.unwrap();
+        let idle = idle_worktrees(&repo).await.unwrap();
+        assert_eq!(idle.len(), 1);
+        assert!(idle[0].join("node_modules/dep").exists());
+        assert_eq!(
+            std::fs::read_to_string(idle[0].join("a.txt")).unwrap(),
+            "one\n"
+        );
+
+        // The branch is free again once the worktree is back in the pool
+        worktree::delete_branch(&repo, "conductor/pool-test")
+            .await
+            .unwrap();
+        assert!(worktree::rev_parse(&repo, "refs/heads/conductor/pool-test")
+            .await
+            .is_err());
+
+        worktree::remove_worktree(&repo, &idle[0]).await.unwrap();
+        std::fs::remove_dir_all(&repo).ok();
+    }
+
+    #[tokio::test]
+    async fn test_prepare_caches_share_and_clone() {
+        let repo = init_repo();
+        std::fs::create_dir_all(repo.join("node_modules/dep")).unwrap();
+        let wt = std::env::temp_dir().join(format!("conductor-cache-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&wt).unwrap();
+
+        let share = WorktreeSetup {
+            cache_dirs: vec!["target".into(), "../escape".into()],
+            cache_strategy: CacheStrategy::Share,
+            ..Default::default()
+        };
+        prepare_caches(&repo, &wt, &share).await.unwrap();
+        assert!(wt.join("target").is_symlink());
+        assert!(!wt.join("../escape").exists());
+
+        let clone = WorktreeSetup {
+            cache_dirs: vec!["node_modules".into()],
+            cache_strategy: CacheStrategy::Clone,
+            ..Default::default()
+        };
+        prepare_caches(&repo, &wt, &clone).await.unwrap();
+        assert!(wt.join("node_modules/dep").is_dir());
+        assert!(!wt.join("node_modules").is_symlink());
+
+        std::fs::remove_dir_all(PathBuf::from(CACHE_BASE).join(repo_key(&repo))).ok();
+        std::fs::remove_dir_all(&wt).ok();
+        std::fs::remove_dir_all(&repo).ok();
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+/// How many times a git command is retried when it fails on another process's lock
+const MAX_RETRIES: u32 = 5;
+/// Delay before the first retry; doubled on each attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(50);
+
+struct RepoLock {
+    mutex: Arc<tokio::sync::Mutex<()>>,
+    stats: Mutex<LockStats>,
+}
+
+static LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<RepoLock>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Lock-wait metrics for one repository
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct LockStats {
+    pub repo_path: String,
+    /// Times the lock was taken
+    pub acquisitions: u64,
+    /// Times the lock was already held and the caller had to wait
+    pub contended: u64,
+    pub total_wait_ms: u64,
+    pub max_wait_ms: u64,
+    /// Git commands retried after failing on a lock held outside conductor
+    pub retries: u64,
+    /// Operation holding the lock right now, if any
+    pub held_by: Option<String>,
+}
+
+/// Held while a mutating git operation runs; releases the repo on drop
+pub struct RepoGuard {
+    lock: Arc<RepoLock>,
+    _guard: tokio::sync::OwnedMutexGuard<()>,
+}
+
+impl Drop for RepoGuard {
+    fn drop(&mut self) {
+        self.lock.stats.lock().unwrap().held_by = None;
+    }
+}
+
+fn repo_lock(repo_path: &Path) -> Arc<RepoLock> {
+    let key = std::fs::canonicalize(repo_path).unwrap_or_else(|_| repo_path.to_path_buf());
+    let mut locks = LOCKS.lock().unwrap();
+    locks
+        .entry(key.clone())
+        .or_insert_with(|| {
+            Arc::new(RepoLock {
+                mutex: Arc::new(tokio::sync::Mutex::new(())),
+                stats: Mutex::new(LockStats {
+                    repo_path: key.to_string_lossy().into_owned(),
+                    ..Default::default()
+                }),
+            })
+        })
+        .clone()
+}
+
+/// Serialize a mutating git operation against a repository.
+/// Operations on the same repo run one at a time, in the order they asked.
+pub async fn lock(repo_path: &Path, operation: &str) -> RepoGuard {
+    let lock = repo_lock(repo_path);
+    let started = Instant::now();
+
+    let (guard, contended) = match lock.mutex.clone().try_lock_owned() {
+        Ok(guard) => (guard, false),
+        Err(_) => {
+            tracing::debug!(
+                "Waiting for git lock on {} ({})",
+                repo_path.display(),
+                operation
+            );
+            (lock.mutex.clone().lock_owned().await, true)
+        }
+    };
+
+    let waited = started.elapsed().as_millis() as u64;
+    {
+        let mut stats = lock.stats.lock().unwrap();
+        stats.acquisitions += 1;
+        if contended {
+            stats.contended += 1;
+            stats.total_wait_ms += waited;
+            stats.max_wait_ms = stats.max_wait_ms.max(waited);
+        }
+        stats.held_by = Some(operation.to_string());
+    }
+
+    RepoGuard {
+        lock,
+        _guard: guard,
+    }
+}
+
+/// Lock-wait metrics for every repository conductor has touched
+pub fn metrics() -> Vec<LockStats> {
+    let locks = LOCKS.lock().unwrap();
+    let mut stats: Vec<LockStats> = locks
+        .values()
+        .map(|l| l.stats.lock().unwrap().clone())
+        .collect();
+    stats.sort_by(|a, b| a.repo_path.cmp(&b.repo_path));
+    stats
+}
+
+/// Whether git failed because another process held one of its lock files
+pub fn is_contention_error(stderr: &str) -> bool {
+    stderr.contains("index.lock")
+        || stderr.contains(".lock': File exists")
+        || stderr.contains("cannot lock ref")
+        || stderr.contains("Another git process seems to be running")
+}
+
+/// Run git in `dir`, retrying with backoff while it fails on lock contention.
+/// `repo_path` identifies the repository the retries are counted against.
+pub async fn git(repo_path: &Path, dir: &Path, args: &[&str]) -> Result<Output> {
+    let mut attempt = 0;
+    loop {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .await
+            .with_context(|| format!("Failed to run git {}", args.join(" ")))?;
+
+        if output.status.success()
+            || attempt >= MAX_RETRIES
+            || !is_contention_error(&String::from_utf8_lossy(&output.stderr))
+        {
+            return Ok(output);
+        }
+
+        let delay = RETRY_BASE_DELAY * 2u32.pow(attempt);
+        attempt += 1;
+        repo_lock(repo_path).stats.lock().unwrap().retries += 1;
+        tracing::warn!(
+            "git {} hit a lock in {}; retrying in {:?} ({}/{})",
+            args.join(" "),
+            repo_path.display(),
+            delay,
+            attempt,
+            MAX_RETRIES
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_repo() -> PathBuf {
+        let repo = std::env::temp_dir().join(format!("conductor-lock-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        let output = std::process::Command::new("git")
+            .args(["init", "-q", "-b", "main"])
+            .current_dir(&repo)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        repo
+    }
+
+    fn stats_for(repo: &Path) -> LockStats {
+        let key = std::fs::canonicalize(repo).unwrap();
+        metrics()
+            .into_iter()
+            .find(|s| s.repo_path == key.to_string_lossy())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_is_contention_error() {
+        assert!(is_contention_error(
+            "fatal: Unable to create '/repo/.git/index.lock': File exists."
+        ));
+        assert!(is_contention_error(
+            "error: cannot lock ref 'refs/heads/main': is at abc but expected def"
+        ));
+        assert!(!is_contention_error("fatal: not a git repository"));
+        assert!(!is_contention_error(
+            "CONFLICT (content): Merge conflict in a.txt"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_lock_serializes_and_records_waits() {
+        let repo = temp_repo();
+
+        let first = lock(&repo, "first").await;
+        assert_eq!(stats_for(&repo).held_by.as_deref(), Some("first"));
+
+        let repo2 = repo.clone();
+        let waiter = tokio::spawn(async move {
+            let _guard = lock(&repo2, "second").await;
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        drop(first);
+        waiter.await.unwrap();
+
+        let stats = stats_for(&repo);
+        assert_eq!(stats.acquisitions, 2);
+        assert_eq!(stats.contended, 1);
+        assert!(stats.max_wait_ms >= 40);
+        assert!(stats.held_by.is_none());
+
+        std::fs::remove_dir_all(&repo).ok();
+    }
+
+    #[tokio::test]
+    async fn test_git_retries_on_index_lock() {
+        let repo = temp_repo();
+        std::fs::write(repo.join("a.txt"), "a\n").unwrap();
+        let index_lock = repo.join(".git").join("index.lock");
+        std::fs::write(&index_lock, "").unwrap();
+
+        let lock_path = index_lock.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(120)).await;
+            std::fs::remove_file(lock_path).unwrap();
+        });
+
+        let output = git(&repo, &repo, &["add", "a.txt"]).await.unwrap();
+        assert!(output.status.success());
+        assert!(stats_for(&repo).retries >= 1);
+
+        std::fs::remove_dir_all(&repo).ok();
+    }
+}
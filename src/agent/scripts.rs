@@ -0,0 +1,185 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+use crate::db::Database;
+
+/// Keep at most this much script output (the tail) in the recorded event
+const MAX_OUTPUT_BYTES: usize = 16 * 1024;
+
+/// Result of running a setup, teardown or check command
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ScriptOutcome {
+    pub command: String,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    /// Combined stdout and stderr, truncated to the last 16 KiB
+    pub output: String,
+}
+
+/// Run a shell command in a worktree, killing it once `timeout` elapses.
+/// `env` is added to the command's environment.
+pub async fn run_script(
+    command: &str,
+    dir: &Path,
+    timeout: Duration,
+    env: &[(&str, String)],
+) -> Result<ScriptOutcome> {
+    let started = Instant::now();
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(format!("exec 2>&1\n{}", command))
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .kill_on_drop(true);
+    for (key, value) in env {
+        cmd.env(key, value);
+    }
+
+    let child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to run '{}'", command))?;
+    let pid = child.id();
+
+    let (exit_code, timed_out, stdout) =
+        match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(output) => {
+                let output = output.with_context(|| format!("Failed to run '{}'", command))?;
+                (output.status.code(), false, output.stdout)
+            }
+            Err(_) => {
+                // The script runs in its own process group; kill everything it started
+                if let Some(pid) = pid {
+                    let _ = Command::new("kill")
+                        .args(["-KILL", "--", &format!("-{}", pid)])
+                        .output()
+                        .await;
+                }
+                (None, true, Vec::new())
+            }
+        };
+
+    let output = String::from_utf8_lossy(&stdout);
+    let output = if output.len() > MAX_OUTPUT_BYTES {
+        let mut start = output.len() - MAX_OUTPUT_BYTES;
+        while !output.is_char_boundary(start) {
+            start += 1;
+        }
+        format!("...{}", &output[start..])
+    } else {
+        output.to_string()
+    };
+
+    Ok(ScriptOutcome {
+        command: command.to_string(),
+        success: !timed_out && exit_code == Some(0),
+        exit_code,
+        timed_out,
+        duration_ms: started.elapsed().as_millis() as u64,
+        output,
+    })
+}
+
+impl ScriptOutcome {
+    /// One-line description for the event stream
+    pub fn summary(&self, phase: &str) -> String {
+        if self.timed_out {
+            format!("{} timed out after {} ms", phase, self.duration_ms)
+        } else if self.success {
+            format!("{} finished in {} ms", phase, self.duration_ms)
+        } else {
+            format!(
+                "{} failed with exit code {}",
+                phase,
+                self.exit_code
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| "unknown".to_string())
+            )
+        }
+    }
+}
+
+/// Record a script run as an agent event (`setup` or `teardown`)
+pub fn record(
+    db: &Database,
+    agent_run_id: &str,
+    event_type: &str,
+    phase: &str,
+    outcome: &ScriptOutcome,
+) -> Result<()> {
+    db.insert_agent_event(
+        agent_run_id,
+        event_type,
+        None,
+        &outcome.summary(phase),
+        Some(&serde_json::to_string(outcome)?),
+        None,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("conductor-script-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_run_script_success_captures_output_and_env() {
+        let dir = temp_dir();
+        let outcome = run_script(
+            "echo hello; echo \"$CONDUCTOR_TASK_ID\" > task.txt; echo oops >&2",
+            &dir,
+            Duration::from_secs(10),
+            &[("CONDUCTOR_TASK_ID", "task-1".to_string())],
+        )
+        .await
+        .unwrap();
+
+        assert!(outcome.success);
+        assert_eq!(outcome.exit_code, Some(0));
+        assert!(outcome.output.contains("hello"));
+        assert!(outcome.output.contains("oops"));
+        assert_eq!(
+            std::fs::read_to_string(dir.join("task.txt")).unwrap(),
+            "task-1\n"
+        );
+        assert_eq!(
+            outcome.summary("Setup").split(" in ").next(),
+            Some("Setup finished")
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_run_script_failure_and_timeout() {
+        let dir = temp_dir();
+        let failed = run_script("exit 3", &dir, Duration::from_secs(10), &[])
+            .await
+            .unwrap();
+        assert!(!failed.success);
+        assert_eq!(failed.exit_code, Some(3));
+        assert_eq!(failed.summary("Setup"), "Setup failed with exit code 3");
+
+        let slow = run_script("sleep 5", &dir, Duration::from_millis(100), &[])
+            .await
+            .unwrap();
+        assert!(slow.timed_out);
+        assert!(!slow.success);
+        assert!(slow.duration_ms < 5000);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,50 +1,16 @@
 use anyhow::{Context, Result};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::{broadcast, mpsc, RwLock};
 
 use crate::agent::event_parser::{self, ParsedEvent};
-use crate::agent::worktree;
-use crate::db::queries::{AgentEvent, AgentRun};
+use crate::agent::{pool, scripts, worktree};
+use crate::db::queries::{AgentEvent, AgentRun, GoalSpace, Task, UncommittedPolicy};
 use crate::db::Database;
-
-/// Message sent to the dispatch loop when an agent finishes or dispatch is requested
-#[derive(Debug, Clone)]
-pub struct DispatchMessage {
-    pub goal_space_id: String,
-    pub branch_to_merge: Option<String>,
-    pub repo_path: Option<String>,
-    pub agent_run_id: Option<String>,
-}
-
-/// Status of an agent session
-#[derive(Debug, Clone, PartialEq, serde::Serialize)]
-#[serde(rename_all = "snake_case")]
-#[allow(dead_code)]
-pub enum AgentStatus {
-    Spawning,
-    Running,
-    Stalled,
-    Done,
-    Failed,
-    Killed,
-}
-
-impl std::fmt::Display for AgentStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            AgentStatus::Spawning => write!(f, "spawning"),
-            AgentStatus::Running => write!(f, "running"),
-            AgentStatus::Stalled => write!(f, "stalled"),
-            AgentStatus::Done => write!(f, "done"),
-            AgentStatus::Failed => write!(f, "failed"),
-            AgentStatus::Killed => write!(f, "killed"),
-        }
-    }
-}
+use crate::goal::task::{RunStatus, TaskStatus};
 
 /// Live state for an active agent session (in-memory)
 struct LiveSession {
@@ -54,12 +20,375 @@ struct LiveSession {
     process: Child,
     worktree_path: PathBuf,
     repo_path: PathBuf,
-    status: AgentStatus,
+    task_id: String,
+    branch: String,
+    /// Teardown command and warm pool the worktree goes through when the agent finishes
+    worktree_setup: pool::WorktreeSetup,
+    status: RunStatus,
     cost_usd: f64,
     input_tokens: i64,
     output_tokens: i64,
 }
 
+/// Environment passed to setup and teardown commands
+fn script_env(
+    repo_path: &Path,
+    agent_run_id: &str,
+    task_id: &str,
+    branch: &str,
+) -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "CONDUCTOR_REPO_PATH",
+            repo_path.to_string_lossy().into_owned(),
+        ),
+        ("CONDUCTOR_AGENT_ID", agent_run_id.to_string()),
+        ("CONDUCTOR_TASK_ID", task_id.to_string()),
+        ("CONDUCTOR_BRANCH", branch.to_string()),
+    ]
+}
+
+/// Run the teardown command in a finished agent's worktree, then return it to the
+/// warm pool or remove it. A failing teardown is recorded but doesn't keep the
+/// worktree around.
+async fn finish_worktree(db: &Database, agent_run_id: &str, session: &LiveSession) -> Result<()> {
+    let setup = &session.worktree_setup;
+    if let Some(ref command) = setup.teardown_command {
+        let env = script_env(
+            &session.repo_path,
+            agent_run_id,
+            &session.task_id,
+            &session.branch,
+        );
+        match scripts::run_script(command, &session.worktree_path, setup.script_timeout, &env).await
+        {
+            Ok(outcome) => {
+                if !outcome.success {
+                    tracing::warn!("{} for agent {}", outcome.summary("Teardown"), agent_run_id);
+                }
+                if let Err(e) = scripts::record(db, agent_run_id, "teardown", "Teardown", &outcome)
+                {
+                    tracing::error!(
+                        "Failed to record teardown for agent {}: {}",
+                        agent_run_id,
+                        e
+                    );
+                }
+            }
+            Err(e) => tracing::warn!("Teardown for agent {} failed: {}", agent_run_id, e),
+        }
+    }
+
+    pool::release(&session.repo_path, &session.worktree_path, setup.pool_size).await
+}
+
+/// Minimum gap between `last_activity_at` writes while an agent is streaming.
+/// The timestamp is informational, so most output lines skip the write.
+const ACTIVITY_WRITE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How long a nudged agent gets to commit the changes it left behind
+const COMMIT_NUDGE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+const COMMIT_NUDGE_MESSAGE: &str = "You finished without committing. Review `git status`, then commit all of your changes with a descriptive message. Don't make any other changes.";
+
+/// Finish a run whose agent exited successfully: resolve any changes it left
+/// uncommitted per the `uncommitted_policy` setting, then mark the run and task.
+/// Returns the run's final status.
+async fn complete_run(
+    db: &Database,
+    event_tx: &broadcast::Sender<BroadcastEvent>,
+    run_id: &str,
+    task_id: &str,
+    session: &LiveSession,
+) -> RunStatus {
+    let committed = match settle_uncommitted(db, event_tx, run_id, task_id, session).await {
+        Ok(committed) => committed,
+        Err(e) => {
+            tracing::error!(
+                "Failed to handle uncommitted changes for agent {}: {}",
+                run_id,
+                e
+            );
+            let _ = db.insert_agent_event(
+                run_id,
+                "uncommitted_changes",
+                None,
+                &format!("Failed to handle uncommitted changes: {}", e),
+                None,
+                None,
+            );
+            false
+        }
+    };
+
+    let (run_status, task_status) = if committed {
+        // In manual merge mode the task waits for review instead
+        (
+            RunStatus::Done,
+            crate::goal::space::completed_task_status(db, task_id),
+        )
+    } else {
+        if let Err(e) = db.set_agent_run_failure_reason(run_id, "uncommitted_changes") {
+            tracing::error!("Failed to set failure reason for {}: {}", run_id, e);
+        }
+        (RunStatus::Failed, TaskStatus::Failed)
+    };
+
+    if let Err(e) = db.update_task(
+        task_id,
+        &crate::db::queries::UpdateTask {
+            status: Some(task_status),
+            ..Default::default()
+        },
+    ) {
+        tracing::error!(
+            "Failed to update task {} to {} for agent {}: {}",
+            task_id,
+            task_status,
+            run_id,
+            e
+        );
+    }
+    if let Err(e) = db.update_agent_run_status(run_id, run_status) {
+        tracing::error!(
+            "Failed to update agent run status to {} for {}: {}",
+            run_status,
+            run_id,
+            e
+        );
+    }
+    run_status
+}
+
+/// Check a finished agent's worktree for uncommitted or untracked changes and apply
+/// the policy, recording the outcome as an `uncommitted_changes` event.
+/// Returns whether the worktree ended up with everything committed.
+async fn settle_uncommitted(
+    db: &Database,
+    event_tx: &broadcast::Sender<BroadcastEvent>,
+    run_id: &str,
+    task_id: &str,
+    session: &LiveSession,
+) -> Result<bool> {
+    let files = worktree::uncommitted_changes(&session.worktree_path).await?;
+    if files.is_empty() {
+        return Ok(true);
+    }
+
+    let task = db.get_task(task_id)?.context("Task not found")?;
+    let goal = db
+        .get_goal_space(&task.goal_space_id)?
+        .context("Goal space not found")?;
+    let policy = crate::goal::space::effective_settings(db, &goal, &task)?.uncommitted_policy();
+    tracing::warn!(
+        "Agent {} finished with {} uncommitted file(s); policy {:?}",
+        run_id,
+        files.len(),
+        policy
+    );
+
+    let (committed, summary, commit) = match policy {
+        UncommittedPolicy::Commit => {
+            let message = format!(
+                "{}\n\nCommitted by conductor: the agent finished without committing {} changed file(s).",
+                task.title,
+                files.len()
+            );
+            let sha = worktree::commit_all(&session.worktree_path, &message).await?;
+            let summary = format!(
+                "Auto-committed {} uncommitted file(s) as {}",
+                files.len(),
+                &sha[..sha.len().min(8)]
+            );
+            (true, summary, Some(sha))
+        }
+        UncommittedPolicy::Nudge => {
+            let nudged = match session.claude_session_id {
+                Some(ref session_id) => {
+                    let mut cmd =
+                        resume_command(session_id, &session.worktree_path, COMMIT_NUDGE_MESSAGE);
+                    cmd.kill_on_drop(true);
+                    let child = cmd.spawn().context("Failed to spawn claude resume")?;
+                    tokio::time::timeout(
+                        COMMIT_NUDGE_TIMEOUT,
+                        stream_resume(db.clone(), event_tx.clone(), run_id.to_string(), child),
+                    )
+                    .await
+                    .unwrap_or(false)
+                }
+                None => false,
+            };
+            let remaining = worktree::uncommitted_changes(&session.worktree_path).await?;
+            let summary = if remaining.is_empty() {
+                format!(
+                    "Agent committed {} uncommitted file(s) after a nudge",
+                    files.len()
+                )
+            } else if nudged {
+                format!(
+                    "Agent left {} file(s) uncommitted after a nudge",
+                    remaining.len()
+                )
+            } else {
+                format!(
+                    "Agent left {} file(s) uncommitted and could not be nudged",
+                    remaining.len()
+                )
+            };
+            let commit = if remaining.is_empty() {
+                worktree::rev_parse(&session.worktree_path, "HEAD")
+                    .await
+                    .ok()
+            } else {
+                None
+            };
+            (remaining.is_empty(), summary, commit)
+        }
+        UncommittedPolicy::Fail => (
+            false,
+            format!("Agent left {} file(s) uncommitted", files.len()),
+            None,
+        ),
+    };
+
+    // The run fails, but its worktree is about to be cleaned, so keep the edits
+    let (summary, kept_at) = if committed {
+        (summary, None)
+    } else {
+        let message = format!(
+            "WIP: {}\n\nSaved by conductor: the agent finished without committing these changes.",
+            task.title
+        );
+        let (wip_ref, sha) = worktree::save_wip(&session.worktree_path, run_id, &message).await?;
+        (
+            format!("{}; saved to {}", summary, wip_ref),
+            Some(serde_json::json!({"ref": wip_ref, "commit": sha})),
+        )
+    };
+
+    db.insert_agent_event(
+        run_id,
+        "uncommitted_changes",
+        None,
+        &summary,
+        Some(
+            &serde_json::json!({
+                "policy": policy,
+                "files": files,
+                "commit": commit,
+                "kept_at": kept_at,
+            })
+            .to_string(),
+        ),
+        None,
+    )?;
+    Ok(committed)
+}
+
+/// Build a `claude --resume` command that sends `message` to an existing session
+fn resume_command(session_id: &str, worktree_path: &Path, message: &str) -> Command {
+    let mut cmd = Command::new("claude");
+    cmd.arg("-p")
+        .arg(message)
+        .arg("--resume")
+        .arg(session_id)
+        .arg("--output-format")
+        .arg("stream-json");
+
+    cmd.current_dir(worktree_path);
+    cmd.stdout(std::process::Stdio::piped());
+    cmd.stderr(std::process::Stdio::piped());
+    cmd
+}
+
+/// Record the events and cost of a resumed session until it exits.
+/// Returns whether the process exited successfully.
+async fn stream_resume(
+    db: Database,
+    event_tx: broadcast::Sender<BroadcastEvent>,
+    run_id: String,
+    mut child: Child,
+) -> bool {
+    let stdout = child.stdout.take().expect("stdout piped");
+    let stderr = child.stderr.take().expect("stderr piped");
+
+    // Collect stderr in background
+    let stderr_handle = tokio::spawn(async move {
+        let mut reader = BufReader::new(stderr);
+        let mut output = String::new();
+        let _ = tokio::io::AsyncReadExt::read_to_string(&mut reader, &mut output).await;
+        output
+    });
+
+    // Parse stdout events
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(parsed) = event_parser::parse_stream_json_line(&line) {
+            // Store in DB and broadcast via SSE
+            if let Ok(agent_event) = event_parser::store_event(&db, &run_id, &parsed, &line).await {
+                let _ = event_tx.send(BroadcastEvent::AgentEvent {
+                    agent_run_id: run_id.clone(),
+                    event: agent_event,
+                });
+            }
+
+            // Update cost from Result event
+            if let ParsedEvent::Result {
+                cost_usd,
+                input_tokens,
+                output_tokens,
+                ..
+            } = &parsed
+            {
+                if *cost_usd > 0.0 {
+                    // Add nudge cost to existing agent run cost
+                    let (id, cost, input, output) =
+                        (run_id.clone(), *cost_usd, *input_tokens, *output_tokens);
+                    let added = db
+                        .call(move |db| db.add_agent_run_cost(&id, cost, input, output))
+                        .await;
+                    if let Err(e) = added {
+                        tracing::error!("Failed to add nudge cost for {}: {}", run_id, e);
+                    }
+                }
+            }
+        }
+    }
+
+    // Wait for process to exit
+    let success = match child.wait().await {
+        Ok(status) if status.success() => {
+            tracing::debug!("Nudge for agent {} completed successfully", run_id);
+            true
+        }
+        Ok(status) => {
+            tracing::warn!("Nudge for agent {} exited with status: {}", run_id, status);
+            false
+        }
+        Err(e) => {
+            tracing::error!(
+                "Failed to wait for nudge process for agent {}: {}",
+                run_id,
+                e
+            );
+            false
+        }
+    };
+
+    let stderr_output = stderr_handle.await.unwrap_or_default();
+    if !stderr_output.trim().is_empty() {
+        tracing::warn!(
+            "Nudge stderr for agent {}: {}",
+            run_id,
+            stderr_output.trim()
+        );
+    }
+    success
+}
+
 /// SSE event broadcast payload
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(tag = "kind")]
@@ -89,14 +418,15 @@ pub struct AgentManager {
     sessions: Arc<RwLock<HashMap<String, LiveSession>>>,
     db: Database,
     event_tx: broadcast::Sender<BroadcastEvent>,
-    dispatch_tx: mpsc::UnboundedSender<DispatchMessage>,
+    /// Wakes the dispatch loop after a job is queued
+    dispatch_tx: mpsc::UnboundedSender<()>,
 }
 
 impl AgentManager {
     pub fn new(
         db: Database,
         event_tx: broadcast::Sender<BroadcastEvent>,
-        dispatch_tx: mpsc::UnboundedSender<DispatchMessage>,
+        dispatch_tx: mpsc::UnboundedSender<()>,
     ) -> Self {
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
@@ -108,15 +438,83 @@ impl AgentManager {
 
     /// Request auto-dispatch of unblocked tasks for a goal space (no merge needed)
     pub fn request_dispatch(&self, goal_space_id: &str) {
-        let _ = self.dispatch_tx.send(DispatchMessage {
-            goal_space_id: goal_space_id.to_string(),
-            branch_to_merge: None,
-            repo_path: None,
-            agent_run_id: None,
-        });
+        if let Err(e) = self.db.enqueue_dispatch_job(goal_space_id, None) {
+            tracing::error!("Failed to queue dispatch for goal {}: {}", goal_space_id, e);
+        }
+        self.wake_dispatch();
+    }
+
+    /// Wake the dispatch loop to work through jobs that are due
+    pub fn wake_dispatch(&self) {
+        let _ = self.dispatch_tx.send(());
     }
 
-    /// Spawn a new agent for a task
+    /// Spawn an agent for a task using its project, goal and task settings
+    pub async fn dispatch_task(&self, goal: &GoalSpace, task: &Task) -> Result<AgentRun> {
+        let effective = crate::goal::space::effective_settings(&self.db, goal, task)?;
+        let review_feedback = self.db.latest_review_feedback(&task.id)?;
+        let rejected_run = self.db.latest_rejected_run(&task.id)?;
+        let prompt =
+            crate::goal::task::agent_prompt(&goal.description, task, review_feedback.as_deref());
+
+        // Stacked tasks start from their dependencies' unmerged branches
+        let stack_on: Vec<String> = if effective.stacked() {
+            crate::agent::stack::unmerged_dependency_runs(
+                &self.db,
+                std::path::Path::new(&goal.repo_path),
+                task,
+            )
+            .await?
+            .into_iter()
+            .filter_map(|run| run.branch)
+            .collect()
+        } else {
+            Vec::new()
+        };
+
+        let worktree_setup = pool::WorktreeSetup::from_settings(&effective);
+        let agent_run = self
+            .spawn_agent(
+                &task.id,
+                &goal.id,
+                &prompt,
+                &goal.repo_path,
+                &effective.model(),
+                Some(effective.max_budget_usd()),
+                Some(effective.max_turns()),
+                Some(effective.allowed_tools()),
+                effective.permission_mode(),
+                effective.system_prompt(),
+                &stack_on,
+                &worktree_setup,
+            )
+            .await?;
+
+        // The follow-up to a rejected run replaces its branch
+        if let Some(branch) = rejected_run.and_then(|run| run.branch) {
+            let repo = PathBuf::from(&goal.repo_path);
+            if let Err(e) = worktree::discard_branch(&repo, &branch).await {
+                tracing::warn!("Failed to delete rejected branch {}: {}", branch, e);
+            }
+        }
+
+        // Top the pool back up for the next agent
+        if worktree_setup.pool_size > 0 {
+            let repo = PathBuf::from(&goal.repo_path);
+            tokio::spawn(async move {
+                if let Err(e) = pool::replenish(&repo, &worktree_setup).await {
+                    tracing::warn!("Failed to replenish worktree pool: {}", e);
+                }
+            });
+        }
+
+        Ok(agent_run)
+    }
+
+    /// Spawn a new agent for a task.
+    /// The worktree starts from the first `stack_on` branch with the rest merged in,
+    /// or from the repo's HEAD when `stack_on` is empty, and is taken from the warm
+    /// pool when `worktree_setup` enables one.
     #[allow(clippy::too_many_arguments)]
     pub async fn spawn_agent(
         &self,
@@ -130,6 +528,8 @@ impl AgentManager {
         allowed_tools: Option<Vec<String>>,
         permission_mode: Option<String>,
         system_prompt: Option<String>,
+        stack_on: &[String],
+        worktree_setup: &pool::WorktreeSetup,
     ) -> Result<AgentRun> {
         let agent_run_id = uuid::Uuid::new_v4().to_string();
 
@@ -140,7 +540,15 @@ impl AgentManager {
         // Create branch name and worktree
         let branch = worktree::branch_name(&agent_run_id, &task_title);
         let repo = std::path::Path::new(repo_path);
-        let worktree_path = worktree::create_worktree(repo, &agent_run_id, &branch).await?;
+        let ready = pool::acquire(
+            repo,
+            &agent_run_id,
+            &branch,
+            stack_on.first().map(String::as_str),
+            worktree_setup,
+        )
+        .await?;
+        let worktree_path = ready.path.clone();
 
         // Drop guard to ensure cleanup if we fail after creating the worktree
         struct CleanupGuard {
@@ -173,7 +581,7 @@ impl AgentManager {
 
                         // If DB record was created, mark it as failed
                         if let Some(id) = run_id {
-                            if let Err(e) = db.update_agent_run_status(&id, "failed") {
+                            if let Err(e) = db.update_agent_run_status(&id, RunStatus::Failed) {
                                 tracing::error!(
                                     "Failed to mark agent run as failed during cleanup: {}",
                                     e
@@ -194,7 +602,7 @@ impl AgentManager {
         };
 
         // Create agent run in DB
-        let agent_run = self.db.create_agent_run(
+        let mut agent_run = self.db.create_agent_run(
             task_id,
             goal_space_id,
             Some(worktree_path.to_str().unwrap()),
@@ -206,11 +614,52 @@ impl AgentManager {
         // Store agent_run_id so cleanup can mark it as failed if needed
         cleanup_guard.agent_run_id = Some(agent_run.id.clone());
 
-        // Mark task as running
+        self.db
+            .set_agent_run_worktree_ready_ms(&agent_run.id, ready.ready_ms as i64)?;
+        agent_run.worktree_ready_ms = Some(ready.ready_ms as i64);
+        self.db.insert_agent_event(
+            &agent_run.id,
+            "worktree_ready",
+            None,
+            &format!(
+                "Worktree ready in {} ms ({})",
+                ready.ready_ms,
+                if ready.from_pool {
+                    "warm pool"
+                } else {
+                    "fresh checkout"
+                }
+            ),
+            None,
+            None,
+        )?;
+
+        // Stacking on several dependencies: merge the remaining branches in
+        if stack_on.len() > 1 {
+            worktree::merge_branches(&worktree_path, &stack_on[1..]).await?;
+        }
+
+        // Record where the branch started so landing can rebase the stack
+        let base_commit = worktree::rev_parse(&worktree_path, "HEAD").await?;
+        self.db
+            .set_agent_run_base_commit(&agent_run.id, &base_commit)?;
+        agent_run.base_commit = Some(base_commit);
+        if !stack_on.is_empty() {
+            self.db.insert_agent_event(
+                &agent_run.id,
+                "stacked",
+                None,
+                &format!("Stacked on {}", stack_on.join(", ")),
+                None,
+                None,
+            )?;
+        }
+
+        // Mark task as running; setup is part of its run
         self.db.update_task(
             task_id,
             &crate::db::queries::UpdateTask {
-                status: Some("running".to_string()),
+                status: Some(TaskStatus::Running),
                 title: None,
                 description: None,
                 priority: None,
@@ -219,6 +668,27 @@ impl AgentManager {
             },
         )?;
 
+        // Bootstrap the worktree; don't start the agent in a broken checkout
+        if let Some(ref command) = worktree_setup.setup_command {
+            let env = script_env(repo, &agent_run.id, task_id, &branch);
+            let outcome =
+                scripts::run_script(command, &worktree_path, worktree_setup.script_timeout, &env)
+                    .await?;
+            scripts::record(&self.db, &agent_run.id, "setup", "Setup", &outcome)?;
+            if !outcome.success {
+                self.db
+                    .set_agent_run_failure_reason(&agent_run.id, "setup_failed")?;
+                self.db.update_task(
+                    task_id,
+                    &crate::db::queries::UpdateTask {
+                        status: Some(TaskStatus::Failed),
+                        ..Default::default()
+                    },
+                )?;
+                anyhow::bail!("{}: {}", outcome.summary("Setup"), command);
+            }
+        }
+
         // Build claude command
         let mut cmd = Command::new("claude");
         cmd.arg("-p")
@@ -285,7 +755,10 @@ impl AgentManager {
                     process: child,
                     worktree_path: worktree_path.clone(),
                     repo_path: repo.to_path_buf(),
-                    status: AgentStatus::Running,
+                    task_id: task_id.to_string(),
+                    branch: branch.clone(),
+                    worktree_setup: worktree_setup.clone(),
+                    status: RunStatus::Running,
                     cost_usd: 0.0,
                     input_tokens: 0,
                     output_tokens: 0,
@@ -294,7 +767,8 @@ impl AgentManager {
         }
 
         // Update status to running
-        self.db.update_agent_run_status(&agent_run.id, "running")?;
+        self.db
+            .update_agent_run_status(&agent_run.id, RunStatus::Running)?;
 
         // Spawn succeeded - disable cleanup guard
         cleanup_guard.should_cleanup = false;
@@ -329,6 +803,7 @@ impl AgentManager {
 
             // Staleness and timeout tracking
             let mut last_event_time = std::time::Instant::now();
+            let mut last_activity_write: Option<std::time::Instant> = None;
             let start_time = std::time::Instant::now();
             let stall_timeout = std::time::Duration::from_secs(10 * 60); // 10 minutes
             let hard_timeout = std::time::Duration::from_secs(20 * 60); // 20 minutes
@@ -336,7 +811,8 @@ impl AgentManager {
             watchdog_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
             // Get max_budget_usd for this run
-            let max_budget = match db.get_agent_run(&run_id) {
+            let id = run_id.clone();
+            let max_budget = match db.call(move |db| db.get_agent_run(&id)).await {
                 Ok(Some(agent_run)) => agent_run.max_budget_usd,
                 _ => None,
             };
@@ -360,10 +836,16 @@ impl AgentManager {
                                 // Clear stalled status if previously set
                                 if stalled {
                                     stalled = false;
-                                    let mut sessions = sessions.write().await;
-                                    if let Some(session) = sessions.get_mut(&run_id) {
-                                        session.status = AgentStatus::Running;
-                                        if let Err(e) = db.update_agent_run_status(&run_id, "running") {
+                                    let resumed = match sessions.write().await.get_mut(&run_id) {
+                                        Some(session) => {
+                                            session.status = RunStatus::Running;
+                                            true
+                                        }
+                                        None => false,
+                                    };
+                                    if resumed {
+                                        let id = run_id.clone();
+                                        if let Err(e) = db.call(move |db| db.update_agent_run_status(&id, RunStatus::Running)).await {
                                             tracing::error!("Failed to update agent run status to running for {}: {}", run_id, e);
                                         }
                                     }
@@ -372,7 +854,7 @@ impl AgentManager {
                                 if let Some(parsed) = event_parser::parse_stream_json_line(&line) {
                                     // Store in DB
                                     if let Ok(agent_event) =
-                                        event_parser::store_event(&db, &run_id, &parsed, &line)
+                                        event_parser::store_event(&db, &run_id, &parsed, &line).await
                                     {
                                         // Broadcast to SSE subscribers
                                         let _ = event_tx.send(BroadcastEvent::AgentEvent {
@@ -395,10 +877,16 @@ impl AgentManager {
                                             if let Ok(v) = serde_json::from_str::<serde_json::Value>(&line) {
                                                 if let Some(sid) = v.get("session_id").and_then(|s| s.as_str()) {
                                                     if !sid.is_empty() {
-                                                        let mut sessions_w = sessions.write().await;
-                                                        if let Some(session) = sessions_w.get_mut(&run_id) {
-                                                            session.claude_session_id = Some(sid.to_string());
-                                                            if let Err(e) = db.update_agent_run_session_id(&run_id, sid) {
+                                                        let captured = match sessions.write().await.get_mut(&run_id) {
+                                                            Some(session) => {
+                                                                session.claude_session_id = Some(sid.to_string());
+                                                                true
+                                                            }
+                                                            None => false,
+                                                        };
+                                                        if captured {
+                                                            let (id, sid) = (run_id.clone(), sid.to_string());
+                                                            if let Err(e) = db.call(move |db| db.update_agent_run_session_id(&id, &sid)).await {
                                                                 tracing::error!("Failed to update agent run session ID for {}: {}", run_id, e);
                                                             }
                                                         }
@@ -416,47 +904,66 @@ impl AgentManager {
                                             output_tokens,
                                             ..
                                         } => {
-                                            let mut sessions = sessions.write().await;
-                                            if let Some(session) = sessions.get_mut(&run_id) {
-                                                session.cost_usd += cost_usd;
-                                                session.input_tokens += input_tokens;
-                                                session.output_tokens += output_tokens;
-                                                if let Err(e) = db.update_agent_run_cost(
-                                                    &run_id,
-                                                    session.cost_usd,
-                                                    session.input_tokens,
-                                                    session.output_tokens,
-                                                ) {
+                                            // Update the session under the lock, then write the
+                                            // totals once it is released
+                                            let totals = {
+                                                let mut sessions = sessions.write().await;
+                                                match sessions.get_mut(&run_id) {
+                                                    Some(session) => {
+                                                        session.cost_usd += cost_usd;
+                                                        session.input_tokens += input_tokens;
+                                                        session.output_tokens += output_tokens;
+                                                        // Enforce max_budget_usd server-side
+                                                        let over_budget =
+                                                            max_budget.filter(|budget| session.cost_usd > *budget);
+                                                        if over_budget.is_some() {
+                                                            session.status = RunStatus::Killed;
+                                                            session.process.kill().await.ok();
+                                                        }
+                                                        Some((session.cost_usd, session.input_tokens, session.output_tokens, over_budget))
+                                                    }
+                                                    None => None,
+                                                }
+                                            };
+                                            if let Some((cost, input, output, over_budget)) = totals {
+                                                let id = run_id.clone();
+                                                if let Err(e) = db.call(move |db| db.update_agent_run_cost(&id, cost, input, output)).await {
                                                     tracing::error!("Failed to update agent run cost for {}: {}", run_id, e);
                                                 }
 
-                                                // Enforce max_budget_usd server-side
-                                                if let Some(budget) = max_budget {
-                                                    if session.cost_usd > budget {
-                                                        tracing::warn!(
-                                                            "Agent {} exceeded budget: ${:.4} > ${:.4}",
-                                                            run_id,
-                                                            session.cost_usd,
-                                                            budget
-                                                        );
-                                                        budget_exceeded = true;
-                                                        session.status = AgentStatus::Killed;
-                                                        if let Err(e) = db.update_agent_run_status(&run_id, "killed") {
-                                                            tracing::error!("Failed to update agent run status to killed for {}: {}", run_id, e);
+                                                if let Some(budget) = over_budget {
+                                                    tracing::warn!(
+                                                        "Agent {} exceeded budget: ${:.4} > ${:.4}",
+                                                        run_id,
+                                                        cost,
+                                                        budget
+                                                    );
+                                                    budget_exceeded = true;
+                                                    let id = run_id.clone();
+                                                    if let Err(e) = db.call(move |db| db.update_agent_run_status(&id, RunStatus::Killed)).await {
+                                                        tracing::error!("Failed to update agent run status to killed for {}: {}", run_id, e);
+                                                    }
+                                                    let exceeded = ParsedEvent::Error {
+                                                        message: format!("Budget exceeded: ${:.4} > ${:.4}", cost, budget),
+                                                    };
+                                                    let raw = serde_json::json!({
+                                                        "type": "budget_exceeded",
+                                                        "cost_usd": cost,
+                                                        "max_budget_usd": budget,
+                                                    })
+                                                    .to_string();
+                                                    match event_parser::store_event(&db, &run_id, &exceeded, &raw).await {
+                                                        Ok(agent_event) => {
+                                                            let _ = event_tx.send(BroadcastEvent::AgentEvent {
+                                                                agent_run_id: run_id.clone(),
+                                                                event: agent_event,
+                                                            });
                                                         }
-                                                        if let Err(e) = db.insert_agent_event(
-                                                            &run_id,
-                                                            "error",
-                                                            None,
-                                                            &format!("Budget exceeded: ${:.4} > ${:.4}", session.cost_usd, budget),
-                                                            None,
-                                                            None,
-                                                        ) {
+                                                        Err(e) => {
                                                             tracing::error!("Failed to insert budget exceeded event for {}: {}", run_id, e);
                                                         }
-                                                        session.process.kill().await.ok();
-                                                        break;
                                                     }
+                                                    break;
                                                 }
                                             }
                                         }
@@ -467,37 +974,45 @@ impl AgentManager {
                                             output_tokens,
                                             ..
                                         } => {
-                                            let mut sessions = sessions.write().await;
-                                            if let Some(session) = sessions.get_mut(&run_id) {
-                                                session.claude_session_id = Some(session_id.clone());
-                                                // Use the Result event's cost if it's non-zero (authoritative total),
-                                                // otherwise keep the accumulated cost from ApiRequest events to avoid
-                                                // overwriting real cost data with a default 0.
-                                                if *cost_usd > 0.0 {
-                                                    session.cost_usd = *cost_usd;
-                                                }
-                                                if *input_tokens > 0 {
-                                                    session.input_tokens = *input_tokens;
-                                                }
-                                                if *output_tokens > 0 {
-                                                    session.output_tokens = *output_tokens;
-                                                }
-                                                if let Err(e) = db.update_agent_run_session_id(&run_id, session_id) {
-                                                    tracing::error!("Failed to update agent run session ID for {}: {}", run_id, e);
-                                                }
-                                                if let Err(e) = db.update_agent_run_cost(
-                                                    &run_id,
-                                                    session.cost_usd,
-                                                    session.input_tokens,
-                                                    session.output_tokens,
-                                                ) {
-                                                    tracing::error!("Failed to update agent run cost for {}: {}", run_id, e);
+                                            let totals = {
+                                                let mut sessions = sessions.write().await;
+                                                sessions.get_mut(&run_id).map(|session| {
+                                                    session.claude_session_id = Some(session_id.clone());
+                                                    // Use the Result event's cost if it's non-zero (authoritative total),
+                                                    // otherwise keep the accumulated cost from ApiRequest events to avoid
+                                                    // overwriting real cost data with a default 0.
+                                                    if *cost_usd > 0.0 {
+                                                        session.cost_usd = *cost_usd;
+                                                    }
+                                                    if *input_tokens > 0 {
+                                                        session.input_tokens = *input_tokens;
+                                                    }
+                                                    if *output_tokens > 0 {
+                                                        session.output_tokens = *output_tokens;
+                                                    }
+                                                    (session.cost_usd, session.input_tokens, session.output_tokens)
+                                                })
+                                            };
+                                            if let Some((cost, input, output)) = totals {
+                                                let (id, sid) = (run_id.clone(), session_id.clone());
+                                                let written = db
+                                                    .call(move |db| {
+                                                        db.update_agent_run_session_id(&id, &sid)?;
+                                                        db.update_agent_run_cost(&id, cost, input, output)
+                                                    })
+                                                    .await;
+                                                if let Err(e) = written {
+                                                    tracing::error!("Failed to update agent run session ID and cost for {}: {}", run_id, e);
                                                 }
                                             }
                                         }
                                         _ => {
-                                            if let Err(e) = db.update_agent_run_activity(&run_id) {
-                                                tracing::error!("Failed to update agent run activity for {}: {}", run_id, e);
+                                            if last_activity_write.is_none_or(|t| t.elapsed() >= ACTIVITY_WRITE_INTERVAL) {
+                                                last_activity_write = Some(std::time::Instant::now());
+                                                let id = run_id.clone();
+                                                if let Err(e) = db.call(move |db| db.update_agent_run_activity(&id)).await {
+                                                    tracing::error!("Failed to update agent run activity for {}: {}", run_id, e);
+                                                }
                                             }
                                         }
                                     }
@@ -521,23 +1036,23 @@ impl AgentManager {
                         if total_elapsed >= hard_timeout {
                             tracing::warn!("Agent {} hard timeout after {:?}", run_id, total_elapsed);
                             timed_out = true;
-                            let mut sessions = sessions.write().await;
-                            if let Some(session) = sessions.get_mut(&run_id) {
-                                session.status = AgentStatus::Failed;
-                                if let Err(e) = db.update_agent_run_status(&run_id, "failed") {
+                            let killed = match sessions.write().await.get_mut(&run_id) {
+                                Some(session) => {
+                                    session.status = RunStatus::Failed;
+                                    session.process.kill().await.ok();
+                                    true
+                                }
+                                None => false,
+                            };
+                            if killed {
+                                let id = run_id.clone();
+                                if let Err(e) = db.call(move |db| db.update_agent_run_status(&id, RunStatus::Failed)).await {
                                     tracing::error!("Failed to update agent run status to failed for {}: {}", run_id, e);
                                 }
-                                if let Err(e) = db.insert_agent_event(
-                                    &run_id,
-                                    "error",
-                                    None,
-                                    &format!("Hard timeout after {:?}", total_elapsed),
-                                    None,
-                                    None,
-                                ) {
+                                let (id, summary) = (run_id.clone(), format!("Hard timeout after {:?}", total_elapsed));
+                                if let Err(e) = db.call(move |db| db.insert_agent_event(&id, "error", None, &summary, None, None)).await {
                                     tracing::error!("Failed to insert hard timeout event for {}: {}", run_id, e);
                                 }
-                                session.process.kill().await.ok();
                             }
                             break;
                         }
@@ -546,20 +1061,23 @@ impl AgentManager {
                         if elapsed_since_last_event >= stall_timeout && !stalled {
                             tracing::warn!("Agent {} stalled - no events for {:?}", run_id, elapsed_since_last_event);
                             stalled = true;
-                            let mut sessions = sessions.write().await;
-                            if let Some(session) = sessions.get_mut(&run_id) {
-                                session.status = AgentStatus::Stalled;
-                                if let Err(e) = db.update_agent_run_status(&run_id, "stalled") {
+                            let marked = match sessions.write().await.get_mut(&run_id) {
+                                Some(session) => {
+                                    session.status = RunStatus::Stalled;
+                                    true
+                                }
+                                None => false,
+                            };
+                            if marked {
+                                let id = run_id.clone();
+                                if let Err(e) = db.call(move |db| db.update_agent_run_status(&id, RunStatus::Stalled)).await {
                                     tracing::error!("Failed to update agent run status to stalled for {}: {}", run_id, e);
                                 }
-                                if let Err(e) = db.insert_agent_event(
-                                    &run_id,
-                                    "warning",
-                                    None,
-                                    &format!("Agent stalled - no events for {:?}", elapsed_since_last_event),
-                                    None,
-                                    None,
-                                ) {
+                                let (id, summary) = (
+                                    run_id.clone(),
+                                    format!("Agent stalled - no events for {:?}", elapsed_since_last_event),
+                                );
+                                if let Err(e) = db.call(move |db| db.insert_agent_event(&id, "warning", None, &summary, None, None)).await {
                                     tracing::error!("Failed to insert stalled event for {}: {}", run_id, e);
                                 }
                             }
@@ -593,7 +1111,7 @@ impl AgentManager {
                         if let Err(e) = db.update_task(
                             &task_id_owned,
                             &crate::db::queries::UpdateTask {
-                                status: Some("failed".to_string()),
+                                status: Some(TaskStatus::Failed),
                                 title: None,
                                 description: None,
                                 priority: None,
@@ -608,12 +1126,12 @@ impl AgentManager {
                                 e
                             );
                         }
-                        "failed"
+                        RunStatus::Failed
                     } else if budget_exceeded {
                         if let Err(e) = db.update_task(
                             &task_id_owned,
                             &crate::db::queries::UpdateTask {
-                                status: Some("failed".to_string()),
+                                status: Some(TaskStatus::Failed),
                                 title: None,
                                 description: None,
                                 priority: None,
@@ -623,7 +1141,7 @@ impl AgentManager {
                         ) {
                             tracing::error!("Failed to update task {} to failed (budget exceeded) for agent {}: {}", task_id_owned, run_id, e);
                         }
-                        "killed"
+                        RunStatus::Killed
                     } else {
                         // Normal exit - wait for process to fully exit before checking status.
                         // Using try_wait() here would race: stdout closes (EOF) before the
@@ -633,25 +1151,8 @@ impl AgentManager {
                         match exit_status {
                             Ok(status) if status.success() => {
                                 // Successful exit code means the agent completed its work.
-                                if let Err(e) = db.update_task(
-                                    &task_id_owned,
-                                    &crate::db::queries::UpdateTask {
-                                        status: Some("done".to_string()),
-                                        title: None,
-                                        description: None,
-                                        priority: None,
-                                        depends_on: None,
-                                        ..Default::default()
-                                    },
-                                ) {
-                                    tracing::error!(
-                                        "Failed to update task {} to done for agent {}: {}",
-                                        task_id_owned,
-                                        run_id,
-                                        e
-                                    );
-                                }
-                                "done"
+                                // The run and task are marked once its changes are committed.
+                                RunStatus::Done
                             }
                             Ok(status) => {
                                 tracing::warn!(
@@ -662,7 +1163,7 @@ impl AgentManager {
                                 if let Err(e) = db.update_task(
                                     &task_id_owned,
                                     &crate::db::queries::UpdateTask {
-                                        status: Some("failed".to_string()),
+                                        status: Some(TaskStatus::Failed),
                                         title: None,
                                         description: None,
                                         priority: None,
@@ -672,7 +1173,7 @@ impl AgentManager {
                                 ) {
                                     tracing::error!("Failed to update task {} to failed (exit code {:?}) for agent {}: {}", task_id_owned, status.code(), run_id, e);
                                 }
-                                "failed"
+                                RunStatus::Failed
                             }
                             Err(e) => {
                                 tracing::error!(
@@ -683,7 +1184,7 @@ impl AgentManager {
                                 if let Err(e) = db.update_task(
                                     &task_id_owned,
                                     &crate::db::queries::UpdateTask {
-                                        status: Some("failed".to_string()),
+                                        status: Some(TaskStatus::Failed),
                                         title: None,
                                         description: None,
                                         priority: None,
@@ -693,29 +1194,60 @@ impl AgentManager {
                                 ) {
                                     tracing::error!("Failed to update task {} to failed (wait error) for agent {}: {}", task_id_owned, run_id, e);
                                 }
-                                "failed"
+                                RunStatus::Failed
                             }
                         }
                     };
 
-                    if let Err(e) = db.update_agent_run_status(&run_id, final_status) {
-                        tracing::error!(
-                            "Failed to update agent run status to {} for {}: {}",
-                            final_status,
-                            run_id,
-                            e
-                        );
+                    // A successful run is marked once its changes are committed
+                    if final_status != RunStatus::Done {
+                        if let Err(e) = db.update_agent_run_status(&run_id, final_status) {
+                            tracing::error!(
+                                "Failed to update agent run status to {} for {}: {}",
+                                final_status,
+                                run_id,
+                                e
+                            );
+                        }
                     }
-                    session.status = match final_status {
-                        "done" => AgentStatus::Done,
-                        "killed" => AgentStatus::Killed,
-                        _ => AgentStatus::Failed,
-                    };
+                    session.status = final_status;
+
+                    // Remove from live sessions; the worktree is torn down below
+                    sessions
+                        .remove(&run_id)
+                        .map(|session| (final_status, session))
+                } else {
+                    sessions.remove(&run_id);
+                    None
+                }
+            }; // sessions write lock dropped here
 
-                    // Cleanup worktree
-                    if let Err(e) =
-                        worktree::remove_worktree(&session.repo_path, &session.worktree_path).await
-                    {
+            // Run teardown and return the worktree to the pool, or remove it
+            let final_status = match final_status {
+                Some((status, session)) => {
+                    let (status, keep) = if status == RunStatus::Done {
+                        let status =
+                            complete_run(&db, &event_tx, &run_id, &task_id_owned, &session).await;
+                        // Edits that couldn't be committed or saved must not be cleaned away
+                        let keep = worktree::uncommitted_changes(&session.worktree_path)
+                            .await
+                            .map_or(true, |files| !files.is_empty());
+                        (status, keep)
+                    } else {
+                        (status, false)
+                    };
+                    if keep {
+                        let path = session.worktree_path.display().to_string();
+                        tracing::warn!("Keeping worktree {} of agent {}", path, run_id);
+                        let _ = db.insert_agent_event(
+                            &run_id,
+                            "uncommitted_changes",
+                            None,
+                            &format!("Worktree kept at {} with uncommitted changes", path),
+                            Some(&serde_json::json!({"kept_at": {"worktree": path}}).to_string()),
+                            None,
+                        );
+                    } else if let Err(e) = finish_worktree(&db, &run_id, &session).await {
                         tracing::error!(
                             "Failed to remove worktree {} for agent {}: {}",
                             session.worktree_path.display(),
@@ -723,39 +1255,91 @@ impl AgentManager {
                             e
                         );
                     }
-
-                    // Remove from live sessions
-                    sessions.remove(&run_id);
-
-                    Some(final_status)
-                } else {
-                    sessions.remove(&run_id);
-                    None
+                    Some(status)
                 }
-            }; // sessions write lock dropped here
+                None => None,
+            };
 
             tracing::info!("Agent {} finished with status {:?}", run_id, final_status);
 
             // Auto-dispatch next unblocked tasks for this goal
-            if final_status == Some("done") {
+            if final_status.is_some() && final_status != Some(RunStatus::Done) {
+                // A failed or killed agent merges nothing but frees its slot
+                if let Err(e) = db.enqueue_dispatch_job(&goal_space_id_owned, Some(&run_id)) {
+                    tracing::error!("Failed to queue dispatch after agent {}: {}", run_id, e);
+                }
+                let _ = dispatch_tx.send(());
+            } else if final_status == Some(RunStatus::Done) {
                 // Look up the branch from the DB so we can merge it
-                let branch_to_merge = match db.get_agent_run(&run_id) {
+                let mut branch_to_merge = match db.get_agent_run(&run_id) {
                     Ok(Some(ar)) => ar.branch,
                     _ => None,
                 };
 
+                // Manual merge mode: keep the branch until a human approves it
+                let awaiting_review = matches!(
+                    db.get_task(&task_id_owned),
+                    Ok(Some(t)) if t.status == TaskStatus::AwaitingReview
+                );
+                if awaiting_review {
+                    if let Some(branch) = branch_to_merge.take() {
+                        if let Err(e) = db.insert_agent_event(
+                            &run_id,
+                            "awaiting_review",
+                            None,
+                            &format!("Branch {} is waiting for review before merging", branch),
+                            None,
+                            None,
+                        ) {
+                            tracing::error!(
+                                "Failed to insert awaiting review event for {}: {}",
+                                run_id,
+                                e
+                            );
+                        }
+                    }
+                }
+
+                // Push the branch to the project's remote before it is merged locally
+                match crate::agent::forge::publish_run(&db, &run_id, None).await {
+                    Ok(Some(published)) => tracing::info!(
+                        "Pushed branch {} for agent {} to {}",
+                        published.branch,
+                        run_id,
+                        published.remote
+                    ),
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::warn!("Failed to publish branch for agent {}: {}", run_id, e);
+                        let _ = db.insert_agent_event(
+                            &run_id,
+                            "push_failed",
+                            None,
+                            &format!("Failed to push branch: {}", e),
+                            None,
+                            None,
+                        );
+                    }
+                }
+
                 // Resolve the actual repo_path from the goal space
                 let repo_path = match db.get_goal_space(&goal_space_id_owned) {
                     Ok(Some(g)) => Some(g.repo_path),
                     _ => None,
                 };
 
-                let _ = dispatch_tx.send(DispatchMessage {
-                    goal_space_id: goal_space_id_owned,
-                    branch_to_merge,
-                    repo_path,
-                    agent_run_id: Some(run_id.clone()),
-                });
+                let queued = match (branch_to_merge, repo_path) {
+                    (Some(branch), Some(repo_path)) => db
+                        .enqueue_merge_job(&goal_space_id_owned, &run_id, &repo_path, &branch)
+                        .map(|_| ()),
+                    _ => db
+                        .enqueue_dispatch_job(&goal_space_id_owned, Some(&run_id))
+                        .map(|_| ()),
+                };
+                if let Err(e) = queued {
+                    tracing::error!("Failed to queue follow-up work for agent {}: {}", run_id, e);
+                }
+                let _ = dispatch_tx.send(());
             }
         });
 
@@ -793,128 +1377,72 @@ impl AgentManager {
             }
         };
 
-        // Spawn a new claude process with --resume, capturing stdout for event tracking
-        let mut cmd = Command::new("claude");
-        cmd.arg("-p")
-            .arg(message)
-            .arg("--resume")
-            .arg(&session_id)
-            .arg("--output-format")
-            .arg("stream-json");
-
-        cmd.current_dir(&worktree_path);
-        cmd.stdout(std::process::Stdio::piped());
-        cmd.stderr(std::process::Stdio::piped());
-
-        let mut child = cmd.spawn().context("Failed to spawn claude resume")?;
+        let child = resume_command(&session_id, &worktree_path, message)
+            .spawn()
+            .context("Failed to spawn claude resume")?;
 
         tracing::info!("Nudged agent {} with message: {}", agent_run_id, message);
 
-        let stdout = child.stdout.take().expect("stdout piped");
-        let stderr = child.stderr.take().expect("stderr piped");
-
-        let db = self.db.clone();
-        let event_tx = self.event_tx.clone();
-        let agent_run_id_owned = agent_run_id.to_string();
-
-        // Spawn a task to read and process the nudge output
-        tokio::spawn(async move {
-            let run_id = agent_run_id_owned;
-
-            // Collect stderr in background
-            let stderr_handle = tokio::spawn(async move {
-                let mut reader = BufReader::new(stderr);
-                let mut output = String::new();
-                let _ = tokio::io::AsyncReadExt::read_to_string(&mut reader, &mut output).await;
-                output
-            });
-
-            // Parse stdout events
-            let mut lines = BufReader::new(stdout).lines();
-            while let Ok(Some(line)) = lines.next_line().await {
-                if line.trim().is_empty() {
-                    continue;
-                }
-                if let Some(parsed) = event_parser::parse_stream_json_line(&line) {
-                    // Store in DB and broadcast via SSE
-                    if let Ok(agent_event) = event_parser::store_event(&db, &run_id, &parsed, &line)
-                    {
-                        let _ = event_tx.send(BroadcastEvent::AgentEvent {
-                            agent_run_id: run_id.clone(),
-                            event: agent_event,
-                        });
-                    }
-
-                    // Update cost from Result event
-                    if let ParsedEvent::Result {
-                        cost_usd,
-                        input_tokens,
-                        output_tokens,
-                        ..
-                    } = &parsed
-                    {
-                        if *cost_usd > 0.0 {
-                            // Add nudge cost to existing agent run cost
-                            if let Ok(Some(ar)) = db.get_agent_run(&run_id) {
-                                let _ = db.update_agent_run_cost(
-                                    &run_id,
-                                    ar.cost_usd + cost_usd,
-                                    ar.input_tokens + input_tokens,
-                                    ar.output_tokens + output_tokens,
-                                );
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Wait for process to exit
-            match child.wait().await {
-                Ok(status) if status.success() => {
-                    tracing::debug!("Nudge for agent {} completed successfully", run_id);
-                }
-                Ok(status) => {
-                    tracing::warn!("Nudge for agent {} exited with status: {}", run_id, status);
-                }
-                Err(e) => {
-                    tracing::error!(
-                        "Failed to wait for nudge process for agent {}: {}",
-                        run_id,
-                        e
-                    );
-                }
-            }
-
-            let stderr_output = stderr_handle.await.unwrap_or_default();
-            if !stderr_output.trim().is_empty() {
-                tracing::warn!(
-                    "Nudge stderr for agent {}: {}",
-                    run_id,
-                    stderr_output.trim()
-                );
-            }
-        });
+        // Read and process the nudge output in the background
+        tokio::spawn(stream_resume(
+            self.db.clone(),
+            self.event_tx.clone(),
+            agent_run_id.to_string(),
+            child,
+        ));
 
         Ok(())
     }
 
     /// Kill a running agent
     pub async fn kill_agent(&self, agent_run_id: &str) -> Result<()> {
-        let mut sessions = self.sessions.write().await;
-        let session = sessions
-            .get_mut(agent_run_id)
-            .context("Agent not found or not running")?;
-
-        // Send SIGTERM
-        session.process.kill().await.ok();
-        session.status = AgentStatus::Killed;
-
-        self.db.update_agent_run_status(agent_run_id, "killed")?;
-
-        // Cleanup worktree
-        worktree::remove_worktree(&session.repo_path, &session.worktree_path).await?;
+        let session = {
+            let mut sessions = self.sessions.write().await;
+            let mut session = sessions
+                .remove(agent_run_id)
+                .context("Agent not found or not running")?;
+            // Send SIGTERM
+            session.process.kill().await.ok();
+            session.status = RunStatus::Killed;
+            session
+        };
 
-        sessions.remove(agent_run_id);
+        // The task stops with its run; it runs again only once retried
+        let (id, task_id) = (agent_run_id.to_string(), session.task_id.clone());
+        let goal_space_id = self
+            .db
+            .call(move |db| {
+                db.update_agent_run_status(&id, RunStatus::Killed)?;
+                let update = crate::db::queries::UpdateTask {
+                    status: Some(TaskStatus::Killed),
+                    ..Default::default()
+                };
+                if let Err(e) = db.update_task(&task_id, &update) {
+                    tracing::error!("Failed to mark task {} killed: {}", task_id, e);
+                }
+                Ok(db.get_task(&task_id)?.map(|task| task.goal_space_id))
+            })
+            .await?;
+
+        // Run teardown and return the worktree to the pool, or remove it
+        finish_worktree(&self.db, agent_run_id, &session).await?;
+
+        // The killed run frees its slot for other tasks
+        if let Some(goal_space_id) = goal_space_id {
+            let id = agent_run_id.to_string();
+            let queued = self
+                .db
+                .call(move |db| db.enqueue_dispatch_job(&goal_space_id, Some(&id)))
+                .await;
+            if let Err(e) = queued {
+                tracing::error!(
+                    "Failed to queue dispatch after killing agent {}: {}",
+                    agent_run_id,
+                    e
+                );
+            }
+            self.wake_dispatch();
+        }
 
         tracing::info!("Killed agent {}", agent_run_id);
 
@@ -935,3 +1463,275 @@ impl AgentManager {
         sessions.contains_key(agent_run_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{CreateGoalSpace, CreateTask, GoalSettings};
+    use crate::goal::task::walk_task_to;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+    }
+
+    /// A finished session whose worktree has one uncommitted file, under `policy`
+    async fn dirty_session(db: &Database, policy: UncommittedPolicy) -> (LiveSession, String) {
+        let repo = std::env::temp_dir().join(format!("conductor-session-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+        run_git(&repo, &["config", "user.email", "test@example.com"]);
+        run_git(&repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("README.md"), "hello\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "init"]);
+        std::fs::write(repo.join("work.txt"), "forgot to commit\n").unwrap();
+
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: repo.to_string_lossy().into(),
+                settings: GoalSettings {
+                    uncommitted_policy: Some(policy),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        let task = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: "Write work".into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let run = db
+            .create_agent_run(&task.id, &goal.id, None, Some("main"), "sonnet", None)
+            .unwrap();
+        // The agent has been running and just exited
+        db.update_agent_run_status(&run.id, RunStatus::Running)
+            .unwrap();
+        walk_task_to(db, &task.id, TaskStatus::Running);
+
+        let session = LiveSession {
+            agent_run_id: run.id.clone(),
+            claude_session_id: None,
+            process: Command::new("true").spawn().unwrap(),
+            worktree_path: repo.clone(),
+            repo_path: repo,
+            task_id: task.id,
+            branch: "main".into(),
+            worktree_setup: Default::default(),
+            status: RunStatus::Done,
+            cost_usd: 0.0,
+            input_tokens: 0,
+            output_tokens: 0,
+        };
+        (session, run.id)
+    }
+
+    #[tokio::test]
+    async fn test_complete_run_auto_commits_leftover_changes() {
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        let (event_tx, _) = broadcast::channel(16);
+        let (session, run_id) = dirty_session(&db, UncommittedPolicy::Commit).await;
+
+        let status = complete_run(&db, &event_tx, &run_id, &session.task_id, &session).await;
+        assert_eq!(status, RunStatus::Done);
+        assert!(worktree::uncommitted_changes(&session.worktree_path)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.get_task(&session.task_id).unwrap().unwrap().status,
+            TaskStatus::Done
+        );
+
+        let events = db.list_agent_events(&run_id).unwrap();
+        let event = events
+            .iter()
+            .find(|e| e.event_type == "uncommitted_changes")
+            .unwrap();
+        assert!(event
+            .summary
+            .starts_with("Auto-committed 1 uncommitted file(s)"));
+
+        std::fs::remove_dir_all(&session.repo_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_complete_run_fails_task_on_leftover_changes() {
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        let (event_tx, _) = broadcast::channel(16);
+        for policy in [UncommittedPolicy::Fail, UncommittedPolicy::Nudge] {
+            // Without a Claude session to resume, a nudge can't help
+            let (session, run_id) = dirty_session(&db, policy).await;
+
+            let status = complete_run(&db, &event_tx, &run_id, &session.task_id, &session).await;
+            assert_eq!(status, RunStatus::Failed);
+            let run = db.get_agent_run(&run_id).unwrap().unwrap();
+            assert_eq!(run.status, RunStatus::Failed);
+            assert_eq!(run.failure_reason.as_deref(), Some("uncommitted_changes"));
+            assert_eq!(
+                db.get_task(&session.task_id).unwrap().unwrap().status,
+                TaskStatus::Failed
+            );
+            let events = db.list_agent_events(&run_id).unwrap();
+            let event = events
+                .iter()
+                .find(|e| e.event_type == "uncommitted_changes")
+                .unwrap();
+            let wip_ref = format!("refs/conductor/wip/{}", run_id);
+            assert!(event.summary.ends_with(&format!("saved to {}", wip_ref)));
+
+            // The edits survive on the WIP ref, off the branch, so releasing the
+            // worktree loses nothing
+            let saved = std::process::Command::new("git")
+                .args(["show", &format!("{}:work.txt", wip_ref)])
+                .current_dir(&session.repo_path)
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8_lossy(&saved.stdout), "forgot to commit\n");
+            assert!(worktree::uncommitted_changes(&session.worktree_path)
+                .await
+                .unwrap()
+                .is_empty());
+            let log = std::process::Command::new("git")
+                .args(["log", "--oneline", "main"])
+                .current_dir(&session.repo_path)
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8_lossy(&log.stdout).lines().count(), 1);
+
+            std::fs::remove_dir_all(&session.repo_path).ok();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_killed_task_can_be_retried() {
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        let (event_tx, _) = broadcast::channel(16);
+        let (dispatch_tx, _dispatch_rx) = mpsc::unbounded_channel();
+        let state = Arc::new(crate::server::AppState {
+            db: db.clone(),
+            agent_manager: AgentManager::new(db.clone(), event_tx.clone(), dispatch_tx),
+            event_tx,
+        });
+
+        let repo = std::env::temp_dir().join(format!("conductor-session-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+        run_git(&repo, &["config", "user.email", "test@example.com"]);
+        run_git(&repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("README.md"), "hello\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "init"]);
+        let worktree_path = repo.join(".worktrees").join("kill");
+        let branch = "conductor/kill";
+        run_git(
+            &repo,
+            &[
+                "worktree",
+                "add",
+                "-q",
+                "-b",
+                branch,
+                worktree_path.to_str().unwrap(),
+            ],
+        );
+
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: repo.to_string_lossy().into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let task = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: "Long task".into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let run = db
+            .create_agent_run(&task.id, &goal.id, None, Some(branch), "sonnet", None)
+            .unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Running)
+            .unwrap();
+        walk_task_to(&db, &task.id, TaskStatus::Running);
+        state.agent_manager.sessions.write().await.insert(
+            run.id.clone(),
+            LiveSession {
+                agent_run_id: run.id.clone(),
+                claude_session_id: None,
+                process: Command::new("sleep").arg("30").spawn().unwrap(),
+                worktree_path: worktree_path.clone(),
+                repo_path: repo.clone(),
+                task_id: task.id.clone(),
+                branch: branch.into(),
+                worktree_setup: Default::default(),
+                status: RunStatus::Running,
+                cost_usd: 0.0,
+                input_tokens: 0,
+                output_tokens: 0,
+            },
+        );
+
+        let post = |uri: String| Request::post(uri).body(Body::empty()).unwrap();
+        let app = crate::server::routes::create_router(state.clone());
+        let resp = app
+            .clone()
+            .oneshot(post(format!("/api/agents/{}/kill", run.id)))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            db.get_agent_run(&run.id).unwrap().unwrap().status,
+            RunStatus::Killed
+        );
+        assert_eq!(
+            db.get_task(&task.id).unwrap().unwrap().status,
+            TaskStatus::Killed
+        );
+        assert!(!worktree_path.exists());
+        // The freed slot is handed back to dispatch
+        let job = db.claim_next_job().unwrap().unwrap();
+        assert_eq!(job.kind, crate::db::jobs::JobKind::Dispatch);
+        assert_eq!(job.agent_run_id.as_deref(), Some(run.id.as_str()));
+
+        let resp = app
+            .oneshot(post(format!("/api/tasks/{}/retry", task.id)))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            db.get_task(&task.id).unwrap().unwrap().status,
+            TaskStatus::Pending
+        );
+
+        std::fs::remove_dir_all(&repo).ok();
+    }
+}
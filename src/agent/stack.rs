@@ -0,0 +1,235 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::agent::worktree;
+use crate::db::queries::{AgentRun, Task};
+use crate::db::Database;
+use crate::goal::task::{RunStatus, TaskStatus};
+
+/// The finished, not yet merged runs of a task's dependencies whose branches
+/// still exist, in dependency order. A stacked task starts from these branches.
+pub async fn unmerged_dependency_runs(
+    db: &Database,
+    repo_path: &Path,
+    task: &Task,
+) -> Result<Vec<AgentRun>> {
+    let mut runs = Vec::new();
+    for dep in &task.depends_on {
+        let dep = dep.clone();
+        let Some(run) = db
+            .call(move |db| db.latest_agent_run_for_task(&dep))
+            .await?
+        else {
+            continue;
+        };
+        if run.status != RunStatus::Done || run.merge_commit.is_some() {
+            continue;
+        }
+        let Some(ref branch) = run.branch else {
+            continue;
+        };
+        if worktree::rev_parse(repo_path, &format!("refs/heads/{}", branch))
+            .await
+            .is_ok()
+        {
+            runs.push(run);
+        }
+    }
+    Ok(runs)
+}
+
+/// The unmerged dependency runs a run was actually stacked on:
+/// those whose branch tip is contained in the run's base commit
+pub async fn stack_parents(
+    db: &Database,
+    repo_path: &Path,
+    run: &AgentRun,
+) -> Result<Vec<AgentRun>> {
+    let Some(ref base) = run.base_commit else {
+        return Ok(Vec::new());
+    };
+    let task_id = run.task_id.clone();
+    let task = db
+        .call(move |db| db.get_task(&task_id))
+        .await?
+        .context("Task not found")?;
+
+    let mut parents = Vec::new();
+    for candidate in unmerged_dependency_runs(db, repo_path, &task).await? {
+        let branch = candidate.branch.as_deref().unwrap_or_default();
+        let tip = worktree::rev_parse(repo_path, &format!("refs/heads/{}", branch)).await?;
+        if worktree::is_ancestor(repo_path, &tip, base).await? {
+            parents.push(candidate);
+        }
+    }
+    Ok(parents)
+}
+
+/// Every run that has to land for `run` to land, bottom of the stack first,
+/// ending with `run` itself
+pub async fn landing_order(
+    db: &Database,
+    repo_path: &Path,
+    run: &AgentRun,
+) -> Result<Vec<AgentRun>> {
+    let mut order = Vec::new();
+    let mut landed: HashSet<String> = HashSet::new();
+    let mut pending = vec![(run.clone(), false)];
+
+    while let Some((current, expanded)) = pending.pop() {
+        if landed.contains(&current.id) {
+            continue;
+        }
+        if expanded {
+            landed.insert(current.id.clone());
+            order.push(current);
+            continue;
+        }
+        let parents = stack_parents(db, repo_path, &current).await?;
+        pending.push((current, true));
+        for parent in parents.into_iter().rev() {
+            if !landed.contains(&parent.id) {
+                pending.push((parent, false));
+            }
+        }
+    }
+
+    Ok(order)
+}
+
+/// Tasks beneath `run` in its stack that haven't been approved yet. A stacked
+/// run can't land until every branch under it may land too.
+pub async fn unapproved_parents(
+    db: &Database,
+    repo_path: &Path,
+    run: &AgentRun,
+) -> Result<Vec<Task>> {
+    let mut waiting = Vec::new();
+    for parent in landing_order(db, repo_path, run).await? {
+        if parent.id == run.id {
+            continue;
+        }
+        let task_id = parent.task_id.clone();
+        let task = db
+            .call(move |db| db.get_task(&task_id))
+            .await?
+            .context("Task not found")?;
+        if task.status != TaskStatus::Done {
+            waiting.push(task);
+        }
+    }
+    Ok(waiting)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{CreateGoalSpace, CreateTask};
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+    }
+
+    fn commit_on(repo: &Path, branch: &str, from: &str, file: &str) -> String {
+        run_git(repo, &["checkout", "-q", "-b", branch, from]);
+        std::fs::write(repo.join(file), format!("{}\n", file)).unwrap();
+        run_git(repo, &["add", "."]);
+        run_git(repo, &["commit", "-q", "-m", file]);
+        run_git(repo, &["checkout", "-q", "main"]);
+        String::from_utf8(
+            std::process::Command::new("git")
+                .args(["rev-parse", branch])
+                .current_dir(repo)
+                .output()
+                .unwrap()
+                .stdout,
+        )
+        .unwrap()
+        .trim()
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_landing_order_follows_stack() {
+        let repo = std::env::temp_dir().join(format!("conductor-stack-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+        run_git(&repo, &["config", "user.email", "test@example.com"]);
+        run_git(&repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("README.md"), "hello\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "init"]);
+
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: repo.to_string_lossy().into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let task = |title: &str, deps: Vec<String>| {
+            db.create_task(
+                &goal.id,
+                &CreateTask {
+                    title: title.into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: deps,
+                    settings: Default::default(),
+                },
+            )
+            .unwrap()
+        };
+        let a = task("A", vec![]);
+        let unrelated = task("U", vec![]);
+        let b = task("B", vec![a.id.clone(), unrelated.id.clone()]);
+
+        let a_tip = commit_on(&repo, "a", "main", "a.txt");
+        commit_on(&repo, "u", "main", "u.txt");
+        commit_on(&repo, "b", "a", "b.txt");
+
+        let run_a = db
+            .create_agent_run(&a.id, &goal.id, None, Some("a"), "sonnet", None)
+            .unwrap();
+        db.update_agent_run_status(&run_a.id, RunStatus::Running)
+            .unwrap();
+        db.update_agent_run_status(&run_a.id, RunStatus::Done)
+            .unwrap();
+        let run_u = db
+            .create_agent_run(&unrelated.id, &goal.id, None, Some("u"), "sonnet", None)
+            .unwrap();
+        db.update_agent_run_status(&run_u.id, RunStatus::Running)
+            .unwrap();
+        db.update_agent_run_status(&run_u.id, RunStatus::Done)
+            .unwrap();
+        let run_b = db
+            .create_agent_run(&b.id, &goal.id, None, Some("b"), "sonnet", None)
+            .unwrap();
+        db.set_agent_run_base_commit(&run_b.id, &a_tip).unwrap();
+        let run_b = db.get_agent_run(&run_b.id).unwrap().unwrap();
+
+        let candidates = unmerged_dependency_runs(&db, &repo, &b).await.unwrap();
+        assert_eq!(candidates.len(), 2);
+
+        // B started from A's tip, so only A is beneath it in the stack
+        let order = landing_order(&db, &repo, &run_b).await.unwrap();
+        let ids: Vec<&str> = order.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec![run_a.id.as_str(), run_b.id.as_str()]);
+
+        // Once A has landed it is no longer part of the stack
+        db.set_agent_run_merge_commit(&run_a.id, &a_tip).unwrap();
+        let order = landing_order(&db, &repo, &run_b).await.unwrap();
+        assert_eq!(order.len(), 1);
+
+        std::fs::remove_dir_all(&repo).ok();
+    }
+}
@@ -2,15 +2,21 @@ use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
 use tokio::process::Command;
 
+use crate::agent::repo_lock;
+use crate::goal::task::{RunStatus, TaskStatus};
+
 pub const WORKTREE_BASE: &str = "/tmp/conductor/worktrees";
 
-/// Create a git worktree for an agent
+/// Create a git worktree for an agent.
+/// The new branch starts from `start_point` if given, otherwise from the current HEAD.
 pub async fn create_worktree(
     repo_path: &Path,
     agent_id: &str,
     branch_name: &str,
+    start_point: Option<&str>,
 ) -> Result<PathBuf> {
     let worktree_path = PathBuf::from(WORKTREE_BASE).join(agent_id);
+    let _lock = repo_lock::lock(repo_path, "create_worktree").await;
 
     // Ensure base directory exists
     tokio::fs::create_dir_all(WORKTREE_BASE)
@@ -19,7 +25,7 @@ pub async fn create_worktree(
 
     // Remove existing worktree if it exists
     if worktree_path.exists() {
-        if let Err(e) = remove_worktree(repo_path, &worktree_path).await {
+        if let Err(e) = remove_worktree_unlocked(repo_path, &worktree_path).await {
             tracing::warn!(
                 "Failed to remove existing worktree at {}: {}",
                 worktree_path.display(),
@@ -33,12 +39,9 @@ pub async fn create_worktree(
         .context("Worktree path contains invalid UTF-8")?;
 
     // Create the worktree with a new branch
-    let output = Command::new("git")
-        .args(["worktree", "add", wt_str, "-b", branch_name])
-        .current_dir(repo_path)
-        .output()
-        .await
-        .context("Failed to run git worktree add")?;
+    let mut args = vec!["worktree", "add", wt_str, "-b", branch_name];
+    args.extend(start_point);
+    let output = repo_lock::git(repo_path, repo_path, &args).await?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -48,12 +51,12 @@ pub async fn create_worktree(
         );
 
         // Branch might already exist, try without -b
-        let output = Command::new("git")
-            .args(["worktree", "add", wt_str, branch_name])
-            .current_dir(repo_path)
-            .output()
-            .await
-            .context("Failed to run git worktree add (existing branch)")?;
+        let output = repo_lock::git(
+            repo_path,
+            repo_path,
+            &["worktree", "add", wt_str, branch_name],
+        )
+        .await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -72,16 +75,22 @@ pub async fn create_worktree(
 
 /// Remove a git worktree and clean up stale metadata
 pub async fn remove_worktree(repo_path: &Path, worktree_path: &Path) -> Result<()> {
+    let _lock = repo_lock::lock(repo_path, "remove_worktree").await;
+    remove_worktree_unlocked(repo_path, worktree_path).await
+}
+
+/// Remove a worktree; the caller must hold the repo lock
+async fn remove_worktree_unlocked(repo_path: &Path, worktree_path: &Path) -> Result<()> {
     let wt_str = worktree_path
         .to_str()
         .context("Worktree path contains invalid UTF-8")?;
 
-    let output = Command::new("git")
-        .args(["worktree", "remove", "--force", wt_str])
-        .current_dir(repo_path)
-        .output()
-        .await
-        .context("Failed to run git worktree remove")?;
+    let output = repo_lock::git(
+        repo_path,
+        repo_path,
+        &["worktree", "remove", "--force", wt_str],
+    )
+    .await?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -100,11 +109,7 @@ pub async fn remove_worktree(repo_path: &Path, worktree_path: &Path) -> Result<(
     }
 
     // Prune stale worktree metadata from the git repo
-    let prune_output = Command::new("git")
-        .args(["worktree", "prune"])
-        .current_dir(repo_path)
-        .output()
-        .await;
+    let prune_output = repo_lock::git(repo_path, repo_path, &["worktree", "prune"]).await;
 
     if let Err(e) = prune_output {
         tracing::warn!("git worktree prune failed: {}", e);
@@ -115,24 +120,13 @@ pub async fn remove_worktree(repo_path: &Path, worktree_path: &Path) -> Result<(
 }
 
 /// Merge a completed agent branch into the repo's current branch (typically main).
-/// Returns Ok(true) on success, Ok(false) if there was nothing to merge.
+/// Returns the SHA of the resulting merge commit.
 /// On conflict, aborts the merge and returns an error.
-pub async fn merge_branch_to_main(repo_path: &Path, branch: &str) -> Result<()> {
-    // Detect the default branch
-    let head_output = Command::new("git")
-        .args(["symbolic-ref", "--short", "HEAD"])
-        .current_dir(repo_path)
-        .output()
-        .await
-        .context("Failed to detect default branch")?;
+pub async fn merge_branch_to_main(repo_path: &Path, branch: &str) -> Result<String> {
+    let _lock = repo_lock::lock(repo_path, "merge_branch_to_main").await;
 
-    let default_branch = String::from_utf8_lossy(&head_output.stdout)
-        .trim()
-        .to_string();
-
-    if default_branch.is_empty() {
-        anyhow::bail!("Could not determine default branch");
-    }
+    // Detect the default branch
+    let default_branch = current_branch(repo_path).await?;
 
     tracing::info!(
         "Merging branch {} into {} in {}",
@@ -142,49 +136,359 @@ pub async fn merge_branch_to_main(repo_path: &Path, branch: &str) -> Result<()>
     );
 
     // Perform the merge
-    let merge_output = Command::new("git")
-        .args([
-            "merge",
-            "--no-ff",
-            branch,
-            "-m",
-            &format!("Merge {}", branch),
-        ])
-        .current_dir(repo_path)
-        .output()
-        .await
-        .context("Failed to run git merge")?;
+    let message = format!("Merge {}", branch);
+    let merge_output = repo_lock::git(
+        repo_path,
+        repo_path,
+        &["merge", "--no-ff", branch, "-m", &message],
+    )
+    .await?;
 
     if !merge_output.status.success() {
         let stderr = String::from_utf8_lossy(&merge_output.stderr);
         tracing::warn!("Merge conflict for branch {}: {}", branch, stderr.trim());
 
         // Abort the failed merge to leave the repo clean
-        let _ = Command::new("git")
-            .args(["merge", "--abort"])
-            .current_dir(repo_path)
-            .output()
-            .await;
+        let _ = repo_lock::git(repo_path, repo_path, &["merge", "--abort"]).await;
 
         anyhow::bail!("Merge conflict for branch {}: {}", branch, stderr.trim());
     }
 
+    let merge_commit = rev_parse(repo_path, "HEAD").await?;
+
     tracing::info!(
-        "Successfully merged branch {} into {}",
+        "Successfully merged branch {} into {} ({})",
         branch,
-        default_branch
+        default_branch,
+        merge_commit
     );
+    Ok(merge_commit)
+}
+
+/// Revert a commit on the repo's current branch (typically main). Merge commits are
+/// reverted against their first parent. Returns the revert commit's SHA, or `None`
+/// when the revert conflicts with later work, in which case it is aborted.
+pub async fn revert_commit(repo_path: &Path, commit: &str) -> Result<Option<String>> {
+    let _lock = repo_lock::lock(repo_path, "revert_commit").await;
+
+    let parents = Command::new("git")
+        .args(["rev-list", "--parents", "-n", "1", commit])
+        .current_dir(repo_path)
+        .output()
+        .await
+        .context("Failed to run git rev-list")?;
+    if !parents.status.success() {
+        let stderr = String::from_utf8_lossy(&parents.stderr);
+        anyhow::bail!("Could not resolve {}: {}", commit, stderr.trim());
+    }
+    let is_merge = String::from_utf8_lossy(&parents.stdout)
+        .split_whitespace()
+        .count()
+        > 2;
+
+    let mut args = vec!["revert", "--no-edit"];
+    if is_merge {
+        args.extend(["-m", "1"]);
+    }
+    args.push(commit);
+    let output = repo_lock::git(repo_path, repo_path, &args).await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !stdout.contains("CONFLICT") && !stderr.contains("could not revert") {
+            anyhow::bail!("Revert of {} failed: {}", commit, stderr.trim());
+        }
+        tracing::warn!("Revert of {} conflicts: {}", commit, stderr.trim());
+
+        // Abort the failed revert to leave the repo clean
+        let _ = repo_lock::git(repo_path, repo_path, &["revert", "--abort"]).await;
+        return Ok(None);
+    }
+
+    let revert_commit = rev_parse(repo_path, "HEAD").await?;
+    tracing::info!(
+        "Reverted {} in {} ({})",
+        commit,
+        repo_path.display(),
+        revert_commit
+    );
+    Ok(Some(revert_commit))
+}
+
+/// Merge several branches into a worktree's HEAD in one (octopus) merge commit.
+/// On failure, aborts the merge and returns an error.
+pub async fn merge_branches(worktree_path: &Path, branches: &[String]) -> Result<()> {
+    if branches.is_empty() {
+        return Ok(());
+    }
+
+    let message = format!("Stack {}", branches.join(", "));
+    let mut args = vec!["merge", "--no-ff", "-m", message.as_str()];
+    args.extend(branches.iter().map(String::as_str));
+    let output = Command::new("git")
+        .args(&args)
+        .current_dir(worktree_path)
+        .output()
+        .await
+        .context("Failed to run git merge")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let _ = Command::new("git")
+            .args(["merge", "--abort"])
+            .current_dir(worktree_path)
+            .output()
+            .await;
+        anyhow::bail!(
+            "Failed to merge {} into {}: {}",
+            branches.join(", "),
+            worktree_path.display(),
+            stderr.trim()
+        );
+    }
+
     Ok(())
 }
 
-/// Delete a branch after it has been successfully merged
-pub async fn delete_branch(repo_path: &Path, branch: &str) -> Result<()> {
+/// Replay the commits of `branch` after `upstream` onto `onto`.
+/// Runs in a temporary worktree so the repo's own checkout is left alone.
+pub async fn rebase_branch(
+    repo_path: &Path,
+    branch: &str,
+    upstream: &str,
+    onto: &str,
+) -> Result<()> {
+    let rebase_path = PathBuf::from(WORKTREE_BASE).join(format!("rebase-{}", uuid::Uuid::new_v4()));
+    let _lock = repo_lock::lock(repo_path, "rebase_branch").await;
+    tokio::fs::create_dir_all(WORKTREE_BASE)
+        .await
+        .context("Failed to create worktree base directory")?;
+    let rebase_str = rebase_path
+        .to_str()
+        .context("Worktree path contains invalid UTF-8")?;
+
+    let output = repo_lock::git(
+        repo_path,
+        repo_path,
+        &["worktree", "add", rebase_str, branch],
+    )
+    .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git worktree add failed: {}", stderr.trim());
+    }
+
+    let output = repo_lock::git(
+        repo_path,
+        &rebase_path,
+        &["rebase", "--onto", onto, upstream],
+    )
+    .await;
+
+    let result = match output {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let _ = repo_lock::git(repo_path, &rebase_path, &["rebase", "--abort"]).await;
+            Err(anyhow::anyhow!(
+                "Rebase of {} onto {} failed: {}",
+                branch,
+                onto,
+                stderr.trim()
+            ))
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = remove_worktree_unlocked(repo_path, &rebase_path).await {
+        tracing::warn!(
+            "Failed to remove rebase worktree {}: {}",
+            rebase_path.display(),
+            e
+        );
+    }
+
+    if result.is_ok() {
+        tracing::info!("Rebased {} onto {}", branch, onto);
+    }
+    result
+}
+
+/// Whether `ancestor` is reachable from `descendant`
+pub async fn is_ancestor(repo_path: &Path, ancestor: &str, descendant: &str) -> Result<bool> {
+    let output = Command::new("git")
+        .args(["merge-base", "--is-ancestor", ancestor, descendant])
+        .current_dir(repo_path)
+        .output()
+        .await
+        .context("Failed to run git merge-base")?;
+
+    match output.status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        _ => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!(
+                "Failed to check whether {} is an ancestor of {}: {}",
+                ancestor,
+                descendant,
+                stderr.trim()
+            )
+        }
+    }
+}
+
+/// Resolve a revision to a full commit SHA
+pub async fn rev_parse(repo_path: &Path, rev: &str) -> Result<String> {
     let output = Command::new("git")
-        .args(["branch", "-d", branch])
+        .args(["rev-parse", "--verify", &format!("{}^{{commit}}", rev)])
         .current_dir(repo_path)
         .output()
         .await
-        .context("Failed to run git branch -d")?;
+        .context("Failed to run git rev-parse")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("Could not resolve {}: {}", rev, stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Paths with uncommitted or untracked changes in a worktree, as `git status --porcelain` lines
+pub async fn uncommitted_changes(worktree_path: &Path) -> Result<Vec<String>> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=all"])
+        .current_dir(worktree_path)
+        .output()
+        .await
+        .context("Failed to run git status")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git status failed: {}", stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.to_string())
+        .collect())
+}
+
+/// Stage and commit everything in a worktree. Falls back to a conductor identity
+/// when the repo has no committer configured. Returns the new commit's SHA.
+pub async fn commit_all(worktree_path: &Path, message: &str) -> Result<String> {
+    let add = Command::new("git")
+        .args(["add", "-A"])
+        .current_dir(worktree_path)
+        .output()
+        .await
+        .context("Failed to run git add")?;
+    if !add.status.success() {
+        let stderr = String::from_utf8_lossy(&add.stderr);
+        anyhow::bail!("git add failed: {}", stderr.trim());
+    }
+
+    let has_identity = Command::new("git")
+        .args(["config", "user.email"])
+        .current_dir(worktree_path)
+        .output()
+        .await
+        .map(|o| o.status.success())
+        .unwrap_or(false);
+    let mut args = Vec::new();
+    if !has_identity {
+        args.extend([
+            "-c",
+            "user.name=Conductor",
+            "-c",
+            "user.email=conductor@localhost",
+        ]);
+    }
+    args.extend(["commit", "-q", "--no-verify", "-m", message]);
+
+    let commit = Command::new("git")
+        .args(&args)
+        .current_dir(worktree_path)
+        .output()
+        .await
+        .context("Failed to run git commit")?;
+    if !commit.status.success() {
+        let stderr = String::from_utf8_lossy(&commit.stderr);
+        anyhow::bail!("git commit failed: {}", stderr.trim());
+    }
+
+    rev_parse(worktree_path, "HEAD").await
+}
+
+/// Save everything uncommitted in a worktree as a commit on `refs/conductor/wip/<name>`,
+/// leaving the branch where it was and the worktree clean. Returns the ref and the
+/// commit's SHA.
+pub async fn save_wip(worktree_path: &Path, name: &str, message: &str) -> Result<(String, String)> {
+    let head = rev_parse(worktree_path, "HEAD").await?;
+    let sha = commit_all(worktree_path, message).await?;
+    let wip_ref = format!("refs/conductor/wip/{}", name);
+
+    for args in [
+        vec!["update-ref", wip_ref.as_str(), sha.as_str()],
+        vec!["reset", "-q", "--hard", head.as_str()],
+    ] {
+        let output = Command::new("git")
+            .args(&args)
+            .current_dir(worktree_path)
+            .output()
+            .await
+            .with_context(|| format!("Failed to run git {}", args[0]))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("git {} failed: {}", args[0], stderr.trim());
+        }
+    }
+
+    Ok((wip_ref, sha))
+}
+
+/// Detect the branch currently checked out in the repo (typically main)
+pub async fn current_branch(repo_path: &Path) -> Result<String> {
+    let output = Command::new("git")
+        .args(["symbolic-ref", "--short", "HEAD"])
+        .current_dir(repo_path)
+        .output()
+        .await
+        .context("Failed to detect current branch")?;
+
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if branch.is_empty() {
+        anyhow::bail!("Could not determine current branch");
+    }
+    Ok(branch)
+}
+
+/// Push a local branch to a remote (a remote name or URL), creating it there if needed
+pub async fn push_branch(repo_path: &Path, remote: &str, branch: &str) -> Result<()> {
+    let refspec = format!("refs/heads/{}:refs/heads/{}", branch, branch);
+    let output = Command::new("git")
+        .args(["push", "--force-with-lease", remote, &refspec])
+        .current_dir(repo_path)
+        .output()
+        .await
+        .context("Failed to run git push")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("Failed to push {} to {}: {}", branch, remote, stderr.trim());
+    }
+
+    tracing::info!("Pushed branch {} to {}", branch, remote);
+    Ok(())
+}
+
+/// Delete a branch after it has been successfully merged
+pub async fn delete_branch(repo_path: &Path, branch: &str) -> Result<()> {
+    let _lock = repo_lock::lock(repo_path, "delete_branch").await;
+    let output = repo_lock::git(repo_path, repo_path, &["branch", "-d", branch]).await?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -196,6 +500,21 @@ pub async fn delete_branch(repo_path: &Path, branch: &str) -> Result<()> {
     Ok(())
 }
 
+/// Delete a branch whose work was thrown away, such as a run rejected in review
+pub async fn discard_branch(repo_path: &Path, branch: &str) -> Result<()> {
+    let _lock = repo_lock::lock(repo_path, "discard_branch").await;
+    let output = repo_lock::git(repo_path, repo_path, &["branch", "-D", branch]).await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        tracing::warn!("Failed to delete branch {}: {}", branch, stderr.trim());
+    } else {
+        tracing::info!("Deleted discarded branch {}", branch);
+    }
+
+    Ok(())
+}
+
 /// List all conductor worktrees for a repo
 #[allow(dead_code)]
 pub async fn list_worktrees(repo_path: &Path) -> Result<Vec<WorktreeInfo>> {
@@ -272,12 +591,12 @@ pub async fn cleanup_stale(
             run.id,
             run.status
         );
-        let _ = db.update_agent_run_status(&run.id, "failed");
+        let _ = db.update_agent_run_status(&run.id, RunStatus::Failed);
         // Also reset the task back to pending so it can be retried
         let _ = db.update_task(
             &run.task_id,
             &crate::db::queries::UpdateTask {
-                status: Some("pending".to_string()),
+                status: Some(TaskStatus::Pending),
                 title: None,
                 description: None,
                 priority: None,
@@ -300,19 +619,14 @@ pub async fn cleanup_stale(
             continue;
         }
 
+        let _lock = repo_lock::lock(repo_path, "cleanup_stale").await;
+
         // Prune stale worktree metadata
-        let _ = Command::new("git")
-            .args(["worktree", "prune"])
-            .current_dir(repo_path)
-            .output()
-            .await;
+        let _ = repo_lock::git(repo_path, repo_path, &["worktree", "prune"]).await;
 
         // Delete conductor/* branches that are no longer needed
-        let branch_output = Command::new("git")
-            .args(["branch", "--list", "conductor/*"])
-            .current_dir(repo_path)
-            .output()
-            .await;
+        let branch_output =
+            repo_lock::git(repo_path, repo_path, &["branch", "--list", "conductor/*"]).await;
 
         if let Ok(output) = branch_output {
             let stdout = String::from_utf8_lossy(&output.stdout);
@@ -322,11 +636,7 @@ pub async fn cleanup_stale(
                     continue;
                 }
                 // Try to delete — will fail if not fully merged, which is fine
-                let del = Command::new("git")
-                    .args(["branch", "-d", branch])
-                    .current_dir(repo_path)
-                    .output()
-                    .await;
+                let del = repo_lock::git(repo_path, repo_path, &["branch", "-d", branch]).await;
                 match del {
                     Ok(o) if o.status.success() => {
                         tracing::info!("Deleted stale branch {}", branch);
@@ -339,10 +649,7 @@ pub async fn cleanup_stale(
                             // Check if any agent run references this branch and is terminal
                             let runs = db.list_agent_runs()?;
                             let is_terminal = runs.iter().any(|r| {
-                                r.branch.as_deref() == Some(branch)
-                                    && (r.status == "done"
-                                        || r.status == "failed"
-                                        || r.status == "killed")
+                                r.branch.as_deref() == Some(branch) && r.status.is_terminal()
                             });
                             if is_terminal {
                                 tracing::warn!(
@@ -372,6 +679,11 @@ pub async fn cleanup_stale(
                 }
                 let dir_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
 
+                // Idle worktrees in the warm pool are kept for the next agent
+                if dir_name == crate::agent::pool::POOL_DIR {
+                    continue;
+                }
+
                 // Check if any active run owns this worktree
                 let is_active = active_run_ids.iter().any(|id| dir_name.contains(id));
                 if !is_active {
@@ -419,6 +731,34 @@ impl std::fmt::Display for CleanupReport {
     }
 }
 
+/// Generate a branch name for an agent's task
+pub fn branch_name(agent_id: &str, task_title: &str) -> String {
+    let sanitized: String = task_title
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>()
+        .to_lowercase();
+
+    let sanitized = sanitized.trim_matches('-');
+    let truncated = if sanitized.len() > 40 {
+        &sanitized[..40]
+    } else {
+        sanitized
+    };
+
+    format!(
+        "conductor/{}/{}",
+        &agent_id[..8.min(agent_id.len())],
+        truncated
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -467,32 +807,37 @@ mod tests {
         let name = branch_name("abcdef12-xxxx", "already-hyphenated-name");
         assert_eq!(name, "conductor/abcdef12/already-hyphenated-name");
     }
-}
-
-/// Generate a branch name for an agent's task
-pub fn branch_name(agent_id: &str, task_title: &str) -> String {
-    let sanitized: String = task_title
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '-' {
-                c
-            } else {
-                '-'
-            }
-        })
-        .collect::<String>()
-        .to_lowercase();
 
-    let sanitized = sanitized.trim_matches('-');
-    let truncated = if sanitized.len() > 40 {
-        &sanitized[..40]
-    } else {
-        sanitized
-    };
-
-    format!(
-        "conductor/{}/{}",
-        &agent_id[..8.min(agent_id.len())],
-        truncated
-    )
+    #[tokio::test]
+    async fn test_uncommitted_changes_and_commit_all() {
+        let repo = std::env::temp_dir().join(format!("conductor-wt-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .args(args)
+                .current_dir(&repo)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q", "-b", "main"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test"]);
+        std::fs::write(repo.join("README.md"), "hello\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "init"]);
+        assert!(uncommitted_changes(&repo).await.unwrap().is_empty());
+
+        std::fs::write(repo.join("README.md"), "changed\n").unwrap();
+        std::fs::create_dir_all(repo.join("src")).unwrap();
+        std::fs::write(repo.join("src/new.rs"), "fn main() {}\n").unwrap();
+        let changes = uncommitted_changes(&repo).await.unwrap();
+        assert_eq!(changes, vec![" M README.md", "?? src/new.rs"]);
+
+        let sha = commit_all(&repo, "Salvage work").await.unwrap();
+        assert_eq!(rev_parse(&repo, "HEAD").await.unwrap(), sha);
+        assert!(uncommitted_changes(&repo).await.unwrap().is_empty());
+
+        std::fs::remove_dir_all(&repo).ok();
+    }
 }
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(
@@ -54,9 +54,155 @@ pub enum Commands {
     Logs {
         /// Agent run ID
         agent_id: String,
+        /// Only events after this event ID
+        #[arg(long)]
+        after: Option<i64>,
+        /// Maximum number of events
+        #[arg(long)]
+        limit: Option<u32>,
+        /// Only events of this type (e.g. tool_call)
+        #[arg(long = "type")]
+        event_type: Option<String>,
+        /// Only events from this tool (e.g. Bash)
+        #[arg(long)]
+        tool: Option<String>,
+    },
+    /// Show what an agent changed
+    Diff {
+        /// Agent run ID
+        agent_id: String,
+        /// Only print per-file stats and commits, not the full diff
+        #[arg(long)]
+        stat: bool,
+    },
+    /// Show the pull-request description for an agent branch, or push it
+    Pr {
+        /// Agent run ID
+        agent_id: String,
+        /// Print the JSON payload instead of markdown
+        #[arg(long)]
+        json: bool,
+        /// Push the branch to the configured remote and write the bundle files
+        #[arg(long)]
+        push: bool,
+        /// Remote to push to, overriding the push_remote setting
+        #[arg(long)]
+        remote: Option<String>,
     },
     /// Clean up stale worktrees, orphaned branches, and stuck agent runs
     Cleanup,
+    /// Search agent events, tasks and goal chat
+    Search {
+        /// Words to search for; a trailing * matches a prefix
+        query: String,
+        /// Only results from this goal
+        #[arg(long)]
+        goal: Option<String>,
+        /// Only results from goals in this project
+        #[arg(long)]
+        project: Option<String>,
+        /// Only these kinds of results: event, task, message (repeatable)
+        #[arg(long = "type")]
+        types: Vec<String>,
+        /// Maximum number of results
+        #[arg(long, default_value = "20")]
+        limit: u32,
+    },
+    /// Report agent cost and usage, grouped and over a date range
+    Analytics(AnalyticsArgs),
+    /// Show how agent slots are shared between goals, or change the fleet limit
+    Scheduler {
+        /// Most agents running at once across all goals (0 removes the limit)
+        #[arg(long)]
+        total_slots: Option<u32>,
+        /// Seconds a goal with ready tasks may wait before it is served ahead of its share
+        #[arg(long)]
+        starvation_secs: Option<u64>,
+    },
+    /// List queued merge and dispatch jobs, or retry a dead one
+    Jobs {
+        /// Only jobs in this status: pending, running, done, dead
+        #[arg(long)]
+        status: Option<String>,
+        /// Only jobs of this kind: merge, dispatch
+        #[arg(long = "type")]
+        kind: Option<String>,
+        /// Only jobs for this goal
+        #[arg(long)]
+        goal: Option<String>,
+        /// Maximum number of jobs
+        #[arg(long, default_value = "50")]
+        limit: u32,
+        /// Put this dead job back on the queue
+        #[arg(long, conflicts_with_all = ["status", "kind", "goal"])]
+        retry: Option<i64>,
+    },
+    /// Manage the conductor database
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+}
+
+#[derive(Args)]
+pub struct AnalyticsArgs {
+    /// Which report: cost (most expensive first) or usage (most runs first)
+    #[arg(default_value = "cost", value_parser = ["cost", "usage"])]
+    pub report: String,
+    /// Group by goal, project, model, task and one of hour, day or week (comma-separated)
+    #[arg(long)]
+    pub group_by: Option<String>,
+    /// Only runs started on or after this date or RFC 3339 time
+    #[arg(long)]
+    pub since: Option<String>,
+    /// Only runs started before this date or RFC 3339 time
+    #[arg(long)]
+    pub until: Option<String>,
+    /// Only runs in this goal
+    #[arg(long)]
+    pub goal: Option<String>,
+    /// Only runs in goals of this project
+    #[arg(long)]
+    pub project: Option<String>,
+    /// Only runs of this model
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Print CSV instead of a table
+    #[arg(long)]
+    pub csv: bool,
+}
+
+#[derive(Subcommand)]
+pub enum DbCommands {
+    /// Apply pending schema migrations
+    Migrate {
+        /// List the migrations that would be applied without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show the space agent events use, per goal
+    Stats,
+    /// Archive or delete old agent event payloads
+    Compact {
+        /// Only events older than this many days
+        #[arg(long, default_value = "30")]
+        older_than_days: u32,
+        /// Only events of goals with this status (repeatable)
+        #[arg(long = "goal-status")]
+        goal_statuses: Vec<String>,
+        /// Only events of this type (repeatable)
+        #[arg(long = "event-type")]
+        event_types: Vec<String>,
+        /// Delete matching events instead of archiving their raw payloads
+        #[arg(long)]
+        delete: bool,
+        /// Reclaim freed space with VACUUM afterwards
+        #[arg(long)]
+        vacuum: bool,
+        /// Report what would change without changing it
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -84,12 +230,75 @@ pub enum GoalCommands {
         /// Goal space ID
         goal_id: String,
     },
+    /// Estimate what a goal's remaining tasks will cost and when they'll finish
+    Estimate {
+        /// Goal space ID
+        goal_id: String,
+    },
+    /// Show the order pending tasks will be dispatched in, and why
+    Schedule {
+        /// Goal space ID
+        goal_id: String,
+    },
+    /// Export a goal with its tasks, runs, events and chat to a bundle
+    Export {
+        /// Goal space ID
+        goal_id: String,
+        /// Output file (defaults to goal-<id>.tar.zst)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Show a goal's history: who changed what, oldest first
+    History {
+        /// Goal space ID
+        goal_id: String,
+        /// Only entries of these types (comma-separated, e.g. task_updated,agent_killed)
+        #[arg(long = "type")]
+        event_type: Option<String>,
+        /// Only entries by this actor (ui, cli, api, hook, dispatch, system)
+        #[arg(long)]
+        actor: Option<String>,
+        /// Show entries after this entry id
+        #[arg(long)]
+        after: Option<i64>,
+        /// Most entries to show
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+    /// Import a goal bundle as a new goal space
+    Import {
+        /// Bundle file written by `goal export`
+        bundle: String,
+        /// Repository path to use on this machine
+        #[arg(long)]
+        repo: Option<String>,
+        /// Project to attach the goal to
+        #[arg(long)]
+        project: Option<String>,
+        /// Name for the imported goal
+        #[arg(long)]
+        name: Option<String>,
+    },
 }
 
 const DEFAULT_API_BASE: &str = "http://localhost:3001";
 
+/// HTTP client for the conductor API. Requests are marked as coming from the
+/// CLI so goal history records who made each change.
+fn api_client() -> reqwest::Client {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        "x-conductor-actor",
+        reqwest::header::HeaderValue::from_static("cli"),
+    );
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap_or_default()
+}
+
 pub async fn handle_goal_command(command: GoalCommands) -> Result<()> {
-    let client = reqwest::Client::new();
+    let client = api_client();
 
     match command {
         GoalCommands::Create {
@@ -206,13 +415,229 @@ pub async fn handle_goal_command(command: GoalCommands) -> Result<()> {
                 anyhow::bail!("Failed to dispatch: {}", err);
             }
         }
+        GoalCommands::Estimate { goal_id } => {
+            let resp = client
+                .get(format!(
+                    "{}/api/goals/{}/estimate",
+                    DEFAULT_API_BASE, goal_id
+                ))
+                .send()
+                .await?;
+
+            if resp.status().is_success() {
+                let est: serde_json::Value = resp.json().await?;
+                let minutes = |v: &serde_json::Value| v.as_f64().unwrap_or(0.0) / 60.0;
+                println!(
+                    "{} of {} tasks remaining ({} past runs sampled)",
+                    est["tasks_remaining"], est["tasks_total"], est["sample_runs"]
+                );
+                println!(
+                    "Spend: ${:.2} so far, ${:.2} expected in total (${:.2}-${:.2})",
+                    est["spent_usd"].as_f64().unwrap_or(0.0),
+                    est["cost_usd"]["expected"].as_f64().unwrap_or(0.0),
+                    est["cost_usd"]["low"].as_f64().unwrap_or(0.0),
+                    est["cost_usd"]["high"].as_f64().unwrap_or(0.0),
+                );
+                println!(
+                    "Time: {:.0} min expected ({:.0}-{:.0} min), done around {}",
+                    minutes(&est["duration_secs"]["expected"]),
+                    minutes(&est["duration_secs"]["low"]),
+                    minutes(&est["duration_secs"]["high"]),
+                    est["eta"]["expected"].as_str().unwrap_or(""),
+                );
+                let path = est["critical_path"].as_array().cloned().unwrap_or_default();
+                if !path.is_empty() {
+                    println!("Critical path:");
+                    for task_id in &path {
+                        let task = est["tasks"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .find(|t| t["task_id"] == *task_id);
+                        if let Some(task) = task {
+                            println!(
+                                "  {} ({:.0} min, {})",
+                                task["title"].as_str().unwrap_or(""),
+                                minutes(&task["duration_secs"]["expected"]),
+                                task["basis"].as_str().unwrap_or(""),
+                            );
+                        }
+                    }
+                }
+            } else {
+                let err = resp.text().await?;
+                anyhow::bail!("Failed to estimate goal: {}", err);
+            }
+        }
+        GoalCommands::Schedule { goal_id } => {
+            let resp = client
+                .get(format!(
+                    "{}/api/goals/{}/schedule",
+                    DEFAULT_API_BASE, goal_id
+                ))
+                .send()
+                .await?;
+
+            if resp.status().is_success() {
+                let schedule: serde_json::Value = resp.json().await?;
+                match schedule["max_concurrent_agents"].as_u64() {
+                    Some(max) => println!("{} of {} agents running", schedule["running"], max),
+                    None => println!("{} agents running (no limit)", schedule["running"]),
+                }
+                let tasks = schedule["tasks"].as_array().cloned().unwrap_or_default();
+                if tasks.is_empty() {
+                    println!("No pending tasks.");
+                }
+                for task in &tasks {
+                    println!(
+                        "{:>3}. {} {:<40} {}",
+                        task["rank"],
+                        if task["dispatch_next"].as_bool() == Some(true) {
+                            "*"
+                        } else {
+                            " "
+                        },
+                        task["title"].as_str().unwrap_or(""),
+                        task["reason"].as_str().unwrap_or(""),
+                    );
+                }
+            } else {
+                let err = resp.text().await?;
+                anyhow::bail!("Failed to get schedule: {}", err);
+            }
+        }
+        GoalCommands::Export { goal_id, output } => {
+            let resp = client
+                .get(format!("{}/api/goals/{}/export", DEFAULT_API_BASE, goal_id))
+                .send()
+                .await?;
+
+            if resp.status().is_success() {
+                let bundle = resp.bytes().await?;
+                let output = output.unwrap_or_else(|| format!("goal-{}.tar.zst", goal_id));
+                std::fs::write(&output, &bundle)?;
+                println!(
+                    "Exported goal {} to {} ({} bytes)",
+                    goal_id,
+                    output,
+                    bundle.len()
+                );
+            } else {
+                let err = resp.text().await?;
+                anyhow::bail!("Failed to export: {}", err);
+            }
+        }
+        GoalCommands::History {
+            goal_id,
+            event_type,
+            actor,
+            after,
+            limit,
+        } => {
+            let mut params = Vec::new();
+            if let Some(event_type) = event_type {
+                params.push(("type", event_type));
+            }
+            if let Some(actor) = actor {
+                params.push(("actor", actor));
+            }
+            if let Some(after) = after {
+                params.push(("after_id", after.to_string()));
+            }
+            if let Some(limit) = limit {
+                params.push(("limit", limit.to_string()));
+            }
+            let resp = client
+                .get(format!(
+                    "{}/api/goals/{}/history",
+                    DEFAULT_API_BASE, goal_id
+                ))
+                .query(&params)
+                .send()
+                .await?;
+
+            if resp.status().is_success() {
+                let next = resp
+                    .headers()
+                    .get("x-next-after-id")
+                    .and_then(|v| v.to_str().ok())
+                    .map(String::from);
+                let entries: Vec<serde_json::Value> = resp.json().await?;
+                for entry in &entries {
+                    println!(
+                        "#{} [{}] {:<8} {}: {}",
+                        entry["id"],
+                        entry["created_at"].as_str().unwrap_or(""),
+                        entry["actor"].as_str().unwrap_or(""),
+                        entry["event_type"].as_str().unwrap_or(""),
+                        entry["description"].as_str().unwrap_or(""),
+                    );
+                }
+                if entries.is_empty() {
+                    println!("No history for goal {}", goal_id);
+                }
+                if let Some(next) = next {
+                    println!(
+                        "More entries: conductor goal history {} --after {}",
+                        goal_id, next
+                    );
+                }
+            } else {
+                let err = resp.text().await?;
+                anyhow::bail!("Failed to get history: {}", err);
+            }
+        }
+        GoalCommands::Import {
+            bundle,
+            repo,
+            project,
+            name,
+        } => {
+            let data = std::fs::read(&bundle)?;
+            let manifest = crate::goal::bundle::read_manifest(&data)?;
+            println!(
+                "Importing '{}' (bundle v{}, exported {}): {} tasks, {} runs, {} events",
+                manifest.goal_name,
+                manifest.version,
+                manifest.exported_at,
+                manifest.counts.tasks,
+                manifest.counts.agent_runs,
+                manifest.counts.agent_events
+            );
+
+            let mut params = Vec::new();
+            if let Some(repo) = repo {
+                params.push(("repo_path", repo));
+            }
+            if let Some(project) = project {
+                params.push(("project_id", project));
+            }
+            if let Some(name) = name {
+                params.push(("name", name));
+            }
+            let resp = client
+                .post(format!("{}/api/goals/import", DEFAULT_API_BASE))
+                .query(&params)
+                .body(data)
+                .send()
+                .await?;
+
+            if resp.status().is_success() {
+                let report: serde_json::Value = resp.json().await?;
+                println!("Imported goal space: {}", report["goal_space_id"]);
+                println!("  Repo: {}", report["repo_path"]);
+            } else {
+                let err = resp.text().await?;
+                anyhow::bail!("Failed to import: {}", err);
+            }
+        }
     }
 
     Ok(())
 }
 
 pub async fn handle_status() -> Result<()> {
-    let client = reqwest::Client::new();
+    let client = api_client();
     let resp = client
         .get(format!("{}/api/agents", DEFAULT_API_BASE))
         .send()
@@ -248,7 +673,7 @@ pub async fn handle_status() -> Result<()> {
 }
 
 pub async fn handle_inspect(agent_id: &str) -> Result<()> {
-    let client = reqwest::Client::new();
+    let client = api_client();
     let resp = client
         .get(format!("{}/api/agents/{}", DEFAULT_API_BASE, agent_id))
         .send()
@@ -300,7 +725,7 @@ pub async fn handle_inspect(agent_id: &str) -> Result<()> {
 }
 
 pub async fn handle_nudge(agent_id: &str, message: &str) -> Result<()> {
-    let client = reqwest::Client::new();
+    let client = api_client();
     let resp = client
         .post(format!(
             "{}/api/agents/{}/nudge",
@@ -321,7 +746,7 @@ pub async fn handle_nudge(agent_id: &str, message: &str) -> Result<()> {
 }
 
 pub async fn handle_kill(agent_id: &str) -> Result<()> {
-    let client = reqwest::Client::new();
+    let client = api_client();
     let resp = client
         .post(format!("{}/api/agents/{}/kill", DEFAULT_API_BASE, agent_id))
         .send()
@@ -344,17 +769,388 @@ pub async fn handle_cleanup(db: &crate::db::Database) -> Result<()> {
     Ok(())
 }
 
-pub async fn handle_logs(agent_id: &str) -> Result<()> {
-    let client = reqwest::Client::new();
+pub async fn handle_search(
+    query: &str,
+    goal: Option<&str>,
+    project: Option<&str>,
+    types: &[String],
+    limit: u32,
+) -> Result<()> {
+    let client = api_client();
+    let mut params = vec![("q", query.to_string()), ("limit", limit.to_string())];
+    if let Some(goal) = goal {
+        params.push(("goal", goal.to_string()));
+    }
+    if let Some(project) = project {
+        params.push(("project", project.to_string()));
+    }
+    if !types.is_empty() {
+        params.push(("type", types.join(",")));
+    }
+
+    let resp = client
+        .get(format!("{}/api/search", DEFAULT_API_BASE))
+        .query(&params)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        let err = resp.text().await?;
+        anyhow::bail!("Search failed: {}", err);
+    }
+
+    let hits: Vec<serde_json::Value> = resp.json().await?;
+    if hits.is_empty() {
+        println!("No matches.");
+        return Ok(());
+    }
+    for hit in hits {
+        let kind = hit["kind"].as_str().unwrap_or("");
+        // Point at the agent for events, otherwise at the task or message itself
+        let reference = hit["agent_run_id"]
+            .as_str()
+            .or_else(|| hit["id"].as_str())
+            .unwrap_or("");
+        println!(
+            "{:<8} {}  [{}] {}",
+            kind,
+            reference,
+            hit["goal_name"].as_str().unwrap_or(""),
+            hit["title"].as_str().unwrap_or("")
+        );
+        println!(
+            "         {}",
+            hit["snippet"].as_str().unwrap_or("").replace('\n', " ")
+        );
+    }
+    Ok(())
+}
+
+pub async fn handle_analytics(args: &AnalyticsArgs) -> Result<()> {
+    let client = api_client();
+    let mut params = Vec::new();
+    for (name, value) in [
+        ("group_by", &args.group_by),
+        ("since", &args.since),
+        ("until", &args.until),
+        ("goal", &args.goal),
+        ("project", &args.project),
+        ("model", &args.model),
+    ] {
+        if let Some(value) = value {
+            params.push((name, value.clone()));
+        }
+    }
+    if args.csv {
+        params.push(("format", "csv".to_string()));
+    }
+
+    let resp = client
+        .get(format!(
+            "{}/api/analytics/{}",
+            DEFAULT_API_BASE, args.report
+        ))
+        .query(&params)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        let err = resp.text().await?;
+        anyhow::bail!("Failed to get analytics: {}", err);
+    }
+    if args.csv {
+        print!("{}", resp.text().await?);
+        return Ok(());
+    }
+
+    let report: serde_json::Value = resp.json().await?;
+    let label = |row: &serde_json::Value| {
+        let parts: Vec<&str> = ["period", "project_name", "goal_name", "task_title", "model"]
+            .iter()
+            .filter_map(|key| row[*key].as_str())
+            .collect();
+        if parts.is_empty() {
+            "(none)".to_string()
+        } else {
+            parts.join(" / ")
+        }
+    };
+    let print_row = |name: &str, row: &serde_json::Value| {
+        let success = row["success_rate"]
+            .as_f64()
+            .map(|r| format!("{:.0}%", r * 100.0))
+            .unwrap_or_else(|| "-".to_string());
+        let duration = row["avg_duration_secs"]
+            .as_f64()
+            .map(|d| format!("{:.0}s", d))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<40} {:>6} {:>8} {:>12} {:>10.2} {:>8}",
+            name,
+            row["runs"],
+            success,
+            row["total_tokens"],
+            row["cost_usd"].as_f64().unwrap_or(0.0),
+            duration
+        );
+    };
+
+    println!(
+        "{:<40} {:>6} {:>8} {:>12} {:>10} {:>8}",
+        "", "RUNS", "SUCCESS", "TOKENS", "COST ($)", "AVG"
+    );
+    let grouped = report["group_by"].as_array().is_some_and(|g| !g.is_empty());
+    if grouped {
+        for row in report["rows"].as_array().into_iter().flatten() {
+            print_row(&label(row), row);
+        }
+    }
+    print_row("Total", &report["totals"]);
+    Ok(())
+}
+
+pub async fn handle_scheduler(
+    total_slots: Option<u32>,
+    starvation_secs: Option<u64>,
+) -> Result<()> {
+    let client = api_client();
+    let url = format!("{}/api/scheduler", DEFAULT_API_BASE);
+    let resp = if total_slots.is_some() || starvation_secs.is_some() {
+        client
+            .put(&url)
+            .json(&serde_json::json!({
+                "total_slots": total_slots,
+                "starvation_secs": starvation_secs,
+            }))
+            .send()
+            .await?
+    } else {
+        client.get(&url).send().await?
+    };
+    if !resp.status().is_success() {
+        let err = resp.text().await?;
+        anyhow::bail!("Failed to get scheduler: {}", err);
+    }
+
+    let shares: serde_json::Value = resp.json().await?;
+    match shares["total_slots"].as_u64() {
+        Some(total) => println!(
+            "{} of {} agent slots in use (starvation after {}s)",
+            shares["running"], total, shares["starvation_secs"]
+        ),
+        None => println!("{} agents running (no fleet limit)", shares["running"]),
+    }
+    println!(
+        "{:<40} {:>6} {:>4} {:>4} {:>7} {:>5} {:>6} {:>4}",
+        "GOAL", "WEIGHT", "MIN", "MAX", "RUNNING", "READY", "TARGET", "FREE"
+    );
+    for goal in shares["goals"].as_array().into_iter().flatten() {
+        println!(
+            "{:<40} {:>6} {:>4} {:>4} {:>7} {:>5} {:>6} {:>4}{}",
+            goal["name"].as_str().unwrap_or(""),
+            goal["weight"],
+            goal["min_agents"],
+            goal["max_agents"]
+                .as_u64()
+                .map_or("-".to_string(), |m| m.to_string()),
+            goal["running"],
+            goal["ready"],
+            goal["target_slots"],
+            goal["free_slots"],
+            if goal["starving"].as_bool() == Some(true) {
+                "  starving"
+            } else {
+                ""
+            },
+        );
+    }
+    Ok(())
+}
+
+pub async fn handle_jobs(
+    status: Option<&str>,
+    kind: Option<&str>,
+    goal: Option<&str>,
+    limit: u32,
+    retry: Option<i64>,
+) -> Result<()> {
+    let client = api_client();
+    if let Some(id) = retry {
+        let resp = client
+            .post(format!("{}/api/jobs/{}/retry", DEFAULT_API_BASE, id))
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            let err = resp.text().await?;
+            anyhow::bail!("Failed to retry job: {}", err);
+        }
+        println!("Job {} queued again", id);
+        return Ok(());
+    }
+
+    let mut params = vec![("limit", limit.to_string())];
+    if let Some(status) = status {
+        params.push(("status", status.to_string()));
+    }
+    if let Some(kind) = kind {
+        params.push(("type", kind.to_string()));
+    }
+    if let Some(goal) = goal {
+        params.push(("goal", goal.to_string()));
+    }
+    let resp = client
+        .get(format!("{}/api/jobs", DEFAULT_API_BASE))
+        .query(&params)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        let err = resp.text().await?;
+        anyhow::bail!("Failed to list jobs: {}", err);
+    }
+
+    let jobs: Vec<serde_json::Value> = resp.json().await?;
+    if jobs.is_empty() {
+        println!("No jobs");
+        return Ok(());
+    }
+    println!(
+        "{:>6} {:<9} {:<8} {:>8} {:<36} {:<25} ERROR",
+        "ID", "KIND", "STATUS", "ATTEMPTS", "GOAL", "UPDATED"
+    );
+    for job in &jobs {
+        println!(
+            "{:>6} {:<9} {:<8} {:>8} {:<36} {:<25} {}",
+            job["id"],
+            job["kind"].as_str().unwrap_or(""),
+            job["status"].as_str().unwrap_or(""),
+            job["attempts"],
+            job["goal_space_id"].as_str().unwrap_or(""),
+            job["updated_at"].as_str().unwrap_or(""),
+            job["last_error"].as_str().unwrap_or(""),
+        );
+    }
+    Ok(())
+}
+
+pub fn handle_db_command(db: &crate::db::Database, command: DbCommands) -> Result<()> {
+    match command {
+        DbCommands::Migrate { dry_run } => {
+            let current = db.schema_version()?;
+            let latest = crate::db::schema::latest_version();
+            let migrations = if dry_run {
+                db.pending_migrations()?
+            } else {
+                db.run_migrations()?
+            };
+
+            println!("Schema version: {} (latest {})", current, latest);
+            if migrations.is_empty() {
+                println!("Database is up to date.");
+                return Ok(());
+            }
+            println!("{}", if dry_run { "Would apply:" } else { "Applied:" });
+            for migration in &migrations {
+                println!("  {:>3}  {}", migration.version, migration.name);
+            }
+        }
+        DbCommands::Stats => {
+            use crate::db::retention::format_bytes;
+
+            let stats = db.storage_stats()?;
+            println!("Database: {}", format_bytes(stats.database_bytes));
+            if let Some(dir) = db.archive_dir() {
+                println!("Archive:  {}", dir.display());
+            }
+            if stats.goals.is_empty() {
+                println!("No goals.");
+                return Ok(());
+            }
+            println!(
+                "\n{:<30} {:<10} {:>5} {:>8} {:>9} {:>10} {:>10} {:>10}",
+                "GOAL", "STATUS", "RUNS", "EVENTS", "ARCHIVED", "SUMMARIES", "RAW", "ARCHIVE"
+            );
+            for goal in &stats.goals {
+                let name: String = goal.name.chars().take(30).collect();
+                println!(
+                    "{:<30} {:<10} {:>5} {:>8} {:>9} {:>10} {:>10} {:>10}",
+                    name,
+                    goal.status,
+                    goal.runs,
+                    goal.events,
+                    goal.archived_events,
+                    format_bytes(goal.summary_bytes as u64),
+                    format_bytes(goal.raw_bytes as u64),
+                    format_bytes(goal.archive_bytes)
+                );
+            }
+        }
+        DbCommands::Compact {
+            older_than_days,
+            goal_statuses,
+            event_types,
+            delete,
+            vacuum,
+            dry_run,
+        } => {
+            let policy = crate::db::retention::RetentionPolicy {
+                older_than_days,
+                goal_statuses,
+                event_types,
+                delete,
+            };
+            let report = db.compact_events(&policy, dry_run)?;
+            println!(
+                "{}{}",
+                if dry_run {
+                    "Would compact "
+                } else {
+                    "Compacted "
+                },
+                report
+            );
+            if vacuum && !dry_run {
+                db.conn().execute_batch("VACUUM")?;
+                println!("Vacuumed database.");
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn handle_logs(
+    agent_id: &str,
+    after: Option<i64>,
+    limit: Option<u32>,
+    event_type: Option<&str>,
+    tool: Option<&str>,
+) -> Result<()> {
+    let client = api_client();
+    let mut params = Vec::new();
+    if let Some(after) = after {
+        params.push(("after_id", after.to_string()));
+    }
+    if let Some(limit) = limit {
+        params.push(("limit", limit.to_string()));
+    }
+    if let Some(event_type) = event_type {
+        params.push(("event_type", event_type.to_string()));
+    }
+    if let Some(tool) = tool {
+        params.push(("tool_name", tool.to_string()));
+    }
     let resp = client
         .get(format!(
             "{}/api/agents/{}/events",
             DEFAULT_API_BASE, agent_id
         ))
+        .query(&params)
         .send()
         .await?;
 
     if resp.status().is_success() {
+        let next = resp
+            .headers()
+            .get("x-next-after-id")
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
         let events: Vec<serde_json::Value> = resp.json().await?;
         for event in &events {
             println!(
@@ -367,6 +1163,9 @@ pub async fn handle_logs(agent_id: &str) -> Result<()> {
         if events.is_empty() {
             println!("No events yet for agent {}", agent_id);
         }
+        if let Some(next) = next {
+            println!("More events: conductor logs {} --after {}", agent_id, next);
+        }
     } else {
         let err = resp.text().await?;
         anyhow::bail!("Failed to get logs: {}", err);
@@ -374,3 +1173,136 @@ pub async fn handle_logs(agent_id: &str) -> Result<()> {
 
     Ok(())
 }
+
+pub async fn handle_pr(
+    agent_id: &str,
+    json_output: bool,
+    push: bool,
+    remote: Option<&str>,
+) -> Result<()> {
+    let client = api_client();
+
+    if push {
+        let resp = client
+            .post(format!(
+                "{}/api/agents/{}/publish",
+                DEFAULT_API_BASE, agent_id
+            ))
+            .json(&serde_json::json!({ "remote": remote }))
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            let err = resp.text().await?;
+            anyhow::bail!("Failed to publish: {}", err);
+        }
+        let published: serde_json::Value = resp.json().await?;
+        println!(
+            "Pushed {} to {}",
+            published["branch"].as_str().unwrap_or(""),
+            published["remote"].as_str().unwrap_or("")
+        );
+        if let Some(url) = published["url"].as_str() {
+            println!("Pull request: {}", url);
+        }
+        println!(
+            "Bundle: {}\n        {}",
+            published["markdown_path"].as_str().unwrap_or(""),
+            published["json_path"].as_str().unwrap_or("")
+        );
+        return Ok(());
+    }
+
+    let resp = client
+        .get(format!("{}/api/agents/{}/pr", DEFAULT_API_BASE, agent_id))
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        let err = resp.text().await?;
+        anyhow::bail!("Failed to build PR bundle: {}", err);
+    }
+    let bundle: serde_json::Value = resp.json().await?;
+
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&bundle)?);
+    } else {
+        print!("{}", bundle["body"].as_str().unwrap_or(""));
+    }
+    Ok(())
+}
+
+pub async fn handle_diff(agent_id: &str, stat_only: bool) -> Result<()> {
+    let client = api_client();
+    let resp = client
+        .get(format!("{}/api/agents/{}/diff", DEFAULT_API_BASE, agent_id))
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let err = resp.text().await?;
+        anyhow::bail!("Failed to get diff: {}", err);
+    }
+    let diff: serde_json::Value = resp.json().await?;
+
+    let commits_resp = client
+        .get(format!(
+            "{}/api/agents/{}/commits",
+            DEFAULT_API_BASE, agent_id
+        ))
+        .send()
+        .await?;
+    let commits: Vec<serde_json::Value> = if commits_resp.status().is_success() {
+        commits_resp.json().await?
+    } else {
+        Vec::new()
+    };
+
+    let short =
+        |v: &serde_json::Value| v.as_str().unwrap_or("").chars().take(8).collect::<String>();
+    println!(
+        "Source: {} ({}..{})",
+        diff["source"].as_str().unwrap_or(""),
+        short(&diff["base"]),
+        if diff["head"].is_null() {
+            "working tree".to_string()
+        } else {
+            short(&diff["head"])
+        }
+    );
+
+    if !commits.is_empty() {
+        println!("\nCommits:");
+        for commit in &commits {
+            println!(
+                "  {} {}",
+                short(&commit["sha"]),
+                commit["subject"].as_str().unwrap_or("")
+            );
+        }
+    }
+
+    let files = diff["files"].as_array().cloned().unwrap_or_default();
+    println!(
+        "\n{} files changed, +{} -{}",
+        files.len(),
+        diff["additions"].as_u64().unwrap_or(0),
+        diff["deletions"].as_u64().unwrap_or(0)
+    );
+    for file in &files {
+        let count = |v: &serde_json::Value| match v.as_u64() {
+            Some(n) => n.to_string(),
+            None => "-".to_string(),
+        };
+        println!(
+            "  +{:<6} -{:<6} {}",
+            count(&file["additions"]),
+            count(&file["deletions"]),
+            file["path"].as_str().unwrap_or("")
+        );
+    }
+
+    if !stat_only {
+        println!("\n{}", diff["diff"].as_str().unwrap_or(""));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,598 @@
+use anyhow::{bail, Result};
+
+use super::Database;
+
+/// A dimension analytics rows can be grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    Goal,
+    Project,
+    Model,
+    Task,
+    Hour,
+    Day,
+    Week,
+}
+
+impl GroupBy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "goal" => Some(GroupBy::Goal),
+            "project" => Some(GroupBy::Project),
+            "model" => Some(GroupBy::Model),
+            "task" => Some(GroupBy::Task),
+            "hour" => Some(GroupBy::Hour),
+            "day" => Some(GroupBy::Day),
+            "week" => Some(GroupBy::Week),
+            _ => None,
+        }
+    }
+
+    /// Parse a comma-separated list such as `project,week`. At most one time
+    /// bucket (hour, day or week) may be given.
+    pub fn parse_list(s: &str) -> Result<Vec<Self>> {
+        let mut dims = Vec::new();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let Some(dim) = GroupBy::parse(part) else {
+                bail!(
+                    "Unknown group_by '{}': expected goal, project, model, task, hour, day or week",
+                    part
+                );
+            };
+            if !dims.contains(&dim) {
+                dims.push(dim);
+            }
+        }
+        if dims.iter().filter(|d| d.is_period()).count() > 1 {
+            bail!("group_by takes at most one of hour, day and week");
+        }
+        Ok(dims)
+    }
+
+    fn is_period(&self) -> bool {
+        matches!(self, GroupBy::Hour | GroupBy::Day | GroupBy::Week)
+    }
+
+    /// Columns selected for this dimension: (key, label)
+    fn columns(&self) -> (&'static str, &'static str) {
+        match self {
+            GroupBy::Goal => ("r.goal_space_id", "g.name"),
+            GroupBy::Project => ("g.project_id", "p.display_name"),
+            GroupBy::Model => ("r.model", "NULL"),
+            GroupBy::Task => ("r.task_id", "t.title"),
+            GroupBy::Hour => ("strftime('%Y-%m-%dT%H:00', r.started_at)", "NULL"),
+            GroupBy::Day => ("strftime('%Y-%m-%d', r.started_at)", "NULL"),
+            GroupBy::Week => ("strftime('%G-W%V', r.started_at)", "NULL"),
+        }
+    }
+}
+
+/// Which agent runs to aggregate and how to group them
+#[derive(Debug, Clone, Default)]
+pub struct AnalyticsQuery {
+    pub group_by: Vec<GroupBy>,
+    /// Runs started at or after this RFC 3339 timestamp or date
+    pub since: Option<String>,
+    /// Runs started before this RFC 3339 timestamp or date
+    pub until: Option<String>,
+    pub goal_space_id: Option<String>,
+    pub project_id: Option<String>,
+    pub model: Option<String>,
+}
+
+/// Aggregated agent run figures. Grouping fields are null unless grouped by.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct AnalyticsRow {
+    pub goal_space_id: Option<String>,
+    pub goal_name: Option<String>,
+    pub project_id: Option<String>,
+    pub project_name: Option<String>,
+    pub model: Option<String>,
+    pub task_id: Option<String>,
+    pub task_title: Option<String>,
+    /// Hour, day or ISO week (`2026-W41`) the runs started in, in UTC
+    pub period: Option<String>,
+    pub runs: i64,
+    pub succeeded: i64,
+    /// Failed or killed
+    pub failed: i64,
+    /// Share of finished runs that succeeded; null when none finished
+    pub success_rate: Option<f64>,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub total_tokens: i64,
+    pub cost_usd: f64,
+    pub avg_cost_usd: f64,
+    /// Mean wall-clock time of finished runs; null when none finished
+    pub avg_duration_secs: Option<f64>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AnalyticsReport {
+    pub group_by: Vec<GroupBy>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub rows: Vec<AnalyticsRow>,
+    pub totals: AnalyticsRow,
+}
+
+/// One finished run, as input to estimates
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSample {
+    pub model: String,
+    pub cost_usd: f64,
+    pub duration_secs: f64,
+}
+
+/// What the report is sorted by when it isn't a time series
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Cost,
+    Runs,
+}
+
+const METRICS: &str = "
+    COUNT(*),
+    SUM(CASE WHEN r.status = 'done' THEN 1 ELSE 0 END),
+    SUM(CASE WHEN r.status IN ('failed', 'killed') THEN 1 ELSE 0 END),
+    COALESCE(SUM(r.input_tokens), 0),
+    COALESCE(SUM(r.output_tokens), 0),
+    COALESCE(SUM(r.cost_usd), 0.0),
+    AVG(CASE WHEN r.finished_at IS NOT NULL
+        THEN unixepoch(r.finished_at, 'subsec') - unixepoch(r.started_at, 'subsec') END)";
+
+impl Database {
+    /// Token, cost, outcome and duration figures for agent runs, grouped by
+    /// `query.group_by`. Time series come back oldest first; other groupings
+    /// are sorted by `sort`, largest first.
+    pub fn run_analytics(&self, query: &AnalyticsQuery, sort: SortBy) -> Result<AnalyticsReport> {
+        let mut clauses = Vec::new();
+        let mut args: Vec<&str> = Vec::new();
+        for (clause, value) in [
+            ("r.started_at >= ?", &query.since),
+            ("r.started_at < ?", &query.until),
+            ("r.goal_space_id = ?", &query.goal_space_id),
+            ("g.project_id = ?", &query.project_id),
+            ("r.model = ?", &query.model),
+        ] {
+            if let Some(value) = value {
+                clauses.push(clause);
+                args.push(value);
+            }
+        }
+        let where_sql = if clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", clauses.join(" AND "))
+        };
+
+        let mut keys = Vec::new();
+        for dim in &query.group_by {
+            let (key, label) = dim.columns();
+            keys.push(key.to_string());
+            keys.push(label.to_string());
+        }
+        let select_keys = if keys.is_empty() {
+            String::new()
+        } else {
+            format!("{},", keys.join(", "))
+        };
+        let group_sql = if query.group_by.is_empty() {
+            String::new()
+        } else {
+            let positions: Vec<String> =
+                (1..=keys.len()).step_by(2).map(|i| i.to_string()).collect();
+            format!(" GROUP BY {}", positions.join(", "))
+        };
+        let metric_at = keys.len();
+        let order_sql = if query.group_by.iter().any(GroupBy::is_period) {
+            let period = query.group_by.iter().position(GroupBy::is_period).unwrap();
+            format!(" ORDER BY {} ASC, {} DESC", period * 2 + 1, metric_at + 6)
+        } else if query.group_by.is_empty() {
+            String::new()
+        } else {
+            let column = match sort {
+                SortBy::Cost => metric_at + 6,
+                SortBy::Runs => metric_at + 1,
+            };
+            format!(" ORDER BY {} DESC", column)
+        };
+
+        let sql = format!(
+            "SELECT {} {} FROM agent_runs r
+             JOIN goal_spaces g ON g.id = r.goal_space_id
+             LEFT JOIN projects p ON p.id = g.project_id
+             LEFT JOIN tasks t ON t.id = r.task_id{}{}{}",
+            select_keys, METRICS, where_sql, group_sql, order_sql
+        );
+
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows = stmt
+            .query_map(rusqlite::params_from_iter(&args), |row| {
+                let mut out = metrics_from_row(row, metric_at)?;
+                for (i, dim) in query.group_by.iter().enumerate() {
+                    let key: Option<String> = row.get(i * 2)?;
+                    let label: Option<String> = row.get(i * 2 + 1)?;
+                    match dim {
+                        GroupBy::Goal => {
+                            out.goal_space_id = key;
+                            out.goal_name = label;
+                        }
+                        GroupBy::Project => {
+                            out.project_id = key;
+                            out.project_name = label;
+                        }
+                        GroupBy::Model => out.model = key,
+                        GroupBy::Task => {
+                            out.task_id = key;
+                            out.task_title = label;
+                        }
+                        GroupBy::Hour | GroupBy::Day | GroupBy::Week => out.period = key,
+                    }
+                }
+                Ok(out)
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let totals = conn.query_row(
+            &format!(
+                "SELECT {} FROM agent_runs r
+                 JOIN goal_spaces g ON g.id = r.goal_space_id{}",
+                METRICS, where_sql
+            ),
+            rusqlite::params_from_iter(&args),
+            |row| metrics_from_row(row, 0),
+        )?;
+        // An ungrouped report is just the totals
+        if query.group_by.is_empty() {
+            rows = vec![totals.clone()];
+        }
+
+        Ok(AnalyticsReport {
+            group_by: query.group_by.clone(),
+            since: query.since.clone(),
+            until: query.until.clone(),
+            rows,
+            totals,
+        })
+    }
+
+    /// Cost and wall-clock duration of the most recent successful runs,
+    /// newest first
+    pub fn recent_run_samples(&self, limit: u32) -> Result<Vec<RunSample>> {
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(
+            "SELECT model, cost_usd,
+                    unixepoch(finished_at, 'subsec') - unixepoch(started_at, 'subsec')
+             FROM agent_runs
+             WHERE status = 'done' AND finished_at IS NOT NULL
+             ORDER BY finished_at DESC
+             LIMIT ?1",
+        )?;
+        let samples = stmt
+            .query_map([limit], |row| {
+                Ok(RunSample {
+                    model: row.get(0)?,
+                    cost_usd: row.get(1)?,
+                    duration_secs: row.get::<_, Option<f64>>(2)?.unwrap_or(0.0).max(0.0),
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(samples)
+    }
+}
+
+fn metrics_from_row(row: &rusqlite::Row<'_>, at: usize) -> rusqlite::Result<AnalyticsRow> {
+    let runs: i64 = row.get(at)?;
+    let succeeded: Option<i64> = row.get(at + 1)?;
+    let failed: Option<i64> = row.get(at + 2)?;
+    let (succeeded, failed) = (succeeded.unwrap_or(0), failed.unwrap_or(0));
+    let input_tokens: i64 = row.get(at + 3)?;
+    let output_tokens: i64 = row.get(at + 4)?;
+    let cost_usd: f64 = row.get(at + 5)?;
+    Ok(AnalyticsRow {
+        runs,
+        succeeded,
+        failed,
+        success_rate: (succeeded + failed > 0)
+            .then(|| succeeded as f64 / (succeeded + failed) as f64),
+        input_tokens,
+        output_tokens,
+        total_tokens: input_tokens + output_tokens,
+        cost_usd,
+        avg_cost_usd: if runs > 0 {
+            cost_usd / runs as f64
+        } else {
+            0.0
+        },
+        avg_duration_secs: row.get(at + 6)?,
+        ..Default::default()
+    })
+}
+
+/// The report as CSV: one column per grouping field, then the metrics,
+/// with a final `total` row.
+pub fn to_csv(report: &AnalyticsReport) -> String {
+    let mut header: Vec<&str> = Vec::new();
+    for dim in &report.group_by {
+        match dim {
+            GroupBy::Goal => header.extend(["goal_space_id", "goal_name"]),
+            GroupBy::Project => header.extend(["project_id", "project_name"]),
+            GroupBy::Model => header.push("model"),
+            GroupBy::Task => header.extend(["task_id", "task_title"]),
+            GroupBy::Hour | GroupBy::Day | GroupBy::Week => header.push("period"),
+        }
+    }
+    let key_columns = header.len();
+    header.extend([
+        "runs",
+        "succeeded",
+        "failed",
+        "success_rate",
+        "input_tokens",
+        "output_tokens",
+        "total_tokens",
+        "cost_usd",
+        "avg_cost_usd",
+        "avg_duration_secs",
+    ]);
+
+    let mut out = header.join(",");
+    out.push('\n');
+    let rows = report.rows.iter().map(|r| (r, false));
+    let total = (!report.group_by.is_empty()).then_some((&report.totals, true));
+    for (row, is_total) in rows.chain(total) {
+        let mut fields: Vec<String> = Vec::with_capacity(header.len());
+        for name in &header[..key_columns] {
+            let value = match *name {
+                "goal_space_id" => &row.goal_space_id,
+                "goal_name" => &row.goal_name,
+                "project_id" => &row.project_id,
+                "project_name" => &row.project_name,
+                "model" => &row.model,
+                "task_id" => &row.task_id,
+                "task_title" => &row.task_title,
+                _ => &row.period,
+            };
+            fields.push(csv_field(value.as_deref().unwrap_or("")));
+        }
+        if is_total && key_columns > 0 {
+            fields[0] = "total".to_string();
+        }
+        fields.extend([
+            row.runs.to_string(),
+            row.succeeded.to_string(),
+            row.failed.to_string(),
+            row.success_rate
+                .map(|r| format!("{:.4}", r))
+                .unwrap_or_default(),
+            row.input_tokens.to_string(),
+            row.output_tokens.to_string(),
+            row.total_tokens.to_string(),
+            format!("{:.4}", row.cost_usd),
+            format!("{:.4}", row.avg_cost_usd),
+            row.avg_duration_secs
+                .map(|d| format!("{:.1}", d))
+                .unwrap_or_default(),
+        ]);
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{CreateGoalSpace, CreateProject, CreateTask};
+    use crate::goal::task::RunStatus;
+
+    fn test_db() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        db
+    }
+
+    /// A finished run with the given cost, started and finished at fixed times
+    fn run(db: &Database, goal_id: &str, model: &str, status: RunStatus, cost: f64, started: &str) {
+        let task = db
+            .create_task(
+                goal_id,
+                &CreateTask {
+                    title: format!("Task {}", started),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let run = db
+            .create_agent_run(&task.id, goal_id, None, None, model, None)
+            .unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Running)
+            .unwrap();
+        if status != RunStatus::Running {
+            db.update_agent_run_status(&run.id, status).unwrap();
+        }
+        db.update_agent_run_cost(&run.id, cost, 1000, 200).unwrap();
+        db.conn()
+            .execute(
+                "UPDATE agent_runs SET started_at = ?1,
+                    finished_at = CASE WHEN finished_at IS NULL THEN NULL
+                        ELSE strftime('%Y-%m-%dT%H:%M:%S+00:00', ?1, '+60 seconds') END
+                 WHERE id = ?2",
+                rusqlite::params![started, run.id],
+            )
+            .unwrap();
+    }
+
+    fn goal(db: &Database, name: &str, project_id: Option<&str>) -> String {
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: name.into(),
+                description: "D".into(),
+                repo_path: format!("/tmp/{}", name),
+                settings: Default::default(),
+            })
+            .unwrap();
+        if let Some(project_id) = project_id {
+            db.conn()
+                .execute(
+                    "UPDATE goal_spaces SET project_id = ?1 WHERE id = ?2",
+                    rusqlite::params![project_id, goal.id],
+                )
+                .unwrap();
+        }
+        goal.id
+    }
+
+    fn populated() -> (Database, String) {
+        let db = test_db();
+        let project = db
+            .create_project(&CreateProject {
+                path: "/tmp/app".into(),
+                display_name: "App".into(),
+                sort_order: 0,
+            })
+            .unwrap();
+        let billing = goal(&db, "billing", Some(&project.id));
+        let docs = goal(&db, "docs", None);
+        run(
+            &db,
+            &billing,
+            "opus",
+            RunStatus::Done,
+            3.0,
+            "2026-10-05T09:15:00+00:00",
+        );
+        run(
+            &db,
+            &billing,
+            "sonnet",
+            RunStatus::Failed,
+            1.0,
+            "2026-10-06T10:00:00+00:00",
+        );
+        run(
+            &db,
+            &docs,
+            "sonnet",
+            RunStatus::Done,
+            0.5,
+            "2026-10-13T11:30:00+00:00",
+        );
+        run(
+            &db,
+            &docs,
+            "sonnet",
+            RunStatus::Running,
+            0.25,
+            "2026-10-13T12:00:00+00:00",
+        );
+        (db, project.id)
+    }
+
+    #[test]
+    fn totals_cover_every_run() {
+        let (db, _) = populated();
+        let report = db
+            .run_analytics(&AnalyticsQuery::default(), SortBy::Cost)
+            .unwrap();
+        let totals = &report.totals;
+        assert_eq!(report.rows, vec![totals.clone()]);
+        assert_eq!(totals.runs, 4);
+        assert_eq!(totals.succeeded, 2);
+        assert_eq!(totals.failed, 1);
+        assert!((totals.success_rate.unwrap() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(totals.total_tokens, 4 * 1200);
+        assert!((totals.cost_usd - 4.75).abs() < 1e-9);
+        assert!((totals.avg_duration_secs.unwrap() - 60.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn groups_by_project_and_week() {
+        let (db, project_id) = populated();
+        let query = AnalyticsQuery {
+            group_by: GroupBy::parse_list("project,week").unwrap(),
+            ..Default::default()
+        };
+        let report = db.run_analytics(&query, SortBy::Cost).unwrap();
+        let rows: Vec<(Option<&str>, Option<&str>, f64)> = report
+            .rows
+            .iter()
+            .map(|r| (r.project_name.as_deref(), r.period.as_deref(), r.cost_usd))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Some("App"), Some("2026-W41"), 4.0),
+                (None, Some("2026-W42"), 0.75),
+            ]
+        );
+        assert_eq!(
+            report.rows[0].project_id.as_deref(),
+            Some(project_id.as_str())
+        );
+        assert!(report.rows[0].model.is_none());
+    }
+
+    #[test]
+    fn filters_and_sorts_by_cost_or_runs() {
+        let (db, _) = populated();
+        let by_model = AnalyticsQuery {
+            group_by: vec![GroupBy::Model],
+            since: Some("2026-10-06".into()),
+            ..Default::default()
+        };
+        let report = db.run_analytics(&by_model, SortBy::Runs).unwrap();
+        assert_eq!(report.rows.len(), 1);
+        assert_eq!(report.rows[0].model.as_deref(), Some("sonnet"));
+        assert_eq!(report.rows[0].runs, 3);
+
+        let all_models = AnalyticsQuery {
+            group_by: vec![GroupBy::Model],
+            ..Default::default()
+        };
+        let by_cost = db.run_analytics(&all_models, SortBy::Cost).unwrap();
+        assert_eq!(by_cost.rows[0].model.as_deref(), Some("opus"));
+        let by_runs = db.run_analytics(&all_models, SortBy::Runs).unwrap();
+        assert_eq!(by_runs.rows[0].model.as_deref(), Some("sonnet"));
+    }
+
+    #[test]
+    fn rejects_unknown_or_conflicting_groups() {
+        assert!(GroupBy::parse_list("goal,month").is_err());
+        assert!(GroupBy::parse_list("day,week").is_err());
+        assert_eq!(
+            GroupBy::parse_list("goal, goal,day").unwrap(),
+            vec![GroupBy::Goal, GroupBy::Day]
+        );
+    }
+
+    #[test]
+    fn csv_has_group_columns_and_a_total_row() {
+        let (db, _) = populated();
+        let query = AnalyticsQuery {
+            group_by: vec![GroupBy::Goal],
+            ..Default::default()
+        };
+        let csv = to_csv(&db.run_analytics(&query, SortBy::Cost).unwrap());
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("goal_space_id,goal_name,runs,succeeded"));
+        assert!(lines[1].contains(",billing,2,1,1,0.5000,"));
+        assert!(lines[3].starts_with("total,,4,2,1,"));
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+}
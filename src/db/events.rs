@@ -0,0 +1,241 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+use super::queries::AgentEvent;
+use super::Database;
+
+/// Most events written in one transaction.
+const MAX_BATCH: usize = 256;
+
+struct PendingEvent {
+    event: AgentEvent,
+    reply: oneshot::Sender<Result<AgentEvent>>,
+}
+
+/// Handle to the thread that writes agent events.
+///
+/// Streaming agents produce an event per output line. Rather than each line
+/// taking the writer lock and committing on its own, events are queued to a
+/// dedicated thread that drains everything waiting and commits it in a single
+/// transaction. Callers await the stored event (with its row id) without
+/// blocking the runtime. The thread exits once every `Database` clone is gone.
+#[derive(Clone)]
+pub struct EventWriter {
+    tx: mpsc::Sender<PendingEvent>,
+}
+
+impl EventWriter {
+    pub(super) fn spawn(conn: Arc<Mutex<Connection>>) -> Self {
+        let (tx, rx) = mpsc::channel::<PendingEvent>();
+        std::thread::Builder::new()
+            .name("conductor-events".into())
+            .spawn(move || {
+                while let Ok(first) = rx.recv() {
+                    let mut batch = vec![first];
+                    while batch.len() < MAX_BATCH {
+                        match rx.try_recv() {
+                            Ok(pending) => batch.push(pending),
+                            Err(_) => break,
+                        }
+                    }
+                    write_batch(&conn, batch);
+                }
+            })
+            .expect("failed to spawn event writer thread");
+        Self { tx }
+    }
+}
+
+fn write_batch(conn: &Mutex<Connection>, batch: Vec<PendingEvent>) {
+    let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+    // Each insert runs in its own savepoint so one bad event (say, a run id that
+    // no longer exists) fails alone instead of rolling back its neighbours.
+    let mut written: Vec<rusqlite::Result<i64>> = Vec::with_capacity(batch.len());
+    let result = (|| -> rusqlite::Result<()> {
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO agent_events (agent_run_id, event_type, tool_name, summary, raw_json, cost_delta_usd, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for pending in &batch {
+                let e = &pending.event;
+                tx.execute_batch("SAVEPOINT agent_event")?;
+                let inserted = stmt
+                    .execute(params![
+                        e.agent_run_id,
+                        e.event_type,
+                        e.tool_name,
+                        e.summary,
+                        e.raw_json,
+                        e.cost_delta_usd,
+                        e.created_at
+                    ])
+                    .map(|_| tx.last_insert_rowid());
+                if inserted.is_err() {
+                    tx.execute_batch("ROLLBACK TO agent_event")?;
+                }
+                tx.execute_batch("RELEASE agent_event")?;
+                written.push(inserted);
+            }
+        }
+        tx.commit()
+    })();
+    drop(conn);
+
+    for (i, pending) in batch.into_iter().enumerate() {
+        let reply = match (&result, written.get(i)) {
+            (Ok(()), Some(Ok(id))) => Ok(AgentEvent {
+                id: *id,
+                ..pending.event
+            }),
+            (Err(e), _) | (Ok(()), Some(Err(e))) => {
+                Err(anyhow!("Failed to write agent event: {}", e))
+            }
+            (Ok(()), None) => unreachable!("a committed batch has an outcome for every event"),
+        };
+        // The caller may have gone away; the outcome stands either way.
+        let _ = pending.reply.send(reply);
+    }
+}
+
+impl Database {
+    /// Queue an agent event for the batched writer and wait for it to be stored.
+    /// Use this on streaming paths; `insert_agent_event` writes immediately.
+    pub async fn record_agent_event(
+        &self,
+        agent_run_id: &str,
+        event_type: &str,
+        tool_name: Option<&str>,
+        summary: &str,
+        raw_json: Option<&str>,
+        cost_delta_usd: Option<f64>,
+    ) -> Result<AgentEvent> {
+        let (reply, stored) = oneshot::channel();
+        let event = AgentEvent {
+            id: 0,
+            agent_run_id: agent_run_id.to_string(),
+            event_type: event_type.to_string(),
+            tool_name: tool_name.map(String::from),
+            summary: summary.to_string(),
+            raw_json: raw_json.map(String::from),
+            cost_delta_usd,
+            created_at: Utc::now().to_rfc3339(),
+        };
+        self.events
+            .tx
+            .send(PendingEvent { event, reply })
+            .map_err(|_| anyhow!("Event writer has stopped"))?;
+        stored
+            .await
+            .map_err(|_| anyhow!("Event writer dropped the event"))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{CreateGoalSpace, CreateTask};
+
+    fn db_with_run() -> (Database, String) {
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "Goal".into(),
+                description: "desc".into(),
+                repo_path: "/tmp/repo".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let task = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: "Task".into(),
+                    description: "desc".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let run = db
+            .create_agent_run(&task.id, &goal.id, None, None, "sonnet", None)
+            .unwrap();
+        (db, run.id)
+    }
+
+    #[tokio::test]
+    async fn concurrent_events_are_all_stored() {
+        let (db, run_id) = db_with_run();
+
+        let writes = (0..50).map(|i| {
+            let db = db.clone();
+            let run_id = run_id.clone();
+            async move {
+                db.record_agent_event(
+                    &run_id,
+                    "text_output",
+                    None,
+                    &format!("line {}", i),
+                    None,
+                    None,
+                )
+                .await
+                .unwrap()
+            }
+        });
+        let stored = futures::future::join_all(writes).await;
+
+        let mut ids: Vec<i64> = stored.iter().map(|e| e.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 50);
+        assert!(ids.iter().all(|id| *id > 0));
+
+        let listed = db.list_agent_events(&run_id).unwrap();
+        assert_eq!(listed.len(), 50);
+        for event in &stored {
+            let row = listed.iter().find(|e| e.id == event.id).unwrap();
+            assert_eq!(row.summary, event.summary);
+        }
+    }
+
+    #[tokio::test]
+    async fn bad_event_fails_alone() {
+        let (db, run_id) = db_with_run();
+
+        // Queued together so they share a batch; the middle one breaks the FK
+        let writes = (0..11).map(|i| {
+            let db = db.clone();
+            let run_id = if i == 5 {
+                "no-such-run".to_string()
+            } else {
+                run_id.clone()
+            };
+            async move {
+                db.record_agent_event(
+                    &run_id,
+                    "text_output",
+                    None,
+                    &format!("line {}", i),
+                    None,
+                    None,
+                )
+                .await
+            }
+        });
+        let stored = futures::future::join_all(writes).await;
+
+        assert!(stored[5].is_err());
+        assert_eq!(stored.iter().filter(|r| r.is_ok()).count(), 10);
+        let listed = db.list_agent_events(&run_id).unwrap();
+        assert_eq!(listed.len(), 10);
+        assert!(listed.iter().all(|e| e.summary != "line 5"));
+    }
+}
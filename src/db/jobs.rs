@@ -0,0 +1,513 @@
+use anyhow::{bail, Result};
+use chrono::Utc;
+use rusqlite::{params, OptionalExtension};
+
+use super::queries::ListFilter;
+use super::Database;
+
+/// Attempts before a failing job is dead-lettered
+pub const MAX_JOB_ATTEMPTS: u32 = 5;
+/// Wait before the first retry; doubled for each further attempt
+const RETRY_BASE_SECS: i64 = 5;
+
+/// What a job does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// Land a finished agent's branch, then dispatch its goal
+    Merge,
+    /// Start agents for a goal's ready tasks
+    Dispatch,
+}
+
+impl JobKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobKind::Merge => "merge",
+            JobKind::Dispatch => "dispatch",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "merge" => JobKind::Merge,
+            "dispatch" => JobKind::Dispatch,
+            _ => bail!("Unknown job kind: {}", s),
+        })
+    }
+}
+
+/// Where a job is in the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    /// Failed `MAX_JOB_ATTEMPTS` times; left for inspection or a manual retry
+    Dead,
+}
+
+impl JobStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Dead => "dead",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "pending" => JobStatus::Pending,
+            "running" => JobStatus::Running,
+            "done" => JobStatus::Done,
+            "dead" => JobStatus::Dead,
+            _ => bail!("Unknown job status: {}", s),
+        })
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Job {
+    pub id: i64,
+    pub kind: JobKind,
+    pub goal_space_id: String,
+    /// The finished run whose branch a merge lands, or whose exit freed a slot
+    pub agent_run_id: Option<String>,
+    pub repo_path: Option<String>,
+    pub branch: Option<String>,
+    pub status: JobStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    /// Not picked up before this time
+    pub run_after: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub finished_at: Option<String>,
+}
+
+const JOB_COLUMNS: &str = "id, kind, goal_space_id, agent_run_id, repo_path, branch, status,
+    attempts, last_error, run_after, created_at, updated_at, finished_at";
+
+fn job_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Job> {
+    let kind: String = row.get(1)?;
+    let status: String = row.get(6)?;
+    Ok(Job {
+        id: row.get(0)?,
+        kind: JobKind::parse(&kind).unwrap_or(JobKind::Dispatch),
+        goal_space_id: row.get(2)?,
+        agent_run_id: row.get(3)?,
+        repo_path: row.get(4)?,
+        branch: row.get(5)?,
+        status: JobStatus::parse(&status).unwrap_or(JobStatus::Dead),
+        attempts: row.get(7)?,
+        last_error: row.get(8)?,
+        run_after: row.get(9)?,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+        finished_at: row.get(12)?,
+    })
+}
+
+impl Database {
+    /// Queue the merge of a finished run's branch. A run is only ever queued
+    /// once, so a repeated completion can't merge twice. Returns the new
+    /// job's id, or None when the run already has a merge job.
+    pub fn enqueue_merge_job(
+        &self,
+        goal_space_id: &str,
+        agent_run_id: &str,
+        repo_path: &str,
+        branch: &str,
+    ) -> Result<Option<i64>> {
+        let now = Utc::now().to_rfc3339();
+        let key = format!("merge:{}", agent_run_id);
+        let conn = self.conn();
+        let inserted = conn.execute(
+            "INSERT INTO jobs (kind, goal_space_id, agent_run_id, repo_path, branch, dedupe_key,
+                               status, attempts, run_after, created_at, updated_at)
+             SELECT 'merge', ?1, ?2, ?3, ?4, ?5, 'pending', 0, ?6, ?6, ?6
+             WHERE NOT EXISTS (SELECT 1 FROM jobs WHERE dedupe_key = ?5)",
+            params![goal_space_id, agent_run_id, repo_path, branch, key, now],
+        )?;
+        Ok((inserted > 0).then(|| conn.last_insert_rowid()))
+    }
+
+    /// Queue a dispatch for a goal. Requests coalesce into the goal's pending
+    /// dispatch job if it has one, so a burst of finishing agents dispatches
+    /// once. Returns the id of the new or existing pending job.
+    pub fn enqueue_dispatch_job(
+        &self,
+        goal_space_id: &str,
+        agent_run_id: Option<&str>,
+    ) -> Result<i64> {
+        let now = Utc::now().to_rfc3339();
+        let key = format!("dispatch:{}", goal_space_id);
+        let conn = self.conn();
+        let pending: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM jobs WHERE dedupe_key = ?1 AND status = 'pending'",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(id) = pending {
+            conn.execute(
+                "UPDATE jobs SET agent_run_id = COALESCE(agent_run_id, ?1), updated_at = ?2
+                 WHERE id = ?3",
+                params![agent_run_id, now, id],
+            )?;
+            return Ok(id);
+        }
+        conn.execute(
+            "INSERT INTO jobs (kind, goal_space_id, agent_run_id, dedupe_key,
+                               status, attempts, run_after, created_at, updated_at)
+             VALUES ('dispatch', ?1, ?2, ?3, 'pending', 0, ?4, ?4, ?4)",
+            params![goal_space_id, agent_run_id, key, now],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Take the oldest pending job that is due, marking it running and
+    /// counting the attempt
+    pub fn claim_next_job(&self) -> Result<Option<Job>> {
+        let now = Utc::now().to_rfc3339();
+        let conn = self.conn();
+        let id: Option<i64> = conn
+            .query_row(
+                "UPDATE jobs SET status = 'running', attempts = attempts + 1, updated_at = ?1
+                 WHERE id = (SELECT id FROM jobs WHERE status = 'pending' AND run_after <= ?1
+                             ORDER BY id LIMIT 1)
+                 RETURNING id",
+                params![now],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(id) = id else {
+            return Ok(None);
+        };
+        let job = conn.query_row(
+            &format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS),
+            params![id],
+            job_from_row,
+        )?;
+        Ok(Some(job))
+    }
+
+    /// Mark a job done. `error` records why a job that can't succeed was
+    /// given up on without retrying.
+    pub fn complete_job(&self, id: i64, error: Option<&str>) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn().execute(
+            "UPDATE jobs SET status = 'done', last_error = ?1, updated_at = ?2, finished_at = ?2
+             WHERE id = ?3",
+            params![error, now, id],
+        )?;
+        Ok(())
+    }
+
+    /// Record a failed attempt. The job goes back in the queue with
+    /// exponential backoff, or is dead-lettered once it has used
+    /// `MAX_JOB_ATTEMPTS`. Returns the job's new status.
+    pub fn fail_job(&self, id: i64, error: &str) -> Result<JobStatus> {
+        let now = Utc::now();
+        let conn = self.conn();
+        let attempts: u32 = conn.query_row(
+            "SELECT attempts FROM jobs WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        let status = if attempts >= MAX_JOB_ATTEMPTS {
+            JobStatus::Dead
+        } else {
+            JobStatus::Pending
+        };
+        let delay = RETRY_BASE_SECS << attempts.saturating_sub(1).min(10);
+        let run_after = (now + chrono::Duration::seconds(delay)).to_rfc3339();
+        let now = now.to_rfc3339();
+        conn.execute(
+            "UPDATE jobs SET status = ?1, last_error = ?2, run_after = ?3, updated_at = ?4,
+                             finished_at = CASE WHEN ?1 = 'dead' THEN ?4 END
+             WHERE id = ?5",
+            params![status.as_str(), error, run_after, now, id],
+        )?;
+        Ok(status)
+    }
+
+    /// Put jobs that were running when the server stopped back in the queue.
+    /// Their attempt stays counted. Returns how many were requeued.
+    pub fn requeue_running_jobs(&self) -> Result<usize> {
+        let now = Utc::now().to_rfc3339();
+        let requeued = self.conn().execute(
+            "UPDATE jobs SET status = 'pending', run_after = ?1, updated_at = ?1
+             WHERE status = 'running'",
+            params![now],
+        )?;
+        Ok(requeued)
+    }
+
+    /// Delete done jobs that finished more than `older_than` ago. Dead jobs
+    /// are kept for inspection. Returns how many were deleted.
+    pub fn prune_done_jobs(&self, older_than: chrono::Duration) -> Result<usize> {
+        let cutoff = (Utc::now() - older_than).to_rfc3339();
+        let deleted = self.conn().execute(
+            "DELETE FROM jobs WHERE status = 'done' AND finished_at < ?1",
+            params![cutoff],
+        )?;
+        Ok(deleted)
+    }
+
+    /// Queue a dead job again with a fresh set of attempts. Returns the job,
+    /// or None when there is no dead job with that id.
+    pub fn retry_dead_job(&self, id: i64) -> Result<Option<Job>> {
+        let now = Utc::now().to_rfc3339();
+        let conn = self.conn();
+        let updated = conn.execute(
+            "UPDATE jobs SET status = 'pending', attempts = 0, run_after = ?1, updated_at = ?1,
+                             finished_at = NULL
+             WHERE id = ?2 AND status = 'dead'",
+            params![now, id],
+        )?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        let job = conn.query_row(
+            &format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS),
+            params![id],
+            job_from_row,
+        )?;
+        Ok(Some(job))
+    }
+
+    pub fn get_job(&self, id: i64) -> Result<Option<Job>> {
+        let conn = self.read_conn();
+        let job = conn
+            .query_row(
+                &format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS),
+                params![id],
+                job_from_row,
+            )
+            .optional()?;
+        Ok(job)
+    }
+
+    /// Jobs, newest first. Filters: status, goal, type (the job kind),
+    /// since/until on creation time; after_id is a job id.
+    pub fn list_jobs(&self, filter: &ListFilter) -> Result<Vec<Job>> {
+        if let Some(status) = &filter.status {
+            JobStatus::parse(status)?;
+        }
+        if let Some(kind) = &filter.event_type {
+            JobKind::parse(kind)?;
+        }
+        let after: Option<String> = match &filter.after_id {
+            Some(after) => Some(
+                after
+                    .parse::<i64>()
+                    .map_err(|_| anyhow::anyhow!("after_id must be a job id"))?
+                    .to_string(),
+            ),
+            None => None,
+        };
+
+        let mut clauses = Vec::new();
+        let mut args: Vec<&str> = Vec::new();
+        for (clause, value) in [
+            ("status = ?", &filter.status),
+            ("goal_space_id = ?", &filter.goal_space_id),
+            ("kind = ?", &filter.event_type),
+            ("created_at >= ?", &filter.since),
+            ("created_at < ?", &filter.until),
+            ("id < CAST(? AS INTEGER)", &after),
+        ] {
+            if let Some(value) = value {
+                clauses.push(clause);
+                args.push(value);
+            }
+        }
+        let where_sql = if clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", clauses.join(" AND "))
+        };
+
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM jobs{} ORDER BY id DESC{}",
+            JOB_COLUMNS,
+            where_sql,
+            filter
+                .limit
+                .map(|limit| format!(" LIMIT {}", limit.clamp(1, super::queries::MAX_PAGE_SIZE)))
+                .unwrap_or_default()
+        ))?;
+        let jobs = stmt
+            .query_map(rusqlite::params_from_iter(&args), job_from_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(jobs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        db
+    }
+
+    #[test]
+    fn test_merge_jobs_are_enqueued_once_per_run() {
+        let db = test_db();
+        let first = db.enqueue_merge_job("g", "run-1", "/repo", "b1").unwrap();
+        assert!(first.is_some());
+        assert_eq!(
+            db.enqueue_merge_job("g", "run-1", "/repo", "b1").unwrap(),
+            None
+        );
+
+        // Still refused once the first has run
+        let job = db.claim_next_job().unwrap().unwrap();
+        db.complete_job(job.id, None).unwrap();
+        assert_eq!(
+            db.enqueue_merge_job("g", "run-1", "/repo", "b1").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dispatch_jobs_coalesce_while_pending() {
+        let db = test_db();
+        let a = db.enqueue_dispatch_job("g", None).unwrap();
+        let b = db.enqueue_dispatch_job("g", Some("run-1")).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(
+            db.get_job(a).unwrap().unwrap().agent_run_id.as_deref(),
+            Some("run-1")
+        );
+        assert_ne!(db.enqueue_dispatch_job("other", None).unwrap(), a);
+
+        // A request arriving while the job runs gets a job of its own
+        let claimed = db.claim_next_job().unwrap().unwrap();
+        assert_eq!(claimed.id, a);
+        assert_ne!(db.enqueue_dispatch_job("g", None).unwrap(), a);
+    }
+
+    #[test]
+    fn test_failed_jobs_back_off_then_dead_letter() {
+        let db = test_db();
+        let id = db.enqueue_dispatch_job("g", None).unwrap();
+        let job = db.claim_next_job().unwrap().unwrap();
+        assert_eq!(job.attempts, 1);
+        assert_eq!(job.status, JobStatus::Running);
+
+        assert_eq!(db.fail_job(id, "boom").unwrap(), JobStatus::Pending);
+        // Not due until the backoff has passed
+        assert!(db.claim_next_job().unwrap().is_none());
+
+        for _ in 1..MAX_JOB_ATTEMPTS {
+            db.conn()
+                .execute(
+                    "UPDATE jobs SET run_after = '2000-01-01T00:00:00+00:00' WHERE id = ?1",
+                    params![id],
+                )
+                .unwrap();
+            db.claim_next_job().unwrap().unwrap();
+            db.fail_job(id, "boom").unwrap();
+        }
+        let job = db.get_job(id).unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Dead);
+        assert_eq!(job.attempts, MAX_JOB_ATTEMPTS);
+        assert_eq!(job.last_error.as_deref(), Some("boom"));
+
+        let job = db.retry_dead_job(id).unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Pending);
+        assert_eq!(job.attempts, 0);
+        assert!(db.retry_dead_job(id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_running_jobs_are_requeued_after_a_crash() {
+        let db = test_db();
+        let id = db
+            .enqueue_merge_job("g", "run-1", "/repo", "b1")
+            .unwrap()
+            .unwrap();
+        db.claim_next_job().unwrap().unwrap();
+        assert!(db.claim_next_job().unwrap().is_none());
+
+        assert_eq!(db.requeue_running_jobs().unwrap(), 1);
+        let job = db.claim_next_job().unwrap().unwrap();
+        assert_eq!(job.id, id);
+        assert_eq!(job.attempts, 2);
+    }
+
+    #[test]
+    fn test_prune_deletes_old_done_jobs() {
+        let db = test_db();
+        let old = db.enqueue_dispatch_job("g1", None).unwrap();
+        db.claim_next_job().unwrap().unwrap();
+        db.complete_job(old, None).unwrap();
+        let recent = db.enqueue_dispatch_job("g2", None).unwrap();
+        db.claim_next_job().unwrap().unwrap();
+        db.complete_job(recent, Some("Merge conflict")).unwrap();
+        let dead = db.enqueue_dispatch_job("g3", None).unwrap();
+        db.claim_next_job().unwrap().unwrap();
+        db.conn()
+            .execute(
+                "UPDATE jobs SET finished_at = '2000-01-01T00:00:00+00:00' WHERE id IN (?1, ?2)",
+                params![old, dead],
+            )
+            .unwrap();
+        db.conn()
+            .execute(
+                "UPDATE jobs SET status = 'dead' WHERE id = ?1",
+                params![dead],
+            )
+            .unwrap();
+        let pending = db.enqueue_dispatch_job("g4", None).unwrap();
+
+        assert_eq!(db.prune_done_jobs(chrono::Duration::days(7)).unwrap(), 1);
+        assert!(db.get_job(old).unwrap().is_none());
+        let recent = db.get_job(recent).unwrap().unwrap();
+        assert_eq!(recent.status, JobStatus::Done);
+        assert_eq!(recent.last_error.as_deref(), Some("Merge conflict"));
+        assert!(db.get_job(dead).unwrap().is_some());
+        assert!(db.get_job(pending).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_list_jobs_filters_and_pages() {
+        let db = test_db();
+        let merge = db
+            .enqueue_merge_job("g1", "run-1", "/repo", "b1")
+            .unwrap()
+            .unwrap();
+        let dispatch = db.enqueue_dispatch_job("g2", None).unwrap();
+
+        let all = db.list_jobs(&ListFilter::default()).unwrap();
+        assert_eq!(
+            all.iter().map(|j| j.id).collect::<Vec<_>>(),
+            vec![dispatch, merge]
+        );
+        let filter = ListFilter {
+            event_type: Some("merge".into()),
+            ..Default::default()
+        };
+        assert_eq!(db.list_jobs(&filter).unwrap()[0].id, merge);
+        let filter = ListFilter {
+            after_id: Some(dispatch.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(db.list_jobs(&filter).unwrap().len(), 1);
+        let filter = ListFilter {
+            status: Some("stuck".into()),
+            ..Default::default()
+        };
+        assert!(db.list_jobs(&filter).is_err());
+    }
+}
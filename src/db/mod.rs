@@ -1,40 +1,186 @@
+pub mod analytics;
+pub mod events;
+pub mod jobs;
 pub mod queries;
+pub mod retention;
 pub mod schema;
+pub mod search;
 
 use anyhow::Result;
-use rusqlite::Connection;
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+use rusqlite::{Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
 
+/// Read-only connections opened alongside the writer for file-backed databases.
+const READER_CONNECTIONS: usize = 4;
+
+/// SQLite handle shared across the server.
+///
+/// All writes go through a single connection (SQLite allows one writer at a
+/// time, and WAL lets readers proceed alongside it). File-backed databases
+/// also get a small pool of read-only connections so API reads do not queue
+/// behind agent event writes. Agent events are written in batches by a
+/// dedicated thread, see [`events`].
 #[derive(Clone)]
 pub struct Database {
     conn: Arc<Mutex<Connection>>,
+    readers: Arc<Vec<Mutex<Connection>>>,
+    next_reader: Arc<AtomicUsize>,
+    events: events::EventWriter,
+    archive_dir: Option<PathBuf>,
 }
 
 impl Database {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
         let conn = Connection::open(path)?;
-        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON;")?;
-        Ok(Self {
-            conn: Arc::new(Mutex::new(conn)),
-        })
+        conn.execute_batch(
+            "PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON; PRAGMA busy_timeout=5000;",
+        )?;
+
+        let mut readers = Vec::with_capacity(READER_CONNECTIONS);
+        for _ in 0..READER_CONNECTIONS {
+            let reader = Connection::open_with_flags(
+                path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )?;
+            reader.execute_batch("PRAGMA busy_timeout=5000;")?;
+            readers.push(Mutex::new(reader));
+        }
+
+        let mut db = Self::from_connections(conn, readers);
+        db.archive_dir = Some(path.with_extension("archive"));
+        Ok(db)
     }
 
     #[allow(dead_code)]
     pub fn open_in_memory() -> Result<Self> {
         let conn = Connection::open_in_memory()?;
         conn.execute_batch("PRAGMA foreign_keys=ON;")?;
-        Ok(Self {
-            conn: Arc::new(Mutex::new(conn)),
-        })
+        // A private in-memory database is only visible to its own connection,
+        // so reads share the writer.
+        Ok(Self::from_connections(conn, Vec::new()))
     }
 
-    pub fn run_migrations(&self) -> Result<()> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
-        schema::run_migrations(&conn)
+    fn from_connections(conn: Connection, readers: Vec<Mutex<Connection>>) -> Self {
+        let conn = Arc::new(Mutex::new(conn));
+        Self {
+            events: events::EventWriter::spawn(conn.clone()),
+            conn,
+            readers: Arc::new(readers),
+            next_reader: Arc::new(AtomicUsize::new(0)),
+            archive_dir: None,
+        }
     }
 
-    pub fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+    /// Use `dir` for compacted event payloads instead of the default next to
+    /// the database file.
+    #[allow(dead_code)]
+    pub fn with_archive_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.archive_dir = Some(dir.into());
+        self
+    }
+
+    /// Where compaction writes per-run archives of raw event payloads.
+    /// In-memory databases have none unless one is set.
+    pub fn archive_dir(&self) -> Option<&Path> {
+        self.archive_dir.as_deref()
+    }
+
+    /// Apply pending schema migrations. Refuses to touch a database whose
+    /// schema is newer than this binary supports.
+    pub fn run_migrations(&self) -> Result<Vec<&'static schema::Migration>> {
+        let conn = self.conn();
+        let applied = schema::run_migrations(&conn)?;
+        for migration in &applied {
+            tracing::info!(
+                "Applied schema migration {} ({})",
+                migration.version,
+                migration.name
+            );
+        }
+        Ok(applied)
+    }
+
+    /// Migrations `run_migrations` would apply, without applying them.
+    pub fn pending_migrations(&self) -> Result<Vec<&'static schema::Migration>> {
+        schema::pending_migrations(&self.conn())
+    }
+
+    pub fn schema_version(&self) -> Result<u32> {
+        schema::current_version(&self.conn())
+    }
+
+    /// The writer connection. Use for anything that modifies the database.
+    pub fn conn(&self) -> MutexGuard<'_, Connection> {
         self.conn.lock().unwrap_or_else(|e| e.into_inner())
     }
+
+    /// A connection for read-only queries: the first idle reader, falling back
+    /// to waiting on one in round-robin order. Uses the writer when the
+    /// database has no readers (in-memory databases).
+    pub fn read_conn(&self) -> MutexGuard<'_, Connection> {
+        if self.readers.is_empty() {
+            return self.conn();
+        }
+        let start = self.next_reader.fetch_add(1, Ordering::Relaxed);
+        for i in 0..self.readers.len() {
+            match self.readers[(start + i) % self.readers.len()].try_lock() {
+                Ok(guard) => return guard,
+                Err(TryLockError::Poisoned(e)) => return e.into_inner(),
+                Err(TryLockError::WouldBlock) => continue,
+            }
+        }
+        self.readers[start % self.readers.len()]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Run blocking database work on tokio's blocking pool so it does not
+    /// stall the async worker threads.
+    pub async fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Database) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.clone();
+        tokio::task::spawn_blocking(move || f(&db)).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_database_reads_through_read_only_pool() {
+        let path = std::env::temp_dir().join(format!("conductor-{}.db", uuid::Uuid::new_v4()));
+        let db = Database::open(&path).unwrap();
+        db.run_migrations().unwrap();
+        assert_eq!(db.readers.len(), READER_CONNECTIONS);
+
+        db.conn()
+            .execute(
+                "INSERT INTO projects (id, path, display_name, created_at, updated_at)
+                 VALUES ('p1', '/tmp/repo', 'repo', 'now', 'now')",
+                [],
+            )
+            .unwrap();
+
+        // Hold one reader so the next read picks another idle one
+        let held = db.read_conn();
+        let count: i64 = db
+            .read_conn()
+            .query_row("SELECT COUNT(*) FROM projects", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+        assert!(held.execute("DELETE FROM projects", []).is_err());
+        drop(held);
+
+        drop(db);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
 }
@@ -1,12 +1,78 @@
 use anyhow::Result;
 use chrono::Utc;
 use rusqlite::params;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 use crate::db::Database;
+use crate::goal::task::{
+    dependency_problems, graph_problems, validate_run_transition, validate_transition,
+    GraphProblem, InvalidDependencies, RunStatus, TaskStatus,
+};
 
 // ── Goal Space types ──
 
+/// How a finished agent branch lands on the main branch
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeMode {
+    /// Merge as soon as the agent finishes
+    #[default]
+    Auto,
+    /// Hold the branch in `awaiting_review` until a human approves it
+    Manual,
+}
+
+/// How shared build directories are provided to agent worktrees
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheStrategy {
+    /// Symlink each directory to a cache shared by all worktrees of the repo
+    #[default]
+    Share,
+    /// Copy-on-write clone of the directory from the main checkout
+    Clone,
+}
+
+/// What to do when an agent exits successfully but leaves changes uncommitted
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UncommittedPolicy {
+    /// Commit everything left in the worktree with a generated message
+    #[default]
+    Commit,
+    /// Resume the agent's session and ask it to commit
+    Nudge,
+    /// Fail the task, leaving the branch as the agent committed it
+    Fail,
+}
+
+/// What to do when the user's checkout isn't safe to merge into
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirtyCheckoutPolicy {
+    /// Queue the merge and retry it once the checkout is clean
+    #[default]
+    Defer,
+    /// Stash uncommitted changes, merge, then restore them
+    Stash,
+    /// Refuse the merge and report it
+    Fail,
+}
+
+/// What to do with the task a post-merge check bisects a regression to
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegressionAction {
+    /// Record the culprit only
+    #[default]
+    Mark,
+    /// Revert the culprit task's merge
+    Revert,
+    /// Create a task for an agent to fix the regression
+    FixUp,
+}
+
 #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct GoalSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -21,6 +87,40 @@ pub struct GoalSettings {
     pub permission_mode: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub system_prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_mode: Option<MergeMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub push_remote: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stacked: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warm_pool_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dirs: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_strategy: Option<CacheStrategy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setup_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub teardown_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script_timeout_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uncommitted_policy: Option<UncommittedPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dirty_checkout_policy: Option<DirtyCheckoutPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_merge_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regression_action: Option<RegressionAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_agents: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_weight: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_agents: Option<u32>,
 }
 
 impl GoalSettings {
@@ -63,6 +163,91 @@ impl GoalSettings {
         self.system_prompt.clone()
     }
 
+    /// Get the resolved merge_mode value (with fallback to default)
+    pub fn merge_mode(&self) -> MergeMode {
+        self.merge_mode.unwrap_or_default()
+    }
+
+    /// Get the resolved push_remote value (returns None if not set)
+    pub fn push_remote(&self) -> Option<String> {
+        self.push_remote.clone()
+    }
+
+    /// Get the resolved stacked value (with fallback to default)
+    pub fn stacked(&self) -> bool {
+        self.stacked.unwrap_or(false)
+    }
+
+    /// Get the resolved warm_pool_size value (0 disables the pool)
+    pub fn warm_pool_size(&self) -> u32 {
+        self.warm_pool_size.unwrap_or(0)
+    }
+
+    /// Get the resolved cache_dirs value (with fallback to default)
+    pub fn cache_dirs(&self) -> Vec<String> {
+        self.cache_dirs.clone().unwrap_or_default()
+    }
+
+    /// Get the resolved cache_strategy value (with fallback to default)
+    pub fn cache_strategy(&self) -> CacheStrategy {
+        self.cache_strategy.unwrap_or_default()
+    }
+
+    /// Get the resolved setup_command value (returns None if not set)
+    pub fn setup_command(&self) -> Option<String> {
+        self.setup_command.clone()
+    }
+
+    /// Get the resolved teardown_command value (returns None if not set)
+    pub fn teardown_command(&self) -> Option<String> {
+        self.teardown_command.clone()
+    }
+
+    /// Get the resolved script_timeout_secs value (with fallback to default)
+    pub fn script_timeout_secs(&self) -> u64 {
+        self.script_timeout_secs.unwrap_or(300)
+    }
+
+    /// Get the resolved uncommitted_policy value (with fallback to default)
+    pub fn uncommitted_policy(&self) -> UncommittedPolicy {
+        self.uncommitted_policy.unwrap_or_default()
+    }
+
+    /// Get the resolved dirty_checkout_policy value (with fallback to default)
+    pub fn dirty_checkout_policy(&self) -> DirtyCheckoutPolicy {
+        self.dirty_checkout_policy.unwrap_or_default()
+    }
+
+    /// Get the resolved target_branch value (returns None if not set)
+    pub fn target_branch(&self) -> Option<String> {
+        self.target_branch.clone()
+    }
+
+    /// Get the resolved post_merge_command value (returns None if not set)
+    pub fn post_merge_command(&self) -> Option<String> {
+        self.post_merge_command.clone()
+    }
+
+    /// Get the resolved regression_action value (with fallback to default)
+    pub fn regression_action(&self) -> RegressionAction {
+        self.regression_action.unwrap_or_default()
+    }
+
+    /// Get the resolved max_concurrent_agents value (None, or 0, means no limit)
+    pub fn max_concurrent_agents(&self) -> Option<u32> {
+        self.max_concurrent_agents.filter(|n| *n > 0)
+    }
+
+    /// Get the resolved share_weight value (with fallback to default)
+    pub fn share_weight(&self) -> f64 {
+        self.share_weight.filter(|w| *w > 0.0).unwrap_or(1.0)
+    }
+
+    /// Get the resolved min_agents value (with fallback to default)
+    pub fn min_agents(&self) -> u32 {
+        self.min_agents.unwrap_or(0)
+    }
+
     /// Merge task-level settings over goal-level settings.
     /// Task settings override goal settings where present.
     pub fn merge(&self, task_settings: &GoalSettings) -> GoalSettings {
@@ -82,6 +267,47 @@ impl GoalSettings {
                 .system_prompt
                 .clone()
                 .or_else(|| self.system_prompt.clone()),
+            merge_mode: task_settings.merge_mode.or(self.merge_mode),
+            push_remote: task_settings
+                .push_remote
+                .clone()
+                .or_else(|| self.push_remote.clone()),
+            stacked: task_settings.stacked.or(self.stacked),
+            warm_pool_size: task_settings.warm_pool_size.or(self.warm_pool_size),
+            cache_dirs: task_settings
+                .cache_dirs
+                .clone()
+                .or_else(|| self.cache_dirs.clone()),
+            cache_strategy: task_settings.cache_strategy.or(self.cache_strategy),
+            setup_command: task_settings
+                .setup_command
+                .clone()
+                .or_else(|| self.setup_command.clone()),
+            teardown_command: task_settings
+                .teardown_command
+                .clone()
+                .or_else(|| self.teardown_command.clone()),
+            script_timeout_secs: task_settings
+                .script_timeout_secs
+                .or(self.script_timeout_secs),
+            uncommitted_policy: task_settings.uncommitted_policy.or(self.uncommitted_policy),
+            dirty_checkout_policy: task_settings
+                .dirty_checkout_policy
+                .or(self.dirty_checkout_policy),
+            target_branch: task_settings
+                .target_branch
+                .clone()
+                .or_else(|| self.target_branch.clone()),
+            post_merge_command: task_settings
+                .post_merge_command
+                .clone()
+                .or_else(|| self.post_merge_command.clone()),
+            regression_action: task_settings.regression_action.or(self.regression_action),
+            max_concurrent_agents: task_settings
+                .max_concurrent_agents
+                .or(self.max_concurrent_agents),
+            share_weight: task_settings.share_weight.or(self.share_weight),
+            min_agents: task_settings.min_agents.or(self.min_agents),
         }
     }
 }
@@ -116,7 +342,7 @@ pub struct Task {
     pub goal_space_id: String,
     pub title: String,
     pub description: String,
-    pub status: String,
+    pub status: TaskStatus,
     pub priority: i32,
     pub depends_on: Vec<String>,
     pub settings: GoalSettings,
@@ -136,16 +362,54 @@ pub struct CreateTask {
     pub settings: GoalSettings,
 }
 
-#[derive(Debug, Default, serde::Deserialize)]
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct UpdateTask {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
-    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TaskStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub priority: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub depends_on: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub settings: Option<GoalSettings>,
 }
 
+// ── Status columns ──
+
+impl rusqlite::types::ToSql for TaskStatus {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for TaskStatus {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: anyhow::Error| rusqlite::types::FromSqlError::Other(e.into()))
+    }
+}
+
+impl rusqlite::types::ToSql for RunStatus {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for RunStatus {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: anyhow::Error| rusqlite::types::FromSqlError::Other(e.into()))
+    }
+}
+
 // ── Agent Run types ──
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -156,7 +420,7 @@ pub struct AgentRun {
     pub claude_session_id: Option<String>,
     pub worktree_path: Option<String>,
     pub branch: Option<String>,
-    pub status: String,
+    pub status: RunStatus,
     pub model: String,
     pub cost_usd: f64,
     pub input_tokens: i64,
@@ -165,6 +429,14 @@ pub struct AgentRun {
     pub started_at: String,
     pub last_activity_at: Option<String>,
     pub finished_at: Option<String>,
+    /// SHA of the merge commit created when this run's branch landed
+    pub merge_commit: Option<String>,
+    /// SHA the run's branch started from (a dependency branch tip when stacked)
+    pub base_commit: Option<String>,
+    /// Milliseconds spent getting the worktree ready (checkout plus cache setup)
+    pub worktree_ready_ms: Option<i64>,
+    /// Why the run failed when it never got to do its work (e.g. `setup_failed`)
+    pub failure_reason: Option<String>,
 }
 
 // ── Agent Event types ──
@@ -211,6 +483,24 @@ pub struct UpdateProject {
     pub settings: Option<GoalSettings>,
 }
 
+// ── Deferred merge types ──
+
+/// A finished branch waiting for the user's checkout to be safe to merge into
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeferredMerge {
+    pub id: String,
+    pub goal_space_id: String,
+    pub task_id: Option<String>,
+    pub agent_run_id: Option<String>,
+    pub repo_path: String,
+    pub branch: String,
+    /// Why the merge couldn't run on the last attempt
+    pub reason: String,
+    pub attempts: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
 // ── Goal Message types ──
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -243,6 +533,83 @@ fn default_metadata_json() -> String {
     "{}".to_string()
 }
 
+// ── Scheduler types ──
+
+/// Fleet-wide scheduler settings
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SchedulerConfig {
+    /// Most agents running at once across all goals; None means no limit
+    pub total_slots: Option<u32>,
+    /// How long a goal with ready tasks may go without starting an agent
+    /// before it is served ahead of its share
+    pub starvation_secs: u64,
+    pub updated_at: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct UpdateSchedulerConfig {
+    /// 0 removes the limit
+    pub total_slots: Option<u32>,
+    pub starvation_secs: Option<u64>,
+}
+
+// ── Goal History types ──
+
+/// Who made a change recorded in goal history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Actor {
+    /// The web UI
+    Ui,
+    /// The `conductor` CLI
+    Cli,
+    /// Any other API client
+    Api,
+    /// A Claude Code hook reporting on an agent
+    Hook,
+    /// The dispatch loop and the agents it runs
+    Dispatch,
+    /// Server-side work nobody asked for directly, such as startup recovery
+    System,
+}
+
+impl Actor {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Actor::Ui => "ui",
+            Actor::Cli => "cli",
+            Actor::Api => "api",
+            Actor::Hook => "hook",
+            Actor::Dispatch => "dispatch",
+            Actor::System => "system",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "ui" => Some(Actor::Ui),
+            "cli" => Some(Actor::Cli),
+            "api" => Some(Actor::Api),
+            "hook" => Some(Actor::Hook),
+            "dispatch" => Some(Actor::Dispatch),
+            "system" => Some(Actor::System),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GoalHistoryEntry {
+    pub id: i64,
+    pub goal_space_id: String,
+    pub event_type: String,
+    pub actor: Actor,
+    pub description: String,
+    /// Structured details of the change, such as the task id and changed fields
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: String,
+}
+
 // ── Stats ──
 
 #[derive(Debug, serde::Serialize)]
@@ -254,10 +621,112 @@ pub struct Stats {
     pub goals_active: i64,
 }
 
+/// Map a row selected with the standard agent_runs column list to an `AgentRun`
+fn agent_run_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<AgentRun> {
+    Ok(AgentRun {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        goal_space_id: row.get(2)?,
+        claude_session_id: row.get(3)?,
+        worktree_path: row.get(4)?,
+        branch: row.get(5)?,
+        status: row.get(6)?,
+        model: row.get(7)?,
+        cost_usd: row.get(8)?,
+        input_tokens: row.get(9)?,
+        output_tokens: row.get(10)?,
+        max_budget_usd: row.get(11)?,
+        started_at: row.get(12)?,
+        last_activity_at: row.get(13)?,
+        finished_at: row.get(14)?,
+        merge_commit: row.get(15)?,
+        base_commit: row.get(16)?,
+        worktree_ready_ms: row.get(17)?,
+        failure_reason: row.get(18)?,
+    })
+}
+
+// ── Listing ──
+
+/// Largest page a list query returns
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+/// Cursor pagination and filters for the list queries. Every field is
+/// optional; each query applies the ones that make sense for its rows.
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+pub struct ListFilter {
+    /// Continue after the row with this id, in the list's order
+    pub after_id: Option<String>,
+    /// Page size, capped at `MAX_PAGE_SIZE` (all rows when unset)
+    pub limit: Option<u32>,
+    pub status: Option<String>,
+    #[serde(alias = "goal")]
+    pub goal_space_id: Option<String>,
+    #[serde(alias = "project")]
+    pub project_id: Option<String>,
+    pub model: Option<String>,
+    /// Only rows created (or started) at or after this RFC 3339 timestamp or date
+    pub since: Option<String>,
+    /// Only rows created (or started) before this RFC 3339 timestamp or date
+    pub until: Option<String>,
+    #[serde(alias = "type")]
+    pub event_type: Option<String>,
+    pub tool_name: Option<String>,
+    pub actor: Option<String>,
+}
+
+impl ListFilter {
+    fn limit_sql(&self) -> String {
+        match self.limit {
+            Some(limit) => format!(" LIMIT {}", limit.clamp(1, MAX_PAGE_SIZE)),
+            None => String::new(),
+        }
+    }
+}
+
+/// WHERE clause built from optional filters, with positional arguments
+#[derive(Default)]
+struct Conditions {
+    clauses: Vec<String>,
+    args: Vec<rusqlite::types::Value>,
+}
+
+impl Conditions {
+    /// Add `clause`, which uses one `?` for each of `values`
+    fn add(&mut self, clause: &str, values: &[&str]) {
+        self.clauses.push(clause.to_string());
+        self.args.extend(
+            values
+                .iter()
+                .map(|v| rusqlite::types::Value::Text(v.to_string())),
+        );
+    }
+
+    fn add_opt(&mut self, clause: &str, value: &Option<String>) {
+        if let Some(value) = value {
+            self.add(clause, &[value]);
+        }
+    }
+
+    fn sql(&self) -> String {
+        if self.clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", self.clauses.join(" AND "))
+        }
+    }
+}
+
 // ── Goal Space Queries ──
 
 impl Database {
+    #[allow(dead_code)]
     pub fn create_goal_space(&self, input: &CreateGoalSpace) -> Result<GoalSpace> {
+        self.create_goal_space_by(input, Actor::System)
+    }
+
+    /// Create a goal, recording `actor` as its creator in the goal history
+    pub fn create_goal_space_by(&self, input: &CreateGoalSpace, actor: Actor) -> Result<GoalSpace> {
         let id = Uuid::new_v4().to_string();
         let now = Utc::now().to_rfc3339();
         let settings_json = serde_json::to_string(&input.settings)?;
@@ -273,6 +742,7 @@ impl Database {
 
         self.insert_goal_history(
             &id,
+            actor,
             "created",
             &format!("Goal space '{}' created", input.name),
             None,
@@ -291,14 +761,31 @@ impl Database {
     }
 
     pub fn list_goal_spaces(&self) -> Result<Vec<GoalSpace>> {
-        let conn = self.conn();
-        let mut stmt = conn.prepare(
+        self.list_goal_spaces_filtered(&ListFilter::default())
+    }
+
+    /// Goal spaces, newest first. Filters: status, project, since/until on creation time.
+    pub fn list_goal_spaces_filtered(&self, filter: &ListFilter) -> Result<Vec<GoalSpace>> {
+        let mut conditions = Conditions::default();
+        conditions.add_opt("status = ?", &filter.status);
+        conditions.add_opt("project_id = ?", &filter.project_id);
+        conditions.add_opt("created_at >= ?", &filter.since);
+        conditions.add_opt("created_at < ?", &filter.until);
+        conditions.add_opt(
+            "(created_at, id) < (SELECT created_at, id FROM goal_spaces WHERE id = ?)",
+            &filter.after_id,
+        );
+
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(&format!(
             "SELECT id, name, description, status, repo_path, created_at, updated_at, settings
-             FROM goal_spaces ORDER BY created_at DESC",
-        )?;
+             FROM goal_spaces{} ORDER BY created_at DESC, id DESC{}",
+            conditions.sql(),
+            filter.limit_sql()
+        ))?;
 
         let goals = stmt
-            .query_map([], |row| {
+            .query_map(rusqlite::params_from_iter(&conditions.args), |row| {
                 let settings_str: String = row.get(7)?;
                 let settings: GoalSettings =
                     serde_json::from_str(&settings_str).unwrap_or_default();
@@ -319,7 +806,7 @@ impl Database {
     }
 
     pub fn get_goal_space(&self, id: &str) -> Result<Option<GoalSpace>> {
-        let conn = self.conn();
+        let conn = self.read_conn();
         let mut stmt = conn.prepare(
             "SELECT id, name, description, status, repo_path, created_at, updated_at, settings
              FROM goal_spaces WHERE id = ?1",
@@ -393,6 +880,7 @@ impl Database {
 
     /// Atomically mark a goal as completed if and only if all its tasks are done.
     /// Returns true if the goal was marked completed, false if not (because there are pending tasks or no tasks).
+    /// A reverted task counts as unfinished: its goal stays open until the task is retried and done again.
     pub fn mark_goal_completed_if_all_tasks_done(&self, goal_space_id: &str) -> Result<bool> {
         let conn = self.conn();
         let now = Utc::now().to_rfc3339();
@@ -426,14 +914,29 @@ impl Database {
 
     // ── Task Queries ──
 
+    /// Create a pending task. Dependencies must be other tasks in the same
+    /// goal; otherwise nothing is written and the error is `InvalidDependencies`.
+    #[allow(dead_code)]
     pub fn create_task(&self, goal_space_id: &str, input: &CreateTask) -> Result<Task> {
+        self.create_task_by(goal_space_id, input, Actor::System)
+    }
+
+    /// `create_task`, recording `actor` as the task's creator in the goal history
+    pub fn create_task_by(
+        &self,
+        goal_space_id: &str,
+        input: &CreateTask,
+        actor: Actor,
+    ) -> Result<Task> {
         let id = Uuid::new_v4().to_string();
         let now = Utc::now().to_rfc3339();
         let depends_on_json = serde_json::to_string(&input.depends_on)?;
         let settings_json = serde_json::to_string(&input.settings)?;
 
         {
+            // Checked under the writer lock so a concurrent edit can't close a cycle
             let conn = self.conn();
+            check_dependencies(&conn, goal_space_id, &id, &input.depends_on)?;
             conn.execute(
                 "INSERT INTO tasks (id, goal_space_id, title, description, status, priority, depends_on, settings, created_at, updated_at)
                  VALUES (?1, ?2, ?3, ?4, 'pending', ?5, ?6, ?7, ?8, ?9)",
@@ -443,9 +946,10 @@ impl Database {
 
         self.insert_goal_history(
             goal_space_id,
+            actor,
             "task_added",
             &format!("Task '{}' added", input.title),
-            None,
+            Some(serde_json::json!({"task_id": id, "depends_on": input.depends_on})),
         )?;
 
         Ok(Task {
@@ -453,7 +957,7 @@ impl Database {
             goal_space_id: goal_space_id.to_string(),
             title: input.title.clone(),
             description: input.description.clone(),
-            status: "pending".to_string(),
+            status: TaskStatus::Pending,
             priority: input.priority,
             depends_on: input.depends_on.clone(),
             settings: input.settings.clone(),
@@ -463,7 +967,7 @@ impl Database {
     }
 
     pub fn list_tasks(&self, goal_space_id: &str) -> Result<Vec<Task>> {
-        let conn = self.conn();
+        let conn = self.read_conn();
         let mut stmt = conn.prepare(
             "SELECT id, goal_space_id, title, description, status, priority, depends_on, settings, created_at, updated_at
              FROM tasks WHERE goal_space_id = ?1 ORDER BY priority DESC, created_at ASC",
@@ -496,7 +1000,7 @@ impl Database {
     }
 
     pub fn get_task(&self, id: &str) -> Result<Option<Task>> {
-        let conn = self.conn();
+        let conn = self.read_conn();
         let mut stmt = conn.prepare(
             "SELECT id, goal_space_id, title, description, status, priority, depends_on, settings, created_at, updated_at
              FROM tasks WHERE id = ?1",
@@ -528,10 +1032,41 @@ impl Database {
         Ok(task)
     }
 
+    /// Apply the given fields to a task. A status change must be allowed by the
+    /// task state machine and new dependencies must keep the goal's graph
+    /// valid; otherwise nothing is written and the error is an
+    /// `InvalidTransition` or `InvalidDependencies`.
     pub fn update_task(&self, id: &str, input: &UpdateTask) -> Result<()> {
         let conn = self.conn();
         let now = Utc::now().to_rfc3339();
 
+        // Checked under the writer lock so a concurrent edit can't close a cycle
+        if let Some(ref depends_on) = input.depends_on {
+            let goal_space_id: Option<String> = conn
+                .query_row(
+                    "SELECT goal_space_id FROM tasks WHERE id = ?1",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(goal_space_id) = goal_space_id {
+                check_dependencies(&conn, &goal_space_id, id, depends_on)?;
+            }
+        }
+
+        if let Some(status) = input.status {
+            let current: Option<TaskStatus> = conn
+                .query_row(
+                    "SELECT status FROM tasks WHERE id = ?1",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(current) = current {
+                validate_transition(current, status)?;
+            }
+        }
+
         if let Some(ref title) = input.title {
             conn.execute(
                 "UPDATE tasks SET title = ?1, updated_at = ?2 WHERE id = ?3",
@@ -544,7 +1079,7 @@ impl Database {
                 params![description, now, id],
             )?;
         }
-        if let Some(ref status) = input.status {
+        if let Some(status) = input.status {
             conn.execute(
                 "UPDATE tasks SET status = ?1, updated_at = ?2 WHERE id = ?3",
                 params![status, now, id],
@@ -574,19 +1109,33 @@ impl Database {
         Ok(())
     }
 
+    /// Everything wrong with a goal's dependency graph as it stands
+    pub fn goal_graph_problems(&self, goal_space_id: &str) -> Result<Vec<GraphProblem>> {
+        let tasks = self.list_tasks(goal_space_id)?;
+        let unknown: Vec<&String> = tasks
+            .iter()
+            .flat_map(|t| &t.depends_on)
+            .filter(|d| !tasks.iter().any(|t| &t.id == *d))
+            .collect();
+        let foreign = foreign_task_goals(&self.read_conn(), goal_space_id, &unknown)?;
+        Ok(graph_problems(&tasks, &foreign))
+    }
+
+    #[allow(dead_code)]
     pub fn get_unblocked_tasks(&self, goal_space_id: &str) -> Result<Vec<Task>> {
         let all_tasks = self.list_tasks(goal_space_id)?;
 
         let done_ids: std::collections::HashSet<String> = all_tasks
             .iter()
-            .filter(|t| t.status == "done")
+            .filter(|t| t.status == TaskStatus::Done)
             .map(|t| t.id.clone())
             .collect();
 
         let unblocked: Vec<Task> = all_tasks
             .into_iter()
             .filter(|t| {
-                t.status == "pending" && t.depends_on.iter().all(|dep| done_ids.contains(dep))
+                t.status == TaskStatus::Pending
+                    && t.depends_on.iter().all(|dep| done_ids.contains(dep))
             })
             .collect();
 
@@ -618,9 +1167,10 @@ impl Database {
 
         self.insert_goal_history(
             goal_space_id,
+            Actor::Dispatch,
             "agent_spawned",
             &format!("Agent {} spawned for task {}", id, task_id),
-            None,
+            Some(serde_json::json!({"task_id": task_id, "agent_run_id": id, "model": model})),
         )?;
 
         Ok(AgentRun {
@@ -630,7 +1180,7 @@ impl Database {
             claude_session_id: None,
             worktree_path: worktree_path.map(String::from),
             branch: branch.map(String::from),
-            status: "spawning".to_string(),
+            status: RunStatus::Spawning,
             model: model.to_string(),
             cost_usd: 0.0,
             input_tokens: 0,
@@ -639,117 +1189,169 @@ impl Database {
             started_at: now,
             last_activity_at: None,
             finished_at: None,
+            merge_commit: None,
+            base_commit: None,
+            worktree_ready_ms: None,
+            failure_reason: None,
         })
     }
 
     pub fn get_agent_run(&self, id: &str) -> Result<Option<AgentRun>> {
-        let conn = self.conn();
+        let conn = self.read_conn();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, goal_space_id, claude_session_id, worktree_path, branch,
                     status, model, cost_usd, input_tokens, output_tokens, max_budget_usd,
-                    started_at, last_activity_at, finished_at
+                    started_at, last_activity_at, finished_at, merge_commit, base_commit,
+                    worktree_ready_ms, failure_reason
              FROM agent_runs WHERE id = ?1",
         )?;
 
+        let run = stmt.query_row(params![id], agent_run_from_row).optional()?;
+
+        Ok(run)
+    }
+
+    pub fn list_agent_runs(&self) -> Result<Vec<AgentRun>> {
+        self.list_agent_runs_filtered(&ListFilter::default())
+    }
+
+    /// Agent runs, most recently started first. Filters: status, goal, project,
+    /// model, since/until on start time.
+    pub fn list_agent_runs_filtered(&self, filter: &ListFilter) -> Result<Vec<AgentRun>> {
+        let mut conditions = Conditions::default();
+        conditions.add_opt("status = ?", &filter.status);
+        conditions.add_opt("goal_space_id = ?", &filter.goal_space_id);
+        conditions.add_opt(
+            "goal_space_id IN (SELECT id FROM goal_spaces WHERE project_id = ?)",
+            &filter.project_id,
+        );
+        conditions.add_opt("model = ?", &filter.model);
+        conditions.add_opt("started_at >= ?", &filter.since);
+        conditions.add_opt("started_at < ?", &filter.until);
+        conditions.add_opt(
+            "(started_at, id) < (SELECT started_at, id FROM agent_runs WHERE id = ?)",
+            &filter.after_id,
+        );
+
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, task_id, goal_space_id, claude_session_id, worktree_path, branch,
+                    status, model, cost_usd, input_tokens, output_tokens, max_budget_usd,
+                    started_at, last_activity_at, finished_at, merge_commit, base_commit,
+                    worktree_ready_ms, failure_reason
+             FROM agent_runs{} ORDER BY started_at DESC, id DESC{}",
+            conditions.sql(),
+            filter.limit_sql()
+        ))?;
+
+        let runs = stmt
+            .query_map(
+                rusqlite::params_from_iter(&conditions.args),
+                agent_run_from_row,
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(runs)
+    }
+
+    /// Get the most recently started agent run for a task
+    pub fn latest_agent_run_for_task(&self, task_id: &str) -> Result<Option<AgentRun>> {
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, goal_space_id, claude_session_id, worktree_path, branch,
+                    status, model, cost_usd, input_tokens, output_tokens, max_budget_usd,
+                    started_at, last_activity_at, finished_at, merge_commit, base_commit,
+                    worktree_ready_ms, failure_reason
+             FROM agent_runs WHERE task_id = ?1
+             ORDER BY started_at DESC LIMIT 1",
+        )?;
+
         let run = stmt
-            .query_row(params![id], |row| {
-                Ok(AgentRun {
-                    id: row.get(0)?,
-                    task_id: row.get(1)?,
-                    goal_space_id: row.get(2)?,
-                    claude_session_id: row.get(3)?,
-                    worktree_path: row.get(4)?,
-                    branch: row.get(5)?,
-                    status: row.get(6)?,
-                    model: row.get(7)?,
-                    cost_usd: row.get(8)?,
-                    input_tokens: row.get(9)?,
-                    output_tokens: row.get(10)?,
-                    max_budget_usd: row.get(11)?,
-                    started_at: row.get(12)?,
-                    last_activity_at: row.get(13)?,
-                    finished_at: row.get(14)?,
-                })
-            })
+            .query_row(params![task_id], agent_run_from_row)
             .optional()?;
 
         Ok(run)
     }
 
-    pub fn list_agent_runs(&self) -> Result<Vec<AgentRun>> {
-        let conn = self.conn();
+    /// Get the most recent agent run of a task that was merged into main
+    pub fn merged_agent_run_for_task(&self, task_id: &str) -> Result<Option<AgentRun>> {
+        let conn = self.read_conn();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, goal_space_id, claude_session_id, worktree_path, branch,
                     status, model, cost_usd, input_tokens, output_tokens, max_budget_usd,
-                    started_at, last_activity_at, finished_at
-             FROM agent_runs ORDER BY started_at DESC",
+                    started_at, last_activity_at, finished_at, merge_commit, base_commit,
+                    worktree_ready_ms, failure_reason
+             FROM agent_runs WHERE task_id = ?1 AND merge_commit IS NOT NULL
+             ORDER BY started_at DESC LIMIT 1",
+        )?;
+
+        let run = stmt
+            .query_row(params![task_id], agent_run_from_row)
+            .optional()?;
+
+        Ok(run)
+    }
+
+    /// List a goal's agent runs that were merged into main and whose work is still
+    /// there: runs of reverted tasks, or whose merge was reverted, are left out
+    pub fn list_merged_agent_runs(&self, goal_space_id: &str) -> Result<Vec<AgentRun>> {
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(
+            "SELECT r.id, r.task_id, r.goal_space_id, r.claude_session_id, r.worktree_path,
+                    r.branch, r.status, r.model, r.cost_usd, r.input_tokens, r.output_tokens,
+                    r.max_budget_usd, r.started_at, r.last_activity_at, r.finished_at,
+                    r.merge_commit, r.base_commit, r.worktree_ready_ms, r.failure_reason
+             FROM agent_runs r JOIN tasks t ON t.id = r.task_id
+             WHERE r.goal_space_id = ?1 AND r.merge_commit IS NOT NULL
+               AND t.status != 'reverted'
+               AND NOT EXISTS (SELECT 1 FROM agent_events e
+                               WHERE e.agent_run_id = r.id AND e.event_type = 'reverted')
+             ORDER BY r.started_at ASC",
         )?;
 
         let runs = stmt
-            .query_map([], |row| {
-                Ok(AgentRun {
-                    id: row.get(0)?,
-                    task_id: row.get(1)?,
-                    goal_space_id: row.get(2)?,
-                    claude_session_id: row.get(3)?,
-                    worktree_path: row.get(4)?,
-                    branch: row.get(5)?,
-                    status: row.get(6)?,
-                    model: row.get(7)?,
-                    cost_usd: row.get(8)?,
-                    input_tokens: row.get(9)?,
-                    output_tokens: row.get(10)?,
-                    max_budget_usd: row.get(11)?,
-                    started_at: row.get(12)?,
-                    last_activity_at: row.get(13)?,
-                    finished_at: row.get(14)?,
-                })
-            })?
+            .query_map(params![goal_space_id], agent_run_from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(runs)
     }
 
     pub fn list_active_agent_runs(&self) -> Result<Vec<AgentRun>> {
-        let conn = self.conn();
+        let conn = self.read_conn();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, goal_space_id, claude_session_id, worktree_path, branch,
                     status, model, cost_usd, input_tokens, output_tokens, max_budget_usd,
-                    started_at, last_activity_at, finished_at
+                    started_at, last_activity_at, finished_at, merge_commit, base_commit,
+                    worktree_ready_ms, failure_reason
              FROM agent_runs WHERE status IN ('spawning', 'running', 'stalled')
              ORDER BY started_at DESC",
         )?;
 
         let runs = stmt
-            .query_map([], |row| {
-                Ok(AgentRun {
-                    id: row.get(0)?,
-                    task_id: row.get(1)?,
-                    goal_space_id: row.get(2)?,
-                    claude_session_id: row.get(3)?,
-                    worktree_path: row.get(4)?,
-                    branch: row.get(5)?,
-                    status: row.get(6)?,
-                    model: row.get(7)?,
-                    cost_usd: row.get(8)?,
-                    input_tokens: row.get(9)?,
-                    output_tokens: row.get(10)?,
-                    max_budget_usd: row.get(11)?,
-                    started_at: row.get(12)?,
-                    last_activity_at: row.get(13)?,
-                    finished_at: row.get(14)?,
-                })
-            })?
+            .query_map([], agent_run_from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(runs)
     }
 
-    pub fn update_agent_run_status(&self, id: &str, status: &str) -> Result<()> {
+    /// Move an agent run to `status`, stamping `finished_at` when it finishes.
+    /// Fails with an `InvalidTransition` if the run state machine doesn't allow it.
+    pub fn update_agent_run_status(&self, id: &str, status: RunStatus) -> Result<()> {
         let conn = self.conn();
         let now = Utc::now().to_rfc3339();
 
-        if status == "done" || status == "failed" || status == "killed" {
+        let current: Option<RunStatus> = conn
+            .query_row(
+                "SELECT status FROM agent_runs WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(current) = current {
+            validate_run_transition(current, status)?;
+        }
+
+        if status.is_terminal() {
             conn.execute(
                 "UPDATE agent_runs SET status = ?1, finished_at = ?2 WHERE id = ?3",
                 params![status, now, id],
@@ -789,6 +1391,60 @@ impl Database {
         Ok(())
     }
 
+    /// Add to a run's cost and token totals in place, without reading them first
+    pub fn add_agent_run_cost(
+        &self,
+        id: &str,
+        cost_usd: f64,
+        input_tokens: i64,
+        output_tokens: i64,
+    ) -> Result<()> {
+        let conn = self.conn();
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "UPDATE agent_runs SET cost_usd = cost_usd + ?1, input_tokens = input_tokens + ?2,
+             output_tokens = output_tokens + ?3, last_activity_at = ?4 WHERE id = ?5",
+            params![cost_usd, input_tokens, output_tokens, now, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_agent_run_merge_commit(&self, id: &str, merge_commit: &str) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "UPDATE agent_runs SET merge_commit = ?1 WHERE id = ?2",
+            params![merge_commit, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_agent_run_base_commit(&self, id: &str, base_commit: &str) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "UPDATE agent_runs SET base_commit = ?1 WHERE id = ?2",
+            params![base_commit, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_agent_run_worktree_ready_ms(&self, id: &str, ready_ms: i64) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "UPDATE agent_runs SET worktree_ready_ms = ?1 WHERE id = ?2",
+            params![ready_ms, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_agent_run_failure_reason(&self, id: &str, reason: &str) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "UPDATE agent_runs SET failure_reason = ?1 WHERE id = ?2",
+            params![reason, id],
+        )?;
+        Ok(())
+    }
+
     pub fn update_agent_run_activity(&self, id: &str) -> Result<()> {
         let conn = self.conn();
         let now = Utc::now().to_rfc3339();
@@ -833,15 +1489,42 @@ impl Database {
         })
     }
 
+    #[allow(dead_code)]
     pub fn list_agent_events(&self, agent_run_id: &str) -> Result<Vec<AgentEvent>> {
-        let conn = self.conn();
-        let mut stmt = conn.prepare(
+        self.list_agent_events_filtered(agent_run_id, &ListFilter::default())
+    }
+
+    /// A run's events, oldest first. Filters: event_type, tool_name, since/until
+    /// on creation time; `after_id` is an event id.
+    pub fn list_agent_events_filtered(
+        &self,
+        agent_run_id: &str,
+        filter: &ListFilter,
+    ) -> Result<Vec<AgentEvent>> {
+        let mut conditions = Conditions::default();
+        conditions.add("agent_run_id = ?", &[agent_run_id]);
+        conditions.add_opt("event_type = ?", &filter.event_type);
+        conditions.add_opt("tool_name = ?", &filter.tool_name);
+        conditions.add_opt("created_at >= ?", &filter.since);
+        conditions.add_opt("created_at < ?", &filter.until);
+        if let Some(after) = &filter.after_id {
+            let after: i64 = after
+                .parse()
+                .map_err(|_| anyhow::anyhow!("after_id must be an event id"))?;
+            conditions.clauses.push("id > ?".to_string());
+            conditions.args.push(after.into());
+        }
+
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(&format!(
             "SELECT id, agent_run_id, event_type, tool_name, summary, raw_json, cost_delta_usd, created_at
-             FROM agent_events WHERE agent_run_id = ?1 ORDER BY id ASC",
-        )?;
+             FROM agent_events{} ORDER BY id ASC{}",
+            conditions.sql(),
+            filter.limit_sql()
+        ))?;
 
         let events = stmt
-            .query_map(params![agent_run_id], |row| {
+            .query_map(rusqlite::params_from_iter(&conditions.args), |row| {
                 Ok(AgentEvent {
                     id: row.get(0)?,
                     agent_run_id: row.get(1)?,
@@ -858,31 +1541,147 @@ impl Database {
         Ok(events)
     }
 
+    /// The task's latest run, if it was rejected in review
+    pub fn latest_rejected_run(&self, task_id: &str) -> Result<Option<AgentRun>> {
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, goal_space_id, claude_session_id, worktree_path, branch,
+                    status, model, cost_usd, input_tokens, output_tokens, max_budget_usd,
+                    started_at, last_activity_at, finished_at, merge_commit, base_commit,
+                    worktree_ready_ms, failure_reason
+             FROM agent_runs r
+             WHERE r.id = (
+                 SELECT id FROM agent_runs WHERE task_id = ?1
+                 ORDER BY started_at DESC LIMIT 1
+             )
+               AND EXISTS (SELECT 1 FROM agent_events e
+                           WHERE e.agent_run_id = r.id AND e.event_type = 'review_rejected')",
+        )?;
+
+        let run = stmt
+            .query_row(params![task_id], agent_run_from_row)
+            .optional()?;
+
+        Ok(run)
+    }
+
+    /// Reviewer feedback from a rejection of the task's latest run, if any.
+    /// Used to brief the follow-up run on what to change. Reads the payload
+    /// back from the archive when the event was compacted, and falls back to
+    /// the summary when the payload is gone.
+    pub fn latest_review_feedback(&self, task_id: &str) -> Result<Option<String>> {
+        let run_id: Option<String> = self
+            .read_conn()
+            .query_row(
+                "SELECT id FROM agent_runs WHERE task_id = ?1 ORDER BY started_at DESC LIMIT 1",
+                params![task_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(run_id) = run_id else {
+            return Ok(None);
+        };
+        let filter = ListFilter {
+            event_type: Some("review_rejected".into()),
+            ..Default::default()
+        };
+        let Some(event) = self.agent_events_with_raw_filtered(&run_id, &filter)?.pop() else {
+            return Ok(None);
+        };
+
+        let feedback = match event.raw_json {
+            Some(json) => serde_json::from_str::<serde_json::Value>(&json)
+                .ok()
+                .and_then(|v| v.get("feedback").and_then(|f| f.as_str()).map(String::from)),
+            None => event
+                .summary
+                .strip_prefix("Rejected in review: ")
+                .map(String::from),
+        };
+        Ok(feedback.filter(|f| !f.is_empty()))
+    }
+
     // ── Goal Space History ──
 
     pub fn insert_goal_history(
         &self,
         goal_space_id: &str,
+        actor: Actor,
         event_type: &str,
         description: &str,
-        metadata: Option<&str>,
+        metadata: Option<serde_json::Value>,
     ) -> Result<()> {
         let conn = self.conn();
         let now = Utc::now().to_rfc3339();
+        let metadata = metadata.map(|m| m.to_string());
 
         conn.execute(
-            "INSERT INTO goal_space_history (goal_space_id, event_type, description, metadata, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![goal_space_id, event_type, description, metadata, now],
+            "INSERT INTO goal_space_history (goal_space_id, event_type, actor, description, metadata, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![goal_space_id, event_type, actor.as_str(), description, metadata, now],
         )?;
 
         Ok(())
     }
 
+    /// A goal's history, oldest first. Filters on `event_type` (comma-separated
+    /// for several), `actor`, `since` and `until`; the cursor is the entry id.
+    pub fn list_goal_history_filtered(
+        &self,
+        goal_space_id: &str,
+        filter: &ListFilter,
+    ) -> Result<Vec<GoalHistoryEntry>> {
+        let mut conditions = Conditions::default();
+        conditions.add("goal_space_id = ?", &[goal_space_id]);
+        if let Some(types) = &filter.event_type {
+            let types: Vec<&str> = types.split(',').map(str::trim).collect();
+            conditions.add(
+                &format!("event_type IN ({})", vec!["?"; types.len()].join(", ")),
+                &types,
+            );
+        }
+        conditions.add_opt("actor = ?", &filter.actor);
+        conditions.add_opt("created_at >= ?", &filter.since);
+        conditions.add_opt("created_at < ?", &filter.until);
+        if let Some(after) = &filter.after_id {
+            let after: i64 = after
+                .parse()
+                .map_err(|_| anyhow::anyhow!("after_id must be a history entry id"))?;
+            conditions.clauses.push("id > ?".to_string());
+            conditions.args.push(after.into());
+        }
+
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, goal_space_id, event_type, actor, description, metadata, created_at
+             FROM goal_space_history{} ORDER BY id ASC{}",
+            conditions.sql(),
+            filter.limit_sql()
+        ))?;
+
+        let entries = stmt
+            .query_map(rusqlite::params_from_iter(&conditions.args), |row| {
+                let actor: String = row.get(3)?;
+                let metadata: Option<String> = row.get(5)?;
+                Ok(GoalHistoryEntry {
+                    id: row.get(0)?,
+                    goal_space_id: row.get(1)?,
+                    event_type: row.get(2)?,
+                    actor: Actor::parse(&actor).unwrap_or(Actor::System),
+                    description: row.get(4)?,
+                    metadata: metadata.and_then(|m| serde_json::from_str(&m).ok()),
+                    created_at: row.get(6)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
     // ── Stats ──
 
     pub fn get_stats(&self) -> Result<Stats> {
-        let conn = self.conn();
+        let conn = self.read_conn();
 
         let active_agents: i64 = conn.query_row(
             "SELECT COUNT(*) FROM agent_runs WHERE status IN ('spawning', 'running', 'stalled')",
@@ -946,7 +1745,7 @@ impl Database {
     }
 
     pub fn list_projects(&self) -> Result<Vec<Project>> {
-        let conn = self.conn();
+        let conn = self.read_conn();
         let mut stmt = conn.prepare(
             "SELECT id, path, display_name, sort_order, settings, created_at, updated_at
              FROM projects ORDER BY sort_order ASC, created_at ASC",
@@ -973,7 +1772,7 @@ impl Database {
     }
 
     pub fn get_project(&self, id: &str) -> Result<Option<Project>> {
-        let conn = self.conn();
+        let conn = self.read_conn();
         let mut stmt = conn.prepare(
             "SELECT id, path, display_name, sort_order, settings, created_at, updated_at
              FROM projects WHERE id = ?1",
@@ -999,6 +1798,33 @@ impl Database {
         Ok(project)
     }
 
+    pub fn get_project_by_path(&self, path: &str) -> Result<Option<Project>> {
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, path, display_name, sort_order, settings, created_at, updated_at
+             FROM projects WHERE path = ?1",
+        )?;
+
+        let project = stmt
+            .query_row(params![path], |row| {
+                let settings_str: String = row.get(4)?;
+                let settings: GoalSettings =
+                    serde_json::from_str(&settings_str).unwrap_or_default();
+                Ok(Project {
+                    id: row.get(0)?,
+                    path: row.get(1)?,
+                    display_name: row.get(2)?,
+                    sort_order: row.get(3)?,
+                    settings,
+                    created_at: row.get(5)?,
+                    updated_at: row.get(6)?,
+                })
+            })
+            .optional()?;
+
+        Ok(project)
+    }
+
     pub fn update_project(&self, id: &str, input: &UpdateProject) -> Result<()> {
         let conn = self.conn();
         let now = Utc::now().to_rfc3339();
@@ -1039,7 +1865,7 @@ impl Database {
     }
 
     pub fn list_goals_by_project(&self, project_id: &str) -> Result<Vec<GoalSpace>> {
-        let conn = self.conn();
+        let conn = self.read_conn();
         let mut stmt = conn.prepare(
             "SELECT id, name, description, status, repo_path, created_at, updated_at, settings
              FROM goal_spaces WHERE project_id = ?1 ORDER BY created_at DESC",
@@ -1066,6 +1892,78 @@ impl Database {
         Ok(goals)
     }
 
+    // ── Deferred Merge Queries ──
+
+    /// Queue a merge, or record another failed attempt if the branch is already queued
+    pub fn upsert_deferred_merge(
+        &self,
+        goal_space_id: &str,
+        task_id: Option<&str>,
+        agent_run_id: Option<&str>,
+        repo_path: &str,
+        branch: &str,
+        reason: &str,
+    ) -> Result<DeferredMerge> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now().to_rfc3339();
+
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO deferred_merges (id, goal_space_id, task_id, agent_run_id, repo_path, branch, reason, attempts, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 1, ?8, ?8)
+             ON CONFLICT(repo_path, branch) DO UPDATE SET
+                reason = excluded.reason, attempts = attempts + 1, updated_at = excluded.updated_at",
+            params![id, goal_space_id, task_id, agent_run_id, repo_path, branch, reason, now],
+        )?;
+
+        let merge = conn.query_row(
+            "SELECT id, goal_space_id, task_id, agent_run_id, repo_path, branch, reason, attempts, created_at, updated_at
+             FROM deferred_merges WHERE repo_path = ?1 AND branch = ?2",
+            params![repo_path, branch],
+            deferred_merge_from_row,
+        )?;
+        Ok(merge)
+    }
+
+    /// List queued merges, oldest first, optionally for one goal space
+    pub fn list_deferred_merges(&self, goal_space_id: Option<&str>) -> Result<Vec<DeferredMerge>> {
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, goal_space_id, task_id, agent_run_id, repo_path, branch, reason, attempts, created_at, updated_at
+             FROM deferred_merges WHERE ?1 IS NULL OR goal_space_id = ?1
+             ORDER BY created_at ASC",
+        )?;
+
+        let merges = stmt
+            .query_map(params![goal_space_id], deferred_merge_from_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(merges)
+    }
+
+    pub fn get_deferred_merge(&self, id: &str) -> Result<Option<DeferredMerge>> {
+        let conn = self.read_conn();
+        let merge = conn
+            .query_row(
+                "SELECT id, goal_space_id, task_id, agent_run_id, repo_path, branch, reason, attempts, created_at, updated_at
+                 FROM deferred_merges WHERE id = ?1",
+                params![id],
+                deferred_merge_from_row,
+            )
+            .optional()?;
+        Ok(merge)
+    }
+
+    /// Remove a queued merge once it has landed or been given up on
+    pub fn delete_deferred_merge(&self, repo_path: &str, branch: &str) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "DELETE FROM deferred_merges WHERE repo_path = ?1 AND branch = ?2",
+            params![repo_path, branch],
+        )?;
+        Ok(())
+    }
+
     // ── Goal Message Queries ──
 
     pub fn create_goal_message(&self, input: &CreateGoalMessage) -> Result<GoalMessage> {
@@ -1091,14 +1989,34 @@ impl Database {
     }
 
     pub fn list_goal_messages(&self, goal_space_id: &str) -> Result<Vec<GoalMessage>> {
-        let conn = self.conn();
-        let mut stmt = conn.prepare(
+        self.list_goal_messages_filtered(goal_space_id, &ListFilter::default())
+    }
+
+    /// A goal's chat messages, oldest first. Filters: since/until on creation time.
+    pub fn list_goal_messages_filtered(
+        &self,
+        goal_space_id: &str,
+        filter: &ListFilter,
+    ) -> Result<Vec<GoalMessage>> {
+        let mut conditions = Conditions::default();
+        conditions.add("goal_space_id = ?", &[goal_space_id]);
+        conditions.add_opt("created_at >= ?", &filter.since);
+        conditions.add_opt("created_at < ?", &filter.until);
+        conditions.add_opt(
+            "(created_at, id) > (SELECT created_at, id FROM goal_messages WHERE id = ?)",
+            &filter.after_id,
+        );
+
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(&format!(
             "SELECT id, goal_space_id, role, content, message_type, metadata_json, created_at
-             FROM goal_messages WHERE goal_space_id = ?1 ORDER BY created_at ASC",
-        )?;
+             FROM goal_messages{} ORDER BY created_at ASC, id ASC{}",
+            conditions.sql(),
+            filter.limit_sql()
+        ))?;
 
         let messages = stmt
-            .query_map(params![goal_space_id], |row| {
+            .query_map(rusqlite::params_from_iter(&conditions.args), |row| {
                 Ok(GoalMessage {
                     id: row.get(0)?,
                     goal_space_id: row.get(1)?,
@@ -1123,12 +2041,151 @@ impl Database {
         )?;
         Ok(())
     }
+
+    // ── Scheduler Queries ──
+
+    pub fn get_scheduler_config(&self) -> Result<SchedulerConfig> {
+        let conn = self.read_conn();
+        let config = conn.query_row(
+            "SELECT total_slots, starvation_secs, updated_at FROM scheduler_config WHERE id = 1",
+            [],
+            |row| {
+                Ok(SchedulerConfig {
+                    total_slots: row.get(0)?,
+                    starvation_secs: row.get(1)?,
+                    updated_at: row.get(2)?,
+                })
+            },
+        )?;
+        Ok(config)
+    }
+
+    pub fn update_scheduler_config(
+        &self,
+        input: &UpdateSchedulerConfig,
+    ) -> Result<SchedulerConfig> {
+        let now = Utc::now().to_rfc3339();
+        {
+            let conn = self.conn();
+            if let Some(total_slots) = input.total_slots {
+                conn.execute(
+                    "UPDATE scheduler_config SET total_slots = ?1, updated_at = ?2 WHERE id = 1",
+                    params![(total_slots > 0).then_some(total_slots), now],
+                )?;
+            }
+            if let Some(starvation_secs) = input.starvation_secs {
+                conn.execute(
+                    "UPDATE scheduler_config SET starvation_secs = ?1, updated_at = ?2 WHERE id = 1",
+                    params![starvation_secs, now],
+                )?;
+            }
+        }
+        self.get_scheduler_config()
+    }
+
+    /// When each goal last started an agent
+    pub fn last_agent_starts(&self) -> Result<HashMap<String, String>> {
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(
+            "SELECT goal_space_id, MAX(started_at) FROM agent_runs GROUP BY goal_space_id",
+        )?;
+        let starts = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+        Ok(starts)
+    }
+}
+
+/// Goals of the given task ids that are not in `goal_space_id`
+fn foreign_task_goals(
+    conn: &rusqlite::Connection,
+    goal_space_id: &str,
+    task_ids: &[&String],
+) -> Result<HashMap<String, String>> {
+    let mut stmt =
+        conn.prepare("SELECT goal_space_id FROM tasks WHERE id = ?1 AND goal_space_id != ?2")?;
+    let mut foreign = HashMap::new();
+    for id in task_ids {
+        if let Some(goal) = stmt
+            .query_row(params![id, goal_space_id], |row| row.get::<_, String>(0))
+            .optional()?
+        {
+            foreign.insert((*id).clone(), goal);
+        }
+    }
+    Ok(foreign)
+}
+
+/// Reject `depends_on` for `task_id` if it would leave the goal unable to finish.
+/// Reads the graph through `conn`, so callers holding the writer see every
+/// committed edit.
+fn check_dependencies(
+    conn: &rusqlite::Connection,
+    goal_space_id: &str,
+    task_id: &str,
+    depends_on: &[String],
+) -> Result<()> {
+    if depends_on.is_empty() {
+        return Ok(());
+    }
+    let mut stmt = conn.prepare("SELECT id, depends_on FROM tasks WHERE goal_space_id = ?1")?;
+    let graph: Vec<(String, Vec<String>)> = stmt
+        .query_map(params![goal_space_id], |row| {
+            let depends_on: String = row.get(1)?;
+            Ok((
+                row.get(0)?,
+                serde_json::from_str(&depends_on).unwrap_or_default(),
+            ))
+        })?
+        .collect::<std::result::Result<_, _>>()?;
+    let unknown: Vec<&String> = depends_on
+        .iter()
+        .filter(|d| !graph.iter().any(|(id, _)| id == *d))
+        .collect();
+    let foreign = foreign_task_goals(conn, goal_space_id, &unknown)?;
+    let problems = dependency_problems(task_id, depends_on, &graph, &foreign);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(InvalidDependencies(problems).into())
+    }
+}
+
+fn deferred_merge_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<DeferredMerge> {
+    Ok(DeferredMerge {
+        id: row.get(0)?,
+        goal_space_id: row.get(1)?,
+        task_id: row.get(2)?,
+        agent_run_id: row.get(3)?,
+        repo_path: row.get(4)?,
+        branch: row.get(5)?,
+        reason: row.get(6)?,
+        attempts: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+    })
+}
+
+// Add the optional() helper for rusqlite
+trait OptionalExt<T> {
+    fn optional(self) -> std::result::Result<Option<T>, rusqlite::Error>;
+}
+
+impl<T> OptionalExt<T> for std::result::Result<T, rusqlite::Error> {
+    fn optional(self) -> std::result::Result<Option<T>, rusqlite::Error> {
+        match self {
+            Ok(val) => Ok(Some(val)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::db::Database;
+    use crate::goal::task::walk_task_to;
 
     fn test_db() -> Database {
         let db = Database::open_in_memory().unwrap();
@@ -1270,7 +2327,7 @@ mod tests {
             .unwrap();
 
         assert_eq!(task.title, "Task 1");
-        assert_eq!(task.status, "pending");
+        assert_eq!(task.status, TaskStatus::Pending);
         assert_eq!(task.priority, 5);
         assert!(task.depends_on.is_empty());
     }
@@ -1387,7 +2444,7 @@ mod tests {
             &task.id,
             &UpdateTask {
                 title: Some("Updated".into()),
-                status: Some("running".into()),
+                status: Some(TaskStatus::Running),
                 description: None,
                 priority: None,
                 depends_on: None,
@@ -1398,7 +2455,7 @@ mod tests {
 
         let updated = db.get_task(&task.id).unwrap().unwrap();
         assert_eq!(updated.title, "Updated");
-        assert_eq!(updated.status, "running");
+        assert_eq!(updated.status, TaskStatus::Running);
     }
 
     #[test]
@@ -1443,6 +2500,133 @@ mod tests {
         assert_eq!(found.depends_on, vec![t1.id]);
     }
 
+    #[test]
+    fn test_task_dependencies_are_validated() {
+        let db = test_db();
+        let goal = |name: &str| {
+            db.create_goal_space(&CreateGoalSpace {
+                name: name.into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap()
+        };
+        let task = |goal_id: &str, title: &str, depends_on: Vec<String>| {
+            db.create_task(
+                goal_id,
+                &CreateTask {
+                    title: title.into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on,
+                    settings: Default::default(),
+                },
+            )
+        };
+        let problems = |e: anyhow::Error| e.downcast::<InvalidDependencies>().unwrap().0;
+
+        let mine = goal("Mine");
+        let other = goal("Other");
+        let foreign = task(&other.id, "Elsewhere", vec![]).unwrap();
+        let a = task(&mine.id, "A", vec![]).unwrap();
+        let b = task(&mine.id, "B", vec![a.id.clone()]).unwrap();
+
+        let err = task(&mine.id, "C", vec![foreign.id.clone(), "missing".into()]).unwrap_err();
+        assert_eq!(problems(err).len(), 2);
+        assert_eq!(db.list_tasks(&mine.id).unwrap().len(), 2);
+
+        // A -> B would close a cycle; nothing in the update is applied
+        let err = db
+            .update_task(
+                &a.id,
+                &UpdateTask {
+                    title: Some("Renamed".into()),
+                    depends_on: Some(vec![b.id.clone()]),
+                    ..Default::default()
+                },
+            )
+            .unwrap_err();
+        assert_eq!(
+            problems(err),
+            vec![GraphProblem::Cycle {
+                task_id: a.id.clone()
+            }]
+        );
+        let unchanged = db.get_task(&a.id).unwrap().unwrap();
+        assert_eq!(unchanged.title, "A");
+        assert!(unchanged.depends_on.is_empty());
+
+        let err = db
+            .update_task(
+                &a.id,
+                &UpdateTask {
+                    depends_on: Some(vec![a.id.clone()]),
+                    ..Default::default()
+                },
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("depends on itself"));
+        assert!(db.goal_graph_problems(&mine.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_dependency_edits_cannot_close_a_cycle() {
+        // File-backed so reads go through the reader pool, as in the server
+        let path = std::env::temp_dir().join(format!("conductor-{}.db", Uuid::new_v4()));
+        let db = Database::open(&path).unwrap();
+        db.run_migrations().unwrap();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let task = |title: &str| {
+            db.create_task(
+                &goal.id,
+                &CreateTask {
+                    title: title.into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap()
+        };
+
+        for _ in 0..50 {
+            let a = task("A");
+            let b = task("B");
+            let barrier = std::sync::Barrier::new(2);
+            let depend = |id: &str, on: &str| {
+                barrier.wait();
+                db.update_task(
+                    id,
+                    &UpdateTask {
+                        depends_on: Some(vec![on.to_string()]),
+                        ..Default::default()
+                    },
+                )
+            };
+            let (ab, ba) = std::thread::scope(|s| {
+                let ab = s.spawn(|| depend(&a.id, &b.id));
+                let ba = s.spawn(|| depend(&b.id, &a.id));
+                (ab.join().unwrap(), ba.join().unwrap())
+            });
+            assert!(ab.is_err() || ba.is_err());
+            assert!(db.goal_graph_problems(&goal.id).unwrap().is_empty());
+        }
+
+        drop(db);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
     #[test]
     fn test_get_unblocked_tasks() {
         let db = test_db();
@@ -1487,18 +2671,7 @@ mod tests {
         assert_eq!(unblocked[0].title, "Independent");
 
         // Mark t1 as done
-        db.update_task(
-            &t1.id,
-            &UpdateTask {
-                status: Some("done".into()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+        walk_task_to(&db, &t1.id, TaskStatus::Done);
 
         // Now t2 is unblocked
         let unblocked = db.get_unblocked_tasks(&goal.id).unwrap();
@@ -1581,18 +2754,7 @@ mod tests {
             )
             .unwrap();
 
-        db.update_task(
-            &t.id,
-            &UpdateTask {
-                status: Some("running".into()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+        walk_task_to(&db, &t.id, TaskStatus::Running);
 
         let unblocked = db.get_unblocked_tasks(&goal.id).unwrap();
         assert!(unblocked.is_empty());
@@ -1635,7 +2797,7 @@ mod tests {
             )
             .unwrap();
 
-        assert_eq!(run.status, "spawning");
+        assert_eq!(run.status, RunStatus::Spawning);
         assert_eq!(run.model, "sonnet");
         assert_eq!(run.cost_usd, 0.0);
         assert_eq!(run.max_budget_usd, Some(5.0));
@@ -1672,15 +2834,27 @@ mod tests {
             .create_agent_run(&task.id, &goal.id, None, None, "sonnet", None)
             .unwrap();
 
-        db.update_agent_run_status(&run.id, "running").unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Running)
+            .unwrap();
         let updated = db.get_agent_run(&run.id).unwrap().unwrap();
-        assert_eq!(updated.status, "running");
+        assert_eq!(updated.status, RunStatus::Running);
         assert!(updated.finished_at.is_none());
 
-        db.update_agent_run_status(&run.id, "done").unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Done)
+            .unwrap();
         let done = db.get_agent_run(&run.id).unwrap().unwrap();
-        assert_eq!(done.status, "done");
+        assert_eq!(done.status, RunStatus::Done);
         assert!(done.finished_at.is_some());
+
+        // A finished run stays finished
+        let err = db
+            .update_agent_run_status(&run.id, RunStatus::Failed)
+            .unwrap_err();
+        assert!(err
+            .downcast_ref::<crate::goal::task::InvalidTransition>()
+            .is_some());
+        let unchanged = db.get_agent_run(&run.id).unwrap().unwrap();
+        assert_eq!(unchanged.status, RunStatus::Done);
     }
 
     #[test]
@@ -1718,6 +2892,41 @@ mod tests {
         assert!(updated.last_activity_at.is_some());
     }
 
+    #[test]
+    fn test_add_agent_run_cost() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let task = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: "T".into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let run = db
+            .create_agent_run(&task.id, &goal.id, None, None, "sonnet", None)
+            .unwrap();
+
+        db.update_agent_run_cost(&run.id, 1.0, 1000, 500).unwrap();
+        db.add_agent_run_cost(&run.id, 0.5, 200, 100).unwrap();
+        let updated = db.get_agent_run(&run.id).unwrap().unwrap();
+        assert!((updated.cost_usd - 1.5).abs() < 1e-9);
+        assert_eq!(updated.input_tokens, 1200);
+        assert_eq!(updated.output_tokens, 600);
+    }
+
     #[test]
     fn test_update_agent_run_session_id() {
         let db = test_db();
@@ -1820,7 +3029,9 @@ mod tests {
             .unwrap();
 
         // r1 is spawning (active), r2 we'll mark done
-        db.update_agent_run_status(&r2.id, "done").unwrap();
+        db.update_agent_run_status(&r2.id, RunStatus::Running)
+            .unwrap();
+        db.update_agent_run_status(&r2.id, RunStatus::Done).unwrap();
 
         let active = db.list_active_agent_runs().unwrap();
         assert_eq!(active.len(), 1);
@@ -1970,24 +3181,14 @@ mod tests {
             .unwrap();
 
         // Mark t1 done
-        db.update_task(
-            &t1.id,
-            &UpdateTask {
-                status: Some("done".into()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+        walk_task_to(&db, &t1.id, TaskStatus::Done);
 
         // Create an active agent
         let run = db
             .create_agent_run(&t2.id, &goal.id, None, None, "sonnet", None)
             .unwrap();
-        db.update_agent_run_status(&run.id, "running").unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Running)
+            .unwrap();
         db.update_agent_run_cost(&run.id, 2.50, 5000, 2000).unwrap();
 
         let stats = db.get_stats().unwrap();
@@ -2059,6 +3260,84 @@ mod tests {
         assert_eq!(count, 1);
     }
 
+    #[test]
+    fn test_list_goal_history_filters_and_pages() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space_by(
+                &CreateGoalSpace {
+                    name: "G".into(),
+                    description: "D".into(),
+                    repo_path: "/tmp".into(),
+                    settings: Default::default(),
+                },
+                Actor::Cli,
+            )
+            .unwrap();
+        db.insert_goal_history(
+            &goal.id,
+            Actor::Ui,
+            "task_updated",
+            "Task 'A' updated",
+            Some(serde_json::json!({"task_id": "a", "changes": {"priority": 2}})),
+        )
+        .unwrap();
+        db.insert_goal_history(&goal.id, Actor::Hook, "task_completed", "done", None)
+            .unwrap();
+
+        let all = db
+            .list_goal_history_filtered(&goal.id, &ListFilter::default())
+            .unwrap();
+        let types: Vec<&str> = all.iter().map(|h| h.event_type.as_str()).collect();
+        assert_eq!(types, vec!["created", "task_updated", "task_completed"]);
+        assert_eq!(all[0].actor, Actor::Cli);
+        assert_eq!(all[1].metadata.as_ref().unwrap()["changes"]["priority"], 2);
+
+        let by_type = db
+            .list_goal_history_filtered(
+                &goal.id,
+                &ListFilter {
+                    event_type: Some("created, task_completed".into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(by_type.len(), 2);
+
+        let by_actor = db
+            .list_goal_history_filtered(
+                &goal.id,
+                &ListFilter {
+                    actor: Some("ui".into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(by_actor.len(), 1);
+        assert_eq!(by_actor[0].event_type, "task_updated");
+
+        let page = db
+            .list_goal_history_filtered(
+                &goal.id,
+                &ListFilter {
+                    after_id: Some(all[0].id.to_string()),
+                    limit: Some(1),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].id, all[1].id);
+
+        let bad_cursor = ListFilter {
+            after_id: Some("nope".into()),
+            ..Default::default()
+        };
+        assert!(db
+            .list_goal_history_filtered(&goal.id, &bad_cursor)
+            .is_err());
+    }
+
     // ── Project tests ──
 
     #[test]
@@ -2290,19 +3569,203 @@ mod tests {
         db.delete_goal_messages(&goal.id).unwrap();
         assert!(db.list_goal_messages(&goal.id).unwrap().is_empty());
     }
-}
 
-// Add the optional() helper for rusqlite
-trait OptionalExt<T> {
-    fn optional(self) -> std::result::Result<Option<T>, rusqlite::Error>;
-}
+    #[test]
+    fn test_list_agent_runs_pages_and_filters() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let task = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: "T".into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        for model in ["sonnet", "opus", "sonnet", "sonnet", "opus"] {
+            db.create_agent_run(&task.id, &goal.id, None, None, model, None)
+                .unwrap();
+        }
+        let all = db.list_agent_runs().unwrap();
+        assert_eq!(all.len(), 5);
+
+        // Walking pages of two visits every run once, in list order
+        let mut seen = Vec::new();
+        let mut filter = ListFilter {
+            limit: Some(2),
+            ..Default::default()
+        };
+        loop {
+            let page = db.list_agent_runs_filtered(&filter).unwrap();
+            seen.extend(page.iter().map(|r| r.id.clone()));
+            if page.len() < 2 {
+                break;
+            }
+            filter.after_id = page.last().map(|r| r.id.clone());
+        }
+        let expected: Vec<String> = all.iter().map(|r| r.id.clone()).collect();
+        assert_eq!(seen, expected);
 
-impl<T> OptionalExt<T> for std::result::Result<T, rusqlite::Error> {
-    fn optional(self) -> std::result::Result<Option<T>, rusqlite::Error> {
-        match self {
-            Ok(val) => Ok(Some(val)),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(e),
+        let opus = db
+            .list_agent_runs_filtered(&ListFilter {
+                model: Some("opus".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(opus.len(), 2);
+        let elsewhere = db
+            .list_agent_runs_filtered(&ListFilter {
+                goal_space_id: Some("other".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(elsewhere.is_empty());
+        let future = db
+            .list_agent_runs_filtered(&ListFilter {
+                since: Some("2999-01-01".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(future.is_empty());
+    }
+
+    #[test]
+    fn test_list_agent_events_filters_by_type_and_tool() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let task = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: "T".into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let run = db
+            .create_agent_run(&task.id, &goal.id, None, None, "sonnet", None)
+            .unwrap();
+        for (event_type, tool) in [
+            ("text_output", None),
+            ("tool_call", Some("Bash")),
+            ("tool_call", Some("Edit")),
+            ("tool_call", Some("Bash")),
+        ] {
+            db.insert_agent_event(&run.id, event_type, tool, "s", None, None)
+                .unwrap();
+        }
+
+        let calls = db
+            .list_agent_events_filtered(
+                &run.id,
+                &ListFilter {
+                    event_type: Some("tool_call".into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(calls.len(), 3);
+
+        let bash = db
+            .list_agent_events_filtered(
+                &run.id,
+                &ListFilter {
+                    tool_name: Some("Bash".into()),
+                    after_id: Some(calls[0].id.to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(bash.len(), 1);
+        assert_eq!(bash[0].id, calls[2].id);
+
+        let bad_cursor = ListFilter {
+            after_id: Some("abc".into()),
+            ..Default::default()
+        };
+        assert!(db.list_agent_events_filtered(&run.id, &bad_cursor).is_err());
+    }
+
+    #[test]
+    fn test_list_goal_spaces_and_messages_paginate() {
+        let db = test_db();
+        let mut ids = Vec::new();
+        for name in ["a", "b", "c"] {
+            let goal = db
+                .create_goal_space(&CreateGoalSpace {
+                    name: name.into(),
+                    description: "D".into(),
+                    repo_path: "/tmp".into(),
+                    settings: Default::default(),
+                })
+                .unwrap();
+            ids.push(goal.id);
+        }
+        db.update_goal_space(&ids[1], None, None, Some("completed"))
+            .unwrap();
+
+        let all = db.list_goal_spaces().unwrap();
+        let page = db
+            .list_goal_spaces_filtered(&ListFilter {
+                after_id: Some(all[0].id.clone()),
+                limit: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].id, all[1].id);
+
+        let completed = db
+            .list_goal_spaces_filtered(&ListFilter {
+                status: Some("completed".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].id, ids[1]);
+
+        for content in ["one", "two", "three"] {
+            db.create_goal_message(&CreateGoalMessage {
+                goal_space_id: ids[0].clone(),
+                role: "user".into(),
+                content: content.into(),
+                message_type: "text".into(),
+                metadata_json: "{}".into(),
+            })
+            .unwrap();
         }
+        let messages = db.list_goal_messages(&ids[0]).unwrap();
+        let rest = db
+            .list_goal_messages_filtered(
+                &ids[0],
+                &ListFilter {
+                    after_id: Some(messages[0].id.clone()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let contents: Vec<&str> = rest.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["two", "three"]);
     }
 }
@@ -0,0 +1,580 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{params, params_from_iter};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use super::queries::{AgentEvent, ListFilter};
+use super::Database;
+
+/// Which agent events a compaction pass touches. Events of runs that are still
+/// active are never touched.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Only events created more than this many days ago
+    pub older_than_days: u32,
+    /// Only events of goals in one of these statuses (any status if empty)
+    pub goal_statuses: Vec<String>,
+    /// Only events of these types (any type if empty)
+    pub event_types: Vec<String>,
+    /// Delete matching events outright instead of archiving their raw payloads
+    pub delete: bool,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct CompactionReport {
+    pub runs: usize,
+    pub events_archived: usize,
+    pub events_deleted: usize,
+    /// Uncompressed size of the raw payloads moved out of (or dropped from) the database
+    pub raw_bytes: u64,
+}
+
+impl std::fmt::Display for CompactionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} run(s): {} event(s) archived, {} deleted, {} of raw payloads",
+            self.runs,
+            self.events_archived,
+            self.events_deleted,
+            format_bytes(self.raw_bytes)
+        )
+    }
+}
+
+/// Space used by one goal's agent events
+#[derive(Debug, serde::Serialize)]
+pub struct GoalStorage {
+    pub goal_space_id: String,
+    pub name: String,
+    pub status: String,
+    pub runs: i64,
+    pub events: i64,
+    pub archived_events: i64,
+    pub summary_bytes: i64,
+    pub raw_bytes: i64,
+    pub archive_bytes: u64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct StorageStats {
+    pub database_bytes: u64,
+    pub goals: Vec<GoalStorage>,
+}
+
+/// Archive file holding the compacted raw payloads of one run
+pub fn archive_path(dir: &Path, agent_run_id: &str) -> PathBuf {
+    dir.join(format!("{}.jsonl.gz", agent_run_id))
+}
+
+/// Event id and raw payload selected by a compaction pass
+type MatchedEvent = (i64, Option<String>);
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ArchivedPayload {
+    id: i64,
+    raw_json: String,
+}
+
+/// Append payloads to a run's archive as a new gzip member, so earlier
+/// compactions of the same run stay readable.
+fn append_archive(dir: &Path, agent_run_id: &str, payloads: &[(i64, String)]) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = archive_path(dir, agent_run_id);
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    for (id, raw_json) in payloads {
+        let line = serde_json::to_string(&ArchivedPayload {
+            id: *id,
+            raw_json: raw_json.clone(),
+        })?;
+        encoder.write_all(line.as_bytes())?;
+        encoder.write_all(b"\n")?;
+    }
+    encoder.finish()?.sync_all()?;
+    Ok(())
+}
+
+/// Raw payloads archived for a run, keyed by event id
+pub fn read_archive(dir: &Path, agent_run_id: &str) -> Result<HashMap<i64, String>> {
+    let path = archive_path(dir, agent_run_id);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let file =
+        std::fs::File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut payloads = HashMap::new();
+    for line in BufReader::new(MultiGzDecoder::new(file)).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let payload: ArchivedPayload = serde_json::from_str(&line)
+            .with_context(|| format!("Corrupt archive {}", path.display()))?;
+        payloads.insert(payload.id, payload.raw_json);
+    }
+    Ok(payloads)
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+impl Database {
+    /// Archive (or delete) the events matching `policy`. With `dry_run` only
+    /// reports what would change.
+    ///
+    /// Archived payloads are written and synced before their rows are
+    /// cleared, so an interrupted pass never loses data.
+    pub fn compact_events(
+        &self,
+        policy: &RetentionPolicy,
+        dry_run: bool,
+    ) -> Result<CompactionReport> {
+        let archive_dir = match (policy.delete, self.archive_dir()) {
+            (true, _) => None,
+            (false, Some(dir)) => Some(dir.to_path_buf()),
+            (false, None) => anyhow::bail!("No archive directory configured for this database"),
+        };
+
+        let cutoff =
+            (Utc::now() - chrono::Duration::days(policy.older_than_days as i64)).to_rfc3339();
+        let mut sql = String::from(
+            "SELECT e.id, e.agent_run_id, e.raw_json
+             FROM agent_events e
+             JOIN agent_runs r ON r.id = e.agent_run_id
+             JOIN goal_spaces g ON g.id = r.goal_space_id
+             WHERE e.created_at < ?
+               AND r.status NOT IN ('spawning', 'running', 'stalled')",
+        );
+        let mut args = vec![cutoff];
+        for (column, values) in [
+            ("g.status", &policy.goal_statuses),
+            ("e.event_type", &policy.event_types),
+        ] {
+            if !values.is_empty() {
+                let placeholders = vec!["?"; values.len()].join(", ");
+                sql.push_str(&format!(" AND {} IN ({})", column, placeholders));
+                args.extend(values.iter().cloned());
+            }
+        }
+        if !policy.delete {
+            sql.push_str(" AND e.raw_json IS NOT NULL");
+        }
+        sql.push_str(" ORDER BY e.agent_run_id, e.id");
+
+        // Group matching events by run
+        let mut by_run: Vec<(String, Vec<MatchedEvent>)> = Vec::new();
+        {
+            let conn = self.read_conn();
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt.query_map(params_from_iter(args.iter()), |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?;
+            for row in rows {
+                let (id, run_id, raw_json) = row?;
+                match by_run.last_mut() {
+                    Some((last, events)) if *last == run_id => events.push((id, raw_json)),
+                    _ => by_run.push((run_id, vec![(id, raw_json)])),
+                }
+            }
+        }
+
+        let mut report = CompactionReport {
+            runs: by_run.len(),
+            ..Default::default()
+        };
+        for (run_id, events) in &by_run {
+            report.raw_bytes += events
+                .iter()
+                .map(|(_, raw)| raw.as_ref().map_or(0, |r| r.len() as u64))
+                .sum::<u64>();
+            if policy.delete {
+                report.events_deleted += events.len();
+            } else {
+                report.events_archived += events.len();
+            }
+            if dry_run {
+                continue;
+            }
+
+            if let Some(dir) = &archive_dir {
+                let payloads: Vec<(i64, String)> = events
+                    .iter()
+                    .filter_map(|(id, raw)| raw.clone().map(|r| (*id, r)))
+                    .collect();
+                append_archive(dir, run_id, &payloads)?;
+            }
+
+            let conn = self.conn();
+            let tx = conn.unchecked_transaction()?;
+            {
+                let mut stmt = if policy.delete {
+                    tx.prepare("DELETE FROM agent_events WHERE id = ?1")?
+                } else {
+                    tx.prepare(
+                        "UPDATE agent_events SET raw_json = NULL, raw_archived = 1 WHERE id = ?1",
+                    )?
+                };
+                for (id, _) in events {
+                    stmt.execute(params![id])?;
+                }
+            }
+            tx.commit()?;
+        }
+
+        Ok(report)
+    }
+
+    /// A run's events with compacted raw payloads restored from its archive.
+    pub fn agent_events_with_raw(&self, agent_run_id: &str) -> Result<Vec<AgentEvent>> {
+        self.agent_events_with_raw_filtered(agent_run_id, &ListFilter::default())
+    }
+
+    /// A filtered page of `agent_events_with_raw`
+    pub fn agent_events_with_raw_filtered(
+        &self,
+        agent_run_id: &str,
+        filter: &ListFilter,
+    ) -> Result<Vec<AgentEvent>> {
+        let mut events = self.list_agent_events_filtered(agent_run_id, filter)?;
+        let archived: i64 = self.read_conn().query_row(
+            "SELECT COUNT(*) FROM agent_events WHERE agent_run_id = ?1 AND raw_archived = 1",
+            params![agent_run_id],
+            |row| row.get(0),
+        )?;
+        if archived == 0 {
+            return Ok(events);
+        }
+        let Some(dir) = self.archive_dir() else {
+            return Ok(events);
+        };
+
+        let mut payloads = read_archive(dir, agent_run_id)?;
+        for event in events.iter_mut().filter(|e| e.raw_json.is_none()) {
+            event.raw_json = payloads.remove(&event.id);
+        }
+        Ok(events)
+    }
+
+    /// Space used by agent events, per goal, largest first
+    pub fn storage_stats(&self) -> Result<StorageStats> {
+        let conn = self.read_conn();
+        let database_bytes: i64 = conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = conn.prepare(
+            "SELECT g.id, g.name, g.status,
+                    COUNT(DISTINCT r.id), COUNT(e.id), COALESCE(SUM(e.raw_archived), 0),
+                    COALESCE(SUM(LENGTH(e.summary)), 0), COALESCE(SUM(LENGTH(e.raw_json)), 0)
+             FROM goal_spaces g
+             LEFT JOIN agent_runs r ON r.goal_space_id = g.id
+             LEFT JOIN agent_events e ON e.agent_run_id = r.id
+             GROUP BY g.id
+             ORDER BY 8 DESC, g.name ASC",
+        )?;
+        let mut goals = stmt
+            .query_map([], |row| {
+                Ok(GoalStorage {
+                    goal_space_id: row.get(0)?,
+                    name: row.get(1)?,
+                    status: row.get(2)?,
+                    runs: row.get(3)?,
+                    events: row.get(4)?,
+                    archived_events: row.get(5)?,
+                    summary_bytes: row.get(6)?,
+                    raw_bytes: row.get(7)?,
+                    archive_bytes: 0,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        if let Some(dir) = self.archive_dir() {
+            let mut runs = conn.prepare("SELECT id, goal_space_id FROM agent_runs")?;
+            let runs = runs
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            for (run_id, goal_id) in runs {
+                let Ok(meta) = std::fs::metadata(archive_path(dir, &run_id)) else {
+                    continue;
+                };
+                if let Some(goal) = goals.iter_mut().find(|g| g.goal_space_id == goal_id) {
+                    goal.archive_bytes += meta.len();
+                }
+            }
+        }
+
+        Ok(StorageStats {
+            database_bytes: database_bytes as u64,
+            goals,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{CreateGoalSpace, CreateTask};
+    use crate::goal::task::RunStatus;
+
+    struct Fixture {
+        db: Database,
+        goal_id: String,
+        run_id: String,
+        dir: PathBuf,
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn fixture() -> Fixture {
+        let dir = std::env::temp_dir().join(format!("conductor-archive-{}", uuid::Uuid::new_v4()));
+        let db = Database::open_in_memory().unwrap().with_archive_dir(&dir);
+        db.run_migrations().unwrap();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "Goal".into(),
+                description: "desc".into(),
+                repo_path: "/tmp/repo".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let task = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: "Task".into(),
+                    description: "desc".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let run = db
+            .create_agent_run(&task.id, &goal.id, None, None, "sonnet", None)
+            .unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Running)
+            .unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Done)
+            .unwrap();
+
+        for (event_type, raw) in [
+            ("text_output", r#"{"type":"assistant","text":"hello"}"#),
+            ("tool_call", r#"{"type":"tool_use","name":"Read"}"#),
+            ("result", r#"{"type":"result","result":"done"}"#),
+        ] {
+            db.insert_agent_event(&run.id, event_type, None, event_type, Some(raw), None)
+                .unwrap();
+        }
+        // Age every event past the retention window
+        db.conn()
+            .execute(
+                "UPDATE agent_events SET created_at = '2000-01-01T00:00:00+00:00'",
+                [],
+            )
+            .unwrap();
+
+        Fixture {
+            db,
+            goal_id: goal.id,
+            run_id: run.id,
+            dir,
+        }
+    }
+
+    fn policy(days: u32) -> RetentionPolicy {
+        RetentionPolicy {
+            older_than_days: days,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compaction_moves_raw_payloads_into_archive() {
+        let f = fixture();
+
+        let report = f.db.compact_events(&policy(30), false).unwrap();
+        assert_eq!(report.runs, 1);
+        assert_eq!(report.events_archived, 3);
+
+        let stored = f.db.list_agent_events(&f.run_id).unwrap();
+        assert_eq!(stored.len(), 3);
+        assert!(stored.iter().all(|e| e.raw_json.is_none()));
+        assert_eq!(stored[2].summary, "result");
+
+        let restored = f.db.agent_events_with_raw(&f.run_id).unwrap();
+        assert_eq!(
+            restored[2].raw_json.as_deref(),
+            Some(r#"{"type":"result","result":"done"}"#)
+        );
+
+        // Nothing left to compact
+        let again = f.db.compact_events(&policy(30), false).unwrap();
+        assert_eq!(again.events_archived, 0);
+    }
+
+    #[test]
+    fn review_feedback_survives_compaction() {
+        let f = fixture();
+        let task_id = f.db.get_agent_run(&f.run_id).unwrap().unwrap().task_id;
+        f.db.insert_agent_event(
+            &f.run_id,
+            "review_rejected",
+            None,
+            "Rejected in review: hash the passwords",
+            Some(r#"{"feedback":"hash the passwords"}"#),
+            None,
+        )
+        .unwrap();
+        f.db.conn()
+            .execute(
+                "UPDATE agent_events SET created_at = '2000-01-01T00:00:00+00:00'",
+                [],
+            )
+            .unwrap();
+
+        f.db.compact_events(&policy(30), false).unwrap();
+        assert_eq!(
+            f.db.latest_review_feedback(&task_id).unwrap().as_deref(),
+            Some("hash the passwords")
+        );
+
+        // Without the archive the summary still carries it
+        std::fs::remove_dir_all(&f.dir).unwrap();
+        assert_eq!(
+            f.db.latest_review_feedback(&task_id).unwrap().as_deref(),
+            Some("hash the passwords")
+        );
+    }
+
+    #[test]
+    fn repeated_compactions_append_to_the_archive() {
+        let f = fixture();
+        let only = |t: &str| RetentionPolicy {
+            event_types: vec![t.to_string()],
+            ..policy(30)
+        };
+
+        f.db.compact_events(&only("text_output"), false).unwrap();
+        f.db.compact_events(&only("tool_call"), false).unwrap();
+
+        let restored = f.db.agent_events_with_raw(&f.run_id).unwrap();
+        assert!(restored.iter().all(|e| e.raw_json.is_some()));
+        assert_eq!(read_archive(&f.dir, &f.run_id).unwrap().len(), 2);
+        // The result payload was never compacted
+        assert!(f.db.list_agent_events(&f.run_id).unwrap()[2]
+            .raw_json
+            .is_some());
+    }
+
+    #[test]
+    fn policy_filters_by_age_goal_status_and_dry_run() {
+        let f = fixture();
+
+        // Events are old, but the window is wider than their age
+        let recent = f.db.compact_events(&policy(365 * 100), false).unwrap();
+        assert_eq!(recent.events_archived, 0);
+
+        let completed_only = RetentionPolicy {
+            goal_statuses: vec!["completed".into()],
+            ..policy(30)
+        };
+        assert_eq!(
+            f.db.compact_events(&completed_only, false)
+                .unwrap()
+                .events_archived,
+            0
+        );
+
+        f.db.update_goal_space(&f.goal_id, None, None, Some("completed"))
+            .unwrap();
+        let preview = f.db.compact_events(&completed_only, true).unwrap();
+        assert_eq!(preview.events_archived, 3);
+        assert!(f.db.list_agent_events(&f.run_id).unwrap()[0]
+            .raw_json
+            .is_some());
+        assert!(!archive_path(&f.dir, &f.run_id).exists());
+    }
+
+    #[test]
+    fn delete_policy_drops_events_and_active_runs_are_skipped() {
+        let f = fixture();
+        let delete_text = RetentionPolicy {
+            event_types: vec!["text_output".into()],
+            delete: true,
+            ..policy(30)
+        };
+
+        // Finished runs don't restart, so put the run back by hand
+        f.db.conn()
+            .execute(
+                "UPDATE agent_runs SET status = 'running' WHERE id = ?1",
+                [&f.run_id],
+            )
+            .unwrap();
+        assert_eq!(f.db.compact_events(&delete_text, false).unwrap().runs, 0);
+
+        f.db.update_agent_run_status(&f.run_id, RunStatus::Done)
+            .unwrap();
+        let report = f.db.compact_events(&delete_text, false).unwrap();
+        assert_eq!(report.events_deleted, 1);
+        let left: Vec<String> =
+            f.db.list_agent_events(&f.run_id)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.event_type)
+                .collect();
+        assert_eq!(left, vec!["tool_call", "result"]);
+    }
+
+    #[test]
+    fn storage_stats_reports_per_goal_usage() {
+        let f = fixture();
+        let before = f.db.storage_stats().unwrap();
+        let goal = &before.goals[0];
+        assert_eq!(goal.events, 3);
+        assert!(goal.raw_bytes > 0);
+        assert_eq!(goal.archive_bytes, 0);
+
+        f.db.compact_events(&policy(30), false).unwrap();
+        let after = f.db.storage_stats().unwrap();
+        let goal = &after.goals[0];
+        assert_eq!(goal.raw_bytes, 0);
+        assert_eq!(goal.archived_events, 3);
+        assert!(goal.archive_bytes > 0);
+        assert!(after.database_bytes > 0);
+    }
+}
@@ -1,7 +1,194 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use rusqlite::Connection;
 
-pub fn run_migrations(conn: &Connection) -> Result<()> {
+/// A numbered schema change. Migrations are applied in order, each inside its
+/// own transaction, and recorded in the `schema_version` table.
+///
+/// Every migration must be safe to run against a database that already has
+/// its change: databases created before `schema_version` existed were
+/// upgraded by ad-hoc checks and are adopted by replaying the whole list.
+#[derive(Debug)]
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    apply: fn(&Connection) -> Result<()>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_tables",
+        apply: initial_tables,
+    },
+    Migration {
+        version: 2,
+        name: "goal_and_task_settings",
+        apply: goal_and_task_settings,
+    },
+    Migration {
+        version: 3,
+        name: "projects",
+        apply: projects,
+    },
+    Migration {
+        version: 4,
+        name: "project_settings",
+        apply: project_settings,
+    },
+    Migration {
+        version: 5,
+        name: "goal_messages",
+        apply: goal_messages,
+    },
+    Migration {
+        version: 6,
+        name: "agent_run_merge_commit",
+        apply: agent_run_merge_commit,
+    },
+    Migration {
+        version: 7,
+        name: "agent_run_base_commit",
+        apply: agent_run_base_commit,
+    },
+    Migration {
+        version: 8,
+        name: "agent_run_worktree_ready_ms",
+        apply: agent_run_worktree_ready_ms,
+    },
+    Migration {
+        version: 9,
+        name: "agent_run_failure_reason",
+        apply: agent_run_failure_reason,
+    },
+    Migration {
+        version: 10,
+        name: "deferred_merges",
+        apply: deferred_merges,
+    },
+    Migration {
+        version: 11,
+        name: "agent_event_raw_archived",
+        apply: agent_event_raw_archived,
+    },
+    Migration {
+        version: 12,
+        name: "search_index",
+        apply: search_index,
+    },
+    Migration {
+        version: 13,
+        name: "list_indexes",
+        apply: list_indexes,
+    },
+    Migration {
+        version: 14,
+        name: "goal_history_actor",
+        apply: goal_history_actor,
+    },
+    Migration {
+        version: 15,
+        name: "scheduler_config",
+        apply: scheduler_config,
+    },
+    Migration {
+        version: 16,
+        name: "jobs",
+        apply: jobs,
+    },
+];
+
+/// The newest schema version this binary knows how to use.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// The highest applied schema version, or 0 for a new (or pre-versioning) database.
+pub fn current_version(conn: &Connection) -> Result<u32> {
+    ensure_version_table(conn)?;
+    let version: Option<u32> =
+        conn.query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+            row.get(0)
+        })?;
+    Ok(version.unwrap_or(0))
+}
+
+/// Migrations that have not been applied yet, in the order they would run.
+/// Fails if the database was written by a newer binary.
+pub fn pending_migrations(conn: &Connection) -> Result<Vec<&'static Migration>> {
+    let current = current_version(conn)?;
+    check_supported(current)?;
+    Ok(MIGRATIONS.iter().filter(|m| m.version > current).collect())
+}
+
+/// Apply all pending migrations and return the ones that ran.
+pub fn run_migrations(conn: &Connection) -> Result<Vec<&'static Migration>> {
+    let pending = pending_migrations(conn)?;
+    for migration in &pending {
+        let tx = conn.unchecked_transaction()?;
+        (migration.apply)(&tx).with_context(|| {
+            format!(
+                "Migration {} ({}) failed",
+                migration.version, migration.name
+            )
+        })?;
+        tx.execute(
+            "INSERT INTO schema_version (version, name, applied_at) VALUES (?1, ?2, ?3)",
+            rusqlite::params![
+                migration.version,
+                migration.name,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+        tx.commit()?;
+    }
+    Ok(pending)
+}
+
+fn check_supported(current: u32) -> Result<()> {
+    let latest = latest_version();
+    if current > latest {
+        anyhow::bail!(
+            "Database schema version {} is newer than this binary supports ({}); upgrade conductor before opening it",
+            current,
+            latest
+        );
+    }
+    Ok(())
+}
+
+fn ensure_version_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        );
+        ",
+    )?;
+    Ok(())
+}
+
+fn columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let names = conn
+        .prepare(&format!("PRAGMA table_info({})", table))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(names)
+}
+
+/// Add a column unless it already exists.
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    if !columns(conn, table)?.iter().any(|c| c == column) {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+fn initial_tables(conn: &Connection) -> Result<()> {
     conn.execute_batch(
         "
         CREATE TABLE IF NOT EXISTS goal_spaces (
@@ -73,35 +260,20 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         CREATE INDEX IF NOT EXISTS idx_goal_history_goal ON goal_space_history(goal_space_id);
         ",
     )?;
+    Ok(())
+}
 
-    // Migration: Add settings column to goal_spaces table
-    // This is safe to run multiple times - it will only add the column if it doesn't exist
-    let table_info: Vec<String> = conn
-        .prepare("PRAGMA table_info(goal_spaces)")?
-        .query_map([], |row| row.get::<_, String>(1))?
-        .collect::<std::result::Result<Vec<_>, _>>()?;
-
-    if !table_info.contains(&"settings".to_string()) {
-        conn.execute(
-            "ALTER TABLE goal_spaces ADD COLUMN settings TEXT NOT NULL DEFAULT '{}'",
-            [],
-        )?;
-    }
-
-    // Migration: Add settings column to tasks table
-    let task_info: Vec<String> = conn
-        .prepare("PRAGMA table_info(tasks)")?
-        .query_map([], |row| row.get::<_, String>(1))?
-        .collect::<std::result::Result<Vec<_>, _>>()?;
-
-    if !task_info.contains(&"settings".to_string()) {
-        conn.execute(
-            "ALTER TABLE tasks ADD COLUMN settings TEXT NOT NULL DEFAULT '{}'",
-            [],
-        )?;
-    }
+fn goal_and_task_settings(conn: &Connection) -> Result<()> {
+    add_column(
+        conn,
+        "goal_spaces",
+        "settings",
+        "TEXT NOT NULL DEFAULT '{}'",
+    )?;
+    add_column(conn, "tasks", "settings", "TEXT NOT NULL DEFAULT '{}'")
+}
 
-    // Migration: Add projects table
+fn projects(conn: &Connection) -> Result<()> {
     conn.execute_batch(
         "
         CREATE TABLE IF NOT EXISTS projects (
@@ -115,14 +287,10 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         ",
     )?;
 
-    // Migration: Add project_id column to goal_spaces
-    // Re-read table_info since we may have altered it above
-    let gs_info: Vec<String> = conn
-        .prepare("PRAGMA table_info(goal_spaces)")?
-        .query_map([], |row| row.get::<_, String>(1))?
-        .collect::<std::result::Result<Vec<_>, _>>()?;
-
-    if !gs_info.contains(&"project_id".to_string()) {
+    if !columns(conn, "goal_spaces")?
+        .iter()
+        .any(|c| c == "project_id")
+    {
         conn.execute("ALTER TABLE goal_spaces ADD COLUMN project_id TEXT", [])?;
 
         // Auto-populate projects from existing goal_spaces.repo_path DISTINCT values
@@ -143,33 +311,26 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
                 .to_string();
 
             conn.execute(
-                "INSERT OR IGNORE INTO projects (id, path, display_name, sort_order, created_at, updated_at)
-                 VALUES (?1, ?2, ?3, 0, ?4, ?5)",
-                rusqlite::params![project_id, path, display_name, now, now],
-            )?;
+            "INSERT OR IGNORE INTO projects (id, path, display_name, sort_order, created_at, updated_at)
+             VALUES (?1, ?2, ?3, 0, ?4, ?5)",
+            rusqlite::params![project_id, path, display_name, now, now],
+        )?;
 
             // Backfill project_id on goal_spaces
             conn.execute(
-                "UPDATE goal_spaces SET project_id = ?1 WHERE repo_path = ?2 AND project_id IS NULL",
-                rusqlite::params![project_id, path],
-            )?;
+            "UPDATE goal_spaces SET project_id = ?1 WHERE repo_path = ?2 AND project_id IS NULL",
+            rusqlite::params![project_id, path],
+        )?;
         }
     }
+    Ok(())
+}
 
-    // Migration: Add settings column to projects table
-    let proj_info: Vec<String> = conn
-        .prepare("PRAGMA table_info(projects)")?
-        .query_map([], |row| row.get::<_, String>(1))?
-        .collect::<std::result::Result<Vec<_>, _>>()?;
-
-    if !proj_info.is_empty() && !proj_info.contains(&"settings".to_string()) {
-        conn.execute(
-            "ALTER TABLE projects ADD COLUMN settings TEXT NOT NULL DEFAULT '{}'",
-            [],
-        )?;
-    }
+fn project_settings(conn: &Connection) -> Result<()> {
+    add_column(conn, "projects", "settings", "TEXT NOT NULL DEFAULT '{}'")
+}
 
-    // Migration: Add goal_messages table
+fn goal_messages(conn: &Connection) -> Result<()> {
     conn.execute_batch(
         "
         CREATE TABLE IF NOT EXISTS goal_messages (
@@ -185,6 +346,300 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         CREATE INDEX IF NOT EXISTS idx_goal_messages_goal ON goal_messages(goal_space_id);
         ",
     )?;
+    Ok(())
+}
+
+fn agent_run_merge_commit(conn: &Connection) -> Result<()> {
+    add_column(conn, "agent_runs", "merge_commit", "TEXT")
+}
+
+fn agent_run_base_commit(conn: &Connection) -> Result<()> {
+    add_column(conn, "agent_runs", "base_commit", "TEXT")
+}
+
+fn agent_run_worktree_ready_ms(conn: &Connection) -> Result<()> {
+    add_column(conn, "agent_runs", "worktree_ready_ms", "INTEGER")
+}
+
+fn agent_run_failure_reason(conn: &Connection) -> Result<()> {
+    add_column(conn, "agent_runs", "failure_reason", "TEXT")
+}
+
+fn deferred_merges(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS deferred_merges (
+            id TEXT PRIMARY KEY,
+            goal_space_id TEXT NOT NULL REFERENCES goal_spaces(id),
+            task_id TEXT,
+            agent_run_id TEXT,
+            repo_path TEXT NOT NULL,
+            branch TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            UNIQUE(repo_path, branch)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_deferred_merges_goal ON deferred_merges(goal_space_id);
+        ",
+    )?;
+    Ok(())
+}
 
+fn agent_event_raw_archived(conn: &Connection) -> Result<()> {
+    add_column(
+        conn,
+        "agent_events",
+        "raw_archived",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_agent_events_created ON agent_events(created_at);",
+    )?;
     Ok(())
 }
+
+/// FTS5 indexes for search, kept current by triggers.
+///
+/// Agent events use an external-content index keyed on their integer id, so
+/// the text is not stored twice. Compaction nulls `raw_json` through the update
+/// trigger, so archived payloads drop out of the index and only the summary
+/// stays searchable. Tasks and goal messages have text ids (and
+/// rowids VACUUM may renumber), so their indexes store their own copy and
+/// carry the id as an unindexed column.
+fn search_index(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE VIRTUAL TABLE IF NOT EXISTS agent_events_fts USING fts5(
+            summary, raw_json,
+            content='agent_events', content_rowid='id',
+            tokenize='porter unicode61'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS agent_events_fts_insert AFTER INSERT ON agent_events BEGIN
+            INSERT INTO agent_events_fts(rowid, summary, raw_json)
+            VALUES (new.id, new.summary, new.raw_json);
+        END;
+        CREATE TRIGGER IF NOT EXISTS agent_events_fts_delete AFTER DELETE ON agent_events BEGIN
+            INSERT INTO agent_events_fts(agent_events_fts, rowid, summary, raw_json)
+            VALUES ('delete', old.id, old.summary, old.raw_json);
+        END;
+        CREATE TRIGGER IF NOT EXISTS agent_events_fts_update AFTER UPDATE ON agent_events BEGIN
+            INSERT INTO agent_events_fts(agent_events_fts, rowid, summary, raw_json)
+            VALUES ('delete', old.id, old.summary, old.raw_json);
+            INSERT INTO agent_events_fts(rowid, summary, raw_json)
+            VALUES (new.id, new.summary, new.raw_json);
+        END;
+
+        CREATE VIRTUAL TABLE IF NOT EXISTS tasks_fts USING fts5(
+            task_id UNINDEXED, title, description,
+            tokenize='porter unicode61'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS tasks_fts_insert AFTER INSERT ON tasks BEGIN
+            INSERT INTO tasks_fts(task_id, title, description)
+            VALUES (new.id, new.title, new.description);
+        END;
+        CREATE TRIGGER IF NOT EXISTS tasks_fts_delete AFTER DELETE ON tasks BEGIN
+            DELETE FROM tasks_fts WHERE task_id = old.id;
+        END;
+        CREATE TRIGGER IF NOT EXISTS tasks_fts_update AFTER UPDATE OF title, description ON tasks BEGIN
+            DELETE FROM tasks_fts WHERE task_id = old.id;
+            INSERT INTO tasks_fts(task_id, title, description)
+            VALUES (new.id, new.title, new.description);
+        END;
+
+        CREATE VIRTUAL TABLE IF NOT EXISTS goal_messages_fts USING fts5(
+            message_id UNINDEXED, content,
+            tokenize='porter unicode61'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS goal_messages_fts_insert AFTER INSERT ON goal_messages BEGIN
+            INSERT INTO goal_messages_fts(message_id, content) VALUES (new.id, new.content);
+        END;
+        CREATE TRIGGER IF NOT EXISTS goal_messages_fts_delete AFTER DELETE ON goal_messages BEGIN
+            DELETE FROM goal_messages_fts WHERE message_id = old.id;
+        END;
+        CREATE TRIGGER IF NOT EXISTS goal_messages_fts_update AFTER UPDATE OF content ON goal_messages BEGIN
+            DELETE FROM goal_messages_fts WHERE message_id = old.id;
+            INSERT INTO goal_messages_fts(message_id, content) VALUES (new.id, new.content);
+        END;
+
+        -- Index existing rows
+        INSERT INTO agent_events_fts(agent_events_fts) VALUES ('rebuild');
+        DELETE FROM tasks_fts;
+        INSERT INTO tasks_fts(task_id, title, description) SELECT id, title, description FROM tasks;
+        DELETE FROM goal_messages_fts;
+        INSERT INTO goal_messages_fts(message_id, content) SELECT id, content FROM goal_messages;
+        ",
+    )?;
+    Ok(())
+}
+
+/// Indexes matching the order and filters of the paginated list queries
+fn list_indexes(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE INDEX IF NOT EXISTS idx_goal_spaces_created ON goal_spaces(created_at, id);
+        CREATE INDEX IF NOT EXISTS idx_goal_spaces_project ON goal_spaces(project_id, created_at);
+        CREATE INDEX IF NOT EXISTS idx_agent_runs_started ON agent_runs(started_at, id);
+        CREATE INDEX IF NOT EXISTS idx_agent_runs_goal_started ON agent_runs(goal_space_id, started_at);
+        CREATE INDEX IF NOT EXISTS idx_agent_events_run_type ON agent_events(agent_run_id, event_type);
+        CREATE INDEX IF NOT EXISTS idx_goal_messages_goal_created ON goal_messages(goal_space_id, created_at, id);
+        ",
+    )?;
+    Ok(())
+}
+
+fn goal_history_actor(conn: &Connection) -> Result<()> {
+    add_column(
+        conn,
+        "goal_space_history",
+        "actor",
+        "TEXT NOT NULL DEFAULT 'system'",
+    )?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_goal_history_goal_type ON goal_space_history(goal_space_id, event_type, id);",
+    )?;
+    Ok(())
+}
+
+/// Fleet-wide scheduler settings, a single row
+fn scheduler_config(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS scheduler_config (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            total_slots INTEGER,
+            starvation_secs INTEGER NOT NULL DEFAULT 600,
+            updated_at TEXT NOT NULL
+        );
+
+        INSERT OR IGNORE INTO scheduler_config (id, total_slots, starvation_secs, updated_at)
+        VALUES (1, NULL, 600, strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now'));
+        ",
+    )?;
+    Ok(())
+}
+
+/// Durable queue of merge and dispatch work for the dispatch loop
+fn jobs(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            goal_space_id TEXT NOT NULL,
+            agent_run_id TEXT,
+            repo_path TEXT,
+            branch TEXT,
+            dedupe_key TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            run_after TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            finished_at TEXT
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_jobs_status_due ON jobs(status, run_after, id);
+        CREATE INDEX IF NOT EXISTS idx_jobs_dedupe ON jobs(dedupe_key, status);
+        CREATE INDEX IF NOT EXISTS idx_jobs_goal ON jobs(goal_space_id, id);
+        ",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(migrations: &[&Migration]) -> Vec<u32> {
+        migrations.iter().map(|m| m.version).collect()
+    }
+
+    #[test]
+    fn versions_are_strictly_increasing() {
+        for pair in MIGRATIONS.windows(2) {
+            assert!(pair[0].version < pair[1].version);
+        }
+    }
+
+    #[test]
+    fn fresh_database_applies_everything_once() {
+        let conn = Connection::open_in_memory().unwrap();
+        let applied = run_migrations(&conn).unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+
+        assert!(run_migrations(&conn).unwrap().is_empty());
+        assert!(columns(&conn, "agent_runs")
+            .unwrap()
+            .contains(&"failure_reason".to_string()));
+    }
+
+    #[test]
+    fn dry_run_lists_pending_without_applying() {
+        let conn = Connection::open_in_memory().unwrap();
+        initial_tables(&conn).unwrap();
+        ensure_version_table(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO schema_version (version, name, applied_at) VALUES (1, 'initial_tables', 'now')",
+            [],
+        )
+        .unwrap();
+
+        let pending = pending_migrations(&conn).unwrap();
+        assert_eq!(
+            versions(&pending),
+            (2..=latest_version()).collect::<Vec<_>>()
+        );
+        assert_eq!(current_version(&conn).unwrap(), 1);
+        assert!(!columns(&conn, "goal_spaces")
+            .unwrap()
+            .contains(&"project_id".to_string()));
+    }
+
+    #[test]
+    fn unversioned_legacy_database_is_adopted() {
+        // A database upgraded by the old ad-hoc checks: every table and column
+        // exists but nothing is recorded in schema_version.
+        let conn = Connection::open_in_memory().unwrap();
+        for migration in MIGRATIONS {
+            (migration.apply)(&conn).unwrap();
+        }
+        conn.execute(
+            "INSERT INTO goal_spaces (id, name, description, repo_path, created_at, updated_at)
+             VALUES ('g1', 'Goal', 'desc', '/tmp/repo', 'now', 'now')",
+            [],
+        )
+        .unwrap();
+
+        let applied = run_migrations(&conn).unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+
+        let goals: i64 = conn
+            .query_row("SELECT COUNT(*) FROM goal_spaces", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(goals, 1);
+    }
+
+    #[test]
+    fn newer_database_is_refused() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO schema_version (version, name, applied_at) VALUES (?1, 'from_the_future', 'now')",
+            [latest_version() + 1],
+        )
+        .unwrap();
+
+        let err = run_migrations(&conn).unwrap_err().to_string();
+        assert!(err.contains("newer than this binary supports"), "{}", err);
+        assert!(pending_migrations(&conn).is_err());
+    }
+}
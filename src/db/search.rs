@@ -0,0 +1,347 @@
+use anyhow::Result;
+use rusqlite::named_params;
+
+use super::Database;
+
+/// What a search hit points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchKind {
+    Event,
+    Task,
+    Message,
+}
+
+impl SearchKind {
+    pub const ALL: [SearchKind; 3] = [SearchKind::Event, SearchKind::Task, SearchKind::Message];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "event" | "events" => Some(SearchKind::Event),
+            "task" | "tasks" => Some(SearchKind::Task),
+            "message" | "messages" => Some(SearchKind::Message),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub text: String,
+    pub goal_space_id: Option<String>,
+    pub project_id: Option<String>,
+    /// Kinds to include (all if empty)
+    pub kinds: Vec<SearchKind>,
+    pub limit: u32,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SearchHit {
+    pub kind: SearchKind,
+    /// Event, task or message id
+    pub id: String,
+    pub goal_space_id: String,
+    pub goal_name: String,
+    pub task_id: Option<String>,
+    pub agent_run_id: Option<String>,
+    /// Event type and tool, task title, or message role
+    pub title: String,
+    /// Matching text with the matched terms wrapped in `[` `]`
+    pub snippet: String,
+    /// bm25 score; lower is a better match
+    pub rank: f64,
+    pub created_at: String,
+}
+
+/// Turn free text into an FTS5 query: every word must match, as a literal
+/// token, so user input can't produce FTS syntax errors. A trailing `*`
+/// on a word keeps prefix matching.
+pub fn fts_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .filter_map(|word| {
+            let (word, prefix) = match word.strip_suffix('*') {
+                Some(stem) => (stem, true),
+                None => (word, false),
+            };
+            if word.is_empty() {
+                return None;
+            }
+            let quoted = format!("\"{}\"", word.replace('"', "\"\""));
+            Some(if prefix { quoted + "*" } else { quoted })
+        })
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+const EVENT_HITS: &str = "
+    SELECT 'event', CAST(e.id AS TEXT), g.id, g.name, r.task_id, e.agent_run_id,
+           e.event_type || COALESCE(': ' || e.tool_name, ''),
+           snippet(agent_events_fts, -1, '[', ']', '…', 16),
+           bm25(agent_events_fts), e.created_at
+    FROM agent_events_fts
+    JOIN agent_events e ON e.id = agent_events_fts.rowid
+    JOIN agent_runs r ON r.id = e.agent_run_id
+    JOIN goal_spaces g ON g.id = r.goal_space_id
+    WHERE agent_events_fts MATCH :query
+      AND (:goal IS NULL OR g.id = :goal)
+      AND (:project IS NULL OR g.project_id = :project)";
+
+const TASK_HITS: &str = "
+    SELECT 'task', t.id, g.id, g.name, t.id, NULL,
+           t.title,
+           snippet(tasks_fts, -1, '[', ']', '…', 16),
+           bm25(tasks_fts), t.created_at
+    FROM tasks_fts
+    JOIN tasks t ON t.id = tasks_fts.task_id
+    JOIN goal_spaces g ON g.id = t.goal_space_id
+    WHERE tasks_fts MATCH :query
+      AND (:goal IS NULL OR g.id = :goal)
+      AND (:project IS NULL OR g.project_id = :project)";
+
+const MESSAGE_HITS: &str = "
+    SELECT 'message', m.id, g.id, g.name, NULL, NULL,
+           m.role,
+           snippet(goal_messages_fts, -1, '[', ']', '…', 16),
+           bm25(goal_messages_fts), m.created_at
+    FROM goal_messages_fts
+    JOIN goal_messages m ON m.id = goal_messages_fts.message_id
+    JOIN goal_spaces g ON g.id = m.goal_space_id
+    WHERE goal_messages_fts MATCH :query
+      AND (:goal IS NULL OR g.id = :goal)
+      AND (:project IS NULL OR g.project_id = :project)";
+
+impl Database {
+    /// Full-text search over agent events, tasks and goal chat, best matches first.
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>> {
+        let Some(fts) = fts_query(&query.text) else {
+            return Ok(Vec::new());
+        };
+        let kinds: &[SearchKind] = if query.kinds.is_empty() {
+            &SearchKind::ALL
+        } else {
+            &query.kinds
+        };
+
+        let parts: Vec<&str> = SearchKind::ALL
+            .iter()
+            .filter(|k| kinds.contains(k))
+            .map(|k| match k {
+                SearchKind::Event => EVENT_HITS,
+                SearchKind::Task => TASK_HITS,
+                SearchKind::Message => MESSAGE_HITS,
+            })
+            .collect();
+        let sql = format!(
+            "{} ORDER BY 9 ASC, 10 DESC LIMIT :limit",
+            parts.join(" UNION ALL ")
+        );
+
+        let conn = self.read_conn();
+        let mut stmt = conn.prepare(&sql)?;
+        let hits = stmt
+            .query_map(
+                named_params! {
+                    ":query": fts,
+                    ":goal": query.goal_space_id,
+                    ":project": query.project_id,
+                    ":limit": query.limit,
+                },
+                |row| {
+                    let kind: String = row.get(0)?;
+                    Ok(SearchHit {
+                        kind: SearchKind::parse(&kind).unwrap_or(SearchKind::Event),
+                        id: row.get(1)?,
+                        goal_space_id: row.get(2)?,
+                        goal_name: row.get(3)?,
+                        task_id: row.get(4)?,
+                        agent_run_id: row.get(5)?,
+                        title: row.get(6)?,
+                        snippet: row.get(7)?,
+                        rank: row.get(8)?,
+                        created_at: row.get(9)?,
+                    })
+                },
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(hits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{CreateGoalMessage, CreateGoalSpace, CreateTask, UpdateTask};
+
+    fn query(text: &str) -> SearchQuery {
+        SearchQuery {
+            text: text.into(),
+            goal_space_id: None,
+            project_id: None,
+            kinds: vec![],
+            limit: 50,
+        }
+    }
+
+    fn goal(db: &Database, name: &str) -> String {
+        db.create_goal_space(&CreateGoalSpace {
+            name: name.into(),
+            description: "desc".into(),
+            repo_path: format!("/tmp/{}", name),
+            settings: Default::default(),
+        })
+        .unwrap()
+        .id
+    }
+
+    fn task(db: &Database, goal_id: &str, title: &str, description: &str) -> String {
+        db.create_task(
+            goal_id,
+            &CreateTask {
+                title: title.into(),
+                description: description.into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap()
+        .id
+    }
+
+    fn test_db() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        db
+    }
+
+    #[test]
+    fn fts_query_quotes_terms() {
+        assert_eq!(fts_query("  "), None);
+        assert_eq!(
+            fts_query(r#"src/main.rs "oops" conn*"#).unwrap(),
+            r#""src/main.rs" """oops""" "conn"*"#
+        );
+    }
+
+    #[test]
+    fn finds_events_tasks_and_messages() {
+        let db = test_db();
+        let goal_id = goal(&db, "auth");
+        let task_id = task(&db, &goal_id, "Add login form", "Render the login form");
+        let run = db
+            .create_agent_run(&task_id, &goal_id, None, None, "sonnet", None)
+            .unwrap();
+        db.insert_agent_event(
+            &run.id,
+            "tool_call",
+            Some("Edit"),
+            "Edit src/login.rs",
+            Some(r#"{"file_path":"src/login.rs","error":"borrow checker"}"#),
+            None,
+        )
+        .unwrap();
+        db.create_goal_message(&CreateGoalMessage {
+            goal_space_id: goal_id.clone(),
+            role: "user".into(),
+            content: "Make the login page accessible".into(),
+            message_type: "text".into(),
+            metadata_json: "{}".into(),
+        })
+        .unwrap();
+
+        let hits = db.search(&query("login")).unwrap();
+        let kinds: Vec<SearchKind> = hits.iter().map(|h| h.kind).collect();
+        assert_eq!(hits.len(), 3);
+        assert!(kinds.contains(&SearchKind::Event));
+        assert!(kinds.contains(&SearchKind::Task));
+        assert!(kinds.contains(&SearchKind::Message));
+
+        // Raw payload text is searchable too
+        let hits = db.search(&query("borrow")).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].agent_run_id.as_deref(), Some(run.id.as_str()));
+        assert_eq!(hits[0].task_id.as_deref(), Some(task_id.as_str()));
+        assert_eq!(hits[0].title, "tool_call: Edit");
+        assert!(hits[0].snippet.contains("[borrow]"));
+
+        let only_tasks = SearchQuery {
+            kinds: vec![SearchKind::Task],
+            ..query("login")
+        };
+        let hits = db.search(&only_tasks).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, task_id);
+    }
+
+    #[test]
+    fn ranks_better_matches_first_and_filters_by_goal() {
+        let db = test_db();
+        let first = goal(&db, "first");
+        let second = goal(&db, "second");
+        task(&db, &first, "Cache", "cache cache cache invalidation");
+        let weak = task(
+            &db,
+            &second,
+            "Refactor",
+            "touches the cache once among many other words here",
+        );
+
+        let hits = db.search(&query("cache")).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].goal_space_id, first);
+        assert!(hits[0].rank <= hits[1].rank);
+
+        let in_second = SearchQuery {
+            goal_space_id: Some(second.clone()),
+            ..query("cache")
+        };
+        let hits = db.search(&in_second).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, weak);
+    }
+
+    #[test]
+    fn index_follows_updates_and_compaction() {
+        let db = test_db();
+        let goal_id = goal(&db, "g");
+        let task_id = task(&db, &goal_id, "Old title", "nothing");
+        db.update_task(
+            &task_id,
+            &UpdateTask {
+                title: Some("Shiny title".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(db.search(&query("old")).unwrap().is_empty());
+        assert_eq!(db.search(&query("shiny")).unwrap().len(), 1);
+
+        let run = db
+            .create_agent_run(&task_id, &goal_id, None, None, "sonnet", None)
+            .unwrap();
+        let event = db
+            .insert_agent_event(
+                &run.id,
+                "text_output",
+                None,
+                "hello",
+                Some(r#"{"x":"zebra"}"#),
+                None,
+            )
+            .unwrap();
+        assert_eq!(db.search(&query("zebra")).unwrap().len(), 1);
+        db.conn()
+            .execute(
+                "UPDATE agent_events SET raw_json = NULL WHERE id = ?1",
+                [event.id],
+            )
+            .unwrap();
+        assert!(db.search(&query("zebra")).unwrap().is_empty());
+        assert_eq!(db.search(&query("hello")).unwrap().len(), 1);
+    }
+}
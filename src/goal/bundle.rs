@@ -0,0 +1,842 @@
+//! Portable goal bundles: a goal space with its tasks, runs, events, chat and
+//! history, packed as a zstd-compressed tar archive.
+//!
+//! Rows are stored as JSON objects keyed by column name. Imports insert only
+//! the columns the current schema has and let the rest take their defaults,
+//! so bundles written before a migration still import after it. Layout
+//! changes bump [`BUNDLE_VERSION`]; older layouts are upgraded on read.
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{params_from_iter, Connection};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use uuid::Uuid;
+
+use crate::db::Database;
+
+/// Identifies conductor goal bundles in the manifest
+pub const BUNDLE_FORMAT: &str = "conductor-goal-bundle";
+
+/// Newest bundle layout this binary writes and reads
+pub const BUNDLE_VERSION: u32 = 1;
+
+type Row = Map<String, Value>;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub conductor_version: String,
+    pub schema_version: u32,
+    pub goal_space_id: String,
+    pub goal_name: String,
+    pub counts: BundleCounts,
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BundleCounts {
+    pub tasks: usize,
+    pub agent_runs: usize,
+    pub agent_events: usize,
+    pub goal_messages: usize,
+    pub history: usize,
+}
+
+/// How an imported goal is attached on this machine
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+pub struct ImportOptions {
+    /// Repository the goal works in (defaults to the bundle's, or the project's path)
+    pub repo_path: Option<String>,
+    /// Project to attach the goal to (defaults to the project registered for the repository)
+    pub project_id: Option<String>,
+    /// New name for the goal
+    pub name: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ImportReport {
+    pub goal_space_id: String,
+    pub source_goal_space_id: String,
+    pub bundle_version: u32,
+    pub repo_path: String,
+    pub project_id: Option<String>,
+    pub counts: BundleCounts,
+}
+
+/// The tables of a bundle, as rows
+#[derive(Debug, Default)]
+struct Contents {
+    goal: Row,
+    tasks: Vec<Row>,
+    agent_runs: Vec<Row>,
+    agent_events: Vec<Row>,
+    goal_messages: Vec<Row>,
+    history: Vec<Row>,
+}
+
+impl Contents {
+    fn counts(&self) -> BundleCounts {
+        BundleCounts {
+            tasks: self.tasks.len(),
+            agent_runs: self.agent_runs.len(),
+            agent_events: self.agent_events.len(),
+            goal_messages: self.goal_messages.len(),
+            history: self.history.len(),
+        }
+    }
+}
+
+/// Build a `.tar.zst` bundle of a goal space
+pub fn export_goal(db: &Database, goal_id: &str) -> Result<Vec<u8>> {
+    let contents = read_goal(db, goal_id)?;
+    let manifest = Manifest {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        exported_at: Utc::now().to_rfc3339(),
+        conductor_version: env!("CARGO_PKG_VERSION").to_string(),
+        schema_version: db.schema_version()?,
+        goal_space_id: goal_id.to_string(),
+        goal_name: text(&contents.goal, "name").unwrap_or_default(),
+        counts: contents.counts(),
+    };
+
+    let encoder = zstd::Encoder::new(Vec::new(), 0)?;
+    let mut tar = tar::Builder::new(encoder);
+    let mut add = |name: &str, data: Vec<u8>| -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(Utc::now().timestamp().max(0) as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, name, data.as_slice())?;
+        Ok(())
+    };
+    add("manifest.json", serde_json::to_vec_pretty(&manifest)?)?;
+    add("goal.json", serde_json::to_vec_pretty(&contents.goal)?)?;
+    add("tasks.json", serde_json::to_vec_pretty(&contents.tasks)?)?;
+    add(
+        "agent_runs.json",
+        serde_json::to_vec_pretty(&contents.agent_runs)?,
+    )?;
+    add("agent_events.jsonl", jsonl(&contents.agent_events)?)?;
+    add(
+        "goal_messages.json",
+        serde_json::to_vec_pretty(&contents.goal_messages)?,
+    )?;
+    add(
+        "history.json",
+        serde_json::to_vec_pretty(&contents.history)?,
+    )?;
+
+    Ok(tar.into_inner()?.finish()?)
+}
+
+/// Read the manifest of a bundle without importing it
+pub fn read_manifest(bundle: &[u8]) -> Result<Manifest> {
+    let files = unpack(bundle)?;
+    parse_manifest(&files)
+}
+
+/// Import a bundle as a new goal space with fresh ids
+pub fn import_goal(db: &Database, bundle: &[u8], options: &ImportOptions) -> Result<ImportReport> {
+    let files = unpack(bundle)?;
+    let manifest = parse_manifest(&files)?;
+    let mut contents = match manifest.version {
+        1 => read_v1(&files)?,
+        v => bail!("Unsupported bundle version {}", v),
+    };
+
+    // Where the goal lives on this machine
+    let bundle_repo = text(&contents.goal, "repo_path").unwrap_or_default();
+    let (repo_path, project_id) = match (&options.repo_path, &options.project_id) {
+        (repo, Some(project_id)) => {
+            let project = db
+                .get_project(project_id)?
+                .with_context(|| format!("Project {} not found", project_id))?;
+            (repo.clone().unwrap_or(project.path), Some(project.id))
+        }
+        (repo, None) => {
+            let repo = repo.clone().unwrap_or_else(|| bundle_repo.clone());
+            let project_id = db.get_project_by_path(&repo)?.map(|p| p.id);
+            (repo, project_id)
+        }
+    };
+    if repo_path.is_empty() {
+        bail!("Bundle has no repo_path; pass one to import it");
+    }
+
+    let source_goal_id = text(&contents.goal, "id").unwrap_or_default();
+    let goal_id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+
+    let mut task_ids = HashMap::new();
+    for task in &contents.tasks {
+        if let Some(id) = text(task, "id") {
+            task_ids.insert(id, Uuid::new_v4().to_string());
+        }
+    }
+    let mut run_ids = HashMap::new();
+    for run in &contents.agent_runs {
+        if let Some(id) = text(run, "id") {
+            run_ids.insert(id, Uuid::new_v4().to_string());
+        }
+    }
+
+    // Goal
+    let goal = &mut contents.goal;
+    goal.insert("id".into(), goal_id.clone().into());
+    goal.insert("repo_path".into(), repo_path.clone().into());
+    goal.insert("project_id".into(), project_id.clone().into());
+    goal.insert("updated_at".into(), now.clone().into());
+    if let Some(name) = &options.name {
+        goal.insert("name".into(), name.clone().into());
+    }
+
+    // Tasks: new ids, dependencies remapped, work in flight reset
+    for task in &mut contents.tasks {
+        remap(task, "id", &task_ids);
+        task.insert("goal_space_id".into(), goal_id.clone().into());
+        if let Some(deps) = text(task, "depends_on") {
+            let deps: Vec<String> = serde_json::from_str(&deps).unwrap_or_default();
+            let deps: Vec<String> = deps
+                .into_iter()
+                .filter_map(|d| task_ids.get(&d).cloned())
+                .collect();
+            task.insert("depends_on".into(), serde_json::to_string(&deps)?.into());
+        }
+        if matches!(
+            text(task, "status").as_deref(),
+            Some("assigned" | "running" | "stalled")
+        ) {
+            task.insert("status".into(), "failed".into());
+        }
+    }
+
+    // Runs: the worktree belonged to the exporting machine, and no agent is
+    // running for them here. In another repository their branches and commits
+    // don't exist, so nothing may merge, revert or bisect them.
+    let rebound = repo_path != bundle_repo;
+    for run in &mut contents.agent_runs {
+        remap(run, "id", &run_ids);
+        remap(run, "task_id", &task_ids);
+        run.insert("goal_space_id".into(), goal_id.clone().into());
+        run.insert("worktree_path".into(), Value::Null);
+        if rebound {
+            for key in ["branch", "merge_commit", "base_commit"] {
+                run.insert(key.into(), Value::Null);
+            }
+        }
+        if matches!(
+            text(run, "status").as_deref(),
+            Some("spawning" | "running" | "stalled")
+        ) {
+            run.insert("status".into(), "failed".into());
+            run.insert("failure_reason".into(), "imported".into());
+        }
+    }
+
+    for event in &mut contents.agent_events {
+        event.remove("id");
+        remap(event, "agent_run_id", &run_ids);
+        event.insert("raw_archived".into(), 0.into());
+    }
+    for message in &mut contents.goal_messages {
+        message.insert("id".into(), Uuid::new_v4().to_string().into());
+        message.insert("goal_space_id".into(), goal_id.clone().into());
+    }
+    // History metadata refers to tasks and runs by their exported ids
+    let mut ids: HashMap<String, String> = task_ids
+        .iter()
+        .chain(&run_ids)
+        .map(|(old, new)| (old.clone(), new.clone()))
+        .collect();
+    ids.insert(source_goal_id.clone(), goal_id.clone());
+    for entry in &mut contents.history {
+        entry.remove("id");
+        entry.insert("goal_space_id".into(), goal_id.clone().into());
+        if let Some(metadata) = text(entry, "metadata") {
+            if let Ok(mut metadata) = serde_json::from_str::<Value>(&metadata) {
+                remap_json(&mut metadata, &ids);
+                entry.insert("metadata".into(), metadata.to_string().into());
+            }
+        }
+    }
+    contents.history.push(
+        [
+            ("event_type", Value::from("imported")),
+            (
+                "description",
+                Value::from(format!(
+                    "Imported from bundle of goal {} (exported {})",
+                    source_goal_id, manifest.exported_at
+                )),
+            ),
+            ("goal_space_id", Value::from(goal_id.clone())),
+            ("created_at", Value::from(now)),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect(),
+    );
+
+    // Rows whose parent is missing from the bundle cannot be attached
+    let new_tasks: HashSet<&String> = task_ids.values().collect();
+    let new_runs: HashSet<&String> = run_ids.values().collect();
+    contents
+        .agent_runs
+        .retain(|r| text(r, "task_id").is_some_and(|id| new_tasks.contains(&id)));
+    contents
+        .agent_events
+        .retain(|e| text(e, "agent_run_id").is_some_and(|id| new_runs.contains(&id)));
+
+    let conn = db.conn();
+    let tx = conn.unchecked_transaction()?;
+    insert_rows(&tx, "goal_spaces", std::slice::from_ref(&contents.goal))?;
+    insert_rows(&tx, "tasks", &contents.tasks)?;
+    insert_rows(&tx, "agent_runs", &contents.agent_runs)?;
+    insert_rows(&tx, "agent_events", &contents.agent_events)?;
+    insert_rows(&tx, "goal_messages", &contents.goal_messages)?;
+    insert_rows(&tx, "goal_space_history", &contents.history)?;
+    tx.commit()?;
+
+    Ok(ImportReport {
+        goal_space_id: goal_id,
+        source_goal_space_id: source_goal_id,
+        bundle_version: manifest.version,
+        repo_path,
+        project_id,
+        counts: contents.counts(),
+    })
+}
+
+fn read_goal(db: &Database, goal_id: &str) -> Result<Contents> {
+    let conn = db.read_conn();
+    let goal = select(&conn, "SELECT * FROM goal_spaces WHERE id = ?1", goal_id)?
+        .into_iter()
+        .next()
+        .with_context(|| format!("Goal {} not found", goal_id))?;
+    let mut contents = Contents {
+        goal,
+        tasks: select(
+            &conn,
+            "SELECT * FROM tasks WHERE goal_space_id = ?1 ORDER BY created_at, rowid",
+            goal_id,
+        )?,
+        agent_runs: select(
+            &conn,
+            "SELECT * FROM agent_runs WHERE goal_space_id = ?1 ORDER BY started_at, rowid",
+            goal_id,
+        )?,
+        agent_events: select(
+            &conn,
+            "SELECT * FROM agent_events WHERE agent_run_id IN
+                 (SELECT id FROM agent_runs WHERE goal_space_id = ?1)
+             ORDER BY id",
+            goal_id,
+        )?,
+        goal_messages: select(
+            &conn,
+            "SELECT * FROM goal_messages WHERE goal_space_id = ?1 ORDER BY created_at, rowid",
+            goal_id,
+        )?,
+        history: select(
+            &conn,
+            "SELECT * FROM goal_space_history WHERE goal_space_id = ?1 ORDER BY id",
+            goal_id,
+        )?,
+    };
+    drop(conn);
+
+    // Bundles are self-contained: put compacted payloads back inline
+    if let Some(dir) = db.archive_dir() {
+        let mut archives: HashMap<String, HashMap<i64, String>> = HashMap::new();
+        for event in &mut contents.agent_events {
+            if event.get("raw_archived").and_then(Value::as_i64) != Some(1) {
+                continue;
+            }
+            let (Some(run_id), Some(id)) = (
+                text(event, "agent_run_id"),
+                event.get("id").and_then(Value::as_i64),
+            ) else {
+                continue;
+            };
+            if !archives.contains_key(&run_id) {
+                let payloads = crate::db::retention::read_archive(dir, &run_id)?;
+                archives.insert(run_id.clone(), payloads);
+            }
+            if let Some(raw) = archives.get_mut(&run_id).and_then(|a| a.remove(&id)) {
+                event.insert("raw_json".into(), raw.into());
+                event.insert("raw_archived".into(), 0.into());
+            }
+        }
+    }
+
+    Ok(contents)
+}
+
+fn unpack(bundle: &[u8]) -> Result<HashMap<String, Vec<u8>>> {
+    let decoder = zstd::Decoder::new(bundle).context("Not a zstd-compressed bundle")?;
+    let mut archive = tar::Archive::new(decoder);
+    let mut files = HashMap::new();
+    for entry in archive.entries().context("Not a tar bundle")? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        files.insert(name, data);
+    }
+    Ok(files)
+}
+
+fn parse_manifest(files: &HashMap<String, Vec<u8>>) -> Result<Manifest> {
+    let data = files
+        .get("manifest.json")
+        .context("Bundle has no manifest.json")?;
+    let manifest: Manifest = serde_json::from_slice(data).context("Invalid manifest.json")?;
+    if manifest.format != BUNDLE_FORMAT {
+        bail!("Not a conductor goal bundle (format '{}')", manifest.format);
+    }
+    if manifest.version > BUNDLE_VERSION {
+        bail!(
+            "Bundle version {} is newer than this binary supports ({}); upgrade conductor to import it",
+            manifest.version,
+            BUNDLE_VERSION
+        );
+    }
+    Ok(manifest)
+}
+
+fn read_v1(files: &HashMap<String, Vec<u8>>) -> Result<Contents> {
+    fn json<T: serde::de::DeserializeOwned + Default>(
+        files: &HashMap<String, Vec<u8>>,
+        name: &str,
+    ) -> Result<T> {
+        match files.get(name) {
+            Some(data) => serde_json::from_slice(data).with_context(|| format!("Invalid {}", name)),
+            None => Ok(T::default()),
+        }
+    }
+
+    let mut agent_events = Vec::new();
+    if let Some(data) = files.get("agent_events.jsonl") {
+        for line in data.split(|b| *b == b'\n').filter(|l| !l.is_empty()) {
+            agent_events.push(serde_json::from_slice(line).context("Invalid agent_events.jsonl")?);
+        }
+    }
+
+    Ok(Contents {
+        goal: files
+            .get("goal.json")
+            .map(|data| serde_json::from_slice(data).context("Invalid goal.json"))
+            .transpose()?
+            .context("Bundle has no goal.json")?,
+        tasks: json(files, "tasks.json")?,
+        agent_runs: json(files, "agent_runs.json")?,
+        agent_events,
+        goal_messages: json(files, "goal_messages.json")?,
+        history: json(files, "history.json")?,
+    })
+}
+
+fn jsonl(rows: &[Row]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for row in rows {
+        serde_json::to_writer(&mut out, row)?;
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
+fn text(row: &Row, key: &str) -> Option<String> {
+    row.get(key).and_then(Value::as_str).map(String::from)
+}
+
+fn remap(row: &mut Row, key: &str, ids: &HashMap<String, String>) {
+    if let Some(new) = text(row, key).and_then(|old| ids.get(&old).cloned()) {
+        row.insert(key.to_string(), new.into());
+    }
+}
+
+/// Rows of a query as JSON objects keyed by column name
+/// Replace every string in `value` that is a key of `ids`
+fn remap_json(value: &mut Value, ids: &HashMap<String, String>) {
+    match value {
+        Value::String(s) => {
+            if let Some(new) = ids.get(s.as_str()) {
+                *s = new.clone();
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| remap_json(v, ids)),
+        Value::Object(map) => map.values_mut().for_each(|v| remap_json(v, ids)),
+        _ => {}
+    }
+}
+
+fn select(conn: &Connection, sql: &str, param: &str) -> Result<Vec<Row>> {
+    let mut stmt = conn.prepare(sql)?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let rows = stmt
+        .query_map([param], |row| {
+            let mut object = Map::new();
+            for (i, column) in columns.iter().enumerate() {
+                let value = match row.get_ref(i)? {
+                    ValueRef::Null | ValueRef::Blob(_) => Value::Null,
+                    ValueRef::Integer(n) => n.into(),
+                    ValueRef::Real(f) => f.into(),
+                    ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned().into(),
+                };
+                object.insert(column.clone(), value);
+            }
+            Ok(object)
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Insert rows, keeping only the columns `table` has in this schema
+fn insert_rows(conn: &Connection, table: &str, rows: &[Row]) -> Result<()> {
+    let known: Vec<String> = conn
+        .prepare(&format!("PRAGMA table_info({})", table))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    for row in rows {
+        let (columns, values): (Vec<&String>, Vec<SqlValue>) = row
+            .iter()
+            .filter(|(column, _)| known.contains(column))
+            .map(|(column, value)| (column, sql_value(value)))
+            .unzip();
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table,
+            columns
+                .iter()
+                .map(|c| c.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            vec!["?"; columns.len()].join(", ")
+        );
+        conn.execute(&sql, params_from_iter(values))
+            .with_context(|| format!("Failed to import a {} row", table))?;
+    }
+    Ok(())
+}
+
+fn sql_value(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{
+        Actor, CreateGoalMessage, CreateGoalSpace, CreateProject, CreateTask, ListFilter,
+    };
+    use crate::goal::task::RunStatus;
+
+    fn test_db() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        db
+    }
+
+    /// A goal with two dependent tasks, a finished run with events, and chat
+    fn populated(db: &Database) -> String {
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "Auth".into(),
+                description: "Add login".into(),
+                repo_path: "/src/app".into(),
+                settings: serde_json::from_value(json_settings()).unwrap(),
+            })
+            .unwrap();
+        let first = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: "Schema".into(),
+                    description: "Users table".into(),
+                    priority: 1,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let second = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: "Form".into(),
+                    description: "Login form".into(),
+                    priority: 0,
+                    depends_on: vec![first.id.clone()],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let run = db
+            .create_agent_run(
+                &second.id,
+                &goal.id,
+                Some("/tmp/wt"),
+                Some("conductor/form"),
+                "sonnet",
+                None,
+            )
+            .unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Running)
+            .unwrap();
+        db.update_agent_run_cost(&run.id, 0.42, 10, 20).unwrap();
+        db.insert_agent_event(&run.id, "text_output", None, "hi", Some(r#"{"t":1}"#), None)
+            .unwrap();
+        db.insert_agent_event(
+            &run.id,
+            "result",
+            None,
+            "done",
+            Some(r#"{"t":2}"#),
+            Some(0.42),
+        )
+        .unwrap();
+        db.set_agent_run_merge_commit(&run.id, "abc123").unwrap();
+        db.insert_goal_history(
+            &goal.id,
+            Actor::Dispatch,
+            "branch_merged",
+            "Merged conductor/form",
+            Some(serde_json::json!({
+                "task_id": second.id,
+                "agent_run_id": run.id,
+                "blocked": [first.id],
+                "merge_commit": "abc123",
+            })),
+        )
+        .unwrap();
+        db.create_goal_message(&CreateGoalMessage {
+            goal_space_id: goal.id.clone(),
+            role: "user".into(),
+            content: "please add login".into(),
+            message_type: "text".into(),
+            metadata_json: "{}".into(),
+        })
+        .unwrap();
+        goal.id
+    }
+
+    fn json_settings() -> Value {
+        serde_json::json!({"model": "opus", "merge_mode": "manual"})
+    }
+
+    #[test]
+    fn round_trips_with_fresh_ids() {
+        let source = test_db();
+        let goal_id = populated(&source);
+        let bundle = export_goal(&source, &goal_id).unwrap();
+
+        let manifest = read_manifest(&bundle).unwrap();
+        assert_eq!(manifest.version, BUNDLE_VERSION);
+        assert_eq!(manifest.goal_name, "Auth");
+        assert_eq!(manifest.counts.tasks, 2);
+        assert_eq!(manifest.counts.agent_events, 2);
+
+        let target = test_db();
+        let report = import_goal(&target, &bundle, &ImportOptions::default()).unwrap();
+        assert_ne!(report.goal_space_id, goal_id);
+        assert_eq!(report.source_goal_space_id, goal_id);
+        assert_eq!(report.repo_path, "/src/app");
+
+        let goal = target
+            .get_goal_space(&report.goal_space_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(goal.name, "Auth");
+        assert_eq!(goal.settings.model(), "opus");
+
+        let tasks = target.list_tasks(&report.goal_space_id).unwrap();
+        assert_eq!(tasks.len(), 2);
+        let schema = tasks.iter().find(|t| t.title == "Schema").unwrap();
+        let form = tasks.iter().find(|t| t.title == "Form").unwrap();
+        assert_eq!(form.depends_on, vec![schema.id.clone()]);
+
+        let runs: Vec<_> = target
+            .list_agent_runs()
+            .unwrap()
+            .into_iter()
+            .filter(|r| r.goal_space_id == report.goal_space_id)
+            .collect();
+        assert_eq!(runs.len(), 1);
+        let run = &runs[0];
+        assert_eq!(run.task_id, form.id);
+        assert_eq!(run.status, RunStatus::Failed);
+        assert_eq!(run.worktree_path, None);
+        assert_eq!(run.branch.as_deref(), Some("conductor/form"));
+        assert_eq!(run.merge_commit.as_deref(), Some("abc123"));
+        assert!((run.cost_usd - 0.42).abs() < 1e-9);
+
+        // Ids in history metadata point at the imported rows
+        let history = target
+            .list_goal_history_filtered(&report.goal_space_id, &ListFilter::default())
+            .unwrap();
+        let merged = history
+            .iter()
+            .find(|h| h.event_type == "branch_merged")
+            .unwrap();
+        assert_eq!(
+            merged.metadata,
+            Some(serde_json::json!({
+                "task_id": form.id,
+                "agent_run_id": run.id,
+                "blocked": [schema.id],
+                "merge_commit": "abc123",
+            }))
+        );
+
+        let events = target.list_agent_events(&run.id).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].raw_json.as_deref(), Some(r#"{"t":2}"#));
+
+        let messages = target.list_goal_messages(&report.goal_space_id).unwrap();
+        assert_eq!(messages[0].content, "please add login");
+
+        // Importing twice gives two independent goals
+        let again = import_goal(&target, &bundle, &ImportOptions::default()).unwrap();
+        assert_ne!(again.goal_space_id, report.goal_space_id);
+        assert_eq!(target.list_goal_spaces().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn rebinds_repo_path_and_project() {
+        let source = test_db();
+        let goal_id = populated(&source);
+        let bundle = export_goal(&source, &goal_id).unwrap();
+
+        let target = test_db();
+        let project = target
+            .create_project(&CreateProject {
+                path: "/home/me/app".into(),
+                display_name: "app".into(),
+                sort_order: 0,
+            })
+            .unwrap();
+
+        let by_path = import_goal(
+            &target,
+            &bundle,
+            &ImportOptions {
+                repo_path: Some("/home/me/app".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(by_path.project_id.as_deref(), Some(project.id.as_str()));
+        // The exporter's branches and commits don't exist in the new repository
+        let run = target
+            .list_agent_runs()
+            .unwrap()
+            .into_iter()
+            .find(|r| r.goal_space_id == by_path.goal_space_id)
+            .unwrap();
+        assert_eq!(run.branch, None);
+        assert_eq!(run.merge_commit, None);
+        assert_eq!(run.base_commit, None);
+        assert!(target
+            .list_merged_agent_runs(&by_path.goal_space_id)
+            .unwrap()
+            .is_empty());
+
+        let by_project = import_goal(
+            &target,
+            &bundle,
+            &ImportOptions {
+                project_id: Some(project.id.clone()),
+                name: Some("Auth (copy)".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(by_project.repo_path, "/home/me/app");
+        let goals = target.list_goals_by_project(&project.id).unwrap();
+        assert_eq!(goals.len(), 2);
+        assert!(goals.iter().any(|g| g.name == "Auth (copy)"));
+
+        let missing = import_goal(
+            &target,
+            &bundle,
+            &ImportOptions {
+                project_id: Some("nope".into()),
+                ..Default::default()
+            },
+        );
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn imports_rows_from_older_schemas_and_rejects_newer_bundles() {
+        // A hand-written v1 bundle from before several columns existed
+        let manifest = serde_json::json!({
+            "format": BUNDLE_FORMAT,
+            "version": 1,
+            "exported_at": "2025-01-01T00:00:00+00:00",
+            "conductor_version": "0.1.0",
+            "schema_version": 0,
+            "goal_space_id": "old-goal",
+            "goal_name": "Old",
+            "counts": {"tasks": 1, "agent_runs": 0, "agent_events": 0, "goal_messages": 0, "history": 0}
+        });
+        let goal = serde_json::json!({
+            "id": "old-goal", "name": "Old", "description": "d", "status": "active",
+            "repo_path": "/old", "created_at": "2025-01-01T00:00:00+00:00",
+            "updated_at": "2025-01-01T00:00:00+00:00", "retired_column": 7
+        });
+        let tasks = serde_json::json!([{
+            "id": "t1", "goal_space_id": "old-goal", "title": "T", "description": "d",
+            "status": "pending", "priority": 0, "depends_on": "[]",
+            "created_at": "2025-01-01T00:00:00+00:00", "updated_at": "2025-01-01T00:00:00+00:00"
+        }]);
+        let bundle = |manifest: &Value| {
+            let mut tar = tar::Builder::new(zstd::Encoder::new(Vec::new(), 0).unwrap());
+            for (name, value) in [
+                ("manifest.json", manifest),
+                ("goal.json", &goal),
+                ("tasks.json", &tasks),
+            ] {
+                let data = serde_json::to_vec(value).unwrap();
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                tar.append_data(&mut header, name, data.as_slice()).unwrap();
+            }
+            tar.into_inner().unwrap().finish().unwrap()
+        };
+
+        let db = test_db();
+        let report = import_goal(&db, &bundle(&manifest), &ImportOptions::default()).unwrap();
+        let imported = db.get_goal_space(&report.goal_space_id).unwrap().unwrap();
+        assert_eq!(imported.name, "Old");
+        assert_eq!(imported.settings.model(), "sonnet");
+        assert_eq!(db.list_tasks(&report.goal_space_id).unwrap().len(), 1);
+
+        let mut newer = manifest.clone();
+        newer["version"] = (BUNDLE_VERSION + 1).into();
+        let err = import_goal(&db, &bundle(&newer), &ImportOptions::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("newer than this binary supports"), "{}", err);
+
+        assert!(import_goal(&db, b"not a bundle", &ImportOptions::default()).is_err());
+    }
+}
@@ -122,32 +122,30 @@ pub async fn run_goal_chat(
                         done: false,
                     });
                 }
-                ParsedEvent::TextMessage { text } => {
-                    // Claude Code CLI emits complete text in "assistant" events
-                    // rather than incremental deltas. Broadcast the full text
-                    // as a chunk so the frontend sees it immediately.
-                    if !text.is_empty() {
-                        full_response.push_str(text);
-                        let _ = event_tx.send(BroadcastEvent::ChatChunk {
-                            operation_id: operation_id.to_string(),
-                            goal_space_id: goal_space_id.to_string(),
-                            chunk: text.clone(),
-                            done: false,
-                        });
-                    }
+                // Claude Code CLI emits complete text in "assistant" events
+                // rather than incremental deltas. Broadcast the full text
+                // as a chunk so the frontend sees it immediately.
+                ParsedEvent::TextMessage { text } if !text.is_empty() => {
+                    full_response.push_str(text);
+                    let _ = event_tx.send(BroadcastEvent::ChatChunk {
+                        operation_id: operation_id.to_string(),
+                        goal_space_id: goal_space_id.to_string(),
+                        chunk: text.clone(),
+                        done: false,
+                    });
                 }
-                ParsedEvent::Result { result_text, .. } => {
-                    // Final result — if we didn't get text from assistant events,
-                    // use the result text as the response
-                    if full_response.is_empty() && !result_text.is_empty() {
-                        full_response = result_text.clone();
-                        let _ = event_tx.send(BroadcastEvent::ChatChunk {
-                            operation_id: operation_id.to_string(),
-                            goal_space_id: goal_space_id.to_string(),
-                            chunk: result_text.clone(),
-                            done: false,
-                        });
-                    }
+                // Final result — if we didn't get text from assistant events,
+                // use the result text as the response
+                ParsedEvent::Result { result_text, .. }
+                    if full_response.is_empty() && !result_text.is_empty() =>
+                {
+                    full_response = result_text.clone();
+                    let _ = event_tx.send(BroadcastEvent::ChatChunk {
+                        operation_id: operation_id.to_string(),
+                        goal_space_id: goal_space_id.to_string(),
+                        chunk: result_text.clone(),
+                        done: false,
+                    });
                 }
                 _ => {}
             }
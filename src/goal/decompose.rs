@@ -385,10 +385,92 @@ pub fn parse_decomposition_output(stdout: &str) -> Result<Vec<CreateTask>> {
     Ok(tasks)
 }
 
+/// Order in which to create decomposed tasks so every `__index_N` dependency
+/// exists before the task that needs it. Fails on a reference to a task that
+/// isn't in the list, a task depending on itself, or a cycle.
+pub fn creation_order(tasks: &[CreateTask]) -> Result<Vec<usize>> {
+    let mut deps: Vec<Vec<usize>> = Vec::with_capacity(tasks.len());
+    for (i, task) in tasks.iter().enumerate() {
+        let mut indices = Vec::new();
+        for dep in &task.depends_on {
+            let index = dep
+                .strip_prefix("__index_")
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|n| *n < tasks.len());
+            match index {
+                Some(n) if n == i => {
+                    anyhow::bail!("Task {} '{}' depends on itself", i, task.title)
+                }
+                Some(n) => indices.push(n),
+                None => anyhow::bail!(
+                    "Task {} '{}' depends on unknown task '{}'",
+                    i,
+                    task.title,
+                    dep.strip_prefix("__index_").unwrap_or(dep)
+                ),
+            }
+        }
+        deps.push(indices);
+    }
+
+    let mut order = Vec::with_capacity(tasks.len());
+    let mut placed = vec![false; tasks.len()];
+    while order.len() < tasks.len() {
+        let ready: Vec<usize> = (0..tasks.len())
+            .filter(|&i| !placed[i] && deps[i].iter().all(|&d| placed[d]))
+            .collect();
+        if ready.is_empty() {
+            let stuck: Vec<String> = (0..tasks.len())
+                .filter(|&i| !placed[i])
+                .map(|i| format!("{} '{}'", i, tasks[i].title))
+                .collect();
+            anyhow::bail!("Dependency cycle between tasks {}", stuck.join(", "));
+        }
+        for i in ready {
+            placed[i] = true;
+            order.push(i);
+        }
+    }
+    Ok(order)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn raw(title: &str, depends_on: &[&str]) -> CreateTask {
+        CreateTask {
+            title: title.into(),
+            description: "D".into(),
+            priority: 0,
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            settings: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_creation_order_puts_dependencies_first() {
+        let tasks = vec![
+            raw("Tests", &["__index_2"]),
+            raw("Schema", &[]),
+            raw("Api", &["__index_1"]),
+        ];
+        assert_eq!(creation_order(&tasks).unwrap(), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_creation_order_rejects_bad_graphs() {
+        let err = creation_order(&[raw("A", &["__index_0"])]).unwrap_err();
+        assert!(err.to_string().contains("depends on itself"));
+
+        let err = creation_order(&[raw("A", &["__index_4"])]).unwrap_err();
+        assert!(err.to_string().contains("unknown task '4'"), "{}", err);
+
+        let err =
+            creation_order(&[raw("A", &["__index_1"]), raw("B", &["__index_0"])]).unwrap_err();
+        assert!(err.to_string().contains("cycle"), "{}", err);
+    }
+
     #[test]
     fn test_parse_direct_schema_output() {
         let output = r#"{"tasks":[{"title":"Add validation","description":"Add input validation","depends_on":[]},{"title":"Write tests","description":"Write tests for validation","depends_on":[0]}]}"#;
@@ -0,0 +1,550 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+
+use crate::db::analytics::RunSample;
+use crate::db::queries::{AgentRun, GoalSettings, GoalSpace, ListFilter, Task};
+use crate::db::Database;
+use crate::goal::space::{effective_settings, goal_settings};
+use crate::goal::task::TaskStatus;
+
+/// How many recent successful runs feed the historical distributions
+const SAMPLE_LIMIT: u32 = 500;
+/// Fewest samples before a distribution is trusted over the next fallback
+const MIN_SAMPLES: usize = 3;
+/// Assumed seconds per agent turn when there is no history to go on
+const DEFAULT_SECS_PER_TURN: f64 = 20.0;
+
+/// Low, expected and high values of an estimate
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct Range {
+    pub low: f64,
+    pub expected: f64,
+    pub high: f64,
+}
+
+impl Range {
+    fn add(self, other: Range) -> Range {
+        Range {
+            low: self.low + other.low,
+            expected: self.expected + other.expected,
+            high: self.high + other.high,
+        }
+    }
+
+    fn max(self, other: Range) -> Range {
+        Range {
+            low: self.low.max(other.low),
+            expected: self.expected.max(other.expected),
+            high: self.high.max(other.high),
+        }
+    }
+
+    /// Subtract what has already been used, never going below zero
+    fn less(self, used: f64) -> Range {
+        Range {
+            low: (self.low - used).max(0.0),
+            expected: (self.expected - used).max(0.0),
+            high: (self.high - used).max(0.0),
+        }
+    }
+
+    fn cap(self, limit: f64) -> Range {
+        Range {
+            low: self.low.min(limit),
+            expected: self.expected.min(limit),
+            high: self.high.min(limit),
+        }
+    }
+}
+
+/// Where a task's figures come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Basis {
+    /// The task is finished; nothing left to spend
+    Actual,
+    /// Past runs of the task's model
+    Model,
+    /// Past runs of any model, when the task's model has too few
+    AllModels,
+    /// The task's budget and turn limit, when there is no usable history
+    Default,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TaskEstimate {
+    pub task_id: String,
+    pub title: String,
+    pub status: TaskStatus,
+    pub model: String,
+    pub max_budget_usd: f64,
+    pub max_turns: u32,
+    pub basis: Basis,
+    /// Cost of the task's runs so far
+    pub spent_usd: f64,
+    /// Cost still to come
+    pub cost_usd: Range,
+    /// Wall-clock time still to come
+    pub duration_secs: Range,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Eta {
+    pub low: String,
+    pub expected: String,
+    pub high: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GoalEstimate {
+    pub goal_space_id: String,
+    pub tasks_total: usize,
+    pub tasks_remaining: usize,
+    /// Cost of the goal's runs so far
+    pub spent_usd: f64,
+    /// Cost of the remaining tasks
+    pub remaining_cost_usd: Range,
+    /// Spent plus remaining cost
+    pub cost_usd: Range,
+    /// Wall-clock time until the last remaining task finishes
+    pub duration_secs: Range,
+    pub eta: Eta,
+    /// Remaining tasks on the longest dependency chain, in run order
+    pub critical_path: Vec<String>,
+    /// Successful runs the distributions were built from
+    pub sample_runs: usize,
+    pub computed_at: String,
+    pub tasks: Vec<TaskEstimate>,
+}
+
+/// Cost and duration distributions of past runs
+struct Distribution {
+    costs: Vec<f64>,
+    durations: Vec<f64>,
+}
+
+impl Distribution {
+    fn new<'a>(samples: impl Iterator<Item = &'a RunSample>) -> Distribution {
+        let (mut costs, mut durations): (Vec<f64>, Vec<f64>) =
+            samples.map(|s| (s.cost_usd, s.duration_secs)).unzip();
+        costs.sort_by(f64::total_cmp);
+        durations.sort_by(f64::total_cmp);
+        Distribution { costs, durations }
+    }
+
+    fn len(&self) -> usize {
+        self.costs.len()
+    }
+
+    fn cost(&self) -> Range {
+        spread(&self.costs)
+    }
+
+    fn duration(&self) -> Range {
+        spread(&self.durations)
+    }
+}
+
+/// 10th percentile, mean and 90th percentile of sorted, non-empty values.
+/// The mean is the expected value, so per-task figures add up.
+fn spread(sorted: &[f64]) -> Range {
+    let at = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+    Range {
+        low: at(0.1),
+        expected: sorted.iter().sum::<f64>() / sorted.len() as f64,
+        high: at(0.9),
+    }
+}
+
+/// Estimate what a goal's remaining tasks will cost and when they'll be done
+pub fn estimate_goal(db: &Database, goal: &GoalSpace) -> Result<GoalEstimate> {
+    let tasks = db.list_tasks(&goal.id)?;
+    let settings = tasks
+        .iter()
+        .map(|t| Ok((t.id.clone(), effective_settings(db, goal, t)?)))
+        .collect::<Result<HashMap<_, _>>>()?;
+    let runs = db.list_agent_runs_filtered(&ListFilter {
+        goal_space_id: Some(goal.id.clone()),
+        ..Default::default()
+    })?;
+    let samples = db.recent_run_samples(SAMPLE_LIMIT)?;
+    let max_concurrent = goal_settings(db, goal)?.max_concurrent_agents();
+    Ok(estimate(
+        &goal.id,
+        &tasks,
+        &settings,
+        &runs,
+        &samples,
+        max_concurrent,
+        Utc::now(),
+    ))
+}
+
+/// Figures for every task, then the goal: remaining cost is the sum over
+/// remaining tasks, and duration is the longest dependency chain through
+/// them, or the remaining work spread over `max_concurrent` agents when
+/// that takes longer
+pub fn estimate(
+    goal_space_id: &str,
+    tasks: &[Task],
+    settings: &HashMap<String, GoalSettings>,
+    runs: &[AgentRun],
+    samples: &[RunSample],
+    max_concurrent: Option<u32>,
+    now: DateTime<Utc>,
+) -> GoalEstimate {
+    let all_models = Distribution::new(samples.iter());
+    let mut by_model: HashMap<String, Distribution> = HashMap::new();
+    let mut runs_by_task: HashMap<&str, Vec<&AgentRun>> = HashMap::new();
+    for run in runs {
+        runs_by_task
+            .entry(run.task_id.as_str())
+            .or_default()
+            .push(run);
+    }
+
+    let mut estimates = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        let effective = settings.get(&task.id).cloned().unwrap_or_default();
+        let model = effective.model();
+        let max_budget_usd = effective.max_budget_usd();
+        let max_turns = effective.max_turns();
+        let task_runs = runs_by_task.remove(task.id.as_str()).unwrap_or_default();
+        let spent_usd: f64 = task_runs.iter().map(|r| r.cost_usd).sum();
+
+        let (basis, cost, duration) =
+            if matches!(task.status, TaskStatus::Done | TaskStatus::AwaitingReview) {
+                (Basis::Actual, Range::default(), Range::default())
+            } else {
+                let for_model = by_model.entry(model.clone()).or_insert_with(|| {
+                    Distribution::new(samples.iter().filter(|s| s.model == model))
+                });
+                let (basis, cost, duration) = if for_model.len() >= MIN_SAMPLES {
+                    (Basis::Model, for_model.cost(), for_model.duration())
+                } else if all_models.len() >= MIN_SAMPLES {
+                    (Basis::AllModels, all_models.cost(), all_models.duration())
+                } else {
+                    let longest = max_turns as f64 * DEFAULT_SECS_PER_TURN;
+                    (
+                        Basis::Default,
+                        Range {
+                            low: max_budget_usd * 0.1,
+                            expected: max_budget_usd * 0.4,
+                            high: max_budget_usd,
+                        },
+                        Range {
+                            low: longest * 0.1,
+                            expected: longest * 0.4,
+                            high: longest,
+                        },
+                    )
+                };
+                // A run in flight has already used part of its time and budget
+                let active = task_runs.iter().find(|r| !r.status.is_terminal());
+                let elapsed = active
+                    .and_then(|r| DateTime::parse_from_rfc3339(&r.started_at).ok())
+                    .map(|started| (now - started.with_timezone(&Utc)).num_seconds().max(0) as f64)
+                    .unwrap_or(0.0);
+                let active_cost = active.map_or(0.0, |r| r.cost_usd);
+                (
+                    basis,
+                    cost.cap(max_budget_usd).less(active_cost),
+                    duration.less(elapsed),
+                )
+            };
+
+        estimates.push(TaskEstimate {
+            task_id: task.id.clone(),
+            title: task.title.clone(),
+            status: task.status,
+            model,
+            max_budget_usd,
+            max_turns,
+            basis,
+            spent_usd,
+            cost_usd: cost,
+            duration_secs: duration,
+        });
+    }
+
+    let remaining: Vec<&TaskEstimate> = estimates
+        .iter()
+        .filter(|e| e.basis != Basis::Actual)
+        .collect();
+    let remaining_cost_usd = remaining
+        .iter()
+        .fold(Range::default(), |sum, e| sum.add(e.cost_usd));
+    let spent_usd: f64 = runs.iter().map(|r| r.cost_usd).sum();
+    let (mut duration_secs, critical_path) = critical_path(tasks, &estimates);
+    if let Some(slots) = max_concurrent {
+        let work = remaining
+            .iter()
+            .fold(Range::default(), |sum, e| sum.add(e.duration_secs));
+        duration_secs = duration_secs.max(Range {
+            low: work.low / slots as f64,
+            expected: work.expected / slots as f64,
+            high: work.high / slots as f64,
+        });
+    }
+    let at =
+        |secs: f64| (now + chrono::Duration::milliseconds((secs * 1000.0) as i64)).to_rfc3339();
+
+    GoalEstimate {
+        goal_space_id: goal_space_id.to_string(),
+        tasks_total: tasks.len(),
+        tasks_remaining: remaining.len(),
+        spent_usd,
+        remaining_cost_usd,
+        cost_usd: remaining_cost_usd.add(Range {
+            low: spent_usd,
+            expected: spent_usd,
+            high: spent_usd,
+        }),
+        duration_secs,
+        eta: Eta {
+            low: at(duration_secs.low),
+            expected: at(duration_secs.expected),
+            high: at(duration_secs.high),
+        },
+        critical_path,
+        sample_runs: samples.len(),
+        computed_at: now.to_rfc3339(),
+        tasks: estimates,
+    }
+}
+
+/// Longest remaining duration through the dependency graph, and the chain
+/// of remaining tasks that sets the expected figure
+fn critical_path(tasks: &[Task], estimates: &[TaskEstimate]) -> (Range, Vec<String>) {
+    let by_id: HashMap<&str, (&Task, &TaskEstimate)> = tasks
+        .iter()
+        .zip(estimates)
+        .map(|(t, e)| (t.id.as_str(), (t, e)))
+        .collect();
+
+    // Finish time of each task, counted from now, and the dependency it waits on longest
+    let mut finish: HashMap<&str, (Range, Option<&str>)> = HashMap::new();
+    fn visit<'a>(
+        id: &'a str,
+        by_id: &HashMap<&'a str, (&'a Task, &'a TaskEstimate)>,
+        finish: &mut HashMap<&'a str, (Range, Option<&'a str>)>,
+        visiting: &mut HashSet<&'a str>,
+    ) -> Range {
+        if let Some((range, _)) = finish.get(id) {
+            return *range;
+        }
+        let Some((task, estimate)) = by_id.get(id) else {
+            return Range::default();
+        };
+        // Graphs are validated on write; a cycle here just stops the walk
+        if !visiting.insert(id) {
+            return Range::default();
+        }
+        let mut start = Range::default();
+        let mut waits_on = None;
+        for dep in &task.depends_on {
+            let dep_finish = visit(dep.as_str(), by_id, finish, visiting);
+            if waits_on.is_none() || dep_finish.expected > start.expected {
+                waits_on = Some(dep.as_str());
+            }
+            start = start.max(dep_finish);
+        }
+        visiting.remove(id);
+        let end = start.add(estimate.duration_secs);
+        finish.insert(id, (end, waits_on));
+        end
+    }
+
+    let mut visiting = HashSet::new();
+    let mut longest = Range::default();
+    let mut last = None;
+    for task in tasks {
+        let end = visit(&task.id, &by_id, &mut finish, &mut visiting);
+        if last.is_none() || end.expected > longest.expected {
+            last = Some(task.id.as_str());
+        }
+        longest = longest.max(end);
+    }
+
+    let mut path = Vec::new();
+    while let Some(id) = last {
+        if by_id[id].1.basis != Basis::Actual {
+            path.push(id.to_string());
+        }
+        last = finish.get(id).and_then(|(_, waits_on)| *waits_on);
+    }
+    path.reverse();
+    (longest, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{CreateGoalSpace, CreateTask};
+    use crate::goal::task::{walk_task_to, RunStatus};
+
+    fn test_db() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        db
+    }
+
+    fn task(db: &Database, goal_id: &str, title: &str, depends_on: Vec<String>) -> Task {
+        db.create_task(
+            goal_id,
+            &CreateTask {
+                title: title.into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on,
+                settings: Default::default(),
+            },
+        )
+        .unwrap()
+    }
+
+    fn sample(model: &str, cost_usd: f64, duration_secs: f64) -> RunSample {
+        RunSample {
+            model: model.into(),
+            cost_usd,
+            duration_secs,
+        }
+    }
+
+    #[test]
+    fn test_spread_uses_percentiles_and_mean() {
+        let values: Vec<f64> = (1..=11).map(f64::from).collect();
+        let range = spread(&values);
+        assert_eq!(range.low, 2.0);
+        assert_eq!(range.expected, 6.0);
+        assert_eq!(range.high, 10.0);
+    }
+
+    #[test]
+    fn test_estimate_follows_critical_path_and_history() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        // a -> b -> c is the long chain; d runs alongside it
+        let a = task(&db, &goal.id, "a", vec![]);
+        let b = task(&db, &goal.id, "b", vec![a.id.clone()]);
+        let c = task(&db, &goal.id, "c", vec![b.id.clone()]);
+        let d = task(&db, &goal.id, "d", vec![]);
+        let tasks = db.list_tasks(&goal.id).unwrap();
+        let settings: HashMap<String, GoalSettings> = HashMap::new();
+        let samples = vec![
+            sample("sonnet", 1.0, 100.0),
+            sample("sonnet", 1.0, 100.0),
+            sample("sonnet", 1.0, 100.0),
+        ];
+
+        let est = estimate(&goal.id, &tasks, &settings, &[], &samples, None, Utc::now());
+        assert_eq!(est.tasks_remaining, 4);
+        assert_eq!(est.remaining_cost_usd.expected, 4.0);
+        assert_eq!(est.duration_secs.expected, 300.0);
+        assert_eq!(est.critical_path, vec![a.id.clone(), b.id, c.id]);
+        assert!(est.tasks.iter().all(|t| t.basis == Basis::Model));
+        assert!(est.tasks.iter().any(|t| t.task_id == d.id));
+
+        // Finishing a task takes it off the path and out of the remaining cost
+        walk_task_to(&db, &a.id, TaskStatus::Done);
+        let tasks = db.list_tasks(&goal.id).unwrap();
+        let est = estimate(&goal.id, &tasks, &settings, &[], &samples, None, Utc::now());
+        assert_eq!(est.tasks_remaining, 3);
+        assert_eq!(est.remaining_cost_usd.expected, 3.0);
+        assert_eq!(est.duration_secs.expected, 200.0);
+        assert_eq!(est.critical_path.len(), 2);
+
+        // One agent at a time has to work through all three in turn
+        let est = estimate(
+            &goal.id,
+            &tasks,
+            &settings,
+            &[],
+            &samples,
+            Some(1),
+            Utc::now(),
+        );
+        assert_eq!(est.duration_secs.expected, 300.0);
+    }
+
+    #[test]
+    fn test_estimate_falls_back_to_budget_and_turns() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: GoalSettings {
+                    max_budget_usd: Some(2.0),
+                    max_turns: Some(10),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        task(&db, &goal.id, "a", vec![]);
+
+        let est = estimate_goal(&db, &goal).unwrap();
+        assert_eq!(est.tasks[0].basis, Basis::Default);
+        assert_eq!(est.remaining_cost_usd.high, 2.0);
+        assert_eq!(est.duration_secs.high, 10.0 * DEFAULT_SECS_PER_TURN);
+    }
+
+    #[test]
+    fn test_estimate_caps_history_at_budget_and_counts_spend() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: GoalSettings {
+                    model: Some("opus".into()),
+                    max_budget_usd: Some(1.0),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        let a = task(&db, &goal.id, "a", vec![]);
+        let run = db
+            .create_agent_run(&a.id, &goal.id, None, None, "opus", None)
+            .unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Running)
+            .unwrap();
+        db.update_agent_run_cost(&run.id, 0.25, 100, 100).unwrap();
+        let tasks = db.list_tasks(&goal.id).unwrap();
+        let settings = HashMap::from([(
+            a.id.clone(),
+            crate::goal::space::effective_settings(&db, &goal, &a).unwrap(),
+        )]);
+        let runs = vec![db.get_agent_run(&run.id).unwrap().unwrap()];
+        // Only sonnet history: falls back to all models, capped at the opus budget
+        let samples = vec![
+            sample("sonnet", 3.0, 60.0),
+            sample("sonnet", 3.0, 60.0),
+            sample("sonnet", 3.0, 60.0),
+        ];
+
+        let est = estimate(
+            &goal.id,
+            &tasks,
+            &settings,
+            &runs,
+            &samples,
+            None,
+            Utc::now(),
+        );
+        assert_eq!(est.tasks[0].basis, Basis::AllModels);
+        assert_eq!(est.spent_usd, 0.25);
+        assert_eq!(est.remaining_cost_usd.high, 0.75);
+        assert_eq!(est.cost_usd.high, 1.0);
+    }
+}
@@ -0,0 +1,352 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+use crate::db::Database;
+use crate::goal::space::{goal_settings, ready_tasks};
+
+/// What a goal wants from the fleet right now
+#[derive(Debug, Clone)]
+pub struct GoalDemand {
+    pub goal_space_id: String,
+    pub name: String,
+    pub weight: f64,
+    pub min_agents: u32,
+    pub max_agents: Option<u32>,
+    pub running: usize,
+    pub ready: usize,
+    /// Seconds since the goal last started an agent
+    pub waiting_secs: i64,
+}
+
+/// A goal's slice of the fleet
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GoalShare {
+    pub goal_space_id: String,
+    pub name: String,
+    pub weight: f64,
+    pub min_agents: u32,
+    pub max_agents: Option<u32>,
+    pub running: usize,
+    pub ready: usize,
+    pub waiting_secs: i64,
+    /// Has ready tasks and has waited past the starvation threshold
+    pub starving: bool,
+    /// Agents the goal is entitled to run at once
+    pub target_slots: usize,
+    /// Agents the goal may start now
+    pub free_slots: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FleetShares {
+    pub total_slots: Option<u32>,
+    pub starvation_secs: u64,
+    pub running: usize,
+    /// Slots free across the fleet; None when there is no limit
+    pub free_slots: Option<usize>,
+    pub goals: Vec<GoalShare>,
+}
+
+/// Split `total_slots` between goals. Starving goals get one more agent
+/// first, then every goal gets up to its `min_agents`, then the remaining
+/// slots go one at a time to the goal with the fewest slots per unit of
+/// weight. No goal is given more than it can use (running plus ready, capped
+/// at `max_agents`). Running agents are never taken away, so a goal over
+/// its share just starts nothing until it drops back under.
+pub fn allocate(
+    total_slots: Option<u32>,
+    starvation_secs: u64,
+    demands: Vec<GoalDemand>,
+) -> Vec<GoalShare> {
+    let usable = |d: &GoalDemand| {
+        let wanted = d.running + d.ready;
+        d.max_agents.map_or(wanted, |max| wanted.min(max as usize))
+    };
+    let starving = |d: &GoalDemand| {
+        d.ready > 0 && usable(d) > d.running && d.waiting_secs >= starvation_secs as i64
+    };
+
+    let Some(total) = total_slots.map(|t| t as usize) else {
+        return demands
+            .into_iter()
+            .map(|d| {
+                let target = usable(&d);
+                share(&d, starving(&d), target, target.saturating_sub(d.running))
+            })
+            .collect();
+    };
+
+    let cap: Vec<usize> = demands.iter().map(usable).collect();
+    let mut target = vec![0usize; demands.len()];
+    let mut left = total;
+
+    // Longest-waiting starving goals first
+    let mut order: Vec<usize> = (0..demands.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(demands[i].waiting_secs));
+    for &i in order.iter().filter(|&&i| starving(&demands[i])) {
+        let want = (demands[i].running + 1).min(cap[i]);
+        let grant = want.min(left);
+        target[i] = grant;
+        left -= grant;
+    }
+
+    // Minimums, heaviest goals first
+    order.sort_by(|&a, &b| demands[b].weight.total_cmp(&demands[a].weight));
+    for &i in &order {
+        let want = (demands[i].min_agents as usize).min(cap[i]);
+        let grant = want.saturating_sub(target[i]).min(left);
+        target[i] += grant;
+        left -= grant;
+    }
+
+    // Weighted water-filling
+    while left > 0 {
+        let next = (0..demands.len())
+            .filter(|&i| target[i] < cap[i])
+            .min_by(|&a, &b| {
+                let (sa, sb) = (
+                    target[a] as f64 / demands[a].weight,
+                    target[b] as f64 / demands[b].weight,
+                );
+                sa.total_cmp(&sb)
+                    .then(demands[b].waiting_secs.cmp(&demands[a].waiting_secs))
+            });
+        let Some(i) = next else { break };
+        target[i] += 1;
+        left -= 1;
+    }
+
+    // Hand out the slots actually free, most underserved goal first
+    let running: usize = demands.iter().map(|d| d.running).sum();
+    let mut free = total.saturating_sub(running);
+    let mut by_need: Vec<usize> = (0..demands.len()).collect();
+    by_need.sort_by(|&a, &b| {
+        starving(&demands[b]).cmp(&starving(&demands[a])).then(
+            (demands[a].running as f64 / demands[a].weight)
+                .total_cmp(&(demands[b].running as f64 / demands[b].weight)),
+        )
+    });
+    let mut free_slots = vec![0usize; demands.len()];
+    for i in by_need {
+        let grant = target[i].saturating_sub(demands[i].running).min(free);
+        free_slots[i] = grant;
+        free -= grant;
+    }
+
+    demands
+        .iter()
+        .enumerate()
+        .map(|(i, d)| share(d, starving(d), target[i], free_slots[i]))
+        .collect()
+}
+
+fn share(d: &GoalDemand, starving: bool, target_slots: usize, free_slots: usize) -> GoalShare {
+    GoalShare {
+        goal_space_id: d.goal_space_id.clone(),
+        name: d.name.clone(),
+        weight: d.weight,
+        min_agents: d.min_agents,
+        max_agents: d.max_agents,
+        running: d.running,
+        ready: d.ready,
+        waiting_secs: d.waiting_secs,
+        starving,
+        target_slots,
+        free_slots,
+    }
+}
+
+/// How the fleet's agent slots are split between the goals that can dispatch
+pub fn fleet_shares(db: &Database) -> Result<FleetShares> {
+    let config = db.get_scheduler_config()?;
+    let mut running: HashMap<String, usize> = HashMap::new();
+    for run in db.list_active_agent_runs()? {
+        *running.entry(run.goal_space_id).or_default() += 1;
+    }
+    let last_starts = db.last_agent_starts()?;
+    let now = Utc::now();
+
+    let mut demands = Vec::new();
+    for goal in db.list_goal_spaces()? {
+        if goal.status == "completed" || goal.status == "archived" {
+            continue;
+        }
+        let settings = goal_settings(db, &goal)?;
+        let since = last_starts.get(&goal.id).unwrap_or(&goal.created_at);
+        let waiting_secs = DateTime::parse_from_rfc3339(since)
+            .map(|t| (now - t.with_timezone(&Utc)).num_seconds().max(0))
+            .unwrap_or(0);
+        demands.push(GoalDemand {
+            running: running.get(&goal.id).copied().unwrap_or(0),
+            ready: ready_tasks(db, &goal)?.len(),
+            goal_space_id: goal.id,
+            name: goal.name,
+            weight: settings.share_weight(),
+            min_agents: settings.min_agents(),
+            max_agents: settings.max_concurrent_agents(),
+            waiting_secs,
+        });
+    }
+
+    let total_running: usize = running.values().sum();
+    Ok(FleetShares {
+        total_slots: config.total_slots,
+        starvation_secs: config.starvation_secs,
+        running: total_running,
+        free_slots: config
+            .total_slots
+            .map(|t| (t as usize).saturating_sub(total_running)),
+        goals: allocate(config.total_slots, config.starvation_secs, demands),
+    })
+}
+
+/// Agents a goal may start under its fair share; None when the fleet has no limit
+pub fn goal_free_slots(db: &Database, goal_space_id: &str) -> Result<Option<usize>> {
+    if db.get_scheduler_config()?.total_slots.is_none() {
+        return Ok(None);
+    }
+    let shares = fleet_shares(db)?;
+    Ok(Some(
+        shares
+            .goals
+            .iter()
+            .find(|g| g.goal_space_id == goal_space_id)
+            .map_or(0, |g| g.free_slots),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::UpdateSchedulerConfig;
+
+    fn demand(id: &str, weight: f64, running: usize, ready: usize) -> GoalDemand {
+        GoalDemand {
+            goal_space_id: id.into(),
+            name: id.into(),
+            weight,
+            min_agents: 0,
+            max_agents: None,
+            running,
+            ready,
+            waiting_secs: 0,
+        }
+    }
+
+    fn targets(shares: &[GoalShare]) -> Vec<(usize, usize)> {
+        shares
+            .iter()
+            .map(|s| (s.target_slots, s.free_slots))
+            .collect()
+    }
+
+    #[test]
+    fn test_allocate_splits_by_weight() {
+        let shares = allocate(
+            Some(6),
+            600,
+            vec![demand("a", 2.0, 0, 10), demand("b", 1.0, 0, 10)],
+        );
+        assert_eq!(targets(&shares), vec![(4, 4), (2, 2)]);
+    }
+
+    #[test]
+    fn test_allocate_gives_unused_share_away_and_respects_limits() {
+        let mut capped = demand("c", 1.0, 0, 10);
+        capped.max_agents = Some(1);
+        let shares = allocate(
+            Some(6),
+            600,
+            vec![demand("a", 1.0, 0, 1), demand("b", 1.0, 0, 10), capped],
+        );
+        assert_eq!(targets(&shares), vec![(1, 1), (4, 4), (1, 1)]);
+    }
+
+    #[test]
+    fn test_allocate_does_not_let_first_goal_take_every_slot() {
+        // a grabbed the whole fleet before b had work
+        let shares = allocate(
+            Some(4),
+            600,
+            vec![demand("a", 1.0, 4, 3), demand("b", 1.0, 0, 3)],
+        );
+        assert_eq!(targets(&shares), vec![(2, 0), (2, 0)]);
+
+        // Once one of a's agents finishes, the free slot goes to b
+        let shares = allocate(
+            Some(4),
+            600,
+            vec![demand("a", 1.0, 3, 3), demand("b", 1.0, 0, 3)],
+        );
+        assert_eq!(targets(&shares), vec![(2, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn test_allocate_minimums_and_starvation() {
+        let mut guaranteed = demand("a", 0.1, 0, 5);
+        guaranteed.min_agents = 2;
+        let mut starving = demand("b", 0.01, 0, 5);
+        starving.waiting_secs = 900;
+        let shares = allocate(
+            Some(4),
+            600,
+            vec![guaranteed, starving, demand("c", 10.0, 0, 5)],
+        );
+        assert!(shares[1].starving);
+        assert_eq!(targets(&shares), vec![(2, 2), (1, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn test_allocate_without_limit_only_applies_goal_maximum() {
+        let mut capped = demand("a", 1.0, 1, 10);
+        capped.max_agents = Some(3);
+        let shares = allocate(None, 600, vec![capped, demand("b", 1.0, 0, 2)]);
+        assert_eq!(targets(&shares), vec![(3, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn test_goal_free_slots_follows_scheduler_config() {
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        let goal = db
+            .create_goal_space(&crate::db::queries::CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        for title in ["a", "b", "c"] {
+            db.create_task(
+                &goal.id,
+                &crate::db::queries::CreateTask {
+                    title: title.into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        }
+
+        assert_eq!(goal_free_slots(&db, &goal.id).unwrap(), None);
+        let config = db
+            .update_scheduler_config(&UpdateSchedulerConfig {
+                total_slots: Some(2),
+                starvation_secs: None,
+            })
+            .unwrap();
+        assert_eq!(config.total_slots, Some(2));
+        assert_eq!(config.starvation_secs, 600);
+        assert_eq!(goal_free_slots(&db, &goal.id).unwrap(), Some(2));
+
+        db.update_scheduler_config(&UpdateSchedulerConfig {
+            total_slots: Some(0),
+            starvation_secs: None,
+        })
+        .unwrap();
+        assert_eq!(goal_free_slots(&db, &goal.id).unwrap(), None);
+    }
+}
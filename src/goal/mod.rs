@@ -1,4 +1,8 @@
+pub mod bundle;
 pub mod chat;
 pub mod decompose;
+pub mod estimate;
+pub mod fair_share;
+pub mod schedule;
 pub mod space;
 pub mod task;
@@ -0,0 +1,302 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+use crate::db::queries::{GoalSpace, Task};
+use crate::db::Database;
+use crate::goal::estimate::{estimate_goal, Basis};
+use crate::goal::fair_share::goal_free_slots;
+use crate::goal::space::{goal_settings, ready_tasks};
+use crate::goal::task::TaskStatus;
+
+/// A pending task's place in the dispatch order
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ScheduledTask {
+    /// Position in the dispatch order, from 1
+    pub rank: usize,
+    pub task_id: String,
+    pub title: String,
+    pub priority: i32,
+    /// Whether the task's dependencies are satisfied
+    pub ready: bool,
+    /// Unfinished dependencies the task waits on
+    pub waiting_on: Vec<String>,
+    /// Unfinished tasks that transitively wait on this one
+    pub dependents: usize,
+    pub expected_duration_secs: f64,
+    pub duration_basis: Basis,
+    /// Whether the next dispatch would start this task
+    pub dispatch_next: bool,
+    /// Why the task is where it is
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Schedule {
+    pub goal_space_id: String,
+    pub max_concurrent_agents: Option<u32>,
+    /// Agents of this goal running now
+    pub running: usize,
+    /// The goal's free slots under the fleet's fair share; None when the
+    /// fleet has no limit
+    pub fair_share_slots: Option<usize>,
+    /// Agents that can start now; None when there is no limit
+    pub free_slots: Option<usize>,
+    pub tasks: Vec<ScheduledTask>,
+}
+
+/// Rank a goal's pending tasks. Ready tasks come first, then higher
+/// `priority`, then tasks more others transitively wait on, then longer
+/// expected runs, so the long chains start early. Ties keep list order.
+pub fn rank(
+    tasks: &[Task],
+    ready: &HashSet<&str>,
+    durations: &HashMap<&str, (f64, Basis)>,
+) -> Vec<ScheduledTask> {
+    let unfinished: HashSet<&str> = tasks
+        .iter()
+        .filter(|t| !matches!(t.status, TaskStatus::Done | TaskStatus::AwaitingReview))
+        .map(|t| t.id.as_str())
+        .collect();
+    let mut waiting: HashMap<&str, Vec<&str>> = HashMap::new();
+    for task in tasks.iter().filter(|t| unfinished.contains(t.id.as_str())) {
+        for dep in &task.depends_on {
+            waiting.entry(dep.as_str()).or_default().push(&task.id);
+        }
+    }
+
+    let mut ranked: Vec<ScheduledTask> = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Pending)
+        .map(|task| {
+            // Everything downstream, each task counted once
+            let mut seen = HashSet::new();
+            let mut stack = vec![task.id.as_str()];
+            while let Some(id) = stack.pop() {
+                for dependent in waiting.get(id).into_iter().flatten() {
+                    if *dependent != task.id && seen.insert(*dependent) {
+                        stack.push(dependent);
+                    }
+                }
+            }
+            let (expected_duration_secs, duration_basis) = durations
+                .get(task.id.as_str())
+                .copied()
+                .unwrap_or((0.0, Basis::Default));
+            ScheduledTask {
+                rank: 0,
+                task_id: task.id.clone(),
+                title: task.title.clone(),
+                priority: task.priority,
+                ready: ready.contains(task.id.as_str()),
+                waiting_on: task
+                    .depends_on
+                    .iter()
+                    .filter(|d| unfinished.contains(d.as_str()))
+                    .cloned()
+                    .collect(),
+                dependents: seen.len(),
+                expected_duration_secs,
+                duration_basis,
+                dispatch_next: false,
+                reason: String::new(),
+            }
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        b.ready
+            .cmp(&a.ready)
+            .then(b.priority.cmp(&a.priority))
+            .then(b.dependents.cmp(&a.dependents))
+            .then(
+                b.expected_duration_secs
+                    .total_cmp(&a.expected_duration_secs),
+            )
+    });
+    for (i, task) in ranked.iter_mut().enumerate() {
+        task.rank = i + 1;
+    }
+    ranked
+}
+
+/// The goal's dispatch order, and which tasks the next dispatch would start
+pub fn goal_schedule(db: &Database, goal: &GoalSpace) -> Result<Schedule> {
+    Ok(build(db, goal)?.0)
+}
+
+/// Ready tasks the next dispatch should start, best first, up to the free slots
+pub fn next_tasks(db: &Database, goal: &GoalSpace) -> Result<Vec<Task>> {
+    let (schedule, ready) = build(db, goal)?;
+    let mut by_id: HashMap<String, Task> = ready.into_iter().map(|t| (t.id.clone(), t)).collect();
+    Ok(schedule
+        .tasks
+        .iter()
+        .filter(|t| t.dispatch_next)
+        .filter_map(|t| by_id.remove(&t.task_id))
+        .collect())
+}
+
+/// The schedule along with the ready tasks it was built from
+fn build(db: &Database, goal: &GoalSpace) -> Result<(Schedule, Vec<Task>)> {
+    let tasks = db.list_tasks(&goal.id)?;
+    let ready = ready_tasks(db, goal)?;
+    let ready_ids: HashSet<&str> = ready.iter().map(|t| t.id.as_str()).collect();
+    let estimate = estimate_goal(db, goal)?;
+    let durations: HashMap<&str, (f64, Basis)> = estimate
+        .tasks
+        .iter()
+        .map(|t| (t.task_id.as_str(), (t.duration_secs.expected, t.basis)))
+        .collect();
+
+    let max_concurrent_agents = goal_settings(db, goal)?.max_concurrent_agents();
+    let running = db
+        .list_active_agent_runs()?
+        .iter()
+        .filter(|r| r.goal_space_id == goal.id)
+        .count();
+    let goal_free = max_concurrent_agents.map(|max| (max as usize).saturating_sub(running));
+    let fair_share_slots = goal_free_slots(db, &goal.id)?;
+    let free_slots = match (goal_free, fair_share_slots) {
+        (Some(own), Some(fleet)) => Some(own.min(fleet)),
+        (own, fleet) => own.or(fleet),
+    };
+
+    let mut ranked = rank(&tasks, &ready_ids, &durations);
+    let mut slots = free_slots.unwrap_or(usize::MAX);
+    for task in &mut ranked {
+        let why = format!(
+            "priority {}, {} waiting on it, ~{:.0} min expected",
+            task.priority,
+            task.dependents,
+            task.expected_duration_secs / 60.0
+        );
+        task.reason = if !task.ready {
+            format!(
+                "waiting on {} unfinished dependencies",
+                task.waiting_on.len()
+            )
+        } else if slots == 0 {
+            match max_concurrent_agents {
+                Some(max) if goal_free == Some(0) => format!(
+                    "{}; no free agent slot ({} of {} running)",
+                    why, running, max
+                ),
+                _ => format!("{}; no free agent slot in the goal's fair share", why),
+            }
+        } else {
+            slots -= 1;
+            task.dispatch_next = true;
+            why
+        };
+    }
+
+    let schedule = Schedule {
+        goal_space_id: goal.id.clone(),
+        max_concurrent_agents,
+        running,
+        fair_share_slots,
+        free_slots,
+        tasks: ranked,
+    };
+    Ok((schedule, ready))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{CreateGoalSpace, CreateTask, GoalSettings};
+    use crate::goal::task::RunStatus;
+
+    fn test_db() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        db.run_migrations().unwrap();
+        db
+    }
+
+    fn task(db: &Database, goal_id: &str, title: &str, priority: i32, deps: Vec<String>) -> Task {
+        db.create_task(
+            goal_id,
+            &CreateTask {
+                title: title.into(),
+                description: "D".into(),
+                priority,
+                depends_on: deps,
+                settings: Default::default(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rank_orders_by_priority_then_dependents_then_duration() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let leaf = task(&db, &goal.id, "leaf", 0, vec![]);
+        let short = task(&db, &goal.id, "short", 0, vec![]);
+        let long = task(&db, &goal.id, "long", 0, vec![]);
+        let root = task(&db, &goal.id, "root", 0, vec![]);
+        let urgent = task(&db, &goal.id, "urgent", 5, vec![]);
+        let mid = task(&db, &goal.id, "mid", 0, vec![root.id.clone()]);
+        task(&db, &goal.id, "end", 0, vec![mid.id.clone()]);
+
+        let tasks = db.list_tasks(&goal.id).unwrap();
+        let ready: HashSet<&str> = [&leaf, &short, &long, &root, &urgent]
+            .iter()
+            .map(|t| t.id.as_str())
+            .collect();
+        let durations = HashMap::from([
+            (short.id.as_str(), (60.0, Basis::Model)),
+            (long.id.as_str(), (600.0, Basis::Model)),
+        ]);
+        let ranked = rank(&tasks, &ready, &durations);
+        let titles: Vec<&str> = ranked.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec!["urgent", "root", "long", "short", "leaf", "mid", "end"]
+        );
+        assert_eq!(ranked[1].dependents, 2);
+        assert_eq!(ranked[5].waiting_on, vec![root.id.clone()]);
+        assert!(!ranked[5].ready);
+    }
+
+    #[test]
+    fn test_next_tasks_respects_concurrency_limit() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: GoalSettings {
+                    max_concurrent_agents: Some(2),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        let running = task(&db, &goal.id, "running", 0, vec![]);
+        task(&db, &goal.id, "low", 0, vec![]);
+        let high = task(&db, &goal.id, "high", 3, vec![]);
+        let run = db
+            .create_agent_run(&running.id, &goal.id, None, None, "sonnet", None)
+            .unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Running)
+            .unwrap();
+        crate::goal::task::walk_task_to(&db, &running.id, TaskStatus::Running);
+
+        let next = next_tasks(&db, &goal).unwrap();
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].id, high.id);
+
+        let schedule = goal_schedule(&db, &goal).unwrap();
+        assert_eq!(schedule.running, 1);
+        assert_eq!(schedule.free_slots, Some(1));
+        assert!(schedule.tasks[1].reason.contains("no free agent slot"));
+    }
+}
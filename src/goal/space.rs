@@ -1,5 +1,7 @@
+use crate::db::queries::{Actor, GoalSettings, GoalSpace, MergeMode, Task, UpdateTask};
 use crate::db::Database;
-use anyhow::Result;
+use crate::goal::task::TaskStatus;
+use anyhow::{Context, Result};
 
 /// Check if all tasks in a goal space are done, and if so mark the goal as completed.
 /// This operation is atomic - it uses a single SQL statement to check and update,
@@ -9,23 +11,218 @@ pub fn check_goal_completion(db: &Database, goal_space_id: &str) -> Result<bool>
     let was_completed = db.mark_goal_completed_if_all_tasks_done(goal_space_id)?;
 
     if was_completed {
-        db.insert_goal_history(goal_space_id, "goal_completed", "All tasks completed", None)?;
+        db.insert_goal_history(
+            goal_space_id,
+            Actor::Dispatch,
+            "goal_completed",
+            "All tasks completed",
+            None,
+        )?;
         tracing::info!("Goal space {} completed", goal_space_id);
     }
 
     Ok(was_completed)
 }
 
+/// Resolve the settings a task runs with: project settings (matched by repo path)
+/// are overridden by goal settings, which are overridden by task settings
+pub fn effective_settings(db: &Database, goal: &GoalSpace, task: &Task) -> Result<GoalSettings> {
+    Ok(goal_settings(db, goal)?.merge(&task.settings))
+}
+
+/// Resolve goal-wide settings: project settings overridden by goal settings
+pub fn goal_settings(db: &Database, goal: &GoalSpace) -> Result<GoalSettings> {
+    let project = db.get_project_by_path(&goal.repo_path)?;
+    let base = project.map(|p| p.settings).unwrap_or_default();
+    Ok(base.merge(&goal.settings))
+}
+
+/// Resolve the merge mode for a task from its effective settings
+pub fn task_merge_mode(db: &Database, task_id: &str) -> Result<MergeMode> {
+    let task = db.get_task(task_id)?.context("Task not found")?;
+    let goal = db
+        .get_goal_space(&task.goal_space_id)?
+        .context("Goal space not found")?;
+    Ok(effective_settings(db, &goal, &task)?.merge_mode())
+}
+
+/// Pending tasks that can be dispatched now. A dependency is satisfied once it is
+/// done and its merge isn't deferred; stacked tasks also accept dependencies still
+/// awaiting review or waiting to merge, since they build on the unmerged branch.
+pub fn ready_tasks(db: &Database, goal: &GoalSpace) -> Result<Vec<Task>> {
+    let tasks = db.list_tasks(&goal.id)?;
+    let deferred: std::collections::HashSet<String> = db
+        .list_deferred_merges(Some(&goal.id))?
+        .into_iter()
+        .filter_map(|m| m.task_id)
+        .collect();
+    let by_id: std::collections::HashMap<&str, &Task> =
+        tasks.iter().map(|t| (t.id.as_str(), t)).collect();
+
+    let mut ready = Vec::new();
+    for task in tasks.iter().filter(|t| t.status == TaskStatus::Pending) {
+        let stacked = effective_settings(db, goal, task)?.stacked();
+        let satisfied = task.depends_on.iter().all(|dep| {
+            match by_id.get(dep.as_str()) {
+                // Deferred merges count as unmerged, whatever the task status says
+                Some(_) if deferred.contains(dep) => stacked,
+                Some(dep) => match dep.status {
+                    TaskStatus::Done => true,
+                    TaskStatus::AwaitingReview => stacked,
+                    _ => false,
+                },
+                None => false,
+            }
+        });
+        if satisfied {
+            ready.push(task.clone());
+        }
+    }
+    Ok(ready)
+}
+
+/// Tasks that depend on `task`, directly or transitively, and whose work already
+/// landed or is waiting for review on top of it. These need a look when `task` is reverted.
+pub fn merged_dependents(db: &Database, task: &Task) -> Result<Vec<Task>> {
+    let tasks = db.list_tasks(&task.goal_space_id)?;
+    let mut seen: std::collections::HashSet<&str> = std::collections::HashSet::new();
+    let mut frontier = vec![task.id.as_str()];
+    let mut affected = Vec::new();
+
+    while let Some(id) = frontier.pop() {
+        for dependent in tasks
+            .iter()
+            .filter(|t| t.depends_on.iter().any(|d| d == id))
+        {
+            if !seen.insert(dependent.id.as_str()) {
+                continue;
+            }
+            if dependent.status == TaskStatus::Done
+                || dependent.status == TaskStatus::AwaitingReview
+            {
+                affected.push(dependent.clone());
+            }
+            frontier.push(dependent.id.as_str());
+        }
+    }
+    Ok(affected)
+}
+
+/// Pending tasks that depend on `task`, directly or transitively, moved to `blocked`
+/// because `task` was reverted and they could otherwise never become ready.
+/// Returns the tasks that were blocked.
+pub fn block_dependents(db: &Database, task: &Task) -> Result<Vec<Task>> {
+    let tasks = db.list_tasks(&task.goal_space_id)?;
+    let mut blocked = Vec::new();
+    for dependent in transitive_dependents(&tasks, &task.id) {
+        if dependent.status != TaskStatus::Pending {
+            continue;
+        }
+        db.update_task(
+            &dependent.id,
+            &UpdateTask {
+                status: Some(TaskStatus::Blocked),
+                ..Default::default()
+            },
+        )?;
+        blocked.push(Task {
+            status: TaskStatus::Blocked,
+            ..dependent.clone()
+        });
+    }
+    Ok(blocked)
+}
+
+/// Move tasks blocked behind `task` back to `pending` once it is retried, unless
+/// another of their dependencies is still reverted. Returns the tasks unblocked.
+pub fn unblock_dependents(db: &Database, task: &Task) -> Result<Vec<Task>> {
+    let tasks = db.list_tasks(&task.goal_space_id)?;
+    let reverted: std::collections::HashSet<&str> = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Reverted)
+        .map(|t| t.id.as_str())
+        .collect();
+    let mut unblocked = Vec::new();
+    for dependent in transitive_dependents(&tasks, &task.id) {
+        if dependent.status != TaskStatus::Blocked
+            || dependent
+                .depends_on
+                .iter()
+                .any(|d| reverted.contains(d.as_str()))
+        {
+            continue;
+        }
+        db.update_task(
+            &dependent.id,
+            &UpdateTask {
+                status: Some(TaskStatus::Pending),
+                ..Default::default()
+            },
+        )?;
+        unblocked.push(Task {
+            status: TaskStatus::Pending,
+            ..dependent.clone()
+        });
+    }
+    Ok(unblocked)
+}
+
+/// Every task that depends on `task_id`, directly or transitively
+fn transitive_dependents<'a>(tasks: &'a [Task], task_id: &str) -> Vec<&'a Task> {
+    let mut seen: std::collections::HashSet<&str> = std::collections::HashSet::new();
+    let mut frontier = vec![task_id];
+    let mut dependents = Vec::new();
+    while let Some(id) = frontier.pop() {
+        for dependent in tasks
+            .iter()
+            .filter(|t| t.depends_on.iter().any(|d| d == id))
+        {
+            if seen.insert(dependent.id.as_str()) {
+                dependents.push(dependent);
+                frontier.push(dependent.id.as_str());
+            }
+        }
+    }
+    dependents
+}
+
+/// Status a task moves to when its agent finishes successfully
+pub fn completed_task_status(db: &Database, task_id: &str) -> TaskStatus {
+    match task_merge_mode(db, task_id) {
+        Ok(MergeMode::Manual) => TaskStatus::AwaitingReview,
+        _ => TaskStatus::Done,
+    }
+}
+
 /// Get summary stats for a goal space
 #[allow(dead_code)]
 pub fn goal_summary(db: &Database, goal_space_id: &str) -> Result<GoalSummary> {
     let tasks = db.list_tasks(goal_space_id)?;
     let total = tasks.len();
-    let done = tasks.iter().filter(|t| t.status == "done").count();
-    let running = tasks.iter().filter(|t| t.status == "running").count();
-    let failed = tasks.iter().filter(|t| t.status == "failed").count();
-    let pending = tasks.iter().filter(|t| t.status == "pending").count();
-    let blocked = tasks.iter().filter(|t| t.status == "blocked").count();
+    let done = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Done)
+        .count();
+    let running = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Running)
+        .count();
+    let failed = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Failed)
+        .count();
+    let pending = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Pending)
+        .count();
+    let blocked = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Blocked)
+        .count();
+    let awaiting_review = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::AwaitingReview)
+        .count();
 
     Ok(GoalSummary {
         total,
@@ -34,6 +231,7 @@ pub fn goal_summary(db: &Database, goal_space_id: &str) -> Result<GoalSummary> {
         failed,
         pending,
         blocked,
+        awaiting_review,
     })
 }
 
@@ -46,12 +244,14 @@ pub struct GoalSummary {
     pub failed: usize,
     pub pending: usize,
     pub blocked: usize,
+    pub awaiting_review: usize,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::db::queries::{CreateGoalSpace, CreateTask, UpdateTask};
+    use crate::db::queries::{CreateGoalSpace, CreateTask};
+    use crate::goal::task::walk_task_to;
 
     fn test_db() -> Database {
         let db = Database::open_in_memory().unwrap();
@@ -117,30 +317,8 @@ mod tests {
             .unwrap();
 
         // Mark both done
-        db.update_task(
-            &t1.id,
-            &UpdateTask {
-                status: Some("done".into()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-        db.update_task(
-            &t2.id,
-            &UpdateTask {
-                status: Some("done".into()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+        walk_task_to(&db, &t1.id, TaskStatus::Done);
+        walk_task_to(&db, &t2.id, TaskStatus::Done);
 
         let completed = check_goal_completion(&db, &goal.id).unwrap();
         assert!(completed);
@@ -186,18 +364,7 @@ mod tests {
         .unwrap();
 
         // Only mark t1 done
-        db.update_task(
-            &t1.id,
-            &UpdateTask {
-                status: Some("done".into()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+        walk_task_to(&db, &t1.id, TaskStatus::Done);
 
         let completed = check_goal_completion(&db, &goal.id).unwrap();
         assert!(!completed);
@@ -254,30 +421,8 @@ mod tests {
         )
         .unwrap();
 
-        db.update_task(
-            &t1.id,
-            &UpdateTask {
-                status: Some("done".into()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-        db.update_task(
-            &t2.id,
-            &UpdateTask {
-                status: Some("running".into()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+        walk_task_to(&db, &t1.id, TaskStatus::Done);
+        walk_task_to(&db, &t2.id, TaskStatus::Running);
 
         let summary = goal_summary(&db, &goal.id).unwrap();
         assert_eq!(summary.total, 3);
@@ -287,6 +432,48 @@ mod tests {
         assert_eq!(summary.failed, 0);
     }
 
+    #[test]
+    fn test_check_goal_completion_waits_for_reverted_task() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let task = |title: &str| {
+            db.create_task(
+                &goal.id,
+                &CreateTask {
+                    title: title.into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap()
+        };
+        let t1 = task("T1");
+        let t2 = task("T2");
+        walk_task_to(&db, &t1.id, TaskStatus::Done);
+        walk_task_to(&db, &t2.id, TaskStatus::Reverted);
+
+        // The reverted work is gone from main, so the goal isn't done
+        assert!(!check_goal_completion(&db, &goal.id).unwrap());
+        assert_eq!(
+            db.get_goal_space(&goal.id).unwrap().unwrap().status,
+            "active"
+        );
+
+        // Retried and done again, it no longer holds the goal open
+        walk_task_to(&db, &t2.id, TaskStatus::Pending);
+        walk_task_to(&db, &t2.id, TaskStatus::Done);
+        assert!(check_goal_completion(&db, &goal.id).unwrap());
+    }
+
     #[test]
     fn test_check_goal_completion_atomic_with_pending_task() {
         // This test verifies that the atomic operation prevents a race condition.
@@ -316,18 +503,7 @@ mod tests {
             .unwrap();
 
         // Mark t1 as done
-        db.update_task(
-            &t1.id,
-            &UpdateTask {
-                status: Some("done".into()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+        walk_task_to(&db, &t1.id, TaskStatus::Done);
 
         // Simulate the race: add a new pending task just before checking completion
         db.create_task(
@@ -351,6 +527,98 @@ mod tests {
         assert_eq!(g.status, "active");
     }
 
+    #[test]
+    fn test_completed_task_status_follows_merge_mode() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: crate::db::queries::GoalSettings {
+                    merge_mode: Some(MergeMode::Manual),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+
+        let reviewed = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: "T1".into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let auto = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: "T2".into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: crate::db::queries::GoalSettings {
+                        merge_mode: Some(MergeMode::Auto),
+                        ..Default::default()
+                    },
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            completed_task_status(&db, &reviewed.id),
+            TaskStatus::AwaitingReview
+        );
+        assert_eq!(completed_task_status(&db, &auto.id), TaskStatus::Done);
+    }
+
+    #[test]
+    fn test_ready_tasks_stacked_accepts_awaiting_review() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let create = |title: &str, deps: Vec<String>, stacked: Option<bool>| {
+            db.create_task(
+                &goal.id,
+                &CreateTask {
+                    title: title.into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: deps,
+                    settings: crate::db::queries::GoalSettings {
+                        stacked,
+                        ..Default::default()
+                    },
+                },
+            )
+            .unwrap()
+        };
+        let base = create("Base", vec![], None);
+        let plain = create("Plain", vec![base.id.clone()], None);
+        let stacked = create("Stacked", vec![base.id.clone()], Some(true));
+        walk_task_to(&db, &base.id, TaskStatus::AwaitingReview);
+
+        let ready = ready_tasks(&db, &goal).unwrap();
+        let ids: Vec<&str> = ready.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec![stacked.id.as_str()]);
+
+        walk_task_to(&db, &base.id, TaskStatus::Done);
+        let ready = ready_tasks(&db, &goal).unwrap();
+        assert_eq!(ready.len(), 2);
+        assert!(ready.iter().any(|t| t.id == plain.id));
+    }
+
     #[test]
     fn test_atomic_completion_returns_false_when_already_completed() {
         let db = test_db();
@@ -377,18 +645,7 @@ mod tests {
             .unwrap();
 
         // Mark task as done
-        db.update_task(
-            &t1.id,
-            &UpdateTask {
-                status: Some("done".into()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+        walk_task_to(&db, &t1.id, TaskStatus::Done);
 
         // First completion should succeed
         let completed = check_goal_completion(&db, &goal.id).unwrap();
@@ -398,4 +655,47 @@ mod tests {
         let completed_again = check_goal_completion(&db, &goal.id).unwrap();
         assert!(!completed_again);
     }
+
+    #[test]
+    fn test_merged_dependents_is_transitive() {
+        let db = test_db();
+        let goal = db
+            .create_goal_space(&CreateGoalSpace {
+                name: "G".into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        let create = |title: &str, deps: Vec<String>, status: TaskStatus| {
+            let task = db
+                .create_task(
+                    &goal.id,
+                    &CreateTask {
+                        title: title.into(),
+                        description: "D".into(),
+                        priority: 0,
+                        depends_on: deps,
+                        settings: Default::default(),
+                    },
+                )
+                .unwrap();
+            walk_task_to(&db, &task.id, status);
+            task
+        };
+        let a = create("A", vec![], TaskStatus::Done);
+        let b = create("B", vec![a.id.clone()], TaskStatus::Pending);
+        let c = create("C", vec![b.id.clone()], TaskStatus::AwaitingReview);
+        let d = create("D", vec![a.id.clone(), c.id.clone()], TaskStatus::Done);
+        create("Unrelated", vec![], TaskStatus::Done);
+
+        let mut titles: Vec<String> = merged_dependents(&db, &a)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        titles.sort();
+        assert_eq!(titles, vec!["C", "D"]);
+        assert!(merged_dependents(&db, &d).unwrap().is_empty());
+    }
 }
@@ -1,41 +1,298 @@
 use anyhow::{bail, Result};
+use std::collections::{HashMap, HashSet};
+
+use crate::db::queries::Task;
+
+/// Lifecycle state of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Pending,
+    Blocked,
+    Assigned,
+    Running,
+    Stalled,
+    AwaitingReview,
+    Done,
+    Failed,
+    Killed,
+    Reverted,
+}
+
+impl TaskStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TaskStatus::Pending => "pending",
+            TaskStatus::Blocked => "blocked",
+            TaskStatus::Assigned => "assigned",
+            TaskStatus::Running => "running",
+            TaskStatus::Stalled => "stalled",
+            TaskStatus::AwaitingReview => "awaiting_review",
+            TaskStatus::Done => "done",
+            TaskStatus::Failed => "failed",
+            TaskStatus::Killed => "killed",
+            TaskStatus::Reverted => "reverted",
+        }
+    }
+
+    /// Statuses this one may move to
+    pub fn allowed_next(self) -> Vec<TaskStatus> {
+        VALID_TRANSITIONS
+            .iter()
+            .filter(|(from, _)| *from == self)
+            .map(|(_, to)| *to)
+            .collect()
+    }
+}
+
+impl std::fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TaskStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "pending" => TaskStatus::Pending,
+            "blocked" => TaskStatus::Blocked,
+            "assigned" => TaskStatus::Assigned,
+            "running" => TaskStatus::Running,
+            "stalled" => TaskStatus::Stalled,
+            "awaiting_review" => TaskStatus::AwaitingReview,
+            "done" => TaskStatus::Done,
+            "failed" => TaskStatus::Failed,
+            "killed" => TaskStatus::Killed,
+            "reverted" => TaskStatus::Reverted,
+            _ => bail!("Unknown task status: {}", s),
+        })
+    }
+}
+
+/// Lifecycle state of an agent run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Spawning,
+    Running,
+    Stalled,
+    Done,
+    Failed,
+    Killed,
+}
+
+impl RunStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RunStatus::Spawning => "spawning",
+            RunStatus::Running => "running",
+            RunStatus::Stalled => "stalled",
+            RunStatus::Done => "done",
+            RunStatus::Failed => "failed",
+            RunStatus::Killed => "killed",
+        }
+    }
+
+    /// Whether the run has finished; finished runs don't change status again
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            RunStatus::Done | RunStatus::Failed | RunStatus::Killed
+        )
+    }
+
+    /// Statuses this one may move to
+    pub fn allowed_next(self) -> Vec<RunStatus> {
+        VALID_RUN_TRANSITIONS
+            .iter()
+            .filter(|(from, _)| *from == self)
+            .map(|(_, to)| *to)
+            .collect()
+    }
+}
+
+impl std::fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for RunStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "spawning" => RunStatus::Spawning,
+            "running" => RunStatus::Running,
+            "stalled" => RunStatus::Stalled,
+            "done" => RunStatus::Done,
+            "failed" => RunStatus::Failed,
+            "killed" => RunStatus::Killed,
+            _ => bail!("Unknown agent run status: {}", s),
+        })
+    }
+}
 
 /// Valid task status transitions
-#[allow(dead_code)]
-const VALID_TRANSITIONS: &[(&str, &str)] = &[
-    ("pending", "assigned"),
-    ("pending", "blocked"),
-    ("pending", "running"), // direct dispatch
-    ("assigned", "running"),
-    ("assigned", "pending"), // unassign
-    ("running", "done"),
-    ("running", "failed"),
-    ("running", "stalled"),
-    ("stalled", "running"), // resumed
-    ("stalled", "failed"),
-    ("stalled", "killed"),
-    ("failed", "pending"),  // retry
-    ("blocked", "pending"), // unblocked
-];
-
-/// Check if a status transition is valid
-#[allow(dead_code)]
-pub fn validate_transition(from: &str, to: &str) -> Result<()> {
-    if from == to {
+const VALID_TRANSITIONS: &[(TaskStatus, TaskStatus)] = {
+    use TaskStatus::*;
+    &[
+        (Pending, Assigned),
+        (Pending, Blocked),
+        (Pending, Running), // direct dispatch
+        (Assigned, Running),
+        (Assigned, Pending), // unassign
+        (Running, Done),
+        (Running, Failed),
+        (Running, Stalled),
+        (Running, AwaitingReview), // finished in manual merge mode
+        (Running, Pending),        // run interrupted by a restart, requeued
+        (Running, Killed),         // agent killed by a user
+        (AwaitingReview, Done),    // approved and merged
+        (AwaitingReview, Pending), // rejected with a follow-up run
+        (AwaitingReview, Failed),  // rejected
+        (Done, Reverted),          // merge reverted on main
+        (Reverted, Pending),       // retried after a revert
+        (Stalled, Running),        // resumed
+        (Stalled, Failed),
+        (Stalled, Killed),
+        (Failed, Pending),  // retry
+        (Killed, Pending),  // retry
+        (Blocked, Pending), // unblocked
+    ]
+};
+
+/// Valid agent run status transitions
+const VALID_RUN_TRANSITIONS: &[(RunStatus, RunStatus)] = {
+    use RunStatus::*;
+    &[
+        (Spawning, Running),
+        (Spawning, Failed), // worktree or process setup failed
+        (Spawning, Killed),
+        (Running, Stalled),
+        (Running, Done),
+        (Running, Failed),
+        (Running, Killed),
+        (Stalled, Running), // output resumed
+        (Stalled, Done),
+        (Stalled, Failed),
+        (Stalled, Killed),
+    ]
+};
+
+/// A status change the state machine doesn't allow
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct InvalidTransition {
+    /// "task" or "agent run"
+    pub kind: &'static str,
+    pub from: String,
+    pub to: String,
+    /// Statuses `from` may move to
+    pub allowed: Vec<String>,
+}
+
+impl std::fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid {} status transition: {} -> {}",
+            self.kind, self.from, self.to
+        )
+    }
+}
+
+impl std::error::Error for InvalidTransition {}
+
+/// Check if a task status transition is valid
+pub fn validate_transition(
+    from: TaskStatus,
+    to: TaskStatus,
+) -> std::result::Result<(), InvalidTransition> {
+    if from == to || VALID_TRANSITIONS.contains(&(from, to)) {
+        return Ok(());
+    }
+    Err(InvalidTransition {
+        kind: "task",
+        from: from.to_string(),
+        to: to.to_string(),
+        allowed: from.allowed_next().iter().map(|s| s.to_string()).collect(),
+    })
+}
+
+/// Check if an agent run status transition is valid
+pub fn validate_run_transition(
+    from: RunStatus,
+    to: RunStatus,
+) -> std::result::Result<(), InvalidTransition> {
+    if from == to || VALID_RUN_TRANSITIONS.contains(&(from, to)) {
         return Ok(());
     }
+    Err(InvalidTransition {
+        kind: "agent run",
+        from: from.to_string(),
+        to: to.to_string(),
+        allowed: from.allowed_next().iter().map(|s| s.to_string()).collect(),
+    })
+}
 
-    for (valid_from, valid_to) in VALID_TRANSITIONS {
-        if *valid_from == from && *valid_to == to {
-            return Ok(());
+/// Move a task to `status` along the shortest chain of valid transitions,
+/// for tests that need a task in a given state
+#[cfg(test)]
+pub(crate) fn walk_task_to(db: &crate::db::Database, task_id: &str, status: TaskStatus) {
+    let current = db.get_task(task_id).unwrap().unwrap().status;
+    let mut paths = std::collections::VecDeque::from([vec![current]]);
+    let mut seen = std::collections::HashSet::from([current]);
+    while let Some(path) = paths.pop_front() {
+        let last = *path.last().unwrap();
+        if last == status {
+            for step in &path[1..] {
+                db.update_task(
+                    task_id,
+                    &crate::db::queries::UpdateTask {
+                        status: Some(*step),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            }
+            return;
+        }
+        for next in last.allowed_next() {
+            if seen.insert(next) {
+                let mut longer = path.clone();
+                longer.push(next);
+                paths.push_back(longer);
+            }
         }
     }
+    panic!("{} is unreachable from {}", status, current);
+}
+
+/// Build the prompt an agent is spawned with for a task.
+/// Feedback from a rejected review is appended so the follow-up run knows what to change.
+pub fn agent_prompt(goal_description: &str, task: &Task, review_feedback: Option<&str>) -> String {
+    let mut prompt = format!(
+        "You are working on the following task as part of the goal: {}\n\n\
+         Task: {}\n\n\
+         Description: {}\n\n\
+         Work in the current directory. Make your changes, test them, and commit when done.",
+        goal_description, task.title, task.description
+    );
+
+    if let Some(feedback) = review_feedback {
+        prompt.push_str(&format!(
+            "\n\nA previous attempt at this task was rejected in review. \
+             Address this feedback:\n{}",
+            feedback
+        ));
+    }
 
-    bail!("Invalid task status transition: {} -> {}", from, to);
+    prompt
 }
 
 /// Check for dependency cycles in a task graph
-#[allow(dead_code)]
 pub fn has_cycle(
     task_id: &str,
     depends_on: &[String],
@@ -62,35 +319,259 @@ pub fn has_cycle(
     false
 }
 
+/// Something wrong with a goal's task dependency graph
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GraphProblem {
+    /// The task lists itself as a dependency
+    SelfDependency { task_id: String },
+    /// A dependency that is not a task
+    MissingDependency {
+        task_id: String,
+        dependency_id: String,
+    },
+    /// A dependency on a task in another goal, which dispatch never sees finish
+    CrossGoalDependency {
+        task_id: String,
+        dependency_id: String,
+        dependency_goal_id: String,
+    },
+    /// The task depends on itself through other tasks
+    Cycle { task_id: String },
+    /// A dependency failed; the task waits until it is retried
+    FailedDependency {
+        task_id: String,
+        dependency_id: String,
+    },
+    /// The task can never be dispatched because a dependency further up its
+    /// chain has one of the problems above
+    Unreachable { task_id: String, blocked_by: String },
+}
+
+impl GraphProblem {
+    pub fn task_id(&self) -> &str {
+        match self {
+            GraphProblem::SelfDependency { task_id }
+            | GraphProblem::MissingDependency { task_id, .. }
+            | GraphProblem::CrossGoalDependency { task_id, .. }
+            | GraphProblem::Cycle { task_id }
+            | GraphProblem::FailedDependency { task_id, .. }
+            | GraphProblem::Unreachable { task_id, .. } => task_id,
+        }
+    }
+}
+
+impl std::fmt::Display for GraphProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphProblem::SelfDependency { task_id } => {
+                write!(f, "task {} depends on itself", task_id)
+            }
+            GraphProblem::MissingDependency {
+                task_id,
+                dependency_id,
+            } => write!(
+                f,
+                "task {} depends on {}, which does not exist",
+                task_id, dependency_id
+            ),
+            GraphProblem::CrossGoalDependency {
+                task_id,
+                dependency_id,
+                dependency_goal_id,
+            } => write!(
+                f,
+                "task {} depends on {}, which belongs to goal {}",
+                task_id, dependency_id, dependency_goal_id
+            ),
+            GraphProblem::Cycle { task_id } => {
+                write!(f, "task {} is part of a dependency cycle", task_id)
+            }
+            GraphProblem::FailedDependency {
+                task_id,
+                dependency_id,
+            } => write!(
+                f,
+                "task {} depends on {}, which failed",
+                task_id, dependency_id
+            ),
+            GraphProblem::Unreachable {
+                task_id,
+                blocked_by,
+            } => write!(
+                f,
+                "task {} can never run because of task {}",
+                task_id, blocked_by
+            ),
+        }
+    }
+}
+
+/// A task write rejected because its dependencies would leave the goal stuck
+#[derive(Debug, Clone)]
+pub struct InvalidDependencies(pub Vec<GraphProblem>);
+
+impl std::fmt::Display for InvalidDependencies {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let problems: Vec<String> = self.0.iter().map(|p| p.to_string()).collect();
+        write!(f, "Invalid task dependencies: {}", problems.join("; "))
+    }
+}
+
+impl std::error::Error for InvalidDependencies {}
+
+/// Problems `depends_on` would cause as the dependencies of `task_id`.
+/// `goal_tasks` is every task in the goal with its current dependencies;
+/// `foreign` maps dependency ids found in other goals to their goal.
+pub fn dependency_problems(
+    task_id: &str,
+    depends_on: &[String],
+    goal_tasks: &[(String, Vec<String>)],
+    foreign: &HashMap<String, String>,
+) -> Vec<GraphProblem> {
+    let mut problems = Vec::new();
+    for dep in depends_on {
+        if dep == task_id {
+            problems.push(GraphProblem::SelfDependency {
+                task_id: task_id.to_string(),
+            });
+        } else if let Some(goal_id) = foreign.get(dep) {
+            problems.push(GraphProblem::CrossGoalDependency {
+                task_id: task_id.to_string(),
+                dependency_id: dep.clone(),
+                dependency_goal_id: goal_id.clone(),
+            });
+        } else if !goal_tasks.iter().any(|(id, _)| id == dep) {
+            problems.push(GraphProblem::MissingDependency {
+                task_id: task_id.to_string(),
+                dependency_id: dep.clone(),
+            });
+        }
+    }
+
+    let others: Vec<&String> = depends_on.iter().filter(|d| *d != task_id).collect();
+    if problems.is_empty() && !others.is_empty() {
+        let graph: Vec<(String, Vec<String>)> = goal_tasks
+            .iter()
+            .filter(|(id, _)| id != task_id)
+            .cloned()
+            .collect();
+        let deps: Vec<String> = others.into_iter().cloned().collect();
+        if has_cycle(task_id, &deps, &graph) {
+            problems.push(GraphProblem::Cycle {
+                task_id: task_id.to_string(),
+            });
+        }
+    }
+    problems
+}
+
+/// Every problem in a goal's dependency graph, grouped by task in list order.
+/// `foreign` maps dependency ids found in other goals to their goal.
+pub fn graph_problems(tasks: &[Task], foreign: &HashMap<String, String>) -> Vec<GraphProblem> {
+    let graph: Vec<(String, Vec<String>)> = tasks
+        .iter()
+        .map(|t| (t.id.clone(), t.depends_on.clone()))
+        .collect();
+    let by_id: HashMap<&str, &Task> = tasks.iter().map(|t| (t.id.as_str(), t)).collect();
+
+    let mut problems = Vec::new();
+    let mut broken: HashSet<&str> = HashSet::new();
+    for task in tasks {
+        let found = dependency_problems(&task.id, &task.depends_on, &graph, foreign);
+        if !found.is_empty() {
+            broken.insert(&task.id);
+        }
+        problems.extend(found);
+    }
+
+    let not_started = |t: &Task| {
+        matches!(
+            t.status,
+            TaskStatus::Pending | TaskStatus::Blocked | TaskStatus::Assigned
+        )
+    };
+    for task in tasks.iter().filter(|t| not_started(t)) {
+        for dep in &task.depends_on {
+            if let Some(dep_task) = by_id.get(dep.as_str()) {
+                if matches!(dep_task.status, TaskStatus::Failed | TaskStatus::Killed) {
+                    problems.push(GraphProblem::FailedDependency {
+                        task_id: task.id.clone(),
+                        dependency_id: dep.clone(),
+                    });
+                }
+            }
+        }
+
+        // Walk up the dependency chain looking for a broken task
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<&str> = task.depends_on.iter().map(String::as_str).collect();
+        while let Some(id) = stack.pop() {
+            if id == task.id || !seen.insert(id) {
+                continue;
+            }
+            if broken.contains(id) {
+                problems.push(GraphProblem::Unreachable {
+                    task_id: task.id.clone(),
+                    blocked_by: id.to_string(),
+                });
+                break;
+            }
+            if let Some(dep_task) = by_id.get(id) {
+                stack.extend(dep_task.depends_on.iter().map(String::as_str));
+            }
+        }
+    }
+
+    let order: HashMap<&str, usize> = tasks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.id.as_str(), i))
+        .collect();
+    problems.sort_by_key(|p| order.get(p.task_id()).copied().unwrap_or(usize::MAX));
+    problems
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     // ── Transition validation tests ──
 
+    use TaskStatus::*;
+
     #[test]
     fn test_valid_transitions() {
-        assert!(validate_transition("pending", "running").is_ok());
-        assert!(validate_transition("running", "done").is_ok());
-        assert!(validate_transition("failed", "pending").is_ok());
+        assert!(validate_transition(Pending, Running).is_ok());
+        assert!(validate_transition(Running, Done).is_ok());
+        assert!(validate_transition(Failed, Pending).is_ok());
     }
 
     #[test]
     fn test_all_valid_transitions() {
         let valid = vec![
-            ("pending", "assigned"),
-            ("pending", "blocked"),
-            ("pending", "running"),
-            ("assigned", "running"),
-            ("assigned", "pending"),
-            ("running", "done"),
-            ("running", "failed"),
-            ("running", "stalled"),
-            ("stalled", "running"),
-            ("stalled", "failed"),
-            ("stalled", "killed"),
-            ("failed", "pending"),
-            ("blocked", "pending"),
+            (Pending, Assigned),
+            (Pending, Blocked),
+            (Pending, Running),
+            (Assigned, Running),
+            (Assigned, Pending),
+            (Running, Done),
+            (Running, Failed),
+            (Running, Stalled),
+            (Running, AwaitingReview),
+            (Running, Pending),
+            (Running, Killed),
+            (AwaitingReview, Done),
+            (AwaitingReview, Pending),
+            (AwaitingReview, Failed),
+            (Done, Reverted),
+            (Reverted, Pending),
+            (Stalled, Running),
+            (Stalled, Failed),
+            (Stalled, Killed),
+            (Failed, Pending),
+            (Killed, Pending),
+            (Blocked, Pending),
         ];
         for (from, to) in valid {
             assert!(
@@ -104,23 +585,26 @@ mod tests {
 
     #[test]
     fn test_invalid_transitions() {
-        assert!(validate_transition("done", "running").is_err());
-        assert!(validate_transition("pending", "done").is_err());
+        assert!(validate_transition(Done, Running).is_err());
+        assert!(validate_transition(Pending, Done).is_err());
     }
 
     #[test]
     fn test_more_invalid_transitions() {
         let invalid = vec![
-            ("done", "pending"),
-            ("done", "failed"),
-            ("done", "running"),
-            ("killed", "running"),
-            ("killed", "pending"),
-            ("blocked", "running"),
-            ("blocked", "done"),
-            ("pending", "failed"),
-            ("pending", "stalled"),
-            ("pending", "killed"),
+            (Done, Pending),
+            (Done, Failed),
+            (Done, Running),
+            (Killed, Running),
+            (Blocked, Running),
+            (Blocked, Done),
+            (Pending, Failed),
+            (Pending, Stalled),
+            (Pending, Killed),
+            (Pending, AwaitingReview),
+            (AwaitingReview, Running),
+            (Pending, Reverted),
+            (Reverted, Done),
         ];
         for (from, to) in invalid {
             assert!(
@@ -134,19 +618,98 @@ mod tests {
 
     #[test]
     fn test_same_status() {
-        assert!(validate_transition("running", "running").is_ok());
-        assert!(validate_transition("pending", "pending").is_ok());
-        assert!(validate_transition("done", "done").is_ok());
-        assert!(validate_transition("failed", "failed").is_ok());
+        assert!(validate_transition(Running, Running).is_ok());
+        assert!(validate_transition(Pending, Pending).is_ok());
+        assert!(validate_transition(Done, Done).is_ok());
+        assert!(validate_transition(Failed, Failed).is_ok());
     }
 
     #[test]
     fn test_invalid_transition_error_message() {
-        let err = validate_transition("done", "running").unwrap_err();
+        let err = validate_transition(Done, Running).unwrap_err();
         let msg = err.to_string();
         assert!(msg.contains("done"));
         assert!(msg.contains("running"));
         assert!(msg.contains("Invalid"));
+        assert_eq!(err.allowed, vec!["reverted"]);
+    }
+
+    #[test]
+    fn test_run_transitions() {
+        assert!(validate_run_transition(RunStatus::Spawning, RunStatus::Running).is_ok());
+        assert!(validate_run_transition(RunStatus::Stalled, RunStatus::Done).is_ok());
+        assert!(validate_run_transition(RunStatus::Done, RunStatus::Done).is_ok());
+        for terminal in [RunStatus::Done, RunStatus::Failed, RunStatus::Killed] {
+            assert!(terminal.is_terminal());
+            assert!(terminal.allowed_next().is_empty());
+            assert!(validate_run_transition(terminal, RunStatus::Running).is_err());
+        }
+        let err = validate_run_transition(RunStatus::Done, RunStatus::Failed).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid agent run status transition: done -> failed"
+        );
+    }
+
+    #[test]
+    fn test_status_strings_round_trip() {
+        for status in [
+            Pending,
+            Blocked,
+            Assigned,
+            Running,
+            Stalled,
+            AwaitingReview,
+            Done,
+            Failed,
+            Killed,
+            Reverted,
+        ] {
+            assert_eq!(status.as_str().parse::<TaskStatus>().unwrap(), status);
+            assert_eq!(
+                serde_json::to_value(status).unwrap(),
+                serde_json::json!(status.as_str())
+            );
+        }
+        assert!("completed".parse::<TaskStatus>().is_err());
+        assert!("completed".parse::<RunStatus>().is_err());
+    }
+
+    // ── Prompt tests ──
+
+    fn task(title: &str, description: &str) -> Task {
+        Task {
+            id: "t1".into(),
+            goal_space_id: "g1".into(),
+            title: title.into(),
+            description: description.into(),
+            status: TaskStatus::Pending,
+            priority: 0,
+            depends_on: vec![],
+            settings: Default::default(),
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_agent_prompt_includes_goal_and_task() {
+        let prompt = agent_prompt("Ship auth", &task("Add login", "Build the form"), None);
+        assert!(prompt.contains("Ship auth"));
+        assert!(prompt.contains("Task: Add login"));
+        assert!(prompt.contains("Build the form"));
+        assert!(!prompt.contains("rejected"));
+    }
+
+    #[test]
+    fn test_agent_prompt_includes_review_feedback() {
+        let prompt = agent_prompt(
+            "Ship auth",
+            &task("Add login", "Build the form"),
+            Some("Use the existing Button component"),
+        );
+        assert!(prompt.contains("rejected in review"));
+        assert!(prompt.contains("Use the existing Button component"));
     }
 
     // ── Cycle detection tests ──
@@ -232,4 +795,106 @@ mod tests {
         let tasks: Vec<(String, Vec<String>)> = vec![];
         assert!(!has_cycle("a", &[], &tasks));
     }
+
+    // ── Graph validation tests ──
+
+    fn node(id: &str, status: TaskStatus, depends_on: &[&str]) -> Task {
+        Task {
+            id: id.into(),
+            status,
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            ..task(id, "")
+        }
+    }
+
+    #[test]
+    fn test_dependency_problems() {
+        let graph = vec![
+            ("a".to_string(), vec![]),
+            ("b".to_string(), vec!["a".to_string()]),
+        ];
+        let foreign = HashMap::from([("x".to_string(), "other-goal".to_string())]);
+        let deps = |ids: &[&str]| ids.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+
+        assert!(dependency_problems("c", &deps(&["a", "b"]), &graph, &foreign).is_empty());
+        assert_eq!(
+            dependency_problems("c", &deps(&["c", "nope", "x"]), &graph, &foreign),
+            vec![
+                GraphProblem::SelfDependency {
+                    task_id: "c".into()
+                },
+                GraphProblem::MissingDependency {
+                    task_id: "c".into(),
+                    dependency_id: "nope".into()
+                },
+                GraphProblem::CrossGoalDependency {
+                    task_id: "c".into(),
+                    dependency_id: "x".into(),
+                    dependency_goal_id: "other-goal".into()
+                },
+            ]
+        );
+        // a -> b while b already depends on a
+        assert_eq!(
+            dependency_problems("a", &deps(&["b"]), &graph, &foreign),
+            vec![GraphProblem::Cycle {
+                task_id: "a".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_graph_problems_reports_failed_and_unreachable() {
+        let tasks = vec![
+            node("a", Failed, &[]),
+            node("b", Pending, &["a"]),
+            node("c", Pending, &["gone"]),
+            node("d", Blocked, &["c"]),
+            node("e", Done, &["c"]),
+            node("f", Pending, &["e"]),
+        ];
+        let problems = graph_problems(&tasks, &HashMap::new());
+        assert_eq!(
+            problems,
+            vec![
+                GraphProblem::FailedDependency {
+                    task_id: "b".into(),
+                    dependency_id: "a".into()
+                },
+                GraphProblem::MissingDependency {
+                    task_id: "c".into(),
+                    dependency_id: "gone".into()
+                },
+                GraphProblem::Unreachable {
+                    task_id: "d".into(),
+                    blocked_by: "c".into()
+                },
+                GraphProblem::Unreachable {
+                    task_id: "f".into(),
+                    blocked_by: "c".into()
+                },
+            ]
+        );
+
+        let json = serde_json::to_value(&problems[0]).unwrap();
+        assert_eq!(json["kind"], "failed_dependency");
+        assert_eq!(problems[0].to_string(), "task b depends on a, which failed");
+    }
+
+    #[test]
+    fn test_graph_problems_reports_existing_cycles() {
+        let tasks = vec![
+            node("a", Pending, &["b"]),
+            node("b", Pending, &["a"]),
+            node("c", Pending, &[]),
+        ];
+        let problems = graph_problems(&tasks, &HashMap::new());
+        assert!(problems.contains(&GraphProblem::Cycle {
+            task_id: "a".into()
+        }));
+        assert!(problems.contains(&GraphProblem::Cycle {
+            task_id: "b".into()
+        }));
+        assert!(problems.iter().all(|p| p.task_id() != "c"));
+    }
 }
@@ -6,7 +6,10 @@ use axum::{
 use serde_json::json;
 use std::sync::Arc;
 
+use crate::agent::worktree;
+use crate::db::queries::Actor;
 use crate::goal::space;
+use crate::goal::task::RunStatus;
 use crate::server::AppState;
 
 /// Payload from Claude Code's Stop hook
@@ -26,7 +29,7 @@ pub async fn handle_stop_hook(
 
     if let Some(session_id) = &payload.session_id {
         // Find the agent run with this Claude session ID
-        let agents = match state.db.list_agent_runs() {
+        let agents = match state.db.call(|db| db.list_agent_runs()).await {
             Ok(a) => a,
             Err(e) => {
                 tracing::error!("Failed to list agents: {}", e);
@@ -42,55 +45,88 @@ pub async fn handle_stop_hook(
             .iter()
             .find(|a| a.claude_session_id.as_deref() == Some(session_id))
         {
-            // Mark agent as done
-            if let Err(e) = state.db.update_agent_run_status(&agent.id, "done") {
-                tracing::error!(
-                    "Failed to update agent run status to done for {}: {}",
-                    agent.id,
-                    e
-                );
+            // Leftover changes are settled by the uncommitted_policy when the agent
+            // exits; marking the run done here would skip that
+            if let Some(ref path) = agent.worktree_path {
+                match worktree::uncommitted_changes(std::path::Path::new(path)).await {
+                    Ok(files) if !files.is_empty() => {
+                        tracing::info!(
+                            "Agent {} stopped with {} uncommitted file(s); leaving it to the exit handler",
+                            agent.id,
+                            files.len()
+                        );
+                        return Json(json!({"ok": true, "uncommitted": files.len()}))
+                            .into_response();
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(
+                        "Could not check worktree {} of agent {}: {}",
+                        path,
+                        agent.id,
+                        e
+                    ),
+                }
             }
 
-            // Mark task as done
-            if let Err(e) = state.db.update_task(
-                &agent.task_id,
-                &crate::db::queries::UpdateTask {
-                    status: Some("done".to_string()),
-                    title: None,
-                    description: None,
-                    priority: None,
-                    depends_on: None,
-                    ..Default::default()
-                },
-            ) {
-                tracing::error!(
-                    "Failed to update task {} to done via stop hook: {}",
-                    agent.task_id,
-                    e
-                );
-            }
+            let run = agent.clone();
+            let _ = state
+                .db
+                .call(move |db| {
+                    // Mark agent as done
+                    if let Err(e) = db.update_agent_run_status(&run.id, RunStatus::Done) {
+                        tracing::error!(
+                            "Failed to update agent run status to done for {}: {}",
+                            run.id,
+                            e
+                        );
+                    }
 
-            if let Err(e) = state.db.insert_goal_history(
-                &agent.goal_space_id,
-                "task_completed",
-                &format!("Task {} completed by agent {}", agent.task_id, agent.id),
-                None,
-            ) {
-                tracing::error!(
-                    "Failed to insert goal history for goal {}: {}",
-                    agent.goal_space_id,
-                    e
-                );
-            }
+                    // Mark task as done, or hold it for review in manual merge mode
+                    let task_status = space::completed_task_status(db, &run.task_id);
+                    if let Err(e) = db.update_task(
+                        &run.task_id,
+                        &crate::db::queries::UpdateTask {
+                            status: Some(task_status),
+                            title: None,
+                            description: None,
+                            priority: None,
+                            depends_on: None,
+                            ..Default::default()
+                        },
+                    ) {
+                        tracing::error!(
+                            "Failed to update task {} to {} via stop hook: {}",
+                            run.task_id,
+                            task_status,
+                            e
+                        );
+                    }
 
-            // Check if the goal is now complete
-            if let Err(e) = space::check_goal_completion(&state.db, &agent.goal_space_id) {
-                tracing::error!(
-                    "Failed to check goal completion for goal {}: {}",
-                    agent.goal_space_id,
-                    e
-                );
-            }
+                    if let Err(e) = db.insert_goal_history(
+                        &run.goal_space_id,
+                        Actor::Hook,
+                        "task_completed",
+                        &format!("Task {} completed by agent {}", run.task_id, run.id),
+                        Some(json!({"task_id": run.task_id, "agent_run_id": run.id})),
+                    ) {
+                        tracing::error!(
+                            "Failed to insert goal history for goal {}: {}",
+                            run.goal_space_id,
+                            e
+                        );
+                    }
+
+                    // Check if the goal is now complete
+                    if let Err(e) = space::check_goal_completion(db, &run.goal_space_id) {
+                        tracing::error!(
+                            "Failed to check goal completion for goal {}: {}",
+                            run.goal_space_id,
+                            e
+                        );
+                    }
+                    Ok(())
+                })
+                .await;
 
             // Auto-dispatch newly unblocked tasks
             state.agent_manager.request_dispatch(&agent.goal_space_id);
@@ -31,6 +31,22 @@ pub fn generate_hooks_config(port: u16) -> serde_json::Value {
     })
 }
 
+/// Write hooks configuration to a worktree's .claude/settings.json
+#[allow(dead_code)]
+pub async fn install_hooks(worktree_path: &std::path::Path, port: u16) -> anyhow::Result<()> {
+    let claude_dir = worktree_path.join(".claude");
+    tokio::fs::create_dir_all(&claude_dir).await?;
+
+    let config = generate_hooks_config(port);
+    let settings_path = claude_dir.join("settings.json");
+
+    tokio::fs::write(&settings_path, serde_json::to_string_pretty(&config)?).await?;
+
+    tracing::info!("Installed hooks config at {}", settings_path.display());
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -83,19 +99,3 @@ mod tests {
         }
     }
 }
-
-/// Write hooks configuration to a worktree's .claude/settings.json
-#[allow(dead_code)]
-pub async fn install_hooks(worktree_path: &std::path::Path, port: u16) -> anyhow::Result<()> {
-    let claude_dir = worktree_path.join(".claude");
-    tokio::fs::create_dir_all(&claude_dir).await?;
-
-    let config = generate_hooks_config(port);
-    let settings_path = claude_dir.join("settings.json");
-
-    tokio::fs::write(&settings_path, serde_json::to_string_pretty(&config)?).await?;
-
-    tracing::info!("Installed hooks config at {}", settings_path.display());
-
-    Ok(())
-}
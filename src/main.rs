@@ -64,23 +64,85 @@ async fn main() -> Result<()> {
         Commands::Kill { agent_id } => {
             cli::handle_kill(&agent_id).await?;
         }
-        Commands::Logs { agent_id } => {
-            cli::handle_logs(&agent_id).await?;
+        Commands::Logs {
+            agent_id,
+            after,
+            limit,
+            event_type,
+            tool,
+        } => {
+            cli::handle_logs(
+                &agent_id,
+                after,
+                limit,
+                event_type.as_deref(),
+                tool.as_deref(),
+            )
+            .await?;
+        }
+        Commands::Diff { agent_id, stat } => {
+            cli::handle_diff(&agent_id, stat).await?;
+        }
+        Commands::Pr {
+            agent_id,
+            json,
+            push,
+            remote,
+        } => {
+            cli::handle_pr(&agent_id, json, push, remote.as_deref()).await?;
         }
         Commands::Cleanup => {
             let db = Database::open(&db_path()?)?;
             db.run_migrations()?;
             cli::handle_cleanup(&db).await?;
         }
+        Commands::Search {
+            query,
+            goal,
+            project,
+            types,
+            limit,
+        } => {
+            cli::handle_search(&query, goal.as_deref(), project.as_deref(), &types, limit).await?;
+        }
+        Commands::Analytics(args) => {
+            cli::handle_analytics(&args).await?;
+        }
+        Commands::Scheduler {
+            total_slots,
+            starvation_secs,
+        } => {
+            cli::handle_scheduler(total_slots, starvation_secs).await?;
+        }
+        Commands::Jobs {
+            status,
+            kind,
+            goal,
+            limit,
+            retry,
+        } => {
+            cli::handle_jobs(
+                status.as_deref(),
+                kind.as_deref(),
+                goal.as_deref(),
+                limit,
+                retry,
+            )
+            .await?;
+        }
+        Commands::Db { command } => {
+            let db = Database::open(&db_path()?)?;
+            if !matches!(command, cli::DbCommands::Migrate { .. }) {
+                db.run_migrations()?;
+            }
+            cli::handle_db_command(&db, command)?;
+        }
     }
 
     Ok(())
 }
 
-async fn init_app_state() -> Result<(
-    Arc<AppState>,
-    tokio::sync::mpsc::UnboundedReceiver<agent::session::DispatchMessage>,
-)> {
+async fn init_app_state() -> Result<(Arc<AppState>, tokio::sync::mpsc::UnboundedReceiver<()>)> {
     let path = db_path()?;
     tracing::info!("Using database at {}", path.display());
     let db = Database::open(&path)?;
@@ -1,12 +1,37 @@
 pub mod routes;
 pub mod sse;
 
+use anyhow::Context;
+use serde_json::json;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::{broadcast, mpsc};
 
-use crate::agent::session::{AgentManager, BroadcastEvent, DispatchMessage};
+use crate::agent::checkout::{self, CheckoutProblem};
+use crate::agent::session::{AgentManager, BroadcastEvent};
 use crate::agent::worktree;
+use crate::db::jobs::{Job, JobKind, JobStatus};
+use crate::db::queries::{
+    Actor, AgentRun, CreateTask, DeferredMerge, DirtyCheckoutPolicy, GoalSpace, RegressionAction,
+    Task, UpdateTask,
+};
 use crate::db::Database;
+use crate::goal::task::TaskStatus;
+
+/// How often the job queue is polled for retries that have come due
+const JOB_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How often finished jobs are pruned from the queue
+const JOB_PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// How long done jobs are kept for inspection
+const JOB_RETENTION_DAYS: i64 = 7;
+
+/// How often merges deferred by a busy checkout are retried
+const DEFERRED_MERGE_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A reverted task keeps its goal from completing until it is retried and done again
+const REVERTED_GOAL_NOTE: &str = "the goal stays open until the task is retried";
 
 pub struct AppState {
     pub db: Database,
@@ -17,11 +42,12 @@ pub struct AppState {
 pub async fn run(
     state: Arc<AppState>,
     port: u16,
-    dispatch_rx: mpsc::UnboundedReceiver<DispatchMessage>,
+    dispatch_rx: mpsc::UnboundedReceiver<()>,
 ) -> anyhow::Result<()> {
     // Spawn the auto-dispatch loop
     let dispatch_state = state.clone();
     tokio::spawn(dispatch_loop(dispatch_state, dispatch_rx));
+    tokio::spawn(deferred_merge_loop(state.clone()));
 
     let app = routes::create_router(state);
 
@@ -35,132 +61,1058 @@ pub async fn run(
     Ok(())
 }
 
-/// Background loop that auto-dispatches unblocked tasks when agents complete
-async fn dispatch_loop(state: Arc<AppState>, mut rx: mpsc::UnboundedReceiver<DispatchMessage>) {
+/// Background loop that works through the persistent job queue: merges of
+/// finished branches and auto-dispatch of unblocked tasks. Jobs left running
+/// by a crash are picked up again on startup; done jobs are pruned after
+/// `JOB_RETENTION_DAYS`.
+async fn dispatch_loop(state: Arc<AppState>, mut rx: mpsc::UnboundedReceiver<()>) {
     tracing::info!("Auto-dispatch loop started");
 
-    while let Some(msg) = rx.recv().await {
-        let goal_space_id = &msg.goal_space_id;
-
-        // Merge completed branch if present
-        if let (Some(branch), Some(repo_path)) = (&msg.branch_to_merge, &msg.repo_path) {
-            let repo = std::path::Path::new(repo_path.as_str());
-            match worktree::merge_branch_to_main(repo, branch).await {
-                Ok(()) => {
-                    tracing::info!("Auto-merged branch {} into main", branch);
-                    // Record merge event on the agent run
-                    if let Some(ref agent_run_id) = msg.agent_run_id {
-                        let _ = state.db.insert_agent_event(
-                            agent_run_id,
-                            "merge_completed",
-                            None,
-                            &format!("Merged branch {} into main", branch),
-                            None,
-                            None,
+    match state.db.call(|db| db.requeue_running_jobs()).await {
+        Ok(0) => {}
+        Ok(n) => tracing::info!("Resuming {} interrupted jobs", n),
+        Err(e) => tracing::error!("Failed to requeue interrupted jobs: {}", e),
+    }
+
+    let mut interval = tokio::time::interval(JOB_POLL_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut prune = tokio::time::interval(JOB_PRUNE_INTERVAL);
+    prune.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        tokio::select! {
+            woken = rx.recv() => if woken.is_none() { break },
+            _ = interval.tick() => {}
+            _ = prune.tick() => {
+                let pruned = state
+                    .db
+                    .call(|db| db.prune_done_jobs(chrono::Duration::days(JOB_RETENTION_DAYS)))
+                    .await;
+                match pruned {
+                    Ok(0) => {}
+                    Ok(n) => tracing::info!("Pruned {} finished jobs", n),
+                    Err(e) => tracing::error!("Failed to prune finished jobs: {}", e),
+                }
+            }
+        }
+        run_due_jobs(&state).await;
+    }
+}
+
+/// Work through every job that is due, oldest first. A failed job is retried
+/// with backoff, unless it failed in a way a retry can't fix.
+pub async fn run_due_jobs(state: &Arc<AppState>) {
+    loop {
+        let job = match state.db.call(|db| db.claim_next_job()).await {
+            Ok(Some(job)) => job,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::error!("Failed to claim job: {}", e);
+                break;
+            }
+        };
+        let id = job.id;
+        let outcome = match process_job(state, &job).await {
+            Ok(()) => state.db.call(move |db| db.complete_job(id, None)).await,
+            // Already recorded on the run and in the goal history; retrying
+            // would only repeat the failure
+            Err(e) if e.downcast_ref::<MergeFailed>().is_some() => {
+                tracing::warn!(
+                    "{} job {} for goal {} failed for good: {}",
+                    job.kind.as_str(),
+                    job.id,
+                    job.goal_space_id,
+                    e
+                );
+                let error = e.to_string();
+                state
+                    .db
+                    .call(move |db| db.complete_job(id, Some(&error)))
+                    .await
+            }
+            Err(e) => {
+                let error = e.to_string();
+                let failed = state.db.call(move |db| db.fail_job(id, &error)).await;
+                failed.map(|status| {
+                    if status == JobStatus::Dead {
+                        tracing::error!(
+                            "{} job {} for goal {} failed {} times, giving up: {}",
+                            job.kind.as_str(),
+                            job.id,
+                            job.goal_space_id,
+                            job.attempts,
+                            e
                         );
+                    } else {
+                        tracing::warn!(
+                            "{} job {} for goal {} failed, will retry: {}",
+                            job.kind.as_str(),
+                            job.id,
+                            job.goal_space_id,
+                            e
+                        );
+                    }
+                })
+            }
+        };
+        if let Err(e) = outcome {
+            tracing::error!("Failed to record outcome of job {}: {}", job.id, e);
+        }
+    }
+}
+
+async fn process_job(state: &Arc<AppState>, job: &Job) -> anyhow::Result<()> {
+    let goal_space_id = &job.goal_space_id;
+    match job.kind {
+        JobKind::Merge => {
+            let (Some(branch), Some(repo_path)) = (&job.branch, &job.repo_path) else {
+                anyhow::bail!("merge job has no branch or repository");
+            };
+            let run_id = job.agent_run_id.as_deref();
+            let (goal_id, run, queued_branch) = (
+                goal_space_id.clone(),
+                job.agent_run_id.clone(),
+                branch.clone(),
+            );
+            let handled = state
+                .db
+                .call(move |db| {
+                    let landed = match run {
+                        Some(ref id) => db
+                            .get_agent_run(id)?
+                            .is_some_and(|run| run.merge_commit.is_some()),
+                        None => false,
+                    };
+                    let deferred = db
+                        .list_deferred_merges(Some(&goal_id))?
+                        .iter()
+                        .any(|m| m.branch == queued_branch && m.agent_run_id == run);
+                    Ok(landed || deferred)
+                })
+                .await?;
+            let result = if handled {
+                tracing::info!("Branch {} already handled, skipping merge", branch);
+                Ok(())
+            } else {
+                land_branch_checked(
+                    &state.db,
+                    Path::new(repo_path.as_str()),
+                    branch,
+                    run_id,
+                    goal_space_id,
+                    Actor::Dispatch,
+                )
+                .await
+                .map(|landing| match landing {
+                    Landing::Merged(_) => {
+                        tracing::info!("Auto-merged branch {} into main", branch);
+                        // Check the new main in the background; dispatch carries on
+                        let check_state = state.clone();
+                        let goal_id = goal_space_id.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) =
+                                run_post_merge_check(&check_state, &goal_id, Actor::Dispatch).await
+                            {
+                                tracing::error!(
+                                    "Post-merge check for goal {} failed to run: {}",
+                                    goal_id,
+                                    e
+                                );
+                            }
+                        });
                     }
-                    // Clean up the merged branch
-                    if let Err(e) = worktree::delete_branch(repo, branch).await {
-                        tracing::warn!("Failed to delete merged branch {}: {}", branch, e);
+                    Landing::Deferred(merge) => {
+                        tracing::info!("Deferred merge of branch {}: {}", branch, merge.reason)
                     }
-                }
-                Err(e) => {
-                    tracing::warn!("Failed to auto-merge branch {}: {}", branch, e);
-                    if let Some(ref agent_run_id) = msg.agent_run_id {
-                        let _ = state.db.insert_agent_event(
-                            agent_run_id,
-                            "merge_failed",
-                            None,
-                            &format!("Failed to merge branch {}: {}", branch, e),
-                            None,
-                            None,
+                })
+            };
+            // Whatever landed, or failed to, the finished run frees a slot
+            let (goal_id, run) = (goal_space_id.clone(), job.agent_run_id.clone());
+            state
+                .db
+                .call(move |db| db.enqueue_dispatch_job(&goal_id, run.as_deref()))
+                .await?;
+            result
+        }
+        JobKind::Dispatch => {
+            // A finished agent frees a fleet slot that may belong to another goal's share
+            if job.agent_run_id.is_some() {
+                wake_fair_share_goals(state, goal_space_id).await;
+            }
+
+            let goal_id = goal_space_id.clone();
+            let Some(goal) = state.db.call(move |db| db.get_goal_space(&goal_id)).await? else {
+                tracing::warn!("Goal {} not found for auto-dispatch", goal_space_id);
+                return Ok(());
+            };
+            if goal.status == "completed" || goal.status == "archived" {
+                return Ok(());
+            }
+
+            tracing::info!(
+                "Auto-dispatching unblocked tasks for goal {}",
+                goal_space_id
+            );
+            let scheduled = goal.clone();
+            let unblocked = state
+                .db
+                .call(move |db| {
+                    let unblocked = crate::goal::schedule::next_tasks(db, &scheduled)?;
+                    if unblocked.is_empty() {
+                        // No new tasks to dispatch — check if goal is fully complete
+                        crate::goal::space::check_goal_completion(db, &scheduled.id)?;
+                    }
+                    Ok(unblocked)
+                })
+                .await?;
+            if unblocked.is_empty() {
+                return Ok(());
+            }
+
+            let mut spawned = 0;
+            for task in &unblocked {
+                match state.agent_manager.dispatch_task(&goal, task).await {
+                    Ok(_) => spawned += 1,
+                    Err(e) => {
+                        tracing::error!(
+                            "Auto-dispatch: failed to spawn agent for task {}: {}",
+                            task.id,
+                            e
                         );
                     }
                 }
             }
-        }
 
-        tracing::info!(
-            "Auto-dispatching unblocked tasks for goal {}",
-            goal_space_id
-        );
+            tracing::info!(
+                "Auto-dispatch: spawned {} agents for goal {}",
+                spawned,
+                goal_space_id
+            );
+            Ok(())
+        }
+    }
+}
 
-        let goal = match state.db.get_goal_space(goal_space_id) {
-            Ok(Some(g)) => g,
-            Ok(None) => {
-                tracing::warn!("Goal {} not found for auto-dispatch", goal_space_id);
-                continue;
+/// Queue dispatch for other goals that have free slots under the fleet's fair share
+async fn wake_fair_share_goals(state: &AppState, except_goal_id: &str) {
+    let shares = state
+        .db
+        .call(|db| {
+            if db.get_scheduler_config()?.total_slots.is_none() {
+                return Ok(None);
+            }
+            crate::goal::fair_share::fleet_shares(db).map(Some)
+        })
+        .await;
+    match shares {
+        Ok(None) => {}
+        Ok(Some(shares)) => {
+            for goal in shares.goals {
+                if goal.free_slots > 0 && goal.goal_space_id != except_goal_id {
+                    state.agent_manager.request_dispatch(&goal.goal_space_id);
+                }
             }
+        }
+        Err(e) => tracing::error!("Failed to compute fair shares: {}", e),
+    }
+}
+
+/// Periodically retry merges that were deferred because the user's checkout was busy
+async fn deferred_merge_loop(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(DEFERRED_MERGE_RETRY_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        let merges = match state.db.call(|db| db.list_deferred_merges(None)).await {
+            Ok(m) => m,
             Err(e) => {
-                tracing::error!("Failed to get goal {}: {}", goal_space_id, e);
+                tracing::error!("Failed to list deferred merges: {}", e);
                 continue;
             }
         };
+        for merge in merges {
+            retry_deferred_merge(&state, &merge, Actor::System).await;
+        }
+    }
+}
+
+/// Try a deferred merge again. Once it lands (or fails for good) it leaves the queue
+/// and dependents held back by it are dispatched.
+pub async fn retry_deferred_merge(
+    state: &AppState,
+    merge: &DeferredMerge,
+    actor: Actor,
+) -> Option<Landing> {
+    let repo = Path::new(&merge.repo_path);
+    let result = land_branch_checked(
+        &state.db,
+        repo,
+        &merge.branch,
+        merge.agent_run_id.as_deref(),
+        &merge.goal_space_id,
+        actor,
+    )
+    .await;
 
-        // Check if goal is already completed
-        if goal.status == "completed" || goal.status == "archived" {
-            continue;
+    match result {
+        Ok(Landing::Deferred(_)) => None,
+        Ok(landing) => {
+            let landed = merge.clone();
+            let _ = state
+                .db
+                .call(move |db| {
+                    db.insert_goal_history(
+                        &landed.goal_space_id,
+                        actor,
+                        "deferred_merge_landed",
+                        &format!("Deferred merge of {} landed", landed.branch),
+                        Some(json!({"branch": landed.branch, "attempts": landed.attempts})),
+                    )
+                })
+                .await;
+            state.agent_manager.request_dispatch(&merge.goal_space_id);
+            Some(landing)
         }
+        Err(e) => {
+            tracing::warn!("Deferred merge of {} failed: {}", merge.branch, e);
+            let (failed, error) = (merge.clone(), e.to_string());
+            let dequeued = state
+                .db
+                .call(move |db| {
+                    let dequeued = db.delete_deferred_merge(&failed.repo_path, &failed.branch);
+                    let _ = db.insert_goal_history(
+                        &failed.goal_space_id,
+                        actor,
+                        "deferred_merge_failed",
+                        &format!("Deferred merge of {} failed: {}", failed.branch, error),
+                        Some(json!({"branch": failed.branch, "attempts": failed.attempts})),
+                    );
+                    dequeued
+                })
+                .await;
+            if let Err(e) = dequeued {
+                tracing::error!("Failed to dequeue merge of {}: {}", merge.branch, e);
+            }
+            None
+        }
+    }
+}
 
-        let unblocked = match state.db.get_unblocked_tasks(goal_space_id) {
-            Ok(tasks) => tasks,
-            Err(e) => {
-                tracing::error!("Failed to get unblocked tasks: {}", e);
-                continue;
+/// Run the goal's `post_merge_command` against main and, when it fails, bisect over
+/// the goal's merges to find the culprit task. The culprit's run is marked with a
+/// `regression` event and handled per `regression_action`. Returns `None` when no
+/// check command is configured.
+pub async fn run_post_merge_check(
+    state: &AppState,
+    goal_space_id: &str,
+    actor: Actor,
+) -> anyhow::Result<Option<crate::agent::bisect::CheckReport>> {
+    let goal_id = goal_space_id.to_string();
+    let (goal, settings) = state
+        .db
+        .call(move |db| {
+            let goal = db
+                .get_goal_space(&goal_id)?
+                .context("Goal space not found")?;
+            let settings = crate::goal::space::goal_settings(db, &goal)?;
+            Ok((goal, settings))
+        })
+        .await?;
+    let Some(command) = settings.post_merge_command() else {
+        return Ok(None);
+    };
+
+    let mut report = crate::agent::bisect::check_and_bisect(
+        &state.db,
+        &goal.id,
+        Path::new(&goal.repo_path),
+        &command,
+        std::time::Duration::from_secs(settings.script_timeout_secs()),
+    )
+    .await?;
+
+    let (goal_id, passed) = (goal.id.clone(), report.passed);
+    let (summary, metadata) = if passed {
+        (
+            format!("Post-merge check passed at {}", report.head),
+            json!({"head": report.head}),
+        )
+    } else {
+        (
+            format!(
+                "Post-merge check failed at {}: {}",
+                report.head,
+                report.check.summary("check")
+            ),
+            json!({"head": report.head, "check": report.check, "note": report.note}),
+        )
+    };
+    let _ = state
+        .db
+        .call(move |db| {
+            let event_type = if passed {
+                "post_merge_check_passed"
+            } else {
+                "post_merge_check_failed"
+            };
+            db.insert_goal_history(&goal_id, actor, event_type, &summary, Some(metadata))
+        })
+        .await;
+    if passed {
+        return Ok(Some(report));
+    }
+
+    let Some(run) = report.culprit.clone() else {
+        return Ok(Some(report));
+    };
+    let merge_commit = run.merge_commit.clone().unwrap_or_default();
+    let (goal_id, culprit, commit) = (goal.id.clone(), run.clone(), merge_commit.clone());
+    let raw = json!({"head": report.head, "command": command}).to_string();
+    let task = state
+        .db
+        .call(move |db| {
+            let Some(task) = db.get_task(&culprit.task_id)? else {
+                return Ok(None);
+            };
+            let summary = format!(
+                "Post-merge check first fails at this task's merge {}",
+                commit
+            );
+            db.insert_agent_event(&culprit.id, "regression", None, &summary, Some(&raw), None)?;
+            db.set_agent_run_failure_reason(&culprit.id, "regression")?;
+            db.insert_goal_history(
+                &goal_id,
+                actor,
+                "regression_found",
+                &format!("Task '{}' broke the post-merge check", task.title),
+                Some(json!({"task_id": task.id, "merge_commit": commit})),
+            )?;
+            Ok(Some(task))
+        })
+        .await?;
+    let Some(task) = task else {
+        return Ok(Some(report));
+    };
+
+    let action = settings.regression_action();
+    match action {
+        RegressionAction::Mark => {}
+        RegressionAction::Revert => {
+            if task.status == TaskStatus::Done {
+                match revert_task(state, &task, &run, &goal, actor).await {
+                    Ok(outcome) => report.follow_up_task_id = outcome.revert_task.map(|t| t.id),
+                    // Recorded in the goal history; the regression stays marked
+                    Err(e) if e.downcast_ref::<RevertBlocked>().is_some() => {
+                        tracing::warn!("{}", e)
+                    }
+                    Err(e) => return Err(e),
+                }
             }
-        };
+        }
+        RegressionAction::FixUp => {
+            let input = CreateTask {
+                title: format!("Fix regression from: {}", task.title),
+                description: format!(
+                    "The post-merge check `{}` started failing with the merge of task '{}' \
+                     ({}). Fix main so the check passes again without dropping that task's work.\n\n\
+                     Check output:\n{}",
+                    command, task.title, merge_commit, report.check.output
+                ),
+                priority: task.priority,
+                depends_on: Vec::new(),
+                settings: Default::default(),
+            };
+            let (goal_id, reopen) = (goal.id.clone(), goal.status == "completed");
+            let fix = state
+                .db
+                .call(move |db| {
+                    let fix = db.create_task_by(&goal_id, &input, actor)?;
+                    if reopen {
+                        db.update_goal_space(&goal_id, None, None, Some("active"))?;
+                    }
+                    Ok(fix)
+                })
+                .await?;
+            report.follow_up_task_id = Some(fix.id);
+            state.agent_manager.request_dispatch(&goal.id);
+        }
+    }
+    report.action = serde_json::to_value(action)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from));
 
-        if unblocked.is_empty() {
-            // No new tasks to dispatch — check if goal is fully complete
-            let _ = crate::goal::space::check_goal_completion(&state.db, goal_space_id);
-            continue;
+    Ok(Some(report))
+}
+
+/// Result of reverting a merged task
+#[derive(Debug)]
+pub struct RevertOutcome {
+    pub merge_commit: String,
+    /// The revert commit on main, or `None` when the revert conflicted
+    pub revert_commit: Option<String>,
+    /// Task created for an agent to do a conflicting revert
+    pub revert_task: Option<Task>,
+    /// Dependents that already landed or await review on top of the reverted work
+    pub dependents: Vec<Task>,
+    /// Pending dependents moved to `blocked` until the reverted task is retried
+    pub blocked: Vec<Task>,
+}
+
+/// A merge that was attempted and failed, or was refused by the `fail` dirty
+/// checkout policy. The failure is already recorded, and retrying the same
+/// branch would fail the same way.
+#[derive(Debug)]
+pub struct MergeFailed(pub String);
+
+impl std::fmt::Display for MergeFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for MergeFailed {}
+
+/// A revert refused because the repo's main checkout isn't safe to change
+#[derive(Debug)]
+pub struct RevertBlocked(pub String);
+
+impl std::fmt::Display for RevertBlocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for RevertBlocked {}
+
+/// Undo a merged task: revert the merge commit recorded on `run` on main, or hand
+/// the revert to an agent as a new task when it conflicts with work merged since.
+/// The task moves to `reverted` and dependents built on it are logged for attention.
+pub async fn revert_task(
+    state: &AppState,
+    task: &Task,
+    run: &AgentRun,
+    goal: &GoalSpace,
+    actor: Actor,
+) -> anyhow::Result<RevertOutcome> {
+    let merge_commit = run
+        .merge_commit
+        .clone()
+        .context("Agent run has no merge commit")?;
+    let repo = Path::new(&goal.repo_path);
+
+    // The revert is committed in the user's checkout, so it gets the same
+    // checks as a merge. A revert isn't queued: `defer` refuses it like `fail`.
+    let (scoped_goal, scoped_task) = (goal.clone(), task.clone());
+    let settings = state
+        .db
+        .call(move |db| crate::goal::space::effective_settings(db, &scoped_goal, &scoped_task))
+        .await?;
+    let stash = match checkout::inspect(repo, settings.target_branch().as_deref()).await? {
+        None => false,
+        Some(CheckoutProblem::Dirty(_))
+            if settings.dirty_checkout_policy() == DirtyCheckoutPolicy::Stash =>
+        {
+            true
+        }
+        Some(problem) => {
+            let message = format!("Refusing to revert task '{}': {}", task.title, problem);
+            tracing::error!("{} in {}", message, repo.display());
+            let (goal_id, summary) = (goal.id.clone(), message.clone());
+            let metadata = json!({"task_id": task.id, "merge_commit": merge_commit});
+            let _ = state
+                .db
+                .call(move |db| {
+                    db.insert_goal_history(
+                        &goal_id,
+                        actor,
+                        "revert_blocked",
+                        &summary,
+                        Some(metadata),
+                    )
+                })
+                .await;
+            return Err(RevertBlocked(message).into());
         }
+    };
+    if stash {
+        checkout::stash(
+            repo,
+            &format!("conductor: before reverting {}", merge_commit),
+        )
+        .await?;
+    }
+    let reverted = worktree::revert_commit(repo, &merge_commit).await;
+    if stash {
+        if let Err(e) = checkout::unstash(repo).await {
+            tracing::error!("{} in {}", e, repo.display());
+            let (goal_id, error) = (goal.id.clone(), e.to_string());
+            let metadata = json!({"task_id": task.id});
+            let _ = state
+                .db
+                .call(move |db| {
+                    db.insert_goal_history(
+                        &goal_id,
+                        actor,
+                        "stash_conflict",
+                        &error,
+                        Some(metadata),
+                    )
+                })
+                .await;
+        }
+    }
+    let revert_commit = reverted?;
+
+    let goal_id = goal.id.clone();
+    let (goal, task, run_id) = (goal.clone(), task.clone(), run.id.clone());
+    let (merge, revert) = (merge_commit.clone(), revert_commit.clone());
+    let (revert_task, dependents, blocked) = state
+        .db
+        .call(move |db| {
+            let (merge_commit, revert_commit) = (merge, revert);
+            // A conflicting revert becomes a new task for an agent to resolve
+            let revert_task = match revert_commit {
+                Some(ref sha) => {
+                    let _ = db.insert_agent_event(
+                        &run_id,
+                        "reverted",
+                        None,
+                        &format!("Merge {} reverted as {}", merge_commit, sha),
+                        Some(&json!({"merge_commit": merge_commit, "revert_commit": sha}).to_string()),
+                        None,
+                    );
+                    None
+                }
+                None => {
+                    let input = CreateTask {
+                        title: format!("Revert: {}", task.title),
+                        description: format!(
+                            "Revert the changes merged for task '{}' in commit {} \
+                             (`git revert -m 1 {}`). The revert conflicts with work merged since; \
+                             resolve the conflicts so the task's changes are removed and later work still builds.",
+                            task.title, merge_commit, merge_commit
+                        ),
+                        priority: task.priority,
+                        depends_on: Vec::new(),
+                        settings: Default::default(),
+                    };
+                    Some(db.create_task_by(&goal.id, &input, actor)?)
+                }
+            };
 
-        let mut spawned = 0;
-        for task in &unblocked {
-            // Merge task-level settings over goal-level settings
-            let effective = goal.settings.merge(&task.settings);
+            db.update_task(
+                &task.id,
+                &UpdateTask {
+                    status: Some(TaskStatus::Reverted),
+                    ..Default::default()
+                },
+            )?;
 
-            let prompt = format!(
-                "You are working on the following task as part of the goal: {}\n\n\
-                 Task: {}\n\n\
-                 Description: {}\n\n\
-                 Work in the current directory. Make your changes, test them, and commit when done.",
-                goal.description, task.title, task.description
+            // Work that landed on top of the reverted task may no longer hold up
+            let dependents = crate::goal::space::merged_dependents(db, &task)?;
+            for dependent in &dependents {
+                let _ = db.insert_goal_history(
+                    &goal.id,
+                    actor,
+                    "dependent_needs_attention",
+                    &format!(
+                        "Task '{}' is {} but depends on reverted task '{}'",
+                        dependent.title, dependent.status, task.title
+                    ),
+                    Some(json!({"task_id": dependent.id, "reverted_task_id": task.id})),
+                );
+            }
+
+            // Work not started yet can't become ready again until the task is redone
+            let blocked = crate::goal::space::block_dependents(db, &task)?;
+            for dependent in &blocked {
+                let _ = db.insert_goal_history(
+                    &goal.id,
+                    actor,
+                    "dependent_blocked",
+                    &format!(
+                        "Task '{}' blocked until reverted task '{}' is retried",
+                        dependent.title, task.title
+                    ),
+                    Some(json!({"task_id": dependent.id, "reverted_task_id": task.id})),
+                );
+            }
+
+            let _ = db.insert_goal_history(
+                &goal.id,
+                actor,
+                "task_reverted",
+                &match revert_commit {
+                    Some(ref sha) => format!(
+                        "Task '{}' reverted as {}; {}",
+                        task.title, sha, REVERTED_GOAL_NOTE
+                    ),
+                    None => format!(
+                        "Task '{}' reverted; the conflicting revert was handed to an agent, and {}",
+                        task.title, REVERTED_GOAL_NOTE
+                    ),
+                },
+                Some(json!({
+                    "task_id": task.id,
+                    "merge_commit": merge_commit,
+                    "revert_commit": revert_commit,
+                    "revert_task_id": revert_task.as_ref().map(|t| &t.id),
+                    "goal_open_until_retried": true,
+                })),
             );
 
-            match state
-                .agent_manager
-                .spawn_agent(
-                    &task.id,
-                    goal_space_id,
-                    &prompt,
-                    &goal.repo_path,
-                    &effective.model(),
-                    Some(effective.max_budget_usd()),
-                    Some(effective.max_turns()),
-                    Some(effective.allowed_tools()),
-                    effective.permission_mode(),
-                    effective.system_prompt(),
-                )
-                .await
-            {
-                Ok(_) => spawned += 1,
-                Err(e) => {
+            // Reopen a completed goal so the revert task (if any) can be dispatched
+            if goal.status == "completed" {
+                db.update_goal_space(&goal.id, None, None, Some("active"))?;
+            }
+            Ok((revert_task, dependents, blocked))
+        })
+        .await?;
+
+    if revert_task.is_some() {
+        state.agent_manager.request_dispatch(&goal_id);
+    }
+
+    Ok(RevertOutcome {
+        merge_commit,
+        revert_commit,
+        revert_task,
+        dependents,
+        blocked,
+    })
+}
+
+/// Outcome of handing a finished branch to the user's checkout
+#[derive(Debug)]
+pub enum Landing {
+    /// Merged; holds the merge commit
+    Merged(String),
+    /// Queued until the checkout is safe to merge into
+    Deferred(DeferredMerge),
+}
+
+/// Land a branch unless the repo's main checkout is in a state a merge shouldn't
+/// touch: uncommitted edits, a detached HEAD, or a branch other than `target_branch`.
+/// The `dirty_checkout_policy` setting decides whether such a merge is deferred,
+/// run around a stash (uncommitted edits only), or refused.
+pub async fn land_branch_checked(
+    db: &Database,
+    repo: &Path,
+    branch: &str,
+    agent_run_id: Option<&str>,
+    goal_space_id: &str,
+    actor: Actor,
+) -> anyhow::Result<Landing> {
+    let (goal_id, run_id) = (goal_space_id.to_string(), agent_run_id.map(String::from));
+    let (run, task, settings) = db
+        .call(move |db| {
+            let goal = db
+                .get_goal_space(&goal_id)?
+                .context("Goal space not found")?;
+            let run = match run_id {
+                Some(ref id) => db.get_agent_run(id)?,
+                None => None,
+            };
+            let task = match run {
+                Some(ref run) => db.get_task(&run.task_id)?,
+                None => None,
+            };
+            let settings = match task {
+                Some(ref task) => crate::goal::space::effective_settings(db, &goal, task)?,
+                None => crate::goal::space::goal_settings(db, &goal)?,
+            };
+            Ok((run, task, settings))
+        })
+        .await?;
+    let policy = settings.dirty_checkout_policy();
+    let repo_path = repo.to_string_lossy().into_owned();
+
+    // A stacked run carries its parents' commits, so it waits for their approval
+    if let Some(ref run) = run {
+        let waiting = crate::agent::stack::unapproved_parents(db, repo, run).await?;
+        if !waiting.is_empty() {
+            let titles: Vec<String> = waiting.iter().map(|t| format!("'{}'", t.title)).collect();
+            let merge = defer_merge(
+                db,
+                goal_space_id,
+                task.as_ref(),
+                agent_run_id,
+                &repo_path,
+                branch,
+                &format!("waiting for approval of {}", titles.join(", ")),
+                actor,
+            )
+            .await?;
+            return Ok(Landing::Deferred(merge));
+        }
+    }
+
+    let problem = checkout::inspect(repo, settings.target_branch().as_deref()).await?;
+    let stash = match (&problem, policy) {
+        (None, _) => false,
+        (Some(CheckoutProblem::Dirty(_)), DirtyCheckoutPolicy::Stash) => true,
+        (Some(problem), DirtyCheckoutPolicy::Fail) => {
+            let message = format!("Refusing to merge {}: {}", branch, problem);
+            tracing::error!("{} in {}", message, repo.display());
+            let (goal_id, run_id, branch, summary) = (
+                goal_space_id.to_string(),
+                agent_run_id.map(String::from),
+                branch.to_string(),
+                message.clone(),
+            );
+            db.call(move |db| {
+                if let Some(ref id) = run_id {
+                    let _ = db.insert_agent_event(id, "merge_blocked", None, &summary, None, None);
+                }
+                let _ = db.insert_goal_history(
+                    &goal_id,
+                    actor,
+                    "merge_blocked",
+                    &summary,
+                    Some(json!({"branch": branch, "agent_run_id": run_id})),
+                );
+                db.delete_deferred_merge(&repo_path, &branch)
+            })
+            .await?;
+            return Err(MergeFailed(message).into());
+        }
+        (Some(problem), _) => {
+            // Deferred, including branch problems a stash can't fix
+            let merge = defer_merge(
+                db,
+                goal_space_id,
+                task.as_ref(),
+                agent_run_id,
+                &repo_path,
+                branch,
+                &problem.to_string(),
+                actor,
+            )
+            .await?;
+            return Ok(Landing::Deferred(merge));
+        }
+    };
+
+    if stash {
+        checkout::stash(repo, &format!("conductor: before merging {}", branch)).await?;
+    }
+    let landed = land_branch(db, repo, branch, agent_run_id).await;
+    if stash {
+        if let Err(e) = checkout::unstash(repo).await {
+            tracing::error!("{} in {}", e, repo.display());
+            let (goal_id, run_id, branch, error) = (
+                goal_space_id.to_string(),
+                agent_run_id.map(String::from),
+                branch.to_string(),
+                e.to_string(),
+            );
+            let _ = db
+                .call(move |db| {
+                    if let Some(ref id) = run_id {
+                        let _ =
+                            db.insert_agent_event(id, "stash_conflict", None, &error, None, None);
+                    }
+                    db.insert_goal_history(
+                        &goal_id,
+                        actor,
+                        "stash_conflict",
+                        &error,
+                        Some(json!({"branch": branch})),
+                    )
+                })
+                .await;
+        }
+    }
+
+    let (goal_id, run_id, branch_name) = (
+        goal_space_id.to_string(),
+        agent_run_id.map(String::from),
+        branch.to_string(),
+    );
+    let merge_commit = landed.as_ref().ok().cloned();
+    db.call(move |db| {
+        if let Some(merge_commit) = merge_commit {
+            let _ = db.insert_goal_history(
+                &goal_id,
+                actor,
+                "branch_merged",
+                &format!("Merged {} into main as {}", branch_name, merge_commit),
+                Some(json!({
+                    "branch": branch_name,
+                    "merge_commit": merge_commit,
+                    "agent_run_id": run_id,
+                    "task_id": task.as_ref().map(|t| &t.id),
+                })),
+            );
+        }
+        // Landed or failed for good: either way it no longer waits in the queue
+        db.delete_deferred_merge(&repo_path, &branch_name)
+    })
+    .await?;
+    landed
+        .map(Landing::Merged)
+        .map_err(|e| MergeFailed(format!("{:#}", e)).into())
+}
+
+/// Queue a merge to be retried later, recording why on its first deferral
+#[allow(clippy::too_many_arguments)]
+async fn defer_merge(
+    db: &Database,
+    goal_space_id: &str,
+    task: Option<&Task>,
+    agent_run_id: Option<&str>,
+    repo_path: &str,
+    branch: &str,
+    reason: &str,
+    actor: Actor,
+) -> anyhow::Result<DeferredMerge> {
+    let (goal_id, task_id, run_id) = (
+        goal_space_id.to_string(),
+        task.map(|t| t.id.clone()),
+        agent_run_id.map(String::from),
+    );
+    let (repo_path, branch, reason) = (
+        repo_path.to_string(),
+        branch.to_string(),
+        reason.to_string(),
+    );
+    db.call(move |db| {
+        let merge = db.upsert_deferred_merge(
+            &goal_id,
+            task_id.as_deref(),
+            run_id.as_deref(),
+            &repo_path,
+            &branch,
+            &reason,
+        )?;
+        if merge.attempts == 1 {
+            let message = format!("Merge of {} deferred: {}", branch, reason);
+            let _ = db.insert_goal_history(
+                &goal_id,
+                actor,
+                "merge_deferred",
+                &message,
+                Some(json!({"branch": branch, "agent_run_id": run_id})),
+            );
+            if let Some(ref id) = run_id {
+                let _ = db.insert_agent_event(id, "merge_deferred", None, &message, None, None);
+            }
+        }
+        Ok(merge)
+    })
+    .await
+}
+
+/// Merge an agent branch into the repo's main branch and delete it.
+/// A stacked run lands the unmerged dependency branches beneath it first, bottom
+/// of the stack first; callers check that their tasks have been approved.
+/// The merge commit and outcome are recorded on each agent run when one is given.
+pub async fn land_branch(
+    db: &Database,
+    repo: &Path,
+    branch: &str,
+    agent_run_id: Option<&str>,
+) -> anyhow::Result<String> {
+    let run = match agent_run_id {
+        Some(id) => {
+            let id = id.to_string();
+            db.call(move |db| db.get_agent_run(&id)).await?
+        }
+        None => None,
+    };
+    let Some(run) = run else {
+        return land_one(db, repo, branch, None).await;
+    };
+
+    let order = crate::agent::stack::landing_order(db, repo, &run).await?;
+    for parent in order.iter().filter(|r| r.id != run.id) {
+        let parent_branch = parent
+            .branch
+            .as_deref()
+            .context("Stacked agent run has no branch")?;
+        land_one(db, repo, parent_branch, Some(parent)).await?;
+        // Landed here, so its own queued merge has nothing left to do
+        let (repo_path, parent_branch) = (
+            repo.to_string_lossy().into_owned(),
+            parent_branch.to_string(),
+        );
+        db.call(move |db| db.delete_deferred_merge(&repo_path, &parent_branch))
+            .await?;
+    }
+
+    land_one(db, repo, branch, Some(&run)).await
+}
+
+/// Land a single branch, rebasing it onto main first if the commit it
+/// started from is no longer part of main (its stack was rebased or octopus-merged)
+async fn land_one(
+    db: &Database,
+    repo: &Path,
+    branch: &str,
+    run: Option<&AgentRun>,
+) -> anyhow::Result<String> {
+    let agent_run_id = run.map(|r| r.id.as_str());
+
+    let result = async {
+        if let Some(base) = run.and_then(|r| r.base_commit.as_deref()) {
+            let main = worktree::current_branch(repo).await?;
+            if matches!(worktree::is_ancestor(repo, base, &main).await, Ok(false)) {
+                worktree::rebase_branch(repo, branch, base, &main).await?;
+            }
+        }
+        worktree::merge_branch_to_main(repo, branch).await
+    }
+    .await;
+
+    match result {
+        Ok(merge_commit) => {
+            if let Some(agent_run_id) = agent_run_id {
+                let (id, commit, summary) = (
+                    agent_run_id.to_string(),
+                    merge_commit.clone(),
+                    format!("Merged branch {} into main", branch),
+                );
+                let recorded = db
+                    .call(move |db| {
+                        let recorded = db.set_agent_run_merge_commit(&id, &commit);
+                        let _ = db.insert_agent_event(
+                            &id,
+                            "merge_completed",
+                            None,
+                            &summary,
+                            None,
+                            None,
+                        );
+                        recorded
+                    })
+                    .await;
+                if let Err(e) = recorded {
                     tracing::error!(
-                        "Auto-dispatch: failed to spawn agent for task {}: {}",
-                        task.id,
+                        "Failed to record merge commit for agent {}: {}",
+                        agent_run_id,
                         e
                     );
                 }
             }
+            // Clean up the merged branch
+            if let Err(e) = worktree::delete_branch(repo, branch).await {
+                tracing::warn!("Failed to delete merged branch {}: {}", branch, e);
+            }
+            Ok(merge_commit)
+        }
+        Err(e) => {
+            if let Some(agent_run_id) = agent_run_id {
+                let (id, summary) = (
+                    agent_run_id.to_string(),
+                    format!("Failed to merge branch {}: {}", branch, e),
+                );
+                let _ = db
+                    .call(move |db| {
+                        db.insert_agent_event(&id, "merge_failed", None, &summary, None, None)
+                    })
+                    .await;
+            }
+            Err(e)
         }
-
-        tracing::info!(
-            "Auto-dispatch: spawned {} agents for goal {}",
-            spawned,
-            goal_space_id
-        );
     }
 }
 
@@ -1,5 +1,5 @@
 use axum::{
-    extract::{Json, Path, State},
+    extract::{DefaultBodyLimit, Json, Path, Query, State},
     http::{header, StatusCode, Uri},
     response::IntoResponse,
     routing::{get, post},
@@ -15,10 +15,14 @@ use tower_http::cors::CorsLayer;
 struct FrontendAssets;
 
 use crate::agent::session::BroadcastEvent;
-use crate::db::queries::{CreateGoalSpace, CreateProject, CreateTask, UpdateProject, UpdateTask};
+use crate::db::queries::{
+    Actor, AgentRun, CreateGoalSpace, CreateProject, CreateTask, GoalSpace, ListFilter, Task,
+    UpdateProject, UpdateTask,
+};
+use crate::goal::task::{validate_transition, InvalidDependencies, InvalidTransition, TaskStatus};
 use crate::hooks;
 use crate::server::sse;
-use crate::server::AppState;
+use crate::server::{AppState, Landing};
 
 pub fn create_router(state: Arc<AppState>) -> Router {
     Router::new()
@@ -30,11 +34,24 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         )
         .route("/api/goals/{id}/decompose", post(decompose_goal))
         .route("/api/goals/{id}/dispatch", post(dispatch_goal))
+        .route("/api/goals/{id}/check", post(check_goal))
+        .route("/api/goals/{id}/export", get(export_goal))
+        .route(
+            "/api/goals/import",
+            post(import_goal).layer(DefaultBodyLimit::max(BUNDLE_BODY_LIMIT)),
+        )
         .route("/api/goals/{id}/tasks", get(list_tasks).post(create_task))
+        .route("/api/goals/{id}/graph/validate", get(validate_goal_graph))
+        .route("/api/goals/{id}/estimate", get(estimate_goal))
+        .route("/api/goals/{id}/schedule", get(goal_schedule))
+        .route("/api/goals/{id}/history", get(list_goal_history))
         // Tasks
         .route("/api/tasks/{id}", get(get_single_task).put(update_task))
         .route("/api/tasks/{id}/retry", post(retry_task))
         .route("/api/tasks/{id}/dispatch", post(dispatch_task))
+        .route("/api/tasks/{id}/approve", post(approve_task))
+        .route("/api/tasks/{id}/reject", post(reject_task))
+        .route("/api/tasks/{id}/revert", post(revert_task))
         .route("/api/goals/{id}/retry-failed", post(retry_all_failed))
         // Agents
         .route("/api/agents", get(list_agents))
@@ -42,6 +59,11 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/api/agents/{id}/nudge", post(nudge_agent))
         .route("/api/agents/{id}/kill", post(kill_agent))
         .route("/api/agents/{id}/events", get(get_agent_events))
+        .route("/api/agents/{id}/transcript", get(get_agent_transcript))
+        .route("/api/agents/{id}/diff", get(get_agent_diff))
+        .route("/api/agents/{id}/commits", get(get_agent_commits))
+        .route("/api/agents/{id}/pr", get(get_agent_pr))
+        .route("/api/agents/{id}/publish", post(publish_agent))
         // SSE
         .route("/api/events", get(sse::global_event_stream))
         .route("/api/agents/{id}/stream", get(sse::agent_event_stream))
@@ -67,7 +89,21 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/api/goals/{id}/chat", post(goal_chat_handler))
         .route("/api/goals/{id}/messages", get(list_goal_messages_handler))
         // Stats
+        .route("/api/search", get(search))
         .route("/api/stats", get(get_stats))
+        .route("/api/stats/git-locks", get(get_git_lock_stats))
+        .route("/api/analytics/cost", get(cost_analytics))
+        .route("/api/analytics/usage", get(usage_analytics))
+        .route("/api/scheduler", get(get_scheduler).put(update_scheduler))
+        // Merges waiting for a clean checkout
+        .route("/api/merges/deferred", get(list_deferred_merges))
+        .route(
+            "/api/merges/deferred/{id}/retry",
+            post(retry_deferred_merge),
+        )
+        // Persistent merge and dispatch queue
+        .route("/api/jobs", get(list_jobs))
+        .route("/api/jobs/{id}/retry", post(retry_job))
         .fallback(static_handler)
         .layer(
             CorsLayer::permissive(), // Allow frontend dev server
@@ -107,9 +143,14 @@ async fn static_handler(uri: Uri) -> impl IntoResponse {
 
 async fn create_goal(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Json(input): Json<CreateGoalSpace>,
 ) -> impl IntoResponse {
-    match state.db.create_goal_space(&input) {
+    match state
+        .db
+        .call(move |db| db.create_goal_space_by(&input, actor))
+        .await
+    {
         Ok(goal) => (StatusCode::CREATED, Json(json!(goal))).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -119,9 +160,58 @@ async fn create_goal(
     }
 }
 
-async fn list_goals(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.db.list_goal_spaces() {
-        Ok(goals) => Json(json!(goals)).into_response(),
+/// Header a client sets to say who it is (`ui`, `cli` or `api`) in goal history
+pub const ACTOR_HEADER: &str = "x-conductor-actor";
+
+/// The actor of a request, from `X-Conductor-Actor`. Clients can only claim to be
+/// the UI, the CLI or a plain API client; anything else counts as `api`.
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for Actor {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .headers
+            .get(ACTOR_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Actor::parse)
+            .filter(|a| matches!(a, Actor::Ui | Actor::Cli | Actor::Api))
+            .unwrap_or(Actor::Api))
+    }
+}
+
+/// Header carrying the cursor for the next page of a list endpoint
+const NEXT_CURSOR_HEADER: &str = "x-next-after-id";
+
+/// A page of rows as a JSON array. When the page is full, the id of its last
+/// row is returned in `X-Next-After-Id` for use as the next `after_id`.
+fn page_response<T: serde::Serialize>(
+    rows: Vec<T>,
+    filter: &ListFilter,
+    id_of: impl Fn(&T) -> String,
+) -> axum::response::Response {
+    let full = filter.limit.is_some_and(|limit| rows.len() as u32 >= limit);
+    let next = rows.last().filter(|_| full).map(&id_of);
+    let mut response = Json(json!(rows)).into_response();
+    if let Some(next) = next.and_then(|id| header::HeaderValue::from_str(&id).ok()) {
+        response.headers_mut().insert(NEXT_CURSOR_HEADER, next);
+    }
+    response
+}
+
+async fn list_goals(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<ListFilter>,
+) -> impl IntoResponse {
+    let query = filter.clone();
+    match state
+        .db
+        .call(move |db| db.list_goal_spaces_filtered(&query))
+        .await
+    {
+        Ok(goals) => page_response(goals, &filter, |g| g.id.clone()),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": e.to_string()})),
@@ -131,7 +221,7 @@ async fn list_goals(State(state): State<Arc<AppState>>) -> impl IntoResponse {
 }
 
 async fn get_goal(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> impl IntoResponse {
-    match state.db.get_goal_space(&id) {
+    match state.db.call(move |db| db.get_goal_space(&id)).await {
         Ok(Some(goal)) => Json(json!(goal)).into_response(),
         Ok(None) => (StatusCode::NOT_FOUND, Json(json!({"error": "Not found"}))).into_response(),
         Err(e) => (
@@ -145,51 +235,155 @@ async fn get_goal(State(state): State<Arc<AppState>>, Path(id): Path<String>) ->
 async fn update_goal(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    actor: Actor,
     Json(input): Json<serde_json::Value>,
 ) -> impl IntoResponse {
-    let name = input.get("name").and_then(|v| v.as_str());
-    let description = input.get("description").and_then(|v| v.as_str());
-    let status = input.get("status").and_then(|v| v.as_str());
+    // Parsed up front so bad settings are refused before anything is written
+    let settings = match input.get("settings").cloned().map(serde_json::from_value) {
+        Some(Ok(settings)) => Some(settings),
+        Some(Err(e)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Invalid settings format: {}", e)})),
+            )
+                .into_response();
+        }
+        None => None,
+    };
 
-    // Update name, description, status if provided
-    if let Err(e) = state.db.update_goal_space(&id, name, description, status) {
-        return (
+    let result = state
+        .db
+        .call(move |db| {
+            let name = input.get("name").and_then(|v| v.as_str());
+            let description = input.get("description").and_then(|v| v.as_str());
+            let status = input.get("status").and_then(|v| v.as_str());
+            let before = db.get_goal_space(&id).ok().flatten();
+
+            // Update name, description, status if provided
+            db.update_goal_space(&id, name, description, status)?;
+            if name.is_some() || description.is_some() || status.is_some() {
+                let _ = db.insert_goal_history(
+                    &id,
+                    actor,
+                    "goal_updated",
+                    "Goal details updated",
+                    Some(json!({"name": name, "description": description, "status": status})),
+                );
+            }
+
+            // Update settings if provided
+            if let Some(settings) = settings {
+                db.update_goal_settings(&id, &settings)?;
+                let _ = db.insert_goal_history(
+                    &id,
+                    actor,
+                    "settings_changed",
+                    "Goal settings changed",
+                    Some(json!({
+                        "before": before.map(|g| g.settings),
+                        "after": settings,
+                    })),
+                );
+            }
+            Ok(())
+        })
+        .await;
+
+    match result {
+        Ok(()) => Json(json!({"ok": true})).into_response(),
+        Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": e.to_string()})),
         )
-            .into_response();
+            .into_response(),
     }
+}
 
-    // Update settings if provided
-    if let Some(settings_value) = input.get("settings") {
-        match serde_json::from_value(settings_value.clone()) {
-            Ok(settings) => {
-                if let Err(e) = state.db.update_goal_settings(&id, &settings) {
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": e.to_string()})),
-                    )
-                        .into_response();
-                }
-            }
-            Err(e) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({"error": format!("Invalid settings format: {}", e)})),
-                )
-                    .into_response();
-            }
-        }
+/// Largest goal bundle the import endpoint accepts
+const BUNDLE_BODY_LIMIT: usize = 1024 * 1024 * 1024;
+
+async fn export_goal(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let goal_id = id.clone();
+    match state
+        .db
+        .call(move |db| match db.get_goal_space(&goal_id)? {
+            Some(_) => crate::goal::bundle::export_goal(db, &goal_id).map(Some),
+            None => Ok(None),
+        })
+        .await
+    {
+        Ok(None) => (StatusCode::NOT_FOUND, Json(json!({"error": "Not found"}))).into_response(),
+        Ok(Some(bundle)) => (
+            [
+                (header::CONTENT_TYPE, "application/zstd".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"goal-{}.tar.zst\"", id),
+                ),
+            ],
+            bundle,
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
     }
+}
 
-    Json(json!({"ok": true})).into_response()
+/// Import a goal bundle from the request body. `repo_path`, `project_id`
+/// and `name` query parameters rebind the imported goal.
+async fn import_goal(
+    State(state): State<Arc<AppState>>,
+    Query(options): Query<crate::goal::bundle::ImportOptions>,
+    actor: Actor,
+    body: axum::body::Bytes,
+) -> impl IntoResponse {
+    match state
+        .db
+        .call(move |db| {
+            let report = crate::goal::bundle::import_goal(db, &body, &options)?;
+            let _ = db.insert_goal_history(
+                &report.goal_space_id,
+                actor,
+                "imported",
+                &format!("Imported from bundle version {}", report.bundle_version),
+                Some(json!({
+                    "source_goal_space_id": report.source_goal_space_id,
+                    "bundle_version": report.bundle_version,
+                })),
+            );
+            Ok(report)
+        })
+        .await
+    {
+        Ok(report) => (StatusCode::CREATED, Json(json!(report))).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": format!("{:#}", e)})),
+        )
+            .into_response(),
+    }
 }
 
 async fn delete_goal(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    actor: Actor,
 ) -> impl IntoResponse {
-    match state.db.delete_goal_space(&id) {
+    let result = state
+        .db
+        .call(move |db| {
+            db.delete_goal_space(&id)?;
+            let _ = db.insert_goal_history(&id, actor, "archived", "Goal archived", None);
+            Ok(())
+        })
+        .await;
+    match result {
         Ok(()) => Json(json!({"ok": true})).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -202,8 +396,10 @@ async fn delete_goal(
 async fn decompose_goal(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    actor: Actor,
 ) -> impl IntoResponse {
-    let goal = match state.db.get_goal_space(&id) {
+    let goal_id = id.clone();
+    let goal = match state.db.call(move |db| db.get_goal_space(&goal_id)).await {
         Ok(Some(g)) => g,
         Ok(None) => {
             return (
@@ -251,12 +447,27 @@ async fn decompose_goal(
             Ok(tasks) => {
                 let mut created_tasks = Vec::new();
                 let mut failed = false;
-                // Map __index_N placeholders to real task UUIDs as we create them
+                // Create tasks dependencies-first so every __index_N placeholder
+                // maps to a real task UUID by the time a task refers to it
+                let order = match crate::goal::decompose::creation_order(&tasks) {
+                    Ok(order) => order,
+                    Err(e) => {
+                        let _ = state.event_tx.send(BroadcastEvent::OperationUpdate {
+                            operation_id: op_id.clone(),
+                            goal_space_id: gs_id.clone(),
+                            operation_type: "decompose".to_string(),
+                            status: "failed".to_string(),
+                            message: format!("Invalid task dependencies: {}", e),
+                            result: None,
+                        });
+                        return;
+                    }
+                };
                 let mut index_to_id: std::collections::HashMap<String, String> =
                     std::collections::HashMap::new();
 
-                for (i, task_input) in tasks.iter().enumerate() {
-                    // Resolve __index_N dependencies to actual task IDs
+                for i in order {
+                    let task_input = &tasks[i];
                     let resolved = CreateTask {
                         title: task_input.title.clone(),
                         description: task_input.description.clone(),
@@ -264,21 +475,17 @@ async fn decompose_goal(
                         depends_on: task_input
                             .depends_on
                             .iter()
-                            .filter_map(|dep| {
-                                index_to_id.get(dep).cloned().or_else(|| {
-                                    tracing::warn!(
-                                        "Unresolved dependency '{}' for task '{}'",
-                                        dep,
-                                        task_input.title
-                                    );
-                                    None
-                                })
-                            })
+                            .filter_map(|dep| index_to_id.get(dep).cloned())
                             .collect(),
                         settings: Default::default(),
                     };
 
-                    match state.db.create_task(&gs_id, &resolved) {
+                    let goal_id = gs_id.clone();
+                    match state
+                        .db
+                        .call(move |db| db.create_task_by(&goal_id, &resolved, actor))
+                        .await
+                    {
                         Ok(task) => {
                             index_to_id.insert(format!("__index_{}", i), task.id.clone());
                             created_tasks.push(task);
@@ -331,8 +538,10 @@ async fn decompose_goal(
 async fn dispatch_goal(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    actor: Actor,
 ) -> impl IntoResponse {
-    let goal = match state.db.get_goal_space(&id) {
+    let goal_id = id.clone();
+    let goal = match state.db.call(move |db| db.get_goal_space(&goal_id)).await {
         Ok(Some(g)) => g,
         Ok(None) => {
             return (
@@ -352,6 +561,19 @@ async fn dispatch_goal(
 
     let operation_id = uuid::Uuid::new_v4().to_string();
     let goal_space_id = id.clone();
+    let metadata = json!({"operation_id": operation_id});
+    let _ = state
+        .db
+        .call(move |db| {
+            db.insert_goal_history(
+                &id,
+                actor,
+                "goal_dispatched",
+                "Dispatch requested for all ready tasks",
+                Some(metadata),
+            )
+        })
+        .await;
 
     // Broadcast running status
     let _ = state.event_tx.send(BroadcastEvent::OperationUpdate {
@@ -367,7 +589,12 @@ async fn dispatch_goal(
     let op_id = operation_id.clone();
     let state = Arc::clone(&state);
     tokio::spawn(async move {
-        let unblocked = match state.db.get_unblocked_tasks(&goal_space_id) {
+        let scheduled = goal.clone();
+        let unblocked = match state
+            .db
+            .call(move |db| crate::goal::schedule::next_tasks(db, &scheduled))
+            .await
+        {
             Ok(tasks) => tasks,
             Err(e) => {
                 let _ = state.event_tx.send(BroadcastEvent::OperationUpdate {
@@ -385,31 +612,7 @@ async fn dispatch_goal(
         let mut agents_spawned = 0;
 
         for task in &unblocked {
-            let effective = goal.settings.merge(&task.settings);
-            let prompt = format!(
-                "You are working on the following task as part of the goal: {}\n\n\
-                 Task: {}\n\n\
-                 Description: {}\n\n\
-                 Work in the current directory. Make your changes, test them, and commit when done.",
-                goal.description, task.title, task.description
-            );
-
-            match state
-                .agent_manager
-                .spawn_agent(
-                    &task.id,
-                    &goal_space_id,
-                    &prompt,
-                    &goal.repo_path,
-                    &effective.model(),
-                    Some(effective.max_budget_usd()),
-                    Some(effective.max_turns()),
-                    Some(effective.allowed_tools()),
-                    effective.permission_mode(),
-                    effective.system_prompt(),
-                )
-                .await
-            {
+            match state.agent_manager.dispatch_task(&goal, task).await {
                 Ok(_) => agents_spawned += 1,
                 Err(e) => {
                     tracing::error!("Failed to spawn agent for task {}: {}", task.id, e);
@@ -446,7 +649,7 @@ async fn get_single_task(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    match state.db.get_task(&id) {
+    match state.db.call(move |db| db.get_task(&id)).await {
         Ok(Some(task)) => Json(json!(task)).into_response(),
         Ok(None) => (StatusCode::NOT_FOUND, Json(json!({"error": "Not found"}))).into_response(),
         Err(e) => (
@@ -461,7 +664,7 @@ async fn list_tasks(
     State(state): State<Arc<AppState>>,
     Path(goal_id): Path<String>,
 ) -> impl IntoResponse {
-    match state.db.list_tasks(&goal_id) {
+    match state.db.call(move |db| db.list_tasks(&goal_id)).await {
         Ok(tasks) => Json(json!(tasks)).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -474,10 +677,43 @@ async fn list_tasks(
 async fn create_task(
     State(state): State<Arc<AppState>>,
     Path(goal_id): Path<String>,
+    actor: Actor,
     Json(input): Json<CreateTask>,
 ) -> impl IntoResponse {
-    match state.db.create_task(&goal_id, &input) {
+    match state
+        .db
+        .call(move |db| db.create_task_by(&goal_id, &input, actor))
+        .await
+    {
         Ok(task) => (StatusCode::CREATED, Json(json!(task))).into_response(),
+        Err(e) => task_write_error(e),
+    }
+}
+
+async fn validate_goal_graph(
+    State(state): State<Arc<AppState>>,
+    Path(goal_id): Path<String>,
+) -> impl IntoResponse {
+    let id = goal_id.clone();
+    let result = state
+        .db
+        .call(move |db| match db.get_goal_space(&id)? {
+            Some(_) => db.goal_graph_problems(&id).map(Some),
+            None => Ok(None),
+        })
+        .await;
+    match result {
+        Ok(Some(problems)) => Json(json!({
+            "goal_space_id": goal_id,
+            "valid": problems.is_empty(),
+            "problems": problems,
+        }))
+        .into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Goal not found"})),
+        )
+            .into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": e.to_string()})),
@@ -486,13 +722,52 @@ async fn create_task(
     }
 }
 
-async fn update_task(
+/// Expected spend and ETA for a goal's remaining tasks, from past runs
+async fn estimate_goal(
     State(state): State<Arc<AppState>>,
-    Path(id): Path<String>,
-    Json(input): Json<UpdateTask>,
+    Path(goal_id): Path<String>,
 ) -> impl IntoResponse {
-    match state.db.update_task(&id, &input) {
-        Ok(()) => Json(json!({"ok": true})).into_response(),
+    let result = state
+        .db
+        .call(move |db| match db.get_goal_space(&goal_id)? {
+            Some(goal) => crate::goal::estimate::estimate_goal(db, &goal).map(Some),
+            None => Ok(None),
+        })
+        .await;
+    match result {
+        Ok(Some(estimate)) => Json(json!(estimate)).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Goal not found"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// The order the goal's pending tasks will be dispatched in, and why
+async fn goal_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(goal_id): Path<String>,
+) -> impl IntoResponse {
+    let result = state
+        .db
+        .call(move |db| match db.get_goal_space(&goal_id)? {
+            Some(goal) => crate::goal::schedule::goal_schedule(db, &goal).map(Some),
+            None => Ok(None),
+        })
+        .await;
+    match result {
+        Ok(Some(schedule)) => Json(json!(schedule)).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Goal not found"})),
+        )
+            .into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": e.to_string()})),
@@ -501,13 +776,76 @@ async fn update_task(
     }
 }
 
+/// 409 for a status change the state machine rejects, listing the allowed next states
+fn transition_conflict(invalid: &InvalidTransition) -> axum::response::Response {
+    (
+        StatusCode::CONFLICT,
+        Json(json!({
+            "error": invalid.to_string(),
+            "from": invalid.from,
+            "to": invalid.to,
+            "allowed": invalid.allowed,
+        })),
+    )
+        .into_response()
+}
+
+/// Response for a failed task write: a rejected transition is a 409, rejected
+/// dependencies a 400 listing the problems, anything else a 500
+fn task_write_error(e: anyhow::Error) -> axum::response::Response {
+    if let Some(invalid) = e.downcast_ref::<InvalidTransition>() {
+        return transition_conflict(invalid);
+    }
+    if let Some(InvalidDependencies(problems)) = e.downcast_ref::<InvalidDependencies>() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": e.to_string(), "problems": problems})),
+        )
+            .into_response();
+    }
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"error": e.to_string()})),
+    )
+        .into_response()
+}
+
+async fn update_task(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    actor: Actor,
+    Json(input): Json<UpdateTask>,
+) -> impl IntoResponse {
+    let result = state
+        .db
+        .call(move |db| {
+            db.update_task(&id, &input)?;
+            if let Ok(Some(task)) = db.get_task(&id) {
+                let _ = db.insert_goal_history(
+                    &task.goal_space_id,
+                    actor,
+                    "task_updated",
+                    &format!("Task '{}' updated", task.title),
+                    Some(json!({"task_id": id, "changes": input})),
+                );
+            }
+            Ok(())
+        })
+        .await;
+    match result {
+        Ok(()) => Json(json!({"ok": true})).into_response(),
+        Err(e) => task_write_error(e),
+    }
+}
+
 async fn retry_task(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    actor: Actor,
 ) -> impl IntoResponse {
     // Reset task to pending so it can be dispatched again
     let update = UpdateTask {
-        status: Some("pending".to_string()),
+        status: Some(TaskStatus::Pending),
         title: None,
         description: None,
         priority: None,
@@ -515,28 +853,97 @@ async fn retry_task(
         ..Default::default()
     };
 
-    match state.db.update_task(&id, &update) {
-        Ok(()) => {
-            // Find the goal_space_id for this task and trigger dispatch
-            if let Ok(Some(task)) = state.db.get_task(&id) {
-                state.agent_manager.request_dispatch(&task.goal_space_id);
+    let result = state
+        .db
+        .call(move |db| {
+            let was_reverted = matches!(
+                db.get_task(&id),
+                Ok(Some(ref t)) if t.status == TaskStatus::Reverted
+            );
+            db.update_task(&id, &update)?;
+            // Find the goal_space_id for this task so dispatch can be triggered
+            let Ok(Some(task)) = db.get_task(&id) else {
+                return Ok(None);
+            };
+            if was_reverted {
+                match crate::goal::space::unblock_dependents(db, &task) {
+                    Ok(unblocked) => {
+                        for dependent in unblocked {
+                            let _ = db.insert_goal_history(
+                                &task.goal_space_id,
+                                actor,
+                                "dependent_unblocked",
+                                &format!(
+                                    "Task '{}' unblocked; '{}' is being redone",
+                                    dependent.title, task.title
+                                ),
+                                Some(json!({"task_id": dependent.id, "retried_task_id": id})),
+                            );
+                        }
+                    }
+                    Err(e) => tracing::error!("Failed to unblock dependents of {}: {}", id, e),
+                }
+            }
+            let _ = db.insert_goal_history(
+                &task.goal_space_id,
+                actor,
+                "task_retried",
+                &format!("Task '{}' reset for retry", task.title),
+                Some(json!({"task_id": id})),
+            );
+            Ok(Some(task.goal_space_id))
+        })
+        .await;
+    match result {
+        Ok(goal_space_id) => {
+            if let Some(goal_space_id) = goal_space_id {
+                state.agent_manager.request_dispatch(&goal_space_id);
             }
             Json(json!({"ok": true, "status": "pending"})).into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => task_write_error(e),
     }
 }
 
 async fn retry_all_failed(
     State(state): State<Arc<AppState>>,
     Path(goal_id): Path<String>,
+    actor: Actor,
 ) -> impl IntoResponse {
-    let tasks = match state.db.list_tasks(&goal_id) {
-        Ok(t) => t,
+    let id = goal_id.clone();
+    let result = state
+        .db
+        .call(move |db| {
+            let mut retried = Vec::new();
+            for task in db.list_tasks(&id)? {
+                if task.status == TaskStatus::Failed {
+                    let update = UpdateTask {
+                        status: Some(TaskStatus::Pending),
+                        title: None,
+                        description: None,
+                        priority: None,
+                        depends_on: None,
+                        ..Default::default()
+                    };
+                    if db.update_task(&task.id, &update).is_ok() {
+                        retried.push(task.id);
+                    }
+                }
+            }
+            if !retried.is_empty() {
+                let _ = db.insert_goal_history(
+                    &id,
+                    actor,
+                    "tasks_retried",
+                    &format!("{} failed tasks reset for retry", retried.len()),
+                    Some(json!({"task_ids": retried})),
+                );
+            }
+            Ok(retried)
+        })
+        .await;
+    let retried = match result {
+        Ok(retried) => retried,
         Err(e) => {
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -546,37 +953,22 @@ async fn retry_all_failed(
         }
     };
 
-    let mut retried = 0;
-    for task in &tasks {
-        if task.status == "failed" {
-            let update = UpdateTask {
-                status: Some("pending".to_string()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
-            };
-            if state.db.update_task(&task.id, &update).is_ok() {
-                retried += 1;
-            }
-        }
-    }
-
     // Trigger auto-dispatch for the newly-pending tasks
-    if retried > 0 {
+    if !retried.is_empty() {
         state.agent_manager.request_dispatch(&goal_id);
     }
 
-    Json(json!({"ok": true, "retried": retried})).into_response()
+    Json(json!({"ok": true, "retried": retried.len()})).into_response()
 }
 
 async fn dispatch_task(
     State(state): State<Arc<AppState>>,
     Path(task_id): Path<String>,
+    actor: Actor,
 ) -> impl IntoResponse {
     // Get the task to find its goal_space_id and other info
-    let task = match state.db.get_task(&task_id) {
+    let id = task_id.clone();
+    let mut task = match state.db.call(move |db| db.get_task(&id)).await {
         Ok(Some(t)) => t,
         Ok(None) => {
             return (
@@ -595,7 +987,8 @@ async fn dispatch_task(
     };
 
     // Get the goal space to find the repo_path and description
-    let goal = match state.db.get_goal_space(&task.goal_space_id) {
+    let goal_id = task.goal_space_id.clone();
+    let goal = match state.db.call(move |db| db.get_goal_space(&goal_id)).await {
         Ok(Some(g)) => g,
         Ok(None) => {
             return (
@@ -613,8 +1006,41 @@ async fn dispatch_task(
         }
     };
 
+    // Dispatching a failed task retries it
+    if task.status == TaskStatus::Failed {
+        let update = UpdateTask {
+            status: Some(TaskStatus::Pending),
+            ..Default::default()
+        };
+        let id = task.id.clone();
+        if let Err(e) = state.db.call(move |db| db.update_task(&id, &update)).await {
+            return task_write_error(e);
+        }
+        task.status = TaskStatus::Pending;
+    }
+    if let Err(invalid) = validate_transition(task.status, TaskStatus::Running) {
+        return transition_conflict(&invalid);
+    }
+
     let operation_id = uuid::Uuid::new_v4().to_string();
     let goal_space_id = task.goal_space_id.clone();
+    let (goal_id, description) = (
+        goal_space_id.clone(),
+        format!("Dispatch requested for task '{}'", task.title),
+    );
+    let metadata = json!({"task_id": task.id, "operation_id": operation_id});
+    let _ = state
+        .db
+        .call(move |db| {
+            db.insert_goal_history(
+                &goal_id,
+                actor,
+                "task_dispatched",
+                &description,
+                Some(metadata),
+            )
+        })
+        .await;
 
     // Broadcast running status
     let _ = state.event_tx.send(BroadcastEvent::OperationUpdate {
@@ -630,31 +1056,7 @@ async fn dispatch_task(
     let op_id = operation_id.clone();
     let state = Arc::clone(&state);
     tokio::spawn(async move {
-        let effective = goal.settings.merge(&task.settings);
-        let prompt = format!(
-            "You are working on the following task as part of the goal: {}\n\n\
-             Task: {}\n\n\
-             Description: {}\n\n\
-             Work in the current directory. Make your changes, test them, and commit when done.",
-            goal.description, task.title, task.description
-        );
-
-        match state
-            .agent_manager
-            .spawn_agent(
-                &task.id,
-                &goal_space_id,
-                &prompt,
-                &goal.repo_path,
-                &effective.model(),
-                Some(effective.max_budget_usd()),
-                Some(effective.max_turns()),
-                Some(effective.allowed_tools()),
-                effective.permission_mode(),
-                effective.system_prompt(),
-            )
-            .await
-        {
+        match state.agent_manager.dispatch_task(&goal, &task).await {
             Ok(_) => {
                 let _ = state.event_tx.send(BroadcastEvent::OperationUpdate {
                     operation_id: op_id,
@@ -686,11 +1088,34 @@ async fn dispatch_task(
         .into_response()
 }
 
-// ── Agent Handlers ──
+/// Run the goal's post-merge check now, bisecting to the culprit task if it fails
+async fn check_goal(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    actor: Actor,
+) -> impl IntoResponse {
+    let goal_id = id.clone();
+    match state.db.call(move |db| db.get_goal_space(&goal_id)).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (StatusCode::NOT_FOUND, Json(json!({"error": "Not found"}))).into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
 
-async fn list_agents(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.db.list_agent_runs() {
-        Ok(agents) => Json(json!(agents)).into_response(),
+    match crate::server::run_post_merge_check(&state, &id, actor).await {
+        Ok(Some(report)) => Json(json!(report)).into_response(),
+        Ok(None) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "No post_merge_command is configured for this goal"})),
+        )
+            .into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": e.to_string()})),
@@ -699,84 +1124,686 @@ async fn list_agents(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     }
 }
 
-async fn get_agent(
+/// Load a task that is waiting for review, along with its goal and latest agent run
+async fn reviewable_task(
+    state: &AppState,
+    id: &str,
+) -> Result<(Task, GoalSpace, AgentRun), (StatusCode, String)> {
+    let id = id.to_string();
+    let (task, goal, run) = state
+        .db
+        .call(move |db| {
+            let task = db.get_task(&id)?;
+            let goal = match task {
+                Some(ref task) => db.get_goal_space(&task.goal_space_id)?,
+                None => None,
+            };
+            Ok((task, goal, db.latest_agent_run_for_task(&id)?))
+        })
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let task = task.ok_or((StatusCode::NOT_FOUND, "Task not found".to_string()))?;
+    if task.status != TaskStatus::AwaitingReview {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Task is {}, not awaiting_review", task.status),
+        ));
+    }
+    let goal = goal.ok_or((StatusCode::NOT_FOUND, "Goal space not found".to_string()))?;
+    let run = run.ok_or((StatusCode::NOT_FOUND, "Task has no agent run".to_string()))?;
+
+    Ok((task, goal, run))
+}
+
+async fn approve_task(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    actor: Actor,
 ) -> impl IntoResponse {
-    match state.db.get_agent_run(&id) {
-        Ok(Some(agent)) => Json(json!(agent)).into_response(),
-        Ok(None) => (StatusCode::NOT_FOUND, Json(json!({"error": "Not found"}))).into_response(),
-        Err(e) => (
+    let (task, goal, run) = match reviewable_task(&state, &id).await {
+        Ok(v) => v,
+        Err((status, msg)) => return (status, Json(json!({"error": msg}))).into_response(),
+    };
+
+    let (merge_commit, deferred_merge) = match run.branch {
+        Some(ref branch) => {
+            let repo = std::path::Path::new(&goal.repo_path);
+            match crate::server::land_branch_checked(
+                &state.db,
+                repo,
+                branch,
+                Some(&run.id),
+                &goal.id,
+                actor,
+            )
+            .await
+            {
+                Ok(Landing::Merged(sha)) => (Some(sha), None),
+                Ok(Landing::Deferred(merge)) => (None, Some(merge)),
+                Err(e) => {
+                    return (StatusCode::CONFLICT, Json(json!({"error": e.to_string()})))
+                        .into_response()
+                }
+            }
+        }
+        None => (None, None),
+    };
+
+    let update = UpdateTask {
+        status: Some(TaskStatus::Done),
+        ..Default::default()
+    };
+    let description = if deferred_merge.is_some() {
+        format!("Task '{}' approved; its merge is deferred", task.title)
+    } else {
+        format!("Task '{}' approved and merged", task.title)
+    };
+    let metadata = json!({
+        "task_id": task.id,
+        "agent_run_id": run.id,
+        "merge_commit": merge_commit,
+        "deferred": deferred_merge.is_some(),
+    });
+    let goal_id = goal.id.clone();
+    let result = state
+        .db
+        .call(move |db| {
+            db.update_task(&task.id, &update)?;
+            let _ = db.insert_goal_history(
+                &goal_id,
+                actor,
+                "task_approved",
+                &description,
+                Some(metadata),
+            );
+            let _ = crate::goal::space::check_goal_completion(db, &goal_id);
+            Ok(())
+        })
+        .await;
+    if let Err(e) = result {
+        return (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": e.to_string()})),
         )
-            .into_response(),
+            .into_response();
     }
+    // Dependents were held back until this task landed
+    state.agent_manager.request_dispatch(&goal.id);
+
+    Json(json!({
+        "ok": true,
+        "status": "done",
+        "merge_commit": merge_commit,
+        "deferred_merge": deferred_merge,
+    }))
+    .into_response()
 }
 
-async fn nudge_agent(
+async fn reject_task(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    actor: Actor,
     Json(input): Json<serde_json::Value>,
 ) -> impl IntoResponse {
-    let message = input.get("message").and_then(|m| m.as_str()).unwrap_or("");
+    let (task, goal, run) = match reviewable_task(&state, &id).await {
+        Ok(v) => v,
+        Err((status, msg)) => return (status, Json(json!({"error": msg}))).into_response(),
+    };
 
-    if message.is_empty() {
+    let feedback = input
+        .get("feedback")
+        .and_then(|f| f.as_str())
+        .unwrap_or("")
+        .to_string();
+    // Start a follow-up run by default when the reviewer left feedback
+    let redispatch = input
+        .get("redispatch")
+        .and_then(|r| r.as_bool())
+        .unwrap_or(!feedback.is_empty());
+
+    let summary = if feedback.is_empty() {
+        "Rejected in review".to_string()
+    } else {
+        format!("Rejected in review: {}", feedback)
+    };
+    let status = if redispatch {
+        TaskStatus::Pending
+    } else {
+        TaskStatus::Failed
+    };
+    let update = UpdateTask {
+        status: Some(status),
+        ..Default::default()
+    };
+    let (run_id, goal_id) = (run.id.clone(), goal.id.clone());
+    let result = state
+        .db
+        .call(move |db| {
+            db.insert_agent_event(
+                &run_id,
+                "review_rejected",
+                None,
+                &summary,
+                Some(&json!({"feedback": feedback}).to_string()),
+                None,
+            )?;
+            db.update_task(&task.id, &update)?;
+            let _ = db.insert_goal_history(
+                &goal_id,
+                actor,
+                "task_rejected",
+                &format!("Task '{}' rejected in review", task.title),
+                Some(json!({
+                    "task_id": task.id,
+                    "agent_run_id": run_id,
+                    "feedback": feedback,
+                    "redispatch": redispatch,
+                })),
+            );
+            Ok(())
+        })
+        .await;
+    if let Err(e) = result {
         return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Message required"})),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
         )
             .into_response();
     }
 
-    match state.agent_manager.nudge_agent(&id, message).await {
-        Ok(()) => Json(json!({"ok": true})).into_response(),
-        Err(e) => {
-            let err_str = e.to_string();
-            // If the agent doesn't have a session ID yet, return 409 Conflict
-            if err_str.contains("no Claude session ID yet") {
-                (
-                    StatusCode::CONFLICT,
-                    Json(json!({"error": "Agent is still starting up. Please wait a moment and try again."})),
-                )
-                    .into_response()
-            } else if err_str.contains("not found or not running") {
-                (
-                    StatusCode::NOT_FOUND,
-                    Json(json!({"error": "Agent not found or not running"})),
-                )
-                    .into_response()
-            } else {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({"error": err_str})),
-                )
-                    .into_response()
-            }
+    if redispatch {
+        // The follow-up run deletes this branch once it has started
+        state.agent_manager.request_dispatch(&goal.id);
+    } else if let Some(ref branch) = run.branch {
+        let repo = std::path::Path::new(&goal.repo_path);
+        if let Err(e) = crate::agent::worktree::discard_branch(repo, branch).await {
+            tracing::warn!("Failed to delete rejected branch {}: {}", branch, e);
         }
     }
+
+    Json(json!({"ok": true, "status": status})).into_response()
 }
 
-async fn kill_agent(
+/// Undo a merged task: revert its merge commit on main, or hand the revert to an
+/// agent as a new task when it conflicts with work merged since
+async fn revert_task(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    actor: Actor,
 ) -> impl IntoResponse {
-    match state.agent_manager.kill_agent(&id).await {
-        Ok(()) => Json(json!({"ok": true})).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e.to_string()})),
+    let error = |status: StatusCode, msg: String| (status, Json(json!({"error": msg})));
+    let internal = |e: anyhow::Error| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+
+    let loaded = state
+        .db
+        .call(move |db| {
+            let task = db.get_task(&id)?;
+            let run = db.merged_agent_run_for_task(&id)?;
+            let goal = match task {
+                Some(ref task) => db.get_goal_space(&task.goal_space_id)?,
+                None => None,
+            };
+            Ok((task, run, goal))
+        })
+        .await;
+    let (task, run, goal) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => return internal(e).into_response(),
+    };
+    let Some(task) = task else {
+        return error(StatusCode::NOT_FOUND, "Task not found".into()).into_response();
+    };
+    if task.status != TaskStatus::Done {
+        return error(
+            StatusCode::CONFLICT,
+            format!("Task is {}, not done", task.status),
         )
-            .into_response(),
+        .into_response();
+    }
+    let Some(run) = run else {
+        return error(
+            StatusCode::CONFLICT,
+            "Task has no merge commit to revert".into(),
+        )
+        .into_response();
+    };
+    let Some(goal) = goal else {
+        return error(StatusCode::NOT_FOUND, "Goal space not found".into()).into_response();
+    };
+
+    let outcome = match crate::server::revert_task(&state, &task, &run, &goal, actor).await {
+        Ok(o) => o,
+        Err(e) if e.downcast_ref::<crate::server::RevertBlocked>().is_some() => {
+            return error(StatusCode::CONFLICT, e.to_string()).into_response()
+        }
+        Err(e) => return internal(e).into_response(),
+    };
+
+    let dependents: Vec<_> = outcome
+        .dependents
+        .iter()
+        .map(|t| json!({"id": t.id, "title": t.title, "status": t.status}))
+        .collect();
+    Json(json!({
+        "ok": true,
+        "status": "reverted",
+        "merge_commit": outcome.merge_commit,
+        "revert_commit": outcome.revert_commit,
+        "revert_task_id": outcome.revert_task.map(|t| t.id),
+        "dependents_needing_attention": dependents,
+        "goal_open_until_retried": true,
+        "blocked_dependents": outcome
+            .blocked
+            .iter()
+            .map(|t| json!({"id": t.id, "title": t.title}))
+            .collect::<Vec<_>>(),
+    }))
+    .into_response()
+}
+
+// ── Agent Handlers ──
+
+async fn list_agents(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<ListFilter>,
+) -> impl IntoResponse {
+    let query = filter.clone();
+    match state
+        .db
+        .call(move |db| db.list_agent_runs_filtered(&query))
+        .await
+    {
+        Ok(agents) => page_response(agents, &filter, |r| r.id.clone()),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_agent(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.db.call(move |db| db.get_agent_run(&id)).await {
+        Ok(Some(agent)) => Json(json!(agent)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, Json(json!({"error": "Not found"}))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// Record a change to an agent run in its goal's history
+async fn record_run_history(
+    state: &AppState,
+    agent_run_id: &str,
+    actor: Actor,
+    event_type: &'static str,
+    description: String,
+    metadata: serde_json::Value,
+) {
+    let agent_run_id = agent_run_id.to_string();
+    let _ = state
+        .db
+        .call(move |db| {
+            if let Some(run) = db.get_agent_run(&agent_run_id)? {
+                let mut metadata = metadata;
+                metadata["agent_run_id"] = json!(run.id);
+                metadata["task_id"] = json!(run.task_id);
+                db.insert_goal_history(
+                    &run.goal_space_id,
+                    actor,
+                    event_type,
+                    &description,
+                    Some(metadata),
+                )?;
+            }
+            Ok(())
+        })
+        .await;
+}
+
+async fn nudge_agent(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    actor: Actor,
+    Json(input): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let message = input.get("message").and_then(|m| m.as_str()).unwrap_or("");
+
+    if message.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Message required"})),
+        )
+            .into_response();
+    }
+
+    match state.agent_manager.nudge_agent(&id, message).await {
+        Ok(()) => {
+            record_run_history(
+                &state,
+                &id,
+                actor,
+                "agent_nudged",
+                format!("Agent {} nudged", id),
+                json!({"message": message}),
+            )
+            .await;
+            Json(json!({"ok": true})).into_response()
+        }
+        Err(e) => {
+            let err_str = e.to_string();
+            // If the agent doesn't have a session ID yet, return 409 Conflict
+            if err_str.contains("no Claude session ID yet") {
+                (
+                    StatusCode::CONFLICT,
+                    Json(json!({"error": "Agent is still starting up. Please wait a moment and try again."})),
+                )
+                    .into_response()
+            } else if err_str.contains("not found or not running") {
+                (
+                    StatusCode::NOT_FOUND,
+                    Json(json!({"error": "Agent not found or not running"})),
+                )
+                    .into_response()
+            } else {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": err_str})),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+async fn kill_agent(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    actor: Actor,
+) -> impl IntoResponse {
+    match state.agent_manager.kill_agent(&id).await {
+        Ok(()) => {
+            record_run_history(
+                &state,
+                &id,
+                actor,
+                "agent_killed",
+                format!("Agent {} killed", id),
+                json!({}),
+            )
+            .await;
+            Json(json!({"ok": true})).into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
     }
 }
 
 async fn get_agent_events(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Query(filter): Query<ListFilter>,
 ) -> impl IntoResponse {
-    match state.db.list_agent_events(&id) {
-        Ok(events) => Json(json!(events)).into_response(),
+    if filter
+        .after_id
+        .as_ref()
+        .is_some_and(|after| after.parse::<i64>().is_err())
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "after_id must be an event id"})),
+        )
+            .into_response();
+    }
+    let query = filter.clone();
+    match state
+        .db
+        .call(move |db| db.agent_events_with_raw_filtered(&id, &query))
+        .await
+    {
+        Ok(events) => page_response(events, &filter, |e| e.id.to_string()),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// The raw stream-json lines of a run as NDJSON, including compacted payloads
+async fn get_agent_transcript(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let result = state
+        .db
+        .call(move |db| {
+            if db.get_agent_run(&id)?.is_none() {
+                return Ok(None);
+            }
+            db.agent_events_with_raw(&id).map(Some)
+        })
+        .await;
+    match result {
+        Ok(Some(events)) => {
+            let mut body = String::new();
+            for raw in events.iter().filter_map(|e| e.raw_json.as_deref()) {
+                body.push_str(raw);
+                body.push('\n');
+            }
+            ([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, Json(json!({"error": "Not found"}))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// Resolve where an agent run's changes can be read from, or an error response
+async fn agent_diff_target(
+    state: &AppState,
+    id: &str,
+) -> Result<crate::agent::diff::DiffTarget, axum::response::Response> {
+    let id = id.to_string();
+    let run = match state.db.call(move |db| db.get_agent_run(&id)).await {
+        Ok(Some(run)) => run,
+        Ok(None) => {
+            return Err((StatusCode::NOT_FOUND, Json(json!({"error": "Not found"}))).into_response())
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response())
+        }
+    };
+
+    let goal_id = run.goal_space_id.clone();
+    let goal = match state.db.call(move |db| db.get_goal_space(&goal_id)).await {
+        Ok(Some(g)) => g,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Goal space not found"})),
+            )
+                .into_response())
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response())
+        }
+    };
+
+    match crate::agent::diff::resolve_target(std::path::Path::new(&goal.repo_path), &run).await {
+        Ok(Some(target)) => Ok(target),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "No worktree, branch or merge commit available for this agent"})),
+        )
+            .into_response()),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response()),
+    }
+}
+
+async fn get_agent_diff(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let target = match agent_diff_target(&state, &id).await {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    match crate::agent::diff::diff(&target).await {
+        Ok(diff) => Json(json!(diff)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_agent_commits(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let target = match agent_diff_target(&state, &id).await {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    match crate::agent::diff::commits(&target).await {
+        Ok(commits) => Json(json!(commits)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_agent_pr(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let run = match state.db.call(move |db| db.get_agent_run(&id)).await {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Agent not found"})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    };
+    if run.branch.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Agent has no branch"})),
+        )
+            .into_response();
+    }
+    let goal_id = run.goal_space_id.clone();
+    let goal = match state.db.call(move |db| db.get_goal_space(&goal_id)).await {
+        Ok(Some(g)) => g,
+        _ => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Goal space not found"})),
+            )
+                .into_response()
+        }
+    };
+
+    let repo = std::path::Path::new(&goal.repo_path);
+    match crate::agent::forge::build_bundle(&state.db, repo, &run).await {
+        Ok(bundle) => Json(json!(bundle)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn publish_agent(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    actor: Actor,
+    input: Option<Json<serde_json::Value>>,
+) -> impl IntoResponse {
+    let run_id = id.clone();
+    match state.db.call(move |db| db.get_agent_run(&run_id)).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Agent not found"})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+
+    let remote = input
+        .as_ref()
+        .and_then(|Json(v)| v.get("remote"))
+        .and_then(|r| r.as_str())
+        .map(String::from);
+
+    match crate::agent::forge::publish_run(&state.db, &id, remote.as_deref()).await {
+        Ok(Some(published)) => {
+            record_run_history(
+                &state,
+                &id,
+                actor,
+                "agent_published",
+                format!("Agent {} branch published", id),
+                json!({"published": published}),
+            )
+            .await;
+            Json(json!(published)).into_response()
+        }
+        Ok(None) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "No push_remote configured for this project, goal or task"})),
+        )
+            .into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": e.to_string()})),
@@ -788,7 +1815,7 @@ async fn get_agent_events(
 // ── Project Handlers ──
 
 async fn list_projects_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.db.list_projects() {
+    match state.db.call(|db| db.list_projects()).await {
         Ok(projects) => Json(json!(projects)).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -802,7 +1829,7 @@ async fn create_project_handler(
     State(state): State<Arc<AppState>>,
     Json(input): Json<CreateProject>,
 ) -> impl IntoResponse {
-    match state.db.create_project(&input) {
+    match state.db.call(move |db| db.create_project(&input)).await {
         Ok(project) => (StatusCode::CREATED, Json(json!(project))).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -816,7 +1843,7 @@ async fn get_project_handler(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    match state.db.get_project(&id) {
+    match state.db.call(move |db| db.get_project(&id)).await {
         Ok(Some(project)) => Json(json!(project)).into_response(),
         Ok(None) => (StatusCode::NOT_FOUND, Json(json!({"error": "Not found"}))).into_response(),
         Err(e) => (
@@ -832,7 +1859,11 @@ async fn update_project_handler(
     Path(id): Path<String>,
     Json(input): Json<UpdateProject>,
 ) -> impl IntoResponse {
-    match state.db.update_project(&id, &input) {
+    match state
+        .db
+        .call(move |db| db.update_project(&id, &input))
+        .await
+    {
         Ok(()) => Json(json!({"ok": true})).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -846,7 +1877,7 @@ async fn delete_project_handler(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    match state.db.delete_project(&id) {
+    match state.db.call(move |db| db.delete_project(&id)).await {
         Ok(()) => Json(json!({"ok": true})).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -860,7 +1891,7 @@ async fn list_project_goals_handler(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    match state.db.list_goals_by_project(&id) {
+    match state.db.call(move |db| db.list_goals_by_project(&id)).await {
         Ok(goals) => Json(json!(goals)).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -889,7 +1920,8 @@ async fn goal_chat_handler(
     };
 
     // Verify goal exists
-    match state.db.get_goal_space(&id) {
+    let goal_id = id.clone();
+    match state.db.call(move |db| db.get_goal_space(&goal_id)).await {
         Ok(Some(_)) => {}
         Ok(None) => {
             return (
@@ -941,12 +1973,291 @@ async fn goal_chat_handler(
         .into_response()
 }
 
+async fn list_goal_history(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(filter): Query<ListFilter>,
+) -> impl IntoResponse {
+    let goal_id = id.clone();
+    match state.db.call(move |db| db.get_goal_space(&goal_id)).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Goal not found"})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+    let query = filter.clone();
+    match state
+        .db
+        .call(move |db| db.list_goal_history_filtered(&id, &query))
+        .await
+    {
+        Ok(entries) => page_response(entries, &filter, |h| h.id.to_string()),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
 async fn list_goal_messages_handler(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Query(filter): Query<ListFilter>,
+) -> impl IntoResponse {
+    let query = filter.clone();
+    match state
+        .db
+        .call(move |db| db.list_goal_messages_filtered(&id, &query))
+        .await
+    {
+        Ok(messages) => page_response(messages, &filter, |m| m.id.clone()),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// ── Search Handler ──
+
+/// Default and maximum number of search results
+const SEARCH_DEFAULT_LIMIT: u32 = 50;
+const SEARCH_MAX_LIMIT: u32 = 500;
+
+async fn search(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> impl IntoResponse {
+    use crate::db::search::{SearchKind, SearchQuery};
+
+    let text = params.get("q").map(|q| q.trim()).unwrap_or_default();
+    if text.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "q is required"})),
+        )
+            .into_response();
+    }
+
+    let mut kinds = Vec::new();
+    for name in params
+        .get("type")
+        .map(|t| {
+            t.split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .collect()
+        })
+        .unwrap_or_else(Vec::new)
+    {
+        match SearchKind::parse(name) {
+            Some(kind) => kinds.push(kind),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": format!("Unknown type '{}': expected event, task or message", name)})),
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    let limit = params
+        .get("limit")
+        .and_then(|l| l.parse::<u32>().ok())
+        .unwrap_or(SEARCH_DEFAULT_LIMIT)
+        .clamp(1, SEARCH_MAX_LIMIT);
+    let query = SearchQuery {
+        text: text.to_string(),
+        goal_space_id: params.get("goal").cloned(),
+        project_id: params.get("project").cloned(),
+        kinds,
+        limit,
+    };
+
+    match state.db.call(move |db| db.search(&query)).await {
+        Ok(hits) => Json(json!(hits)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// ── Analytics Handlers ──
+
+async fn cost_analytics(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> impl IntoResponse {
+    analytics_response(&state, params, crate::db::analytics::SortBy::Cost).await
+}
+
+async fn usage_analytics(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> impl IntoResponse {
+    analytics_response(&state, params, crate::db::analytics::SortBy::Runs).await
+}
+
+/// An analytics report as JSON, or as CSV with `format=csv`
+async fn analytics_response(
+    state: &AppState,
+    params: std::collections::HashMap<String, String>,
+    sort: crate::db::analytics::SortBy,
+) -> axum::response::Response {
+    use crate::db::analytics::{to_csv, AnalyticsQuery, GroupBy};
+
+    let group_by = match GroupBy::parse_list(params.get("group_by").map_or("", |g| g.as_str())) {
+        Ok(g) => g,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    };
+    let csv =
+        match params.get("format").map(String::as_str) {
+            None | Some("json") => false,
+            Some("csv") => true,
+            Some(other) => return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Unknown format '{}': expected json or csv", other)})),
+            )
+                .into_response(),
+        };
+    let query = AnalyticsQuery {
+        group_by,
+        since: params.get("since").cloned(),
+        until: params.get("until").cloned(),
+        goal_space_id: params.get("goal").cloned(),
+        project_id: params.get("project").cloned(),
+        model: params.get("model").cloned(),
+    };
+
+    match state
+        .db
+        .call(move |db| db.run_analytics(&query, sort))
+        .await
+    {
+        Ok(report) if csv => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"conductor-analytics.csv\"",
+                ),
+            ],
+            to_csv(&report),
+        )
+            .into_response(),
+        Ok(report) => Json(json!(report)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// ── Scheduler Handlers ──
+
+async fn get_scheduler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.db.call(crate::goal::fair_share::fleet_shares).await {
+        Ok(shares) => Json(json!(shares)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// Change the fleet's slot limit or starvation threshold, then dispatch
+/// for goals the change gives room to
+async fn update_scheduler(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::db::queries::UpdateSchedulerConfig>,
+) -> impl IntoResponse {
+    let result = state
+        .db
+        .call(move |db| {
+            db.update_scheduler_config(&input)?;
+            crate::goal::fair_share::fleet_shares(db)
+        })
+        .await;
+    match result {
+        Ok(shares) => {
+            for goal in shares.goals.iter().filter(|g| g.free_slots > 0) {
+                state.agent_manager.request_dispatch(&goal.goal_space_id);
+            }
+            Json(json!(shares)).into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// ── Job Handlers ──
+
+async fn list_jobs(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<ListFilter>,
 ) -> impl IntoResponse {
-    match state.db.list_goal_messages(&id) {
-        Ok(messages) => Json(json!(messages)).into_response(),
+    let query = filter.clone();
+    match state.db.call(move |db| db.list_jobs(&query)).await {
+        Ok(jobs) => page_response(jobs, &filter, |j| j.id.to_string()),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// Put a dead-lettered job back on the queue with a fresh set of attempts
+async fn retry_job(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> impl IntoResponse {
+    let result = state
+        .db
+        .call(move |db| match db.retry_dead_job(id)? {
+            Some(job) => Ok((Some(job), None)),
+            None => Ok((None, db.get_job(id)?)),
+        })
+        .await;
+    match result {
+        Ok((Some(job), _)) => {
+            state.agent_manager.wake_dispatch();
+            Json(json!(job)).into_response()
+        }
+        Ok((None, Some(job))) => (
+            StatusCode::CONFLICT,
+            Json(json!({"error": format!("Job is {}, not dead", job.status.as_str())})),
+        )
+            .into_response(),
+        Ok((None, None)) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Job not found"})),
+        )
+            .into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": e.to_string()})),
@@ -957,8 +2268,67 @@ async fn list_goal_messages_handler(
 
 // ── Stats Handler ──
 
+async fn list_deferred_merges(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.db.call(|db| db.list_deferred_merges(None)).await {
+        Ok(merges) => Json(json!(merges)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// Retry a deferred merge now instead of waiting for the background retry
+async fn retry_deferred_merge(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    actor: Actor,
+) -> impl IntoResponse {
+    let merge_id = id.clone();
+    let merge = match state
+        .db
+        .call(move |db| db.get_deferred_merge(&merge_id))
+        .await
+    {
+        Ok(Some(m)) => m,
+        Ok(None) => {
+            return (StatusCode::NOT_FOUND, Json(json!({"error": "Not found"}))).into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    };
+
+    match crate::server::retry_deferred_merge(&state, &merge, actor).await {
+        Some(Landing::Merged(sha)) => {
+            Json(json!({"ok": true, "status": "merged", "merge_commit": sha})).into_response()
+        }
+        _ => match state.db.call(move |db| db.get_deferred_merge(&id)).await {
+            Ok(Some(merge)) => (
+                StatusCode::CONFLICT,
+                Json(json!({"status": "deferred", "deferred_merge": merge})),
+            )
+                .into_response(),
+            _ => (
+                StatusCode::CONFLICT,
+                Json(json!({"status": "failed", "error": "Merge failed; see goal history"})),
+            )
+                .into_response(),
+        },
+    }
+}
+
+async fn get_git_lock_stats() -> impl IntoResponse {
+    Json(json!(crate::agent::repo_lock::metrics()))
+}
+
 async fn get_stats(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.db.get_stats() {
+    match state.db.call(|db| db.get_stats()).await {
         Ok(stats) => Json(json!(stats)).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
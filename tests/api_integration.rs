@@ -7,6 +7,7 @@ use axum::body::Body;
 use axum::http::{Request, StatusCode};
 use conductor::agent::session::AgentManager;
 use conductor::db::Database;
+use conductor::goal::task::{RunStatus, TaskStatus};
 use conductor::server::routes::create_router;
 use conductor::server::AppState;
 use serde_json::{json, Value};
@@ -26,6 +27,29 @@ fn test_state() -> Arc<AppState> {
     })
 }
 
+/// Run a git command in a test repository, panicking on failure
+fn git(dir: &std::path::Path, args: &[&str]) {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?} failed", args);
+}
+
+/// Create a throwaway git repository on `main` with one commit
+fn init_repo() -> std::path::PathBuf {
+    let repo = std::env::temp_dir().join(format!("conductor-it-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&repo).unwrap();
+    git(&repo, &["init", "-q", "-b", "main"]);
+    git(&repo, &["config", "user.email", "test@example.com"]);
+    git(&repo, &["config", "user.name", "Test"]);
+    std::fs::write(repo.join("README.md"), "hello\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "init"]);
+    repo
+}
+
 async fn json_body(resp: axum::response::Response) -> Value {
     let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
         .await
@@ -33,6 +57,38 @@ async fn json_body(resp: axum::response::Response) -> Value {
     serde_json::from_slice(&bytes).unwrap()
 }
 
+/// Move a task to `status` along the shortest chain of valid transitions
+fn walk_task_to(state: &AppState, task_id: &str, status: TaskStatus) {
+    let current = state.db.get_task(task_id).unwrap().unwrap().status;
+    let mut paths = std::collections::VecDeque::from([vec![current]]);
+    let mut seen = std::collections::HashSet::from([current]);
+    while let Some(path) = paths.pop_front() {
+        if *path.last().unwrap() == status {
+            for step in &path[1..] {
+                state
+                    .db
+                    .update_task(
+                        task_id,
+                        &conductor::db::queries::UpdateTask {
+                            status: Some(*step),
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+            }
+            return;
+        }
+        for next in path.last().unwrap().allowed_next() {
+            if seen.insert(next) {
+                let mut longer = path.clone();
+                longer.push(next);
+                paths.push_back(longer);
+            }
+        }
+    }
+    panic!("{} is unreachable from {}", status, current);
+}
+
 // ── Goal Space API Tests ──
 
 #[tokio::test]
@@ -143,7 +199,7 @@ async fn test_get_goal() {
     let resp = app
         .oneshot(
             Request::builder()
-                .uri(&format!("/api/goals/{}", goal.id))
+                .uri(format!("/api/goals/{}", goal.id))
                 .body(Body::empty())
                 .unwrap(),
         )
@@ -192,7 +248,7 @@ async fn test_update_goal() {
         .oneshot(
             Request::builder()
                 .method("PUT")
-                .uri(&format!("/api/goals/{}", goal.id))
+                .uri(format!("/api/goals/{}", goal.id))
                 .header("content-type", "application/json")
                 .body(Body::from(json!({"name": "Updated Name"}).to_string()))
                 .unwrap(),
@@ -225,7 +281,7 @@ async fn test_delete_goal_archives() {
         .oneshot(
             Request::builder()
                 .method("DELETE")
-                .uri(&format!("/api/goals/{}", goal.id))
+                .uri(format!("/api/goals/{}", goal.id))
                 .body(Body::empty())
                 .unwrap(),
         )
@@ -329,7 +385,7 @@ async fn test_update_goal_settings() {
         .oneshot(
             Request::builder()
                 .method("PUT")
-                .uri(&format!("/api/goals/{}", goal.id))
+                .uri(format!("/api/goals/{}", goal.id))
                 .header("content-type", "application/json")
                 .body(Body::from(
                     json!({
@@ -373,6 +429,7 @@ async fn test_list_goals_includes_settings() {
                 allowed_tools: Some(vec!["Bash".to_string(), "Read".to_string()]),
                 permission_mode: None,
                 system_prompt: None,
+                ..Default::default()
             },
         })
         .unwrap();
@@ -412,6 +469,7 @@ async fn test_get_goal_includes_settings() {
                 allowed_tools: None,
                 permission_mode: None,
                 system_prompt: None,
+                ..Default::default()
             },
         })
         .unwrap();
@@ -420,7 +478,7 @@ async fn test_get_goal_includes_settings() {
     let resp = app
         .oneshot(
             Request::builder()
-                .uri(&format!("/api/goals/{}", goal.id))
+                .uri(format!("/api/goals/{}", goal.id))
                 .body(Body::empty())
                 .unwrap(),
         )
@@ -434,6 +492,82 @@ async fn test_get_goal_includes_settings() {
     assert_eq!(body["settings"]["max_budget_usd"], 7.5);
 }
 
+#[tokio::test]
+async fn test_goal_export_import_round_trip() {
+    let state = test_state();
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "Portable".into(),
+            description: "D".into(),
+            repo_path: "/tmp/source".into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+    state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "T".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+
+    let resp = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/goals/{}/export", goal.id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "application/zstd");
+    let bundle = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    let resp = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/goals/import?repo_path=/tmp/target&name=Imported")
+                .body(Body::from(bundle))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let report = json_body(resp).await;
+    assert_eq!(report["counts"]["tasks"], 1);
+    let imported = state
+        .db
+        .get_goal_space(report["goal_space_id"].as_str().unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(imported.name, "Imported");
+    assert_eq!(imported.repo_path, "/tmp/target");
+    assert_eq!(state.db.list_tasks(&imported.id).unwrap().len(), 1);
+
+    let resp = create_router(state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/goals/import")
+                .body(Body::from("garbage"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
 // ── Task API Tests ──
 
 #[tokio::test]
@@ -454,7 +588,7 @@ async fn test_create_task() {
         .oneshot(
             Request::builder()
                 .method("POST")
-                .uri(&format!("/api/goals/{}/tasks", goal.id))
+                .uri(format!("/api/goals/{}/tasks", goal.id))
                 .header("content-type", "application/json")
                 .body(Body::from(
                     json!({
@@ -520,7 +654,7 @@ async fn test_list_tasks() {
     let resp = app
         .oneshot(
             Request::builder()
-                .uri(&format!("/api/goals/{}/tasks", goal.id))
+                .uri(format!("/api/goals/{}/tasks", goal.id))
                 .body(Body::empty())
                 .unwrap(),
         )
@@ -563,7 +697,7 @@ async fn test_update_task() {
         .oneshot(
             Request::builder()
                 .method("PUT")
-                .uri(&format!("/api/tasks/{}", task.id))
+                .uri(format!("/api/tasks/{}", task.id))
                 .header("content-type", "application/json")
                 .body(Body::from(json!({"status": "running"}).to_string()))
                 .unwrap(),
@@ -574,11 +708,11 @@ async fn test_update_task() {
     assert_eq!(resp.status(), StatusCode::OK);
 
     let updated = state.db.get_task(&task.id).unwrap().unwrap();
-    assert_eq!(updated.status, "running");
+    assert_eq!(updated.status, TaskStatus::Running);
 }
 
 #[tokio::test]
-async fn test_retry_task() {
+async fn test_update_task_rejects_invalid_transition() {
     let state = test_state();
     let goal = state
         .db
@@ -594,7 +728,7 @@ async fn test_retry_task() {
         .create_task(
             &goal.id,
             &conductor::db::queries::CreateTask {
-                title: "Failed Task".into(),
+                title: "Original".into(),
                 description: "D".into(),
                 priority: 0,
                 depends_on: vec![],
@@ -603,28 +737,263 @@ async fn test_retry_task() {
         )
         .unwrap();
 
-    // Mark as failed
-    state
+    let resp = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/api/tasks/{}", task.id))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({"status": "done", "title": "Renamed"}).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body = json_body(resp).await;
+    assert_eq!(body["from"], "pending");
+    assert_eq!(body["to"], "done");
+    assert_eq!(body["allowed"], json!(["assigned", "blocked", "running"]));
+
+    // Nothing in the rejected update was applied
+    let unchanged = state.db.get_task(&task.id).unwrap().unwrap();
+    assert_eq!(unchanged.status, TaskStatus::Pending);
+    assert_eq!(unchanged.title, "Original");
+
+    let resp = create_router(state)
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/api/tasks/{}", task.id))
+                .header("content-type", "application/json")
+                .body(Body::from(json!({"status": "finished"}).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_task_dependencies_validated_and_reported() {
+    let state = test_state();
+    let goal = state
         .db
-        .update_task(
-            &task.id,
-            &conductor::db::queries::UpdateTask {
-                status: Some("failed".into()),
-                title: None,
-                description: None,
-                priority: None,
-                depends_on: None,
-                ..Default::default()
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: "/tmp".into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+    let create = |title: &str, depends_on: Vec<String>| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/api/goals/{}/tasks", goal.id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({"title": title, "description": "D", "depends_on": depends_on}).to_string(),
+            ))
+            .unwrap()
+    };
+
+    let resp = create_router(state.clone())
+        .oneshot(create("Orphan", vec!["no-such-task".into()]))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body = json_body(resp).await;
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Invalid task dependencies"));
+    assert_eq!(body["problems"][0]["kind"], "missing_dependency");
+    assert_eq!(body["problems"][0]["dependency_id"], "no-such-task");
+
+    let first = json_body(
+        create_router(state.clone())
+            .oneshot(create("First", vec![]))
+            .await
+            .unwrap(),
+    )
+    .await;
+    let first_id = first["id"].as_str().unwrap().to_string();
+    let second = json_body(
+        create_router(state.clone())
+            .oneshot(create("Second", vec![first_id.clone()]))
+            .await
+            .unwrap(),
+    )
+    .await;
+    let second_id = second["id"].as_str().unwrap().to_string();
+
+    let resp = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/api/tasks/{}", first_id))
+                .header("content-type", "application/json")
+                .body(Body::from(json!({"depends_on": [second_id]}).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(json_body(resp).await["problems"][0]["kind"], "cycle");
+
+    let validate = |goal_id: &str| {
+        Request::builder()
+            .uri(format!("/api/goals/{}/graph/validate", goal_id))
+            .body(Body::empty())
+            .unwrap()
+    };
+    let body = json_body(
+        create_router(state.clone())
+            .oneshot(validate(&goal.id))
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(body["valid"], true);
+
+    // A failed dependency leaves the second task waiting
+    walk_task_to(&state, &first_id, TaskStatus::Failed);
+    let body = json_body(
+        create_router(state.clone())
+            .oneshot(validate(&goal.id))
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(body["valid"], false);
+    assert_eq!(
+        body["problems"],
+        json!([{"kind": "failed_dependency", "task_id": second_id, "dependency_id": first_id}])
+    );
+
+    let resp = create_router(state)
+        .oneshot(validate("no-such-goal"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_goal_history_records_actor_and_filters_by_type() {
+    let state = test_state();
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: "/tmp".into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+
+    let resp = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/goals/{}/tasks", goal.id))
+                .header("content-type", "application/json")
+                .header("x-conductor-actor", "ui")
+                .body(Body::from(
+                    json!({"title": "T", "description": "D"}).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let task_id = json_body(resp).await["id"].as_str().unwrap().to_string();
+
+    // A client can't pass itself off as the dispatch loop
+    let resp = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/api/tasks/{}", task_id))
+                .header("content-type", "application/json")
+                .header("x-conductor-actor", "dispatch")
+                .body(Body::from(json!({"priority": 3}).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .uri(format!(
+                    "/api/goals/{}/history?type=task_added,task_updated",
+                    goal.id
+                ))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    let entries = body.as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["event_type"], "task_added");
+    assert_eq!(entries[0]["actor"], "ui");
+    assert_eq!(entries[0]["metadata"]["task_id"], task_id.as_str());
+    assert_eq!(entries[1]["event_type"], "task_updated");
+    assert_eq!(entries[1]["actor"], "api");
+    assert_eq!(entries[1]["metadata"]["changes"], json!({"priority": 3}));
+
+    let resp = create_router(state)
+        .oneshot(
+            Request::builder()
+                .uri("/api/goals/no-such-goal/history")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_retry_task() {
+    let state = test_state();
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: "/tmp".into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+    let task = state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "Failed Task".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
             },
         )
         .unwrap();
 
+    // Mark as failed
+    walk_task_to(&state, &task.id, TaskStatus::Failed);
+
     let app = create_router(state.clone());
     let resp = app
         .oneshot(
             Request::builder()
                 .method("POST")
-                .uri(&format!("/api/tasks/{}/retry", task.id))
+                .uri(format!("/api/tasks/{}/retry", task.id))
                 .body(Body::empty())
                 .unwrap(),
         )
@@ -634,7 +1003,7 @@ async fn test_retry_task() {
     assert_eq!(resp.status(), StatusCode::OK);
 
     let retried = state.db.get_task(&task.id).unwrap().unwrap();
-    assert_eq!(retried.status, "pending");
+    assert_eq!(retried.status, TaskStatus::Pending);
 }
 
 // ── Agent API Tests ──
@@ -711,7 +1080,7 @@ async fn test_get_agent_with_data() {
     let resp = app
         .oneshot(
             Request::builder()
-                .uri(&format!("/api/agents/{}", run.id))
+                .uri(format!("/api/agents/{}", run.id))
                 .body(Body::empty())
                 .unwrap(),
         )
@@ -775,7 +1144,7 @@ async fn test_get_agent_events() {
     let resp = app
         .oneshot(
             Request::builder()
-                .uri(&format!("/api/agents/{}/events", run.id))
+                .uri(format!("/api/agents/{}/events", run.id))
                 .body(Body::empty())
                 .unwrap(),
         )
@@ -792,74 +1161,104 @@ async fn test_get_agent_events() {
 }
 
 #[tokio::test]
-async fn test_nudge_agent_no_message() {
+async fn test_get_agent_events_paginates() {
     let state = test_state();
-    let app = create_router(state);
-
-    let resp = app
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: "/tmp".into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+    let task = state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "T".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+    let run = state
+        .db
+        .create_agent_run(&task.id, &goal.id, None, None, "sonnet", None)
+        .unwrap();
+    for tool in ["Read", "Bash", "Edit"] {
+        state
+            .db
+            .insert_agent_event(&run.id, "tool_call", Some(tool), tool, None, None)
+            .unwrap();
+    }
+
+    let resp = create_router(state.clone())
         .oneshot(
             Request::builder()
-                .method("POST")
-                .uri("/api/agents/some-id/nudge")
-                .header("content-type", "application/json")
-                .body(Body::from(json!({}).to_string()))
+                .uri(format!("/api/agents/{}/events?limit=2", run.id))
+                .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let next = resp.headers()["x-next-after-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = json_body(resp).await;
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(next, body[1]["id"].to_string());
 
-    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
-}
-
-#[tokio::test]
-async fn test_nudge_agent_empty_message() {
-    let state = test_state();
-    let app = create_router(state);
-
-    let resp = app
+    let resp = create_router(state.clone())
         .oneshot(
             Request::builder()
-                .method("POST")
-                .uri("/api/agents/some-id/nudge")
-                .header("content-type", "application/json")
-                .body(Body::from(json!({"message": ""}).to_string()))
+                .uri(format!(
+                    "/api/agents/{}/events?limit=2&after_id={}",
+                    run.id, next
+                ))
+                .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
+    assert!(resp.headers().get("x-next-after-id").is_none());
+    let body = json_body(resp).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["tool_name"], "Edit");
 
-    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
-}
-
-// ── Stats API Tests ──
-
-#[tokio::test]
-async fn test_stats_empty() {
-    let state = test_state();
-    let app = create_router(state);
-
-    let resp = app
+    let resp = create_router(state)
         .oneshot(
             Request::builder()
-                .uri("/api/stats")
+                .uri(format!("/api/agents/{}/events?after_id=abc", run.id))
                 .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
-
-    assert_eq!(resp.status(), StatusCode::OK);
-    let body = json_body(resp).await;
-    assert_eq!(body["active_agents"], 0);
-    assert_eq!(body["total_cost_usd"], 0.0);
-    assert_eq!(body["tasks_completed"], 0);
-    assert_eq!(body["tasks_total"], 0);
-    assert_eq!(body["goals_active"], 0);
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
 
 #[tokio::test]
-async fn test_stats_with_data() {
-    let state = test_state();
+async fn test_agent_transcript_reads_compacted_payloads() {
+    let archive =
+        std::env::temp_dir().join(format!("conductor-it-archive-{}", uuid::Uuid::new_v4()));
+    let db = Database::open_in_memory()
+        .unwrap()
+        .with_archive_dir(&archive);
+    db.run_migrations().unwrap();
+    let (event_tx, _) = tokio::sync::broadcast::channel(1024);
+    let (dispatch_tx, _dispatch_rx) = tokio::sync::mpsc::unbounded_channel();
+    let agent_manager = AgentManager::new(db.clone(), event_tx.clone(), dispatch_tx);
+    let state = Arc::new(AppState {
+        db,
+        agent_manager,
+        event_tx,
+    });
 
     let goal = state
         .db
@@ -889,36 +1288,106 @@ async fn test_stats_with_data() {
         .unwrap();
     state
         .db
-        .update_agent_run_status(&run.id, "running")
+        .update_agent_run_status(&run.id, RunStatus::Running)
         .unwrap();
     state
         .db
-        .update_agent_run_cost(&run.id, 1.50, 3000, 1000)
+        .update_agent_run_status(&run.id, RunStatus::Done)
         .unwrap();
+    for raw in [r#"{"type":"system"}"#, r#"{"type":"result","result":"ok"}"#] {
+        state
+            .db
+            .insert_agent_event(&run.id, "system", None, "line", Some(raw), None)
+            .unwrap();
+    }
+
+    let report = state
+        .db
+        .compact_events(&conductor::db::retention::RetentionPolicy::default(), false)
+        .unwrap();
+    assert_eq!(report.events_archived, 2);
 
-    let app = create_router(state);
-    let resp = app
+    let resp = create_router(state.clone())
         .oneshot(
             Request::builder()
-                .uri("/api/stats")
+                .uri(format!("/api/agents/{}/transcript", run.id))
                 .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
-
     assert_eq!(resp.status(), StatusCode::OK);
-    let body = json_body(resp).await;
-    assert_eq!(body["active_agents"], 1);
-    assert_eq!(body["total_cost_usd"], 1.5);
-    assert_eq!(body["tasks_total"], 1);
-    assert_eq!(body["goals_active"], 1);
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(bytes.to_vec()).unwrap(),
+        "{\"type\":\"system\"}\n{\"type\":\"result\",\"result\":\"ok\"}\n"
+    );
+
+    let resp = create_router(state)
+        .oneshot(
+            Request::builder()
+                .uri("/api/agents/missing/transcript")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let _ = std::fs::remove_dir_all(&archive);
 }
 
-// ── Hook API Tests ──
+#[tokio::test]
+async fn test_search_endpoint() {
+    let state = test_state();
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: "/tmp".into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+    state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "Fix flaky websocket test".into(),
+                description: "The reconnect test times out".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+
+    let get = |uri: &str| {
+        create_router(state.clone())
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+
+    let resp = get("/api/search?q=websocket&type=task").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let hits = json_body(resp).await;
+    let hits = hits.as_array().unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0]["kind"], "task");
+    assert_eq!(hits[0]["goal_space_id"], goal.id.as_str());
+
+    let resp = get("/api/search?q=websocket&type=message").await.unwrap();
+    assert!(json_body(resp).await.as_array().unwrap().is_empty());
+
+    let resp = get("/api/search?q=%20").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = get("/api/search?q=x&type=file").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
 
 #[tokio::test]
-async fn test_stop_hook() {
+async fn test_nudge_agent_no_message() {
     let state = test_state();
     let app = create_router(state);
 
@@ -926,23 +1395,19 @@ async fn test_stop_hook() {
         .oneshot(
             Request::builder()
                 .method("POST")
-                .uri("/api/hooks/stop")
+                .uri("/api/agents/some-id/nudge")
                 .header("content-type", "application/json")
-                .body(Body::from(
-                    json!({"session_id": "test-session"}).to_string(),
-                ))
+                .body(Body::from(json!({}).to_string()))
                 .unwrap(),
         )
         .await
         .unwrap();
 
-    assert_eq!(resp.status(), StatusCode::OK);
-    let body = json_body(resp).await;
-    assert_eq!(body["ok"], true);
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
 
 #[tokio::test]
-async fn test_subagent_stop_hook() {
+async fn test_nudge_agent_empty_message() {
     let state = test_state();
     let app = create_router(state);
 
@@ -950,22 +1415,47 @@ async fn test_subagent_stop_hook() {
         .oneshot(
             Request::builder()
                 .method("POST")
-                .uri("/api/hooks/subagent-stop")
+                .uri("/api/agents/some-id/nudge")
                 .header("content-type", "application/json")
-                .body(Body::from(json!({"data": "test"}).to_string()))
+                .body(Body::from(json!({"message": ""}).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+// ── Stats API Tests ──
+
+#[tokio::test]
+async fn test_stats_empty() {
+    let state = test_state();
+    let app = create_router(state);
+
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/stats")
+                .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
 
     assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert_eq!(body["active_agents"], 0);
+    assert_eq!(body["total_cost_usd"], 0.0);
+    assert_eq!(body["tasks_completed"], 0);
+    assert_eq!(body["tasks_total"], 0);
+    assert_eq!(body["goals_active"], 0);
 }
 
 #[tokio::test]
-async fn test_stop_hook_marks_agent_done() {
+async fn test_stats_with_data() {
     let state = test_state();
 
-    // Set up a goal, task, and agent run with a session ID
     let goal = state
         .db
         .create_goal_space(&conductor::db::queries::CreateGoalSpace {
@@ -994,35 +1484,2009 @@ async fn test_stop_hook_marks_agent_done() {
         .unwrap();
     state
         .db
-        .update_agent_run_status(&run.id, "running")
+        .update_agent_run_status(&run.id, RunStatus::Running)
         .unwrap();
     state
         .db
-        .update_agent_run_session_id(&run.id, "claude-sess-42")
+        .update_agent_run_cost(&run.id, 1.50, 3000, 1000)
+        .unwrap();
+
+    let app = create_router(state);
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/stats")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert_eq!(body["active_agents"], 1);
+    assert_eq!(body["total_cost_usd"], 1.5);
+    assert_eq!(body["tasks_total"], 1);
+    assert_eq!(body["goals_active"], 1);
+}
+
+#[tokio::test]
+async fn test_goal_estimate() {
+    let state = test_state();
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: "/tmp".into(),
+            settings: conductor::db::queries::GoalSettings {
+                max_budget_usd: Some(2.0),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+    let first = state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "First".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+    let second = state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "Second".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![first.id.clone()],
+                settings: Default::default(),
+            },
+        )
         .unwrap();
 
     let app = create_router(state.clone());
     let resp = app
+        .clone()
         .oneshot(
             Request::builder()
-                .method("POST")
-                .uri("/api/hooks/stop")
-                .header("content-type", "application/json")
-                .body(Body::from(
-                    json!({"session_id": "claude-sess-42"}).to_string(),
-                ))
+                .uri(format!("/api/goals/{}/estimate", goal.id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert_eq!(body["tasks_remaining"], 2);
+    assert_eq!(body["cost_usd"]["high"], 4.0);
+    assert_eq!(body["critical_path"], json!([first.id, second.id]));
+    assert_eq!(body["tasks"][0]["basis"], "default");
+    assert!(body["eta"]["expected"].is_string());
+
+    // The estimate tracks progress: a finished task drops out
+    walk_task_to(&state, &first.id, TaskStatus::Done);
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/goals/{}/estimate", goal.id))
+                .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
+    let body = json_body(resp).await;
+    assert_eq!(body["tasks_remaining"], 1);
+    assert_eq!(body["critical_path"], json!([second.id]));
+
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/goals/nope/estimate")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_goal_schedule_ranks_pending_tasks() {
+    let state = test_state();
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: "/tmp".into(),
+            settings: conductor::db::queries::GoalSettings {
+                max_concurrent_agents: Some(1),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+    let create = |title: &str, priority: i32, depends_on: Vec<String>| {
+        state
+            .db
+            .create_task(
+                &goal.id,
+                &conductor::db::queries::CreateTask {
+                    title: title.into(),
+                    description: "D".into(),
+                    priority,
+                    depends_on,
+                    settings: Default::default(),
+                },
+            )
+            .unwrap()
+    };
+    create("Side", 0, vec![]);
+    let base = create("Base", 0, vec![]);
+    create("Top", 0, vec![base.id.clone()]);
 
+    let app = create_router(state.clone());
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/goals/{}/schedule", goal.id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
     assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert_eq!(body["max_concurrent_agents"], 1);
+    assert_eq!(body["free_slots"], 1);
+    let tasks = body["tasks"].as_array().unwrap();
+    let titles: Vec<&str> = tasks.iter().map(|t| t["title"].as_str().unwrap()).collect();
+    // Base unblocks Top, so it goes first and takes the only slot
+    assert_eq!(titles, vec!["Base", "Side", "Top"]);
+    assert_eq!(tasks[0]["dependents"], 1);
+    assert_eq!(tasks[0]["dispatch_next"], true);
+    assert_eq!(tasks[1]["dispatch_next"], false);
+    assert_eq!(tasks[2]["ready"], false);
+    assert_eq!(tasks[2]["waiting_on"], json!([base.id]));
 
-    // Agent run should be marked done
-    let updated_run = state.db.get_agent_run(&run.id).unwrap().unwrap();
-    assert_eq!(updated_run.status, "done");
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/goals/nope/schedule")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_scheduler_shares_slots_by_weight() {
+    let state = test_state();
+    let mut goals = Vec::new();
+    for (name, weight) in [("Heavy", 2.0), ("Light", 1.0)] {
+        let goal = state
+            .db
+            .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+                name: name.into(),
+                description: "D".into(),
+                repo_path: "/tmp".into(),
+                settings: Default::default(),
+            })
+            .unwrap();
+        for i in 0..4 {
+            state
+                .db
+                .create_task(
+                    &goal.id,
+                    &conductor::db::queries::CreateTask {
+                        title: format!("T{}", i),
+                        description: "D".into(),
+                        priority: 0,
+                        depends_on: vec![],
+                        settings: Default::default(),
+                    },
+                )
+                .unwrap();
+        }
+        goals.push((goal.id, weight));
+    }
+
+    let app = create_router(state.clone());
+    // Weights are goal settings, editable at runtime
+    for (goal_id, weight) in &goals {
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("/api/goals/{}", goal_id))
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({"settings": {"share_weight": weight}}).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/scheduler")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = json_body(resp).await;
+    assert_eq!(body["total_slots"], Value::Null);
+    assert_eq!(body["goals"][0]["free_slots"], 4);
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri("/api/scheduler")
+                .header("content-type", "application/json")
+                .body(Body::from(json!({"total_slots": 3}).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert_eq!(body["total_slots"], 3);
+    let target = |id: &str| {
+        body["goals"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|g| g["goal_space_id"] == id)
+            .unwrap()["target_slots"]
+            .clone()
+    };
+    assert_eq!(target(&goals[0].0), 2);
+    assert_eq!(target(&goals[1].0), 1);
+
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/goals/{}/schedule", goals[1].0))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = json_body(resp).await;
+    assert_eq!(body["fair_share_slots"], 1);
+    assert_eq!(body["free_slots"], 1);
+}
+
+#[tokio::test]
+async fn test_list_and_retry_jobs() {
+    let state = test_state();
+    let dispatch = state.db.enqueue_dispatch_job("goal-b", None).unwrap();
+
+    // Dead-letter the dispatch job
+    for _ in 0..conductor::db::jobs::MAX_JOB_ATTEMPTS {
+        state
+            .db
+            .conn()
+            .execute(
+                "UPDATE jobs SET run_after = '2000-01-01T00:00:00+00:00' WHERE kind = 'dispatch'",
+                [],
+            )
+            .unwrap();
+        let job = state.db.claim_next_job().unwrap().unwrap();
+        assert_eq!(job.id, dispatch);
+        state.db.fail_job(job.id, "goal store unavailable").unwrap();
+    }
+
+    let merge = state
+        .db
+        .enqueue_merge_job("goal-a", "run-1", "/tmp", "agent/run-1")
+        .unwrap()
+        .unwrap();
+    // The same run is never merged twice
+    assert!(state
+        .db
+        .enqueue_merge_job("goal-a", "run-1", "/tmp", "agent/run-1")
+        .unwrap()
+        .is_none());
+
+    let app = create_router(state.clone());
+    let resp = app
+        .clone()
+        .oneshot(Request::get("/api/jobs").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let jobs = json_body(resp).await;
+    let jobs = jobs.as_array().unwrap();
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[0]["id"], merge);
+    assert_eq!(jobs[0]["kind"], "merge");
+    assert_eq!(jobs[0]["status"], "pending");
+    assert_eq!(jobs[1]["id"], dispatch);
+    assert_eq!(jobs[1]["status"], "dead");
+    assert_eq!(jobs[1]["last_error"], "goal store unavailable");
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::get("/api/jobs?type=merge&goal=goal-a")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let jobs = json_body(resp).await;
+    assert_eq!(jobs.as_array().unwrap().len(), 1);
+    assert_eq!(jobs[0]["branch"], "agent/run-1");
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::get("/api/jobs?status=stuck")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Only dead jobs can be retried
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::post(format!("/api/jobs/{}/retry", merge))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::post(format!("/api/jobs/{}/retry", dispatch))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let job = json_body(resp).await;
+    assert_eq!(job["status"], "pending");
+    assert_eq!(job["attempts"], 0);
+
+    let resp = app
+        .oneshot(
+            Request::post("/api/jobs/9999/retry")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_merge_jobs_resume_after_restart_and_give_up_on_conflicts() {
+    use conductor::db::jobs::{JobKind, JobStatus};
+    use conductor::db::queries::ListFilter;
+
+    let state = test_state();
+    let repo = init_repo();
+    let repo_path = repo.to_string_lossy().to_string();
+
+    // A merge job that was running when the server stopped
+    git(&repo, &["checkout", "-q", "-b", "conductor/feature"]);
+    std::fs::write(repo.join("feature.txt"), "work\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "Agent work"]);
+    git(&repo, &["checkout", "-q", "main"]);
+    let (_, run_id) = awaiting_review_task(&state, &repo, "conductor/feature");
+    let goal_id = state
+        .db
+        .get_agent_run(&run_id)
+        .unwrap()
+        .unwrap()
+        .goal_space_id;
+    let merge = state
+        .db
+        .enqueue_merge_job(&goal_id, &run_id, &repo_path, "conductor/feature")
+        .unwrap()
+        .unwrap();
+    state.db.claim_next_job().unwrap().unwrap();
+
+    assert_eq!(state.db.requeue_running_jobs().unwrap(), 1);
+    conductor::server::run_due_jobs(&state).await;
+    assert!(repo.join("feature.txt").exists());
+    let run = state.db.get_agent_run(&run_id).unwrap().unwrap();
+    assert!(run.merge_commit.is_some());
+    let job = state.db.get_job(merge).unwrap().unwrap();
+    assert_eq!(job.status, JobStatus::Done);
+    assert_eq!(job.attempts, 2);
+    // The merge queued a dispatch for the goal, which ran too
+    let dispatches = state
+        .db
+        .list_jobs(&ListFilter {
+            goal_space_id: Some(goal_id.clone()),
+            event_type: Some("dispatch".into()),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(dispatches.len(), 1);
+    assert_eq!(dispatches[0].status, JobStatus::Done);
+
+    // A conflicting branch fails once and is not retried
+    git(&repo, &["checkout", "-q", "-b", "conductor/conflict"]);
+    std::fs::write(repo.join("README.md"), "theirs\n").unwrap();
+    git(&repo, &["commit", "-q", "-am", "Agent work"]);
+    git(&repo, &["checkout", "-q", "main"]);
+    std::fs::write(repo.join("README.md"), "ours\n").unwrap();
+    git(&repo, &["commit", "-q", "-am", "User work"]);
+    let (_, run_id) = awaiting_review_task(&state, &repo, "conductor/conflict");
+    let goal_id = state
+        .db
+        .get_agent_run(&run_id)
+        .unwrap()
+        .unwrap()
+        .goal_space_id;
+    let merge = state
+        .db
+        .enqueue_merge_job(&goal_id, &run_id, &repo_path, "conductor/conflict")
+        .unwrap()
+        .unwrap();
+
+    conductor::server::run_due_jobs(&state).await;
+    let job = state.db.get_job(merge).unwrap().unwrap();
+    assert_eq!(job.status, JobStatus::Done);
+    assert_eq!(job.attempts, 1);
+    assert!(job.last_error.unwrap().contains("Merge conflict"));
+    let failures = state
+        .db
+        .list_agent_events(&run_id)
+        .unwrap()
+        .into_iter()
+        .filter(|e| e.event_type == "merge_failed")
+        .count();
+    assert_eq!(failures, 1);
+    // The slot it held is still handed on
+    let jobs = state
+        .db
+        .list_jobs(&ListFilter {
+            goal_space_id: Some(goal_id),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[0].kind, JobKind::Dispatch);
+    assert!(jobs.iter().all(|j| j.status == JobStatus::Done));
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+#[tokio::test]
+async fn test_cost_analytics_json_and_csv() {
+    let state = test_state();
+
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "Billing, v2".into(),
+            description: "D".into(),
+            repo_path: "/tmp".into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+    let task = state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "T".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+    for cost in [1.25, 0.75] {
+        let run = state
+            .db
+            .create_agent_run(&task.id, &goal.id, None, None, "sonnet", None)
+            .unwrap();
+        state
+            .db
+            .update_agent_run_cost(&run.id, cost, 1000, 500)
+            .unwrap();
+    }
+
+    let app = create_router(state);
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/analytics/cost?group_by=goal")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert_eq!(body["group_by"], json!(["goal"]));
+    assert_eq!(body["rows"].as_array().unwrap().len(), 1);
+    assert_eq!(body["rows"][0]["goal_name"], "Billing, v2");
+    assert_eq!(body["rows"][0]["runs"], 2);
+    assert_eq!(body["rows"][0]["cost_usd"], 2.0);
+    assert_eq!(body["totals"]["total_tokens"], 3000);
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/analytics/usage?group_by=goal&format=csv")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/csv"));
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let csv = String::from_utf8(bytes.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("goal_space_id,goal_name,"));
+    assert!(lines[1].contains("\"Billing, v2\""));
+    assert!(lines[2].starts_with("total,"));
+
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/analytics/cost?group_by=month")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+// ── Hook API Tests ──
+
+#[tokio::test]
+async fn test_stop_hook() {
+    let state = test_state();
+    let app = create_router(state);
+
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/hooks/stop")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({"session_id": "test-session"}).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert_eq!(body["ok"], true);
+}
+
+#[tokio::test]
+async fn test_subagent_stop_hook() {
+    let state = test_state();
+    let app = create_router(state);
+
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/hooks/subagent-stop")
+                .header("content-type", "application/json")
+                .body(Body::from(json!({"data": "test"}).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_stop_hook_marks_agent_done() {
+    let state = test_state();
+
+    // Set up a goal, task, and agent run with a session ID
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: "/tmp".into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+    let task = state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "T".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+    let run = state
+        .db
+        .create_agent_run(&task.id, &goal.id, None, None, "sonnet", None)
+        .unwrap();
+    state
+        .db
+        .update_agent_run_status(&run.id, RunStatus::Running)
+        .unwrap();
+    state
+        .db
+        .update_agent_run_session_id(&run.id, "claude-sess-42")
+        .unwrap();
+    walk_task_to(&state, &task.id, TaskStatus::Running);
+
+    let app = create_router(state.clone());
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/hooks/stop")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({"session_id": "claude-sess-42"}).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Agent run should be marked done
+    let updated_run = state.db.get_agent_run(&run.id).unwrap().unwrap();
+    assert_eq!(updated_run.status, RunStatus::Done);
 
     // Task should be marked done
     let updated_task = state.db.get_task(&task.id).unwrap().unwrap();
-    assert_eq!(updated_task.status, "done");
+    assert_eq!(updated_task.status, TaskStatus::Done);
+}
+
+#[tokio::test]
+async fn test_stop_hook_leaves_dirty_worktree_to_uncommitted_policy() {
+    let state = test_state();
+    let repo = init_repo();
+    std::fs::write(repo.join("work.txt"), "forgot to commit\n").unwrap();
+
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: repo.to_string_lossy().into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+    let task = state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "T".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+    let run = state
+        .db
+        .create_agent_run(
+            &task.id,
+            &goal.id,
+            Some(&repo.to_string_lossy()),
+            Some("main"),
+            "sonnet",
+            None,
+        )
+        .unwrap();
+    state
+        .db
+        .update_agent_run_status(&run.id, RunStatus::Running)
+        .unwrap();
+    state
+        .db
+        .update_agent_run_session_id(&run.id, "claude-sess-dirty")
+        .unwrap();
+    walk_task_to(&state, &task.id, TaskStatus::Running);
+
+    let app = create_router(state.clone());
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/hooks/stop")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({"session_id": "claude-sess-dirty"}).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(json_body(resp).await["uncommitted"], 1);
+
+    let run = state.db.get_agent_run(&run.id).unwrap().unwrap();
+    assert_eq!(run.status, RunStatus::Running);
+    let task = state.db.get_task(&task.id).unwrap().unwrap();
+    assert_eq!(task.status, TaskStatus::Running);
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+// ── Agent Diff Tests ──
+
+#[tokio::test]
+async fn test_agent_diff_not_found() {
+    let state = test_state();
+    let app = create_router(state);
+
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/agents/nonexistent/diff")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_agent_commits_without_source() {
+    let state = test_state();
+    let repo = init_repo();
+
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: repo.to_string_lossy().into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+    let task = state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "T".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+    let run = state
+        .db
+        .create_agent_run(
+            &task.id,
+            &goal.id,
+            None,
+            Some("conductor/deleted"),
+            "sonnet",
+            None,
+        )
+        .unwrap();
+
+    let app = create_router(state);
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/agents/{}/commits", run.id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+// ── Review Gate Tests ──
+
+/// Set up a goal in manual merge mode with one task awaiting review on `branch`
+fn awaiting_review_task(
+    state: &Arc<AppState>,
+    repo: &std::path::Path,
+    branch: &str,
+) -> (String, String) {
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: repo.to_string_lossy().into(),
+            settings: conductor::db::queries::GoalSettings {
+                merge_mode: Some(conductor::db::queries::MergeMode::Manual),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+    let task = state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "T".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+    let run = state
+        .db
+        .create_agent_run(&task.id, &goal.id, None, Some(branch), "sonnet", None)
+        .unwrap();
+    state
+        .db
+        .update_agent_run_status(&run.id, RunStatus::Running)
+        .unwrap();
+    state
+        .db
+        .update_agent_run_status(&run.id, RunStatus::Done)
+        .unwrap();
+    walk_task_to(state, &task.id, TaskStatus::AwaitingReview);
+    (task.id, run.id)
+}
+
+#[tokio::test]
+async fn test_approve_task_not_awaiting_review() {
+    let state = test_state();
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: "/tmp".into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+    let task = state
+        .db
+        .create_task(
+            &goal.id,
+            &conductor::db::queries::CreateTask {
+                title: "T".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+
+    let app = create_router(state);
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/tasks/{}/approve", task.id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_approve_task_merges_branch() {
+    let state = test_state();
+    let repo = init_repo();
+    git(&repo, &["checkout", "-q", "-b", "conductor/review"]);
+    std::fs::write(repo.join("feature.txt"), "work\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "Agent work"]);
+    git(&repo, &["checkout", "-q", "main"]);
+
+    let (task_id, run_id) = awaiting_review_task(&state, &repo, "conductor/review");
+
+    let app = create_router(state.clone());
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/tasks/{}/approve", task_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert_eq!(body["status"], "done");
+    assert!(repo.join("feature.txt").exists());
+
+    let task = state.db.get_task(&task_id).unwrap().unwrap();
+    assert_eq!(task.status, TaskStatus::Done);
+    let run = state.db.get_agent_run(&run_id).unwrap().unwrap();
+    assert_eq!(run.merge_commit.as_deref(), body["merge_commit"].as_str());
+
+    // The merge went through the repo's git lock
+    let app = create_router(state.clone());
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/stats/git-locks")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let locks = json_body(resp).await;
+    let canonical = std::fs::canonicalize(&repo).unwrap();
+    let entry = locks
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|l| l["repo_path"] == canonical.to_string_lossy().as_ref())
+        .expect("repo missing from lock metrics");
+    assert!(entry["acquisitions"].as_u64().unwrap() >= 2);
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+#[tokio::test]
+async fn test_reject_task_with_feedback_requeues() {
+    let state = test_state();
+    let (task_id, run_id) = awaiting_review_task(&state, std::path::Path::new("/tmp"), "b");
+
+    let app = create_router(state.clone());
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/tasks/{}/reject", task_id))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({"feedback": "Add tests for the edge cases"}).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert_eq!(body["status"], "pending");
+
+    let task = state.db.get_task(&task_id).unwrap().unwrap();
+    assert_eq!(task.status, TaskStatus::Pending);
+    assert_eq!(
+        state
+            .db
+            .latest_review_feedback(&task_id)
+            .unwrap()
+            .as_deref(),
+        Some("Add tests for the edge cases")
+    );
+    let events = state.db.list_agent_events(&run_id).unwrap();
+    assert!(events.iter().any(|e| e.event_type == "review_rejected"));
+    // The follow-up run deletes the rejected branch once it starts
+    let rejected = state.db.latest_rejected_run(&task_id).unwrap().unwrap();
+    assert_eq!(rejected.id, run_id);
+}
+
+#[tokio::test]
+async fn test_reject_task_without_redispatch_fails_task() {
+    let state = test_state();
+    let repo = init_repo();
+    git(&repo, &["branch", "conductor/rejected"]);
+    let (task_id, _) = awaiting_review_task(&state, &repo, "conductor/rejected");
+
+    let app = create_router(state.clone());
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/tasks/{}/reject", task_id))
+                .header("content-type", "application/json")
+                .body(Body::from(json!({"redispatch": false}).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let task = state.db.get_task(&task_id).unwrap().unwrap();
+    assert_eq!(task.status, TaskStatus::Failed);
+
+    // Nothing will build on the rejected branch, so it is deleted
+    let branches = std::process::Command::new("git")
+        .args(["branch", "--list", "conductor/rejected"])
+        .current_dir(&repo)
+        .output()
+        .unwrap();
+    assert!(branches.stdout.is_empty());
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+// ── Publish Tests ──
+
+#[tokio::test]
+async fn test_publish_without_remote_is_bad_request() {
+    let state = test_state();
+    let (_, run_id) = awaiting_review_task(&state, std::path::Path::new("/tmp"), "b");
+
+    let app = create_router(state);
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/agents/{}/publish", run_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_publish_pushes_to_project_remote() {
+    let state = test_state();
+    let repo = init_repo();
+    let remote = repo.with_extension("git");
+    std::fs::create_dir_all(&remote).unwrap();
+    git(&remote, &["init", "-q", "--bare"]);
+
+    git(&repo, &["checkout", "-q", "-b", "conductor/publish"]);
+    std::fs::write(repo.join("feature.txt"), "work\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "Agent work"]);
+    git(&repo, &["checkout", "-q", "main"]);
+
+    let project = state
+        .db
+        .create_project(&conductor::db::queries::CreateProject {
+            path: repo.to_string_lossy().into(),
+            display_name: "Repo".into(),
+            sort_order: 0,
+        })
+        .unwrap();
+    state
+        .db
+        .update_project(
+            &project.id,
+            &conductor::db::queries::UpdateProject {
+                settings: Some(conductor::db::queries::GoalSettings {
+                    push_remote: Some(remote.to_string_lossy().into()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let (_, run_id) = awaiting_review_task(&state, &repo, "conductor/publish");
+
+    let app = create_router(state.clone());
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/agents/{}/pr", run_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let bundle = json_body(resp).await;
+    assert_eq!(bundle["title"], "T");
+    assert_eq!(bundle["base"], "main");
+    assert_eq!(bundle["files"][0]["path"], "feature.txt");
+    assert!(bundle["body"].as_str().unwrap().contains("## Verification"));
+
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/agents/{}/publish", run_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let published = json_body(resp).await;
+    assert_eq!(published["forge"], "git");
+    assert_eq!(published["branch"], "conductor/publish");
+    assert!(std::path::Path::new(published["markdown_path"].as_str().unwrap()).exists());
+
+    let pushed = std::process::Command::new("git")
+        .args(["rev-parse", "refs/heads/conductor/publish"])
+        .current_dir(&remote)
+        .output()
+        .unwrap();
+    assert!(pushed.status.success());
+
+    let events = state.db.list_agent_events(&run_id).unwrap();
+    assert!(events.iter().any(|e| e.event_type == "branch_pushed"));
+
+    std::fs::remove_dir_all(&repo).ok();
+    std::fs::remove_dir_all(&remote).ok();
+}
+
+// ── Stacked Branch Tests ──
+
+#[tokio::test]
+async fn test_approve_stacked_task_waits_for_stack_approval() {
+    use conductor::agent::worktree;
+    use conductor::db::queries::{CreateGoalSpace, CreateTask, GoalSettings, MergeMode};
+
+    let state = test_state();
+    let repo = init_repo();
+    let db = &state.db;
+
+    let goal = db
+        .create_goal_space(&CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: repo.to_string_lossy().into(),
+            settings: GoalSettings {
+                merge_mode: Some(MergeMode::Manual),
+                stacked: Some(true),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+    let create = |title: &str, deps: Vec<String>| {
+        db.create_task(
+            &goal.id,
+            &CreateTask {
+                title: title.into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: deps,
+                settings: Default::default(),
+            },
+        )
+        .unwrap()
+    };
+    let a = create("A", vec![]);
+    let c = create("C", vec![]);
+    let b = create("B", vec![a.id.clone(), c.id.clone()]);
+
+    // A and C each commit on their own branch from main
+    let mut runs = Vec::new();
+    for (task, file) in [(&a, "a.txt"), (&c, "c.txt")] {
+        let branch = format!("conductor/stack-{}", file.trim_end_matches(".txt"));
+        git(&repo, &["checkout", "-q", "-b", &branch, "main"]);
+        std::fs::write(repo.join(file), "work\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", file]);
+        git(&repo, &["checkout", "-q", "main"]);
+        let run = db
+            .create_agent_run(&task.id, &goal.id, None, Some(&branch), "sonnet", None)
+            .unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Running)
+            .unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Done)
+            .unwrap();
+        runs.push(run);
+    }
+
+    // B starts from A's branch with C's merged in (octopus base), then commits
+    let b_id = uuid::Uuid::new_v4().to_string();
+    let wt =
+        worktree::create_worktree(&repo, &b_id, "conductor/stack-b", Some("conductor/stack-a"))
+            .await
+            .unwrap();
+    worktree::merge_branches(&wt, &["conductor/stack-c".to_string()])
+        .await
+        .unwrap();
+    let base = worktree::rev_parse(&wt, "HEAD").await.unwrap();
+    std::fs::write(wt.join("b.txt"), "work\n").unwrap();
+    git(&wt, &["add", "."]);
+    git(&wt, &["commit", "-q", "-m", "b.txt"]);
+    worktree::remove_worktree(&repo, &wt).await.unwrap();
+
+    let run_b = db
+        .create_agent_run(
+            &b.id,
+            &goal.id,
+            None,
+            Some("conductor/stack-b"),
+            "sonnet",
+            None,
+        )
+        .unwrap();
+    db.update_agent_run_status(&run_b.id, RunStatus::Running)
+        .unwrap();
+    db.update_agent_run_status(&run_b.id, RunStatus::Done)
+        .unwrap();
+    db.set_agent_run_base_commit(&run_b.id, &base).unwrap();
+    for task in [&a, &c, &b] {
+        walk_task_to(&state, &task.id, TaskStatus::AwaitingReview);
+    }
+
+    let app = create_router(state.clone());
+    let approve = |task_id: String| {
+        app.clone().oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/tasks/{}/approve", task_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    // B waits until the branches under it have been approved
+    let resp = approve(b.id.clone()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert!(body["merge_commit"].is_null());
+    let reason = body["deferred_merge"]["reason"].as_str().unwrap();
+    assert!(
+        reason.contains("'A'") && reason.contains("'C'"),
+        "{}",
+        reason
+    );
+    assert!(!repo.join("a.txt").exists());
+    assert_eq!(
+        db.get_task(&a.id).unwrap().unwrap().status,
+        TaskStatus::AwaitingReview
+    );
+
+    for task in [&a, &c] {
+        let resp = approve(task.id.clone()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let merge_id = body["deferred_merge"]["id"].as_str().unwrap();
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/merges/deferred/{}/retry", merge_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    for file in ["a.txt", "b.txt", "c.txt"] {
+        assert!(repo.join(file).exists(), "{} missing on main", file);
+    }
+    for task in [&a, &c, &b] {
+        assert_eq!(
+            db.get_task(&task.id).unwrap().unwrap().status,
+            TaskStatus::Done
+        );
+    }
+    for run in runs.iter().chain([&run_b]) {
+        let run = db.get_agent_run(&run.id).unwrap().unwrap();
+        assert!(run.merge_commit.is_some());
+    }
+
+    // B was rebased off the octopus commit, so that commit never reached main
+    let on_main = worktree::is_ancestor(&repo, &base, "main").await.unwrap();
+    assert!(!on_main);
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+#[tokio::test]
+async fn test_auto_merge_child_does_not_land_unapproved_parent() {
+    use conductor::db::queries::Actor;
+    use conductor::db::queries::{CreateGoalSpace, CreateTask, GoalSettings, MergeMode};
+    use conductor::server::Landing;
+
+    let state = test_state();
+    let repo = init_repo();
+    let db = &state.db;
+
+    // Auto-merge goal; only the parent task needs a review
+    let goal = db
+        .create_goal_space(&CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: repo.to_string_lossy().into(),
+            settings: GoalSettings {
+                stacked: Some(true),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+    let parent = db
+        .create_task(
+            &goal.id,
+            &CreateTask {
+                title: "Parent".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: GoalSettings {
+                    merge_mode: Some(MergeMode::Manual),
+                    ..Default::default()
+                },
+            },
+        )
+        .unwrap();
+    let child = db
+        .create_task(
+            &goal.id,
+            &CreateTask {
+                title: "Child".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![parent.id.clone()],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+
+    git(&repo, &["checkout", "-q", "-b", "conductor/parent", "main"]);
+    std::fs::write(repo.join("parent.txt"), "work\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "parent"]);
+    let base = String::from_utf8(
+        std::process::Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(&repo)
+            .output()
+            .unwrap()
+            .stdout,
+    )
+    .unwrap()
+    .trim()
+    .to_string();
+    git(&repo, &["checkout", "-q", "-b", "conductor/child"]);
+    std::fs::write(repo.join("child.txt"), "work\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "child"]);
+    git(&repo, &["checkout", "-q", "main"]);
+
+    let mut runs = Vec::new();
+    for (task, branch) in [(&parent, "conductor/parent"), (&child, "conductor/child")] {
+        let run = db
+            .create_agent_run(&task.id, &goal.id, None, Some(branch), "sonnet", None)
+            .unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Running)
+            .unwrap();
+        db.update_agent_run_status(&run.id, RunStatus::Done)
+            .unwrap();
+        runs.push(run);
+    }
+    db.set_agent_run_base_commit(&runs[1].id, &base).unwrap();
+    walk_task_to(&state, &parent.id, TaskStatus::AwaitingReview);
+    walk_task_to(&state, &child.id, TaskStatus::Done);
+
+    // The child's auto-merge is held back instead of landing the parent unreviewed
+    let landing = conductor::server::land_branch_checked(
+        db,
+        &repo,
+        "conductor/child",
+        Some(&runs[1].id),
+        &goal.id,
+        Actor::Dispatch,
+    )
+    .await
+    .unwrap();
+    let Landing::Deferred(merge) = landing else {
+        panic!("child landed before its parent was approved");
+    };
+    assert!(merge.reason.contains("'Parent'"));
+    assert!(!repo.join("parent.txt").exists());
+    assert_eq!(
+        db.get_task(&parent.id).unwrap().unwrap().status,
+        TaskStatus::AwaitingReview
+    );
+    assert!(db
+        .get_agent_run(&runs[0].id)
+        .unwrap()
+        .unwrap()
+        .merge_commit
+        .is_none());
+
+    let app = create_router(state.clone());
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/tasks/{}/approve", parent.id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(repo.join("parent.txt").exists());
+
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/merges/deferred/{}/retry", merge.id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(repo.join("child.txt").exists());
+    assert!(db
+        .get_agent_run(&runs[1].id)
+        .unwrap()
+        .unwrap()
+        .merge_commit
+        .is_some());
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+#[tokio::test]
+async fn test_failed_setup_script_fails_run_without_starting_agent() {
+    use conductor::db::queries::{CreateGoalSpace, CreateTask, GoalSettings};
+
+    let state = test_state();
+    let repo = init_repo();
+    let db = &state.db;
+
+    let goal = db
+        .create_goal_space(&CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: repo.to_string_lossy().into(),
+            settings: GoalSettings {
+                setup_command: Some("echo \"setting up $CONDUCTOR_BRANCH\"; exit 7".into()),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+    let task = db
+        .create_task(
+            &goal.id,
+            &CreateTask {
+                title: "T".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+
+    let result = state.agent_manager.dispatch_task(&goal, &task).await;
+    assert!(result.is_err());
+
+    let run = db.latest_agent_run_for_task(&task.id).unwrap().unwrap();
+    assert_eq!(run.failure_reason.as_deref(), Some("setup_failed"));
+    assert_eq!(
+        db.get_task(&task.id).unwrap().unwrap().status,
+        TaskStatus::Failed
+    );
+
+    let events = db.list_agent_events(&run.id).unwrap();
+    let setup = events.iter().find(|e| e.event_type == "setup").unwrap();
+    assert_eq!(setup.summary, "Setup failed with exit code 7");
+    let raw: Value = serde_json::from_str(setup.raw_json.as_deref().unwrap()).unwrap();
+    assert!(raw["output"]
+        .as_str()
+        .unwrap()
+        .contains(run.branch.as_deref().unwrap()));
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+/// Commit `file` on a branch and approve it, leaving a task merged into main
+async fn merged_task(state: &Arc<AppState>, repo: &std::path::Path, file: &str) -> String {
+    let branch = format!("conductor/{}", file.replace('.', "-"));
+    git(repo, &["checkout", "-q", "-b", &branch]);
+    std::fs::write(repo.join(file), "work\n").unwrap();
+    git(repo, &["add", "."]);
+    git(repo, &["commit", "-q", "-m", "Agent work"]);
+    git(repo, &["checkout", "-q", "main"]);
+
+    let (task_id, _) = awaiting_review_task(state, repo, &branch);
+    let resp = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/tasks/{}/approve", task_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    task_id
+}
+
+async fn post_revert(state: &Arc<AppState>, task_id: &str) -> axum::response::Response {
+    create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/tasks/{}/revert", task_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_revert_task_reverts_merge_and_flags_dependents() {
+    use conductor::db::queries::CreateTask;
+
+    let state = test_state();
+    let repo = init_repo();
+    let task_id = merged_task(&state, &repo, "feature.txt").await;
+    assert!(repo.join("feature.txt").exists());
+
+    // A dependent that already landed on top of the task
+    let task = state.db.get_task(&task_id).unwrap().unwrap();
+    let dependent = state
+        .db
+        .create_task(
+            &task.goal_space_id,
+            &CreateTask {
+                title: "Dependent".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![task_id.clone()],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+    walk_task_to(&state, &dependent.id, TaskStatus::Done);
+    // And one that hasn't started yet
+    let waiting = state
+        .db
+        .create_task(
+            &task.goal_space_id,
+            &CreateTask {
+                title: "Waiting".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![task_id.clone()],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+
+    let resp = post_revert(&state, &task_id).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert_eq!(body["status"], "reverted");
+    assert!(body["revert_commit"].is_string());
+    assert!(body["revert_task_id"].is_null());
+    assert_eq!(body["dependents_needing_attention"][0]["id"], dependent.id);
+    assert_eq!(body["blocked_dependents"][0]["id"], waiting.id);
+    assert_eq!(body["goal_open_until_retried"], true);
+    assert_eq!(
+        state.db.get_task(&waiting.id).unwrap().unwrap().status,
+        TaskStatus::Blocked
+    );
+    let history = state
+        .db
+        .list_goal_history_filtered(
+            &task.goal_space_id,
+            &conductor::db::queries::ListFilter {
+                event_type: Some("task_reverted".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert!(history[0]
+        .description
+        .ends_with("the goal stays open until the task is retried"));
+    assert_eq!(
+        history[0].metadata.as_ref().unwrap()["goal_open_until_retried"],
+        true
+    );
+
+    assert!(!repo.join("feature.txt").exists());
+    assert_eq!(
+        state.db.get_task(&task_id).unwrap().unwrap().status,
+        TaskStatus::Reverted
+    );
+
+    // Only done tasks can be reverted
+    let resp = post_revert(&state, &task_id).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    // Retrying the reverted task lets the blocked dependent wait on it again
+    let resp = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/tasks/{}/retry", task_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        state.db.get_task(&waiting.id).unwrap().unwrap().status,
+        TaskStatus::Pending
+    );
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+#[tokio::test]
+async fn test_revert_refuses_busy_checkout() {
+    let state = test_state();
+    let repo = init_repo();
+    let task_id = merged_task(&state, &repo, "feature.txt").await;
+
+    // The user is editing a tracked file in their checkout
+    std::fs::write(repo.join("README.md"), "local edit\n").unwrap();
+
+    let resp = post_revert(&state, &task_id).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body = json_body(resp).await;
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Refusing to revert"));
+
+    assert!(repo.join("feature.txt").exists());
+    assert_eq!(
+        std::fs::read_to_string(repo.join("README.md")).unwrap(),
+        "local edit\n"
+    );
+    assert_eq!(
+        state.db.get_task(&task_id).unwrap().unwrap().status,
+        TaskStatus::Done
+    );
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+#[tokio::test]
+async fn test_conflicting_revert_creates_revert_task() {
+    let state = test_state();
+    let repo = init_repo();
+    let task_id = merged_task(&state, &repo, "feature.txt").await;
+
+    // Later work on main edits the same file
+    std::fs::write(repo.join("feature.txt"), "work\nmore work\n").unwrap();
+    git(&repo, &["commit", "-q", "-am", "Build on feature"]);
+
+    let resp = post_revert(&state, &task_id).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert!(body["revert_commit"].is_null());
+    let revert_task_id = body["revert_task_id"].as_str().unwrap();
+
+    let revert_task = state.db.get_task(revert_task_id).unwrap().unwrap();
+    assert_eq!(revert_task.status, TaskStatus::Pending);
+    assert!(revert_task.title.starts_with("Revert: "));
+    assert_eq!(
+        state.db.get_task(&task_id).unwrap().unwrap().status,
+        TaskStatus::Reverted
+    );
+
+    // The aborted revert left main untouched
+    let status = std::process::Command::new("git")
+        .args(["status", "--porcelain"])
+        .current_dir(&repo)
+        .output()
+        .unwrap();
+    assert!(status.stdout.is_empty());
+    assert_eq!(
+        std::fs::read_to_string(repo.join("feature.txt")).unwrap(),
+        "work\nmore work\n"
+    );
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+/// A task awaiting review on `conductor/feature`, in a goal using `policy` for busy checkouts
+fn review_with_policy(
+    state: &Arc<AppState>,
+    repo: &std::path::Path,
+    policy: conductor::db::queries::DirtyCheckoutPolicy,
+) -> String {
+    git(repo, &["checkout", "-q", "-b", "conductor/feature"]);
+    std::fs::write(repo.join("feature.txt"), "work\n").unwrap();
+    git(repo, &["add", "."]);
+    git(repo, &["commit", "-q", "-m", "Agent work"]);
+    git(repo, &["checkout", "-q", "main"]);
+
+    let (task_id, _) = awaiting_review_task(state, repo, "conductor/feature");
+    let task = state.db.get_task(&task_id).unwrap().unwrap();
+    state
+        .db
+        .update_goal_settings(
+            &task.goal_space_id,
+            &conductor::db::queries::GoalSettings {
+                merge_mode: Some(conductor::db::queries::MergeMode::Manual),
+                dirty_checkout_policy: Some(policy),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    task_id
+}
+
+async fn post_empty(state: &Arc<AppState>, uri: &str) -> axum::response::Response {
+    create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_dirty_checkout_defers_merge_until_clean() {
+    use conductor::db::queries::{CreateTask, DirtyCheckoutPolicy};
+
+    let state = test_state();
+    let repo = init_repo();
+    let task_id = review_with_policy(&state, &repo, DirtyCheckoutPolicy::Defer);
+    let task = state.db.get_task(&task_id).unwrap().unwrap();
+    let dependent = state
+        .db
+        .create_task(
+            &task.goal_space_id,
+            &CreateTask {
+                title: "Dependent".into(),
+                description: "D".into(),
+                priority: 0,
+                depends_on: vec![task_id.clone()],
+                settings: Default::default(),
+            },
+        )
+        .unwrap();
+
+    // The user is in the middle of editing main
+    std::fs::write(repo.join("README.md"), "editing\n").unwrap();
+
+    let resp = post_empty(&state, &format!("/api/tasks/{}/approve", task_id)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = json_body(resp).await;
+    assert!(body["merge_commit"].is_null());
+    assert_eq!(
+        body["deferred_merge"]["reason"],
+        "checkout has uncommitted changes to 1 file(s)"
+    );
+    assert!(!repo.join("feature.txt").exists());
+    assert_eq!(
+        std::fs::read_to_string(repo.join("README.md")).unwrap(),
+        "editing\n"
+    );
+
+    // Dependents wait for the merge to land
+    let goal = state
+        .db
+        .get_goal_space(&task.goal_space_id)
+        .unwrap()
+        .unwrap();
+    let ready = conductor::goal::space::ready_tasks(&state.db, &goal).unwrap();
+    assert!(ready.is_empty());
+
+    let resp = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .uri("/api/merges/deferred")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let merges = json_body(resp).await;
+    assert_eq!(merges.as_array().unwrap().len(), 1);
+    let merge_id = merges[0]["id"].as_str().unwrap().to_string();
+
+    // Still dirty: retrying keeps it queued
+    let resp = post_empty(&state, &format!("/api/merges/deferred/{}/retry", merge_id)).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(json_body(resp).await["deferred_merge"]["attempts"], 2);
+
+    git(&repo, &["checkout", "--", "README.md"]);
+    let resp = post_empty(&state, &format!("/api/merges/deferred/{}/retry", merge_id)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(repo.join("feature.txt").exists());
+    assert!(state.db.list_deferred_merges(None).unwrap().is_empty());
+    let ready = conductor::goal::space::ready_tasks(&state.db, &goal).unwrap();
+    assert_eq!(ready[0].id, dependent.id);
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+#[tokio::test]
+async fn test_dirty_checkout_stash_and_fail_policies() {
+    use conductor::db::queries::DirtyCheckoutPolicy;
+
+    // Stash: merge around the user's edits and put them back
+    let state = test_state();
+    let repo = init_repo();
+    let task_id = review_with_policy(&state, &repo, DirtyCheckoutPolicy::Stash);
+    std::fs::write(repo.join("README.md"), "editing\n").unwrap();
+
+    let resp = post_empty(&state, &format!("/api/tasks/{}/approve", task_id)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(json_body(resp).await["merge_commit"].is_string());
+    assert!(repo.join("feature.txt").exists());
+    assert_eq!(
+        std::fs::read_to_string(repo.join("README.md")).unwrap(),
+        "editing\n"
+    );
+    std::fs::remove_dir_all(&repo).ok();
+
+    // Fail: refuse outright
+    let state = test_state();
+    let repo = init_repo();
+    let task_id = review_with_policy(&state, &repo, DirtyCheckoutPolicy::Fail);
+    git(&repo, &["checkout", "-q", "--detach"]);
+
+    let resp = post_empty(&state, &format!("/api/tasks/{}/approve", task_id)).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert!(json_body(resp).await["error"]
+        .as_str()
+        .unwrap()
+        .contains("detached HEAD"));
+    assert!(state.db.list_deferred_merges(None).unwrap().is_empty());
+    assert_eq!(
+        state.db.get_task(&task_id).unwrap().unwrap().status,
+        TaskStatus::AwaitingReview
+    );
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+#[tokio::test]
+async fn test_post_merge_check_bisects_and_reverts_culprit() {
+    use conductor::db::queries::{CreateGoalSpace, CreateTask, GoalSettings, RegressionAction};
+
+    let state = test_state();
+    let repo = init_repo();
+    let db = &state.db;
+    let goal = db
+        .create_goal_space(&CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: repo.to_string_lossy().into(),
+            settings: GoalSettings {
+                post_merge_command: Some("test ! -f broken.txt".into()),
+                regression_action: Some(RegressionAction::Revert),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+
+    // Three tasks land on main; the second one breaks the check
+    let mut task_ids = Vec::new();
+    for file in ["a.txt", "broken.txt", "c.txt"] {
+        let task = db
+            .create_task(
+                &goal.id,
+                &CreateTask {
+                    title: file.into(),
+                    description: "D".into(),
+                    priority: 0,
+                    depends_on: vec![],
+                    settings: Default::default(),
+                },
+            )
+            .unwrap();
+        let branch = format!("conductor/{}", file.replace('.', "-"));
+        git(&repo, &["checkout", "-q", "-b", &branch]);
+        std::fs::write(repo.join(file), "x\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", file]);
+        git(&repo, &["checkout", "-q", "main"]);
+        git(&repo, &["merge", "-q", "--no-ff", &branch, "-m", "merge"]);
+        let merge = conductor::agent::worktree::rev_parse(&repo, "HEAD")
+            .await
+            .unwrap();
+        let run = db
+            .create_agent_run(&task.id, &goal.id, None, Some(&branch), "sonnet", None)
+            .unwrap();
+        db.set_agent_run_merge_commit(&run.id, &merge).unwrap();
+        walk_task_to(&state, &task.id, TaskStatus::Done);
+        task_ids.push(task.id);
+    }
+
+    let resp = post_empty(&state, &format!("/api/goals/{}/check", goal.id)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let report = json_body(resp).await;
+    assert_eq!(report["passed"], false);
+    assert_eq!(report["culprit"]["task_id"], task_ids[1].as_str());
+    assert_eq!(report["action"], "revert");
+
+    let culprit = db.get_task(&task_ids[1]).unwrap().unwrap();
+    assert_eq!(culprit.status, TaskStatus::Reverted);
+    assert!(!repo.join("broken.txt").exists());
+    assert!(repo.join("c.txt").exists());
+    let run = db.merged_agent_run_for_task(&task_ids[1]).unwrap().unwrap();
+    assert_eq!(run.failure_reason.as_deref(), Some("regression"));
+
+    // Main is healthy again
+    let resp = post_empty(&state, &format!("/api/goals/{}/check", goal.id)).await;
+    assert_eq!(json_body(resp).await["passed"], true);
+
+    std::fs::remove_dir_all(&repo).ok();
+}
+
+#[tokio::test]
+async fn test_post_merge_check_requires_command() {
+    let state = test_state();
+    let goal = state
+        .db
+        .create_goal_space(&conductor::db::queries::CreateGoalSpace {
+            name: "G".into(),
+            description: "D".into(),
+            repo_path: "/tmp".into(),
+            settings: Default::default(),
+        })
+        .unwrap();
+
+    let resp = post_empty(&state, &format!("/api/goals/{}/check", goal.id)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }